@@ -0,0 +1,220 @@
+use backhand::{FilesystemReader, InnerNode};
+use goblin::elf::Elf;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::binary_kind::is_dynamic_loader;
+use crate::oci_image::inside;
+use crate::scan_dir::is_elf;
+use crate::TopoSortResult;
+
+const SQUASHFS_MAGIC: &[u8] = b"hsqs";
+
+/// Libraries of the AppImage excludelist: every desktop Linux has them and a bundle must not ship its own, as they
+/// have to match the kernel drivers, the display server or the C library of the host
+pub const EXCLUDELIST: [&str; 42] = [
+    "libc.so.6", "libm.so.6", "libdl.so.2", "libpthread.so.0", "librt.so.1", "libresolv.so.2", "libutil.so.1",
+    "libnsl.so.1", "libanl.so.1", "libBrokenLocale.so.1", "libmvec.so.1", "libthread_db.so.1", "libgcc_s.so.1",
+    "libGL.so.1", "libEGL.so.1", "libGLX.so.0", "libGLdispatch.so.0", "libOpenGL.so.0", "libGLESv2.so.2", "libdrm.so.2",
+    "libgbm.so.1", "libvulkan.so.1", "libglapi.so.0", "libX11.so.6", "libX11-xcb.so.1", "libxcb.so.1", "libxcb-dri2.so.0",
+    "libxcb-dri3.so.0", "libICE.so.6", "libSM.so.6", "libasound.so.2", "libjack.so.0", "libpipewire-0.3.so.0",
+    "libfontconfig.so.1", "libfreetype.so.6", "libharfbuzz.so.0", "libfribidi.so.0", "libexpat.so.1", "libz.so.1",
+    "libgmp.so.10", "libcom_err.so.2", "libuuid.so.1",
+];
+
+/// Offset of the squashfs payload in `image`: 0 for a raw squashfs image, the end of the ELF runtime for an AppImage
+/// of type 2, where the section headers end
+pub fn payload_offset(image: &Path) -> io::Result<u64> {
+    let mut file = File::open(image)?;
+    let mut header = [0u8; 64];
+    let read = file.read(&mut header)?;
+    if header.starts_with(SQUASHFS_MAGIC) {
+        return Ok(0);
+    }
+    let not_an_appimage = || io::Error::new(io::ErrorKind::InvalidData, format!("{} is neither an AppImage nor a squashfs image", image.display()));
+    let elf = Elf::parse_header(&header[..read]).map_err(|_| not_an_appimage())?;
+    let offset = elf.e_shoff + elf.e_shentsize as u64 * elf.e_shnum as u64;
+    let mut magic = [0u8; 4];
+    std::io::Seek::seek(&mut file, io::SeekFrom::Start(offset))?;
+    match file.read_exact(&mut magic) {
+        Ok(()) if magic == SQUASHFS_MAGIC => Ok(offset),
+        _ => Err(not_an_appimage()),
+    }
+}
+
+/// Extracts the squashfs payload of the AppImage or squashfs image `image` into the directory `root`. Entries that
+/// would land outside of `root` are skipped, as are symbolic links that point out of it: those resolve to files of the
+/// host, which the bundle does not provide. Returns the number of files and links extracted.
+pub fn extract(image: &Path, root: &Path) -> io::Result<usize> {
+    let offset = payload_offset(image)?;
+    let filesystem = FilesystemReader::from_reader_with_offset(BufReader::new(File::open(image)?), offset).map_err(io::Error::other)?;
+    std::fs::create_dir_all(root)?;
+    let mut extracted = 0;
+    for node in filesystem.files() {
+        let relative = node.fullpath.strip_prefix("/").unwrap_or(&node.fullpath);
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let target = match inside(root, relative) {
+            Some(target) => target,
+            None => continue,
+        };
+        match &node.inner {
+            InnerNode::Dir(_) => {
+                if !target.exists() {
+                    std::fs::create_dir(&target)?;
+                }
+                continue;
+            }
+            InnerNode::File(file) => {
+                io::copy(&mut filesystem.file(file).reader(), &mut File::create(&target)?)?;
+                std::fs::set_permissions(&target, std::fs::Permissions::from_mode(node.header.permissions as u32 & 0o777))?;
+            }
+            InnerNode::Symlink(symlink) if stays_inside(relative, &symlink.link) => std::os::unix::fs::symlink(&symlink.link, &target)?,
+            InnerNode::Symlink(_) => continue,
+            // devices, pipes and sockets are of no use to the analysis
+            _ => continue,
+        }
+        extracted += 1;
+    }
+    Ok(extracted)
+}
+
+/// Whether `link`, the target of a symbolic link at the relative path `from`, names a path under the same root
+fn stays_inside(from: &Path, link: &Path) -> bool {
+    let mut depth = from.components().count() - 1;
+    for component in link.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// The binary the AppRun entry point of the AppDir `root` starts: AppRun itself when it is an ELF file, the file it
+/// links to, or the command of the `Exec` key of the desktop file next to it when AppRun is a script
+pub fn apprun_target(root: &Path) -> Option<PathBuf> {
+    let mut apprun = root.join("AppRun");
+    // AppRun may link to a link
+    for _ in 0..8 {
+        match std::fs::read_link(&apprun) {
+            Ok(link) => apprun = apprun.parent().unwrap_or(root).join(link),
+            Err(_) => break,
+        }
+    }
+    if is_elf(&apprun) {
+        return Some(apprun);
+    }
+    let desktop = std::fs::read_dir(root).ok()?.flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|extension| extension == "desktop"))?;
+    let content = std::fs::read_to_string(desktop).ok()?;
+    let exec = content.lines().find_map(|line| line.strip_prefix("Exec="))?.split_whitespace().next()?.trim_matches('"');
+    [root.join(exec.trim_start_matches('/')), root.join("usr/bin").join(exec)].into_iter().find(|path| is_elf(path))
+}
+
+/// The libraries `result` needs that neither the bundle nor the excludelist provide, in the order of `unresolved`
+pub fn unbundled(result: &TopoSortResult) -> Vec<String> {
+    result.unresolved.iter()
+        .filter(|name| !EXCLUDELIST.contains(&name.as_str()) && !is_dynamic_loader(name))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use backhand::{FilesystemWriter, NodeHeader};
+
+    use std::io::Cursor;
+
+    use crate::appimage::{apprun_target, extract, payload_offset, unbundled};
+    use crate::query::tests::small_dag;
+
+    /// A squashfs image of an AppDir whose AppRun links to `usr/bin/app`, appended to `runtime`
+    fn appimage(runtime: &[u8]) -> Vec<u8> {
+        let header = NodeHeader::new(0o755, 0, 0, 0);
+        let mut writer = FilesystemWriter::default();
+        writer.push_dir_all("usr/bin", header).unwrap();
+        writer.push_dir_all("usr/lib", header).unwrap();
+        writer.push_file(Cursor::new(b"\x7fELF app".to_vec()), "usr/bin/app", header).unwrap();
+        writer.push_file(Cursor::new(b"\x7fELF lib".to_vec()), "usr/lib/libfoo.so.1", header).unwrap();
+        writer.push_symlink("usr/bin/app", "AppRun", header).unwrap();
+        writer.push_symlink("/etc/passwd", "usr/lib/escape", header).unwrap();
+        writer.push_symlink("../../../etc/passwd", "usr/lib/relative-escape", header).unwrap();
+        writer.push_symlink("../lib/libfoo.so.1", "usr/bin/libfoo.so.1", header).unwrap();
+        let mut image = Cursor::new(runtime.to_vec());
+        writer.write_with_offset(&mut image, runtime.len() as u64).unwrap();
+        image.into_inner()
+    }
+
+    #[test]
+    fn extract_should_read_the_payload_of_appimages_and_squashfs_images() {
+        let dir = std::env::temp_dir().join(format!("lddtopo-appimage-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // a 64-bit ELF header whose section headers end right after it: e_shoff 64, no section header
+        let mut runtime = b"\x7fELF\x02\x01\x01".to_vec();
+        runtime.resize(16, 0);
+        runtime.extend(2u16.to_le_bytes());
+        runtime.extend(62u16.to_le_bytes());
+        runtime.extend(1u32.to_le_bytes());
+        runtime.extend([0; 16]);
+        runtime.extend(64u64.to_le_bytes());
+        runtime.extend([0; 4]);
+        runtime.extend(64u16.to_le_bytes());
+        runtime.extend(56u16.to_le_bytes());
+        runtime.extend([0; 2]);
+        runtime.extend(64u16.to_le_bytes());
+        runtime.extend([0; 4]);
+        std::fs::write(dir.join("App.AppImage"), appimage(&runtime)).unwrap();
+        std::fs::write(dir.join("app.squashfs"), appimage(&[])).unwrap();
+        std::fs::write(dir.join("runtime"), &runtime).unwrap();
+
+        let offsets = (payload_offset(&dir.join("App.AppImage")).ok(), payload_offset(&dir.join("app.squashfs")).ok());
+        let runtime_only = payload_offset(&dir.join("runtime")).is_err();
+        let from_appimage = extract(&dir.join("App.AppImage"), &dir.join("appimage"));
+        let from_squashfs = extract(&dir.join("app.squashfs"), &dir.join("squashfs"));
+        let target = apprun_target(&dir.join("appimage"));
+        let libfoo = std::fs::read(dir.join("squashfs/usr/lib/libfoo.so.1")).ok();
+        let escapes = ["usr/lib/escape", "usr/lib/relative-escape"].map(|link| std::fs::symlink_metadata(dir.join("squashfs").join(link)).is_ok());
+        let linked = std::fs::read(dir.join("squashfs/usr/bin/libfoo.so.1")).ok();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((Some(64), Some(0)), offsets);
+        assert!(runtime_only);
+        assert_eq!(4, from_appimage.unwrap());
+        assert_eq!(4, from_squashfs.unwrap());
+        assert_eq!([false, false], escapes);
+        assert_eq!(libfoo, linked);
+        assert_eq!(Some(dir.join("appimage/usr/bin/app")), target);
+        assert_eq!(Some(b"\x7fELF lib".to_vec()), libfoo);
+    }
+
+    #[test]
+    fn apprun_target_should_fall_back_to_the_exec_key_of_the_desktop_file() {
+        let dir = std::env::temp_dir().join(format!("lddtopo-appdir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("usr/bin")).unwrap();
+        std::fs::write(dir.join("AppRun"), "#!/bin/sh\nexec \"$APPDIR/usr/bin/app\" \"$@\"\n").unwrap();
+        std::fs::write(dir.join("app.desktop"), "[Desktop Entry]\nName=App\nExec=app %F\n").unwrap();
+        std::fs::write(dir.join("usr/bin/app"), b"\x7fELF").unwrap();
+        let target = apprun_target(&dir);
+        let without_desktop = {
+            std::fs::remove_file(dir.join("app.desktop")).unwrap();
+            apprun_target(&dir)
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(dir.join("usr/bin/app")), target);
+        assert_eq!(None, without_desktop);
+    }
+
+    #[test]
+    fn unbundled_should_skip_the_excludelist_and_the_loader() {
+        let mut result = small_dag();
+        result.unresolved = ["libc.so.6", "ld-linux-x86-64.so.2", "libssl.so.3", "libGL.so.1", "libQt5Core.so.5"].map(String::from).to_vec();
+        assert_eq!(vec!["libssl.so.3", "libQt5Core.so.5"], unbundled(&result));
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::query::direct_dependencies_of;
+use crate::TopoSortResult;
+
+/// Turns a library name into a valid Bazel target name: anything but letters, digits, `_`, `-`
+/// and `.` becomes `_`, and names made of dots only (which Bazel reserves) are prefixed with `_`
+pub fn sanitize_target_name(name: &str) -> String {
+    let sanitized: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    if sanitized.is_empty() || sanitized.chars().all(|c| c == '.') {
+        format!("_{}", sanitized)
+    } else {
+        sanitized
+    }
+}
+
+/// Target names for every vertex, names that collide after sanitizing get a `_2`, `_3`, ... suffix in vertex order
+fn target_names(vertices: &[String]) -> HashMap<&str, String> {
+    let mut taken: BTreeSet<String> = BTreeSet::new();
+    let mut targets: HashMap<&str, String> = HashMap::new();
+    for v in vertices {
+        let base = sanitize_target_name(v);
+        let mut target = base.clone();
+        let mut n = 2;
+        while !taken.insert(target.clone()) {
+            target = format!("{}_{}", base, n);
+            n += 1;
+        }
+        targets.insert(v.as_str(), target);
+    }
+    targets
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The label of the file at `path` in a package at `root`, `None` when the file is outside of it
+fn file_label(path: &str, root: &Path) -> Option<String> {
+    let relative = Path::new(path).strip_prefix(root).ok()?.to_str()?;
+    (!relative.is_empty()).then(|| relative.to_string())
+}
+
+/// A `.bzl` fragment defining `macro_name()`, which declares one `cc_import` per library in load order, to be
+/// loaded from a BUILD file at `root`. The `deps` of each target are the libraries it needs directly. Libraries
+/// that are unresolved or outside of `root` have no file to import and are declared as `system_provided`, linked
+/// against an `interface_library` named after the library that the package has to provide.
+pub fn to_bzl(result: &TopoSortResult, root: &Path, macro_name: &str) -> String {
+    let targets = target_names(&result.vertices);
+    let mut bzl = String::from("# Generated by lddtopo-rs from the ELF dependency graph, do not edit\n\n");
+    writeln!(bzl, "def {}():", macro_name).unwrap();
+    if result.topo_sorted_libs.is_empty() {
+        bzl.push_str("    pass\n");
+    }
+    for lib in &result.topo_sorted_libs {
+        let unresolved = result.unresolved.contains(&lib.name);
+        bzl.push_str("    native.cc_import(\n");
+        writeln!(bzl, "        name = {},", quote(&targets[lib.name.as_str()])).unwrap();
+        match lib.path.as_deref().filter(|_| !unresolved).and_then(|path| file_label(path, root)) {
+            Some(label) => writeln!(bzl, "        shared_library = {},", quote(&label)).unwrap(),
+            None => {
+                writeln!(bzl, "        interface_library = {},", quote(&lib.name)).unwrap();
+                bzl.push_str("        system_provided = True,\n");
+            }
+        }
+        let deps = direct_dependencies_of(result, &lib.name).unwrap_or_default();
+        if !deps.is_empty() {
+            bzl.push_str("        deps = [\n");
+            for dep in deps {
+                writeln!(bzl, "            {},", quote(&format!(":{}", targets[dep.as_str()]))).unwrap();
+            }
+            bzl.push_str("        ],\n");
+        }
+        bzl.push_str("    )\n");
+    }
+    bzl
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::bazel::{sanitize_target_name, target_names, to_bzl};
+    use crate::binary_kind::BinaryKind;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::{get_topologically_sorted_result, AnalysisOptions};
+    use std::path::Path;
+
+    #[test]
+    fn sanitize_target_name_should_replace_invalid_characters() {
+        assert_eq!("libstdc__.so.6", sanitize_target_name("libstdc++.so.6"));
+        assert_eq!("libfoo_bar.so", sanitize_target_name("libfoo bar.so"));
+        assert_eq!("_..", sanitize_target_name(".."));
+        assert_eq!("_", sanitize_target_name(""));
+    }
+
+    #[test]
+    fn target_names_should_disambiguate_collisions() {
+        let vertices = vec!["lib+.so".to_string(), "lib_.so".to_string(), "lib .so".to_string()];
+        let targets = target_names(&vertices);
+        assert_eq!("lib_.so", targets["lib+.so"]);
+        assert_eq!("lib_.so_2", targets["lib_.so"]);
+        assert_eq!("lib_.so_3", targets["lib .so"]);
+    }
+
+    #[test]
+    fn to_bzl_should_declare_targets_in_load_order_with_deps() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("libstdc++.so.6")
+            .add_needed("libmissing.so")
+            .add_library("libstdc++.so.6", &[])
+            .add_missing_library("libmissing.so")
+            .build();
+        let result = get_topologically_sorted_result("app", "/opt/app", BinaryKind::Executable, &dt, &AnalysisOptions::default()).unwrap();
+        let expected = "# Generated by lddtopo-rs from the ELF dependency graph, do not edit
+
+def declare_libraries():
+    native.cc_import(
+        name = \"libmissing.so\",
+        interface_library = \"libmissing.so\",
+        system_provided = True,
+    )
+    native.cc_import(
+        name = \"libstdc__.so.6\",
+        shared_library = \"usr/lib/libstdc++.so.6\",
+    )
+    native.cc_import(
+        name = \"app\",
+        shared_library = \"opt/app\",
+        deps = [
+            \":libmissing.so\",
+            \":libstdc__.so.6\",
+        ],
+    )
+";
+        assert_eq!(expected, to_bzl(&result, Path::new("/"), "declare_libraries"));
+
+        // under another root, the files outside of it are provided by the system
+        let bzl = to_bzl(&result, Path::new("/usr"), "declare_libraries");
+        assert!(bzl.contains("shared_library = \"lib/libstdc++.so.6\""));
+        assert!(bzl.contains("name = \"app\",\n        interface_library = \"app\",\n        system_provided = True,"));
+    }
+}
@@ -0,0 +1,111 @@
+use goblin::elf::dynamic::DF_1_PIE;
+use goblin::elf::header::{ET_DYN, ET_EXEC};
+use goblin::elf::Elf;
+
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+/// What kind of ELF object the analyzed file is.
+///
+/// Position-independent executables are `ET_DYN` just like shared libraries, so the ELF type alone
+/// is not enough to tell them apart.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryKind {
+    Executable,
+    PieExecutable,
+    SharedLibrary,
+    Other,
+}
+
+impl BinaryKind {
+    /// Classifies an ELF object by its type and dynamic section.
+    ///
+    /// `DF_1_PIE` is authoritative when the linker set it. Otherwise an `ET_DYN` object with an
+    /// interpreter is a PIE executable, unless it also carries a soname: libraries such as
+    /// `libc.so.6` have `PT_INTERP` so they can be run directly, but they are still libraries.
+    pub fn classify(e_type: u16, has_interpreter: bool, has_soname: bool, pie_flag: bool) -> BinaryKind {
+        match e_type {
+            ET_EXEC => BinaryKind::Executable,
+            ET_DYN if pie_flag => BinaryKind::PieExecutable,
+            ET_DYN if has_interpreter && !has_soname => BinaryKind::PieExecutable,
+            ET_DYN => BinaryKind::SharedLibrary,
+            _ => BinaryKind::Other,
+        }
+    }
+
+    pub fn from_elf(elf: &Elf) -> BinaryKind {
+        let pie_flag = elf.dynamic.as_ref()
+            .map(|dynamic| dynamic.info.flags_1 & DF_1_PIE != 0)
+            .unwrap_or(false);
+        BinaryKind::classify(elf.header.e_type, elf.interpreter.is_some(), elf.soname.is_some(), pie_flag)
+    }
+
+    pub fn detect(path: &Path) -> Result<BinaryKind, goblin::error::Error> {
+        let bytes = std::fs::read(path)?;
+        let elf = Elf::parse(&bytes)?;
+        Ok(BinaryKind::from_elf(&elf))
+    }
+
+    pub fn is_executable(&self) -> bool {
+        matches!(self, BinaryKind::Executable | BinaryKind::PieExecutable)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            BinaryKind::Executable => "executable",
+            BinaryKind::PieExecutable => "position-independent executable",
+            BinaryKind::SharedLibrary => "shared library",
+            BinaryKind::Other => "ELF object",
+        }
+    }
+}
+
+/// Whether `name` is the soname of a dynamic loader, which some libraries need directly and comes with every platform
+pub(crate) fn is_dynamic_loader(name: &str) -> bool {
+    name.starts_with("ld-linux") || name.starts_with("ld64.so.") || name.starts_with("ld-musl-") || name == "ld.so.1"
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use goblin::elf::header::{ET_DYN, ET_EXEC, ET_REL};
+    use crate::binary_kind::{is_dynamic_loader, BinaryKind};
+
+    #[test]
+    fn is_dynamic_loader_should_match_the_loaders_of_glibc_and_musl() {
+        for name in ["ld-linux-x86-64.so.2", "ld-linux-aarch64.so.1", "ld64.so.2", "ld-musl-x86_64.so.1", "ld.so.1"] {
+            assert!(is_dynamic_loader(name), "{}", name);
+        }
+        assert!(!is_dynamic_loader("libc.so.6"));
+        assert!(!is_dynamic_loader("ld.so.2"));
+    }
+
+    #[test]
+    fn classify_when_et_dyn_has_interpreter_should_be_pie_executable() {
+        assert_eq!(BinaryKind::PieExecutable, BinaryKind::classify(ET_DYN, true, false, false));
+    }
+
+    #[test]
+    fn classify_when_et_dyn_has_no_interpreter_should_be_shared_library() {
+        assert_eq!(BinaryKind::SharedLibrary, BinaryKind::classify(ET_DYN, false, true, false));
+        assert_eq!(BinaryKind::SharedLibrary, BinaryKind::classify(ET_DYN, false, false, false));
+    }
+
+    #[test]
+    fn classify_when_et_dyn_has_interpreter_and_soname_should_be_shared_library() {
+        assert_eq!(BinaryKind::SharedLibrary, BinaryKind::classify(ET_DYN, true, true, false));
+    }
+
+    #[test]
+    fn classify_when_pie_flag_is_set_should_be_pie_executable() {
+        assert_eq!(BinaryKind::PieExecutable, BinaryKind::classify(ET_DYN, true, true, true));
+    }
+
+    #[test]
+    fn classify_when_et_exec_should_be_executable() {
+        assert_eq!(BinaryKind::Executable, BinaryKind::classify(ET_EXEC, true, false, false));
+        assert!(BinaryKind::classify(ET_EXEC, false, false, false).is_executable());
+        assert_eq!(BinaryKind::Other, BinaryKind::classify(ET_REL, false, false, false));
+    }
+}
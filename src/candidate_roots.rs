@@ -0,0 +1,108 @@
+use lddtree::DependencyTree;
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Outcome of resolving the analyzed binary under one candidate root
+#[derive(Debug)]
+pub struct RootEvaluation {
+    pub root: PathBuf,
+    /// Names of the libraries that could not be found, `Err` if the analysis itself failed
+    pub unresolved: Result<Vec<String>, String>,
+}
+
+/// Names of the libraries lddtree could not find on disk, sorted
+pub fn unresolved_libraries(deps: &DependencyTree) -> Vec<String> {
+    let mut unresolved: Vec<String> = deps.libraries.values()
+        .filter(|lib| !lib.found())
+        .map(|lib| lib.name.clone())
+        .collect();
+    unresolved.sort();
+    unresolved.dedup();
+    unresolved
+}
+
+pub fn evaluate_roots<F>(roots: &[PathBuf], analyze: F) -> Vec<RootEvaluation>
+    where F: Fn(&Path) -> Result<DependencyTree, lddtree::Error> {
+    roots.iter().map(|root| {
+        let unresolved = analyze(root)
+            .map(|deps| unresolved_libraries(&deps))
+            .map_err(|err| err.to_string());
+        RootEvaluation { root: root.clone(), unresolved }
+    }).collect()
+}
+
+/// The root with the fewest unresolved libraries; ties go to the root listed first
+pub fn best_root(evaluations: &[RootEvaluation]) -> Option<&RootEvaluation> {
+    evaluations.iter()
+        .filter_map(|e| e.unresolved.as_ref().ok().map(|unresolved| (e, unresolved.len())))
+        .min_by_key(|(_, count)| *count)
+        .map(|(e, _)| e)
+}
+
+pub fn format_table(evaluations: &[RootEvaluation], best: Option<&RootEvaluation>) -> String {
+    let width = evaluations.iter()
+        .map(|e| e.root.display().to_string().len())
+        .chain(std::iter::once("root".len()))
+        .max()
+        .unwrap_or(0);
+    let mut table = String::new();
+    writeln!(table, "  {:<width$}  unresolved", "root", width = width).unwrap();
+    for e in evaluations {
+        let marker = if best.map(|b| std::ptr::eq(b, e)).unwrap_or(false) { "*" } else { " " };
+        let unresolved = match &e.unresolved {
+            Ok(unresolved) => unresolved.len().to_string(),
+            Err(err) => format!("error: {}", err),
+        };
+        writeln!(table, "{} {:<width$}  {}", marker, e.root.display(), unresolved, width = width).unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+    use lddtree::DependencyTree;
+    use crate::candidate_roots::{best_root, evaluate_roots, format_table};
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+
+    fn tree(found: &[&str], missing: &[&str]) -> DependencyTree {
+        let mut builder = DependencyTreeBuilder::new();
+        for name in found {
+            builder = builder.add_needed(name).add_library_at(name, *name, &[]);
+        }
+        for name in missing {
+            builder = builder.add_needed(name).add_missing_library(name);
+        }
+        builder.build()
+    }
+
+    fn analyze(root: &Path) -> Result<DependencyTree, lddtree::Error> {
+        match root.to_str().unwrap() {
+            "/a" => Ok(tree(&["B"], &["C", "D"])),
+            "/b" => Ok(tree(&["B", "C"], &["D"])),
+            "/c" => Ok(tree(&["B", "D"], &["C"])),
+            _ => Err(lddtree::Error::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "no such root"))),
+        }
+    }
+
+    #[test]
+    fn best_root_should_pick_fewest_unresolved_and_prefer_first_on_tie() {
+        let roots: Vec<PathBuf> = ["/missing", "/a", "/b", "/c"].iter().map(PathBuf::from).collect();
+        let evaluations = evaluate_roots(&roots, analyze);
+        assert!(evaluations[0].unresolved.is_err());
+        assert_eq!(&vec!["C".to_string(), "D".to_string()], evaluations[1].unresolved.as_ref().unwrap());
+        let best = best_root(&evaluations).unwrap();
+        assert_eq!(PathBuf::from("/b"), best.root);
+
+        let table = format_table(&evaluations, Some(best));
+        assert!(table.contains("* /b        1"));
+        assert!(table.contains("  /a        2"));
+    }
+
+    #[test]
+    fn best_root_when_every_analysis_fails_should_return_none() {
+        let evaluations = evaluate_roots(&[PathBuf::from("/missing")], analyze);
+        assert!(best_root(&evaluations).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Edge, TopoSortResult};
+
+/// Graph used only for visual exports: libraries sharing a collapsed prefix are folded into one
+/// synthetic node named `<prefix>*`.
+#[derive(Debug)]
+pub struct CollapsedView {
+    pub vertices: Vec<String>,
+    /// Displayed edge -> number of original edges merged into it
+    pub edges: BTreeMap<Edge, usize>,
+    /// Synthetic node name -> the libraries folded into it
+    pub groups: BTreeMap<String, Vec<String>>,
+}
+
+/// The synthetic node a library is folded into, if any. When several prefixes match, the longest
+/// one wins so that `--collapse-by-prefix libLLVM --collapse-by-prefix libLLVMCore` behaves as expected.
+fn group_of(vertex: &str, prefixes: &[String]) -> Option<String> {
+    prefixes.iter()
+        .filter(|prefix| !prefix.is_empty() && vertex.starts_with(prefix.as_str()))
+        .max_by_key(|prefix| prefix.len())
+        .map(|prefix| format!("{}*", prefix))
+}
+
+/// The view of `result` with the libraries found by `--scan-dlopen` and `--runtime-trace` and their edges, so that they
+/// are folded into the groups like any other library
+pub fn collapse_result(result: &TopoSortResult, prefixes: &[String]) -> CollapsedView {
+    let extra_libs: Vec<String> = result.dlopen_libs.iter().chain(&result.runtime_libs).map(|lib| lib.name.clone()).collect();
+    let edges = result.edges.iter().chain(&result.dlopen_edges).chain(&result.runtime_edges);
+    collapse_by_prefix(result.vertices.iter().chain(&extra_libs), edges, prefixes)
+}
+
+/// Folds the `vertices` sharing one of `prefixes` into one node each. Only the `edges` between two of `vertices` are kept.
+pub fn collapse_by_prefix<'a>(vertices: impl IntoIterator<Item = &'a String>, edges: impl IntoIterator<Item = &'a Edge>,
+                              prefixes: &[String]) -> CollapsedView {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut vertex_to_node: BTreeMap<&str, String> = BTreeMap::new();
+    for v in vertices {
+        if vertex_to_node.contains_key(v.as_str()) {
+            continue;
+        }
+        let node = match group_of(v, prefixes) {
+            None => v.clone(),
+            Some(group) => {
+                groups.entry(group.clone()).or_default().push(v.clone());
+                group
+            }
+        };
+        vertex_to_node.insert(v.as_str(), node);
+    }
+    groups.values_mut().for_each(|members| members.sort());
+
+    let collapsed_vertices: BTreeSet<String> = vertex_to_node.values().cloned().collect();
+    let mut collapsed_edges: BTreeMap<Edge, usize> = BTreeMap::new();
+    for edge in edges {
+        // An edge to a library that is not one of `vertices`, e.g. one of an inconsistent result, is left out so
+        // that every exporter can rely on both ends being drawn
+        let (Some(src), Some(dst)) = (vertex_to_node.get(edge.src.as_str()).cloned(), vertex_to_node.get(edge.dst.as_str()).cloned()) else {
+            continue;
+        };
+        // Edges between members of the same group disappear inside the synthetic node
+        if src != dst {
+            *collapsed_edges.entry(Edge { src, dst, kind: edge.kind, ..Default::default() }).or_insert(0) += 1;
+        }
+    }
+    CollapsedView {
+        vertices: collapsed_vertices.into_iter().collect(),
+        edges: collapsed_edges,
+        groups,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::collapse::{collapse_by_prefix, collapse_result};
+    use crate::query::tests::small_dag;
+    use crate::{Edge, EdgeKind, Lib};
+
+    fn edge(src: &str, dst: &str) -> Edge {
+        Edge { src: src.to_string(), dst: dst.to_string(), kind: EdgeKind::Needed, ..Default::default() }
+    }
+
+    #[test]
+    fn collapse_by_prefix_when_no_prefixes_should_keep_graph() {
+        let vertices = vec!["A".to_string(), "B".to_string()];
+        let edges = vec![edge("B", "A")];
+        let view = collapse_by_prefix(&vertices, &edges, &[]);
+        assert_eq!(vertices, view.vertices);
+        assert_eq!(vec![(&edge("B", "A"), &1)], view.edges.iter().collect::<Vec<_>>());
+        assert!(view.groups.is_empty());
+    }
+
+    #[test]
+    fn collapse_by_prefix_should_fold_members_and_keep_edges_to_group() {
+        let vertices: Vec<String> = ["app", "libLLVMCore.so", "libLLVMSupport.so", "libc.so.6"]
+            .iter().map(|s| s.to_string()).collect();
+        let edges = vec![
+            edge("libLLVMCore.so", "app"),
+            edge("libLLVMSupport.so", "app"),
+            edge("libLLVMSupport.so", "libLLVMCore.so"),
+            edge("libc.so.6", "libLLVMSupport.so"),
+        ];
+        let view = collapse_by_prefix(&vertices, &edges, &["libLLVM".to_string()]);
+        assert_eq!(vec!["app", "libLLVM*", "libc.so.6"], view.vertices);
+        assert_eq!(vec![(&edge("libLLVM*", "app"), &2), (&edge("libc.so.6", "libLLVM*"), &1)], view.edges.iter().collect::<Vec<_>>());
+        assert_eq!(vec!["libLLVMCore.so", "libLLVMSupport.so"], view.groups["libLLVM*"]);
+    }
+
+    #[test]
+    fn collapse_by_prefix_when_prefixes_overlap_should_use_longest() {
+        let vertices: Vec<String> = ["libLLVMCore.so", "libLLVMSupport.so"].iter().map(|s| s.to_string()).collect();
+        let prefixes = vec!["libLLVM".to_string(), "libLLVMCore".to_string()];
+        let view = collapse_by_prefix(&vertices, &[], &prefixes);
+        assert_eq!(vec!["libLLVM*", "libLLVMCore*"], view.vertices);
+    }
+
+    #[test]
+    fn collapse_by_prefix_should_keep_the_kind_of_edges() {
+        let vertices: Vec<String> = ["app", "ld-linux-x86-64.so.2", "libLLVMCore.so", "libLLVMSupport.so"]
+            .iter().map(|s| s.to_string()).collect();
+        let with_kind = |src: &str, dst: &str, kind: EdgeKind| Edge { kind, ..edge(src, dst) };
+        let edges = vec![
+            with_kind("ld-linux-x86-64.so.2", "app", EdgeKind::Interpreter),
+            edge("libLLVMCore.so", "app"),
+            with_kind("libLLVMSupport.so", "app", EdgeKind::Weak),
+        ];
+        let view = collapse_by_prefix(&vertices, &edges, &["libLLVM".to_string()]);
+        assert_eq!(vec![
+            (&with_kind("ld-linux-x86-64.so.2", "app", EdgeKind::Interpreter), &1),
+            (&edge("libLLVM*", "app"), &1),
+            (&with_kind("libLLVM*", "app", EdgeKind::Weak), &1),
+        ], view.edges.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn collapse_result_should_fold_dlopen_libraries_into_groups() {
+        let mut result = small_dag();
+        result.dlopen_libs = ["libplugin-a.so", "libplugin-b.so"].map(|name| Lib { name: name.to_string(), ..Default::default() }).into();
+        let dlopen = |src: &str, dst: &str| Edge { kind: EdgeKind::Dlopen, ..edge(src, dst) };
+        result.dlopen_edges = vec![dlopen("libplugin-a.so", "A"), dlopen("libplugin-b.so", "A"), dlopen("libplugin-b.so", "D")];
+        let view = collapse_result(&result, &["libplugin".to_string()]);
+        assert_eq!(vec!["A", "B", "C", "D", "E", "F", "libplugin*"], view.vertices);
+        assert_eq!(Some(&2), view.edges.get(&dlopen("libplugin*", "A")));
+        assert_eq!(Some(&1), view.edges.get(&dlopen("libplugin*", "D")));
+        assert!(view.edges.keys().all(|edge| view.vertices.contains(&edge.src) && view.vertices.contains(&edge.dst)));
+        assert_eq!(vec!["libplugin-a.so", "libplugin-b.so"], view.groups["libplugin*"]);
+    }
+
+    #[test]
+    fn collapse_result_should_fold_traced_libraries_into_groups() {
+        let mut result = small_dag();
+        result.runtime_libs = vec![Lib { name: "libgtk-3.so.0".to_string(), ..Default::default() }];
+        let runtime = |src: &str, dst: &str| Edge { kind: EdgeKind::Runtime, ..edge(src, dst) };
+        result.runtime_edges = vec![runtime("libgtk-3.so.0", "A"), runtime("E", "libgtk-3.so.0")];
+        let view = collapse_result(&result, &["libgtk".to_string(), "E".to_string()]);
+        assert_eq!(vec!["A", "B", "C", "D", "E*", "F", "libgtk*"], view.vertices);
+        assert_eq!(Some(&1), view.edges.get(&runtime("libgtk*", "A")));
+        assert_eq!(Some(&1), view.edges.get(&runtime("E*", "libgtk*")));
+        assert!(view.edges.keys().all(|edge| view.vertices.contains(&edge.src) && view.vertices.contains(&edge.dst)));
+    }
+
+    #[test]
+    fn collapse_by_prefix_should_leave_out_edges_to_libraries_that_are_not_vertices() {
+        let vertices = vec!["A".to_string()];
+        let view = collapse_by_prefix(&vertices, &[edge("libmissing.so", "A"), edge("A", "libgone.so")], &[]);
+        assert_eq!(vertices, view.vertices);
+        assert!(view.edges.is_empty());
+    }
+}
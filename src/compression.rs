@@ -0,0 +1,61 @@
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// `reader` decompressed according to its magic when it is gzip, xz or zstd, as is otherwise. xz and zstd streams are
+/// decompressed in memory.
+pub fn decompressed<'a>(reader: impl Read + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(GZIP_MAGIC) {
+        return Ok(Box::new(MultiGzDecoder::new(reader)));
+    }
+    if magic.starts_with(XZ_MAGIC) {
+        let mut bytes = Vec::new();
+        lzma_rs::xz_decompress(&mut reader, &mut bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("xz: {:?}", err)))?;
+        return Ok(Box::new(Cursor::new(bytes)));
+    }
+    if magic.starts_with(ZSTD_MAGIC) {
+        // zstd -T writes several frames, each one is decoded in turn
+        let mut bytes = Vec::new();
+        while !reader.fill_buf()?.is_empty() {
+            StreamingDecoder::new(&mut reader)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("zstd: {}", err)))?
+                .read_to_end(&mut bytes)?;
+        }
+        return Ok(Box::new(Cursor::new(bytes)));
+    }
+    Ok(Box::new(reader))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{Read, Write};
+
+    use crate::compression::decompressed;
+
+    #[test]
+    fn decompressed_should_detect_the_format_by_its_magic() {
+        let read = |bytes: &[u8]| {
+            let mut content = String::new();
+            decompressed(bytes).unwrap().read_to_string(&mut content).unwrap();
+            content
+        };
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(b"gzip").unwrap();
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut &b"xz"[..], &mut xz).unwrap();
+        let mut zstd = ruzstd::encoding::compress_to_vec(&b"zst"[..], ruzstd::encoding::CompressionLevel::Fastest);
+        zstd.extend(ruzstd::encoding::compress_to_vec(&b"d"[..], ruzstd::encoding::CompressionLevel::Fastest));
+        assert_eq!("gzip", read(&gzip.finish().unwrap()));
+        assert_eq!("xz", read(&xz));
+        assert_eq!("zstd", read(&zstd));
+        assert_eq!("plain", read(b"plain"));
+        assert!(decompressed(&[0xfd, b'7', b'z', b'X', b'Z', 0, 1][..]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::Direction;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::toposort::lexicographic_toposort;
+
+/// Finds every strongly connected component that contains a cycle (more than one member or a
+/// self-loop) and returns one representative cycle per component.
+///
+/// The graph edges point from a dependency to its dependent, the returned paths are in the
+/// `depends on` direction and closed: `[A, B, A]` means A depends on B which depends on A.
+/// Each path starts at the member with the smallest name and cycles are sorted by that name.
+pub fn find_cycles<F>(di_graph_map: &DiGraphMap<u32, ()>, name_of: F) -> Vec<Vec<String>>
+    where F: Fn(u32) -> String {
+    let mut cycles: Vec<Vec<String>> = tarjan_scc(di_graph_map).into_iter()
+        .filter(|scc| scc.len() > 1 || di_graph_map.contains_edge(scc[0], scc[0]))
+        .map(|scc| {
+            let start = *scc.iter().min_by_key(|id| name_of(**id)).unwrap();
+            let members: HashSet<u32> = scc.into_iter().collect();
+            shortest_cycle_through(di_graph_map, start, &members).into_iter().map(&name_of).collect()
+        })
+        .collect();
+    cycles.sort();
+    cycles
+}
+
+/// Members of every strongly connected component that contains a cycle, each sorted by name, the
+/// components sorted by their smallest member
+pub fn cyclic_components<F>(di_graph_map: &DiGraphMap<u32, ()>, name_of: F) -> Vec<Vec<String>>
+    where F: Fn(u32) -> String {
+    let mut components: Vec<Vec<String>> = tarjan_scc(di_graph_map).into_iter()
+        .filter(|scc| scc.len() > 1 || di_graph_map.contains_edge(scc[0], scc[0]))
+        .map(|scc| {
+            let mut members: Vec<String> = scc.into_iter().map(&name_of).collect();
+            members.sort();
+            members
+        })
+        .collect();
+    components.sort();
+    components
+}
+
+/// The strongly connected components of the graph in load order, i.e. a topological order of its condensation
+/// where every component comes after the components it depends on and ties go to the smallest first member.
+/// Members of a component are sorted by name, a component of one library without a self-loop is not a cycle.
+pub fn condensed_order<F>(di_graph_map: &DiGraphMap<u32, ()>, name_of: F) -> Vec<Vec<u32>>
+    where F: Fn(u32) -> String {
+    let components: Vec<Vec<u32>> = tarjan_scc(di_graph_map).into_iter()
+        .map(|mut scc| {
+            scc.sort_by_key(|id| name_of(*id));
+            scc
+        })
+        .collect();
+    let component_of: HashMap<u32, u32> = components.iter().enumerate()
+        .flat_map(|(index, scc)| scc.iter().map(move |id| (*id, index as u32)))
+        .collect();
+    let mut condensation: DiGraphMap<u32, ()> = DiGraphMap::new();
+    for index in 0..components.len() {
+        condensation.add_node(index as u32);
+    }
+    for (from, to, _) in di_graph_map.all_edges() {
+        if component_of[&from] != component_of[&to] {
+            condensation.add_edge(component_of[&from], component_of[&to], ());
+        }
+    }
+    lexicographic_toposort(&condensation, |index| name_of(components[index as usize][0]))
+        .expect("the condensation of a graph is acyclic")
+        .into_iter()
+        .map(|index| components[index as usize].clone())
+        .collect()
+}
+
+/// The distinct libraries of a closed cycle `[A, B, A]`
+pub fn cycle_members(cycle: &[String]) -> Vec<String> {
+    cycle.iter().take(cycle.len().saturating_sub(1)).cloned().collect()
+}
+
+/// BFS from `start` back to itself, restricted to the members of its component
+fn shortest_cycle_through(di_graph_map: &DiGraphMap<u32, ()>, start: u32, members: &HashSet<u32>) -> Vec<u32> {
+    let mut parent: HashMap<u32, u32> = HashMap::new();
+    let mut queue: VecDeque<u32> = VecDeque::from([start]);
+    while let Some(id) = queue.pop_front() {
+        let mut dependencies: Vec<u32> = di_graph_map.neighbors_directed(id, Direction::Incoming)
+            .filter(|dep| members.contains(dep))
+            .collect();
+        dependencies.sort();
+        for dep in dependencies {
+            if dep == start {
+                let mut path = vec![start];
+                let mut current = id;
+                while current != start {
+                    path.push(current);
+                    current = parent[&current];
+                }
+                path[1..].reverse();
+                path.push(start);
+                return path;
+            }
+            if let std::collections::hash_map::Entry::Vacant(e) = parent.entry(dep) {
+                e.insert(id);
+                queue.push_back(dep);
+            }
+        }
+    }
+    unreachable!("every member of a strongly connected component lies on a cycle")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use petgraph::graphmap::DiGraphMap;
+    use crate::cycles::{condensed_order, cyclic_components, find_cycles};
+
+    fn name_of(id: u32) -> String {
+        ((b'A' + id as u8) as char).to_string()
+    }
+
+    #[test]
+    fn find_cycles_when_graph_is_dag_should_return_nothing() {
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (2, 0), (2, 1)]);
+        assert!(find_cycles(&graph, name_of).is_empty());
+    }
+
+    #[test]
+    fn find_cycles_should_return_closed_path_in_depends_direction() {
+        // A depends on B, B depends on C, C depends on A. Edges point from the dependency to the dependent
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (2, 1), (0, 2)]);
+        assert_eq!(vec![vec!["A", "B", "C", "A"]], find_cycles(&graph, name_of));
+    }
+
+    #[test]
+    fn cyclic_components_should_skip_acyclic_vertices() {
+        // A <-> B, C <-> D <-> E, F depends on A
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (0, 1), (3, 2), (2, 3), (4, 3), (3, 4), (0, 5)]);
+        assert_eq!(vec![vec!["A", "B"], vec!["C", "D", "E"]], cyclic_components(&graph, name_of));
+    }
+
+    #[test]
+    fn find_cycles_should_report_self_loops() {
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (1, 1)]);
+        assert_eq!(vec![vec!["B", "B"]], find_cycles(&graph, name_of));
+    }
+
+    #[test]
+    fn condensed_order_should_put_components_after_their_dependencies() {
+        // A depends on B and C, B <-> C, C depends on D
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (2, 0), (2, 1), (1, 2), (3, 2)]);
+        let order: Vec<Vec<String>> = condensed_order(&graph, name_of).into_iter()
+            .map(|component| component.into_iter().map(name_of).collect())
+            .collect();
+        assert_eq!(vec![vec!["D"], vec!["B", "C"], vec!["A"]], order);
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{Lib, TopoSortResult};
+
+/// `s` as a single-quoted Cypher string
+fn quoted(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The NEEDED graph as a Cypher script for Neo4j, a single statement so that the node variables `n<index>`,
+/// in `vertices` order, can be used by the relationships. Every library is a `:Library` node with its `name`,
+/// `path`, depths and whether it is `unresolved`; properties without a value are left out. Every NEEDED edge
+/// is a `:DEPENDS_ON` relationship from the dependent to the library it needs, the reverse of `edges`.
+pub fn to_cypher(result: &TopoSortResult) -> String {
+    let libs: HashMap<&str, &Lib> = result.topo_sorted_libs.iter().map(|lib| (lib.name.as_str(), lib)).collect();
+    let index: HashMap<&str, usize> = result.vertices.iter().enumerate().map(|(idx, v)| (v.as_str(), idx)).collect();
+    let mut cypher = String::new();
+    for (idx, v) in result.vertices.iter().enumerate() {
+        let lib = libs.get(v.as_str());
+        let mut properties = vec![format!("name: {}", quoted(v))];
+        if let Some(path) = lib.and_then(|lib| lib.path.as_deref()) {
+            properties.push(format!("path: {}", quoted(path)));
+        }
+        if let Some(depth) = lib.and_then(|lib| lib.min_depth) {
+            properties.push(format!("min_depth: {}", depth));
+        }
+        if let Some(depth) = lib.and_then(|lib| lib.max_depth) {
+            properties.push(format!("max_depth: {}", depth));
+        }
+        properties.push(format!("unresolved: {}", result.unresolved.contains(v)));
+        writeln!(cypher, "CREATE (n{}:Library {{{}}})", idx, properties.join(", ")).unwrap();
+    }
+    for edge in &result.edges {
+        if let (Some(src), Some(dst)) = (index.get(edge.src.as_str()), index.get(edge.dst.as_str())) {
+            writeln!(cypher, "CREATE (n{})-[:DEPENDS_ON]->(n{})", dst, src).unwrap();
+        }
+    }
+    cypher.push_str(";\n");
+    cypher
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::cypher::to_cypher;
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn to_cypher_should_create_libraries_and_dependencies() {
+        let mut result = small_dag();
+        result.vertices[5] = "lib'F'".to_string();
+        result.edges.retain(|edge| edge.src != "F");
+        let cypher = to_cypher(&result);
+        assert!(cypher.contains("CREATE (n3:Library {name: 'D', path: '/usr/lib/D', min_depth: 2, max_depth: 2, unresolved: false})\n"));
+        // Not in the libraries, so only the name and the unresolved flag are known
+        assert!(cypher.contains("CREATE (n5:Library {name: 'lib\\'F\\'', unresolved: false})\n"));
+        // B needs D
+        assert!(cypher.contains("CREATE (n1)-[:DEPENDS_ON]->(n3)\n"));
+        assert_eq!(result.edges.len(), cypher.matches(":DEPENDS_ON").count());
+        assert_eq!(1, cypher.matches(';').count());
+        assert!(cypher.ends_with(")\n;\n"));
+    }
+}
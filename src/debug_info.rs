@@ -0,0 +1,76 @@
+use goblin::elf::note::NT_GNU_BUILD_ID;
+use goblin::elf::Elf;
+
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+
+/// Metadata found for a library in a separate debug sysroot (`--debug-root`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// GNU build-id of the runtime library, hex encoded
+    pub build_id: String,
+    /// Path of the matching separate debug file, if there is one under the debug root
+    pub debug_file: Option<String>,
+    /// Number of entries in the debug file's `.symtab`
+    pub symbol_count: Option<usize>,
+    /// Whether the debug file carries DWARF (`.debug_info`)
+    pub has_dwarf: Option<bool>,
+}
+
+pub fn read_build_id(elf: &Elf, bytes: &[u8]) -> Option<String> {
+    elf.iter_note_headers(bytes)?
+        .filter_map(Result::ok)
+        .find(|note| note.n_type == NT_GNU_BUILD_ID && note.name == "GNU")
+        .map(|note| note.desc.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Where debuggers look for the debug file of a build-id: `.build-id/<first byte>/<rest>.debug`,
+/// either under `usr/lib/debug` of the debug root or directly under it when the debug root *is*
+/// the `usr/lib/debug` directory.
+pub fn debug_file_candidates(debug_root: &Path, build_id: &str) -> Vec<PathBuf> {
+    if build_id.len() < 3 {
+        return Vec::new();
+    }
+    let relative = Path::new(".build-id").join(&build_id[..2]).join(format!("{}.debug", &build_id[2..]));
+    vec![debug_root.join("usr/lib/debug").join(&relative), debug_root.join(relative)]
+}
+
+/// Looks up the debug information of the library at `path`, `None` when it has no build-id
+pub fn lookup(path: &Path, debug_root: &Path) -> Option<DebugInfo> {
+    let bytes = std::fs::read(path).ok()?;
+    let elf = Elf::parse(&bytes).ok()?;
+    let build_id = read_build_id(&elf, &bytes)?;
+    let mut info = DebugInfo { build_id, debug_file: None, symbol_count: None, has_dwarf: None };
+    if let Some(debug_file) = debug_file_candidates(debug_root, &info.build_id).into_iter().find(|p| p.is_file()) {
+        if let Ok(debug_bytes) = std::fs::read(&debug_file) {
+            if let Ok(debug_elf) = Elf::parse(&debug_bytes) {
+                info.symbol_count = Some(debug_elf.syms.len());
+                info.has_dwarf = Some(debug_elf.section_headers.iter()
+                    .any(|sh| debug_elf.shdr_strtab.get_at(sh.sh_name) == Some(".debug_info")));
+            }
+        }
+        info.debug_file = debug_file.to_str().map(String::from);
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::debug_info::debug_file_candidates;
+
+    #[test]
+    fn debug_file_candidates_should_follow_build_id_layout() {
+        let candidates = debug_file_candidates(Path::new("/srv/debug"), "0a1b2c3d");
+        assert_eq!(vec![
+            PathBuf::from("/srv/debug/usr/lib/debug/.build-id/0a/1b2c3d.debug"),
+            PathBuf::from("/srv/debug/.build-id/0a/1b2c3d.debug"),
+        ], candidates);
+    }
+
+    #[test]
+    fn debug_file_candidates_when_build_id_is_too_short_should_be_empty() {
+        assert!(debug_file_candidates(Path::new("/srv/debug"), "0a").is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeSet;
+
+use crate::query::dependencies_of;
+use crate::TopoSortResult;
+
+/// Libraries one binary (transitively) depends on and the other does not, compared by soname
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LibraryDelta {
+    pub a: String,
+    pub b: String,
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    pub common: Vec<String>,
+}
+
+fn closure(result: &TopoSortResult, main_lib_name: &str) -> Result<BTreeSet<String>, String> {
+    dependencies_of(result, main_lib_name).map(|deps| deps.into_iter().collect())
+}
+
+/// Splits the dependency closures of `a` and `b` into the sonames only one of them needs and the common ones,
+/// each list sorted. Resolved paths play no part, so the same soname found in different places counts as common.
+pub fn library_delta(a_name: &str, a: &TopoSortResult, b_name: &str, b: &TopoSortResult) -> Result<LibraryDelta, String> {
+    let a_libs = closure(a, a_name)?;
+    let b_libs = closure(b, b_name)?;
+    Ok(LibraryDelta {
+        a: a_name.to_string(),
+        b: b_name.to_string(),
+        only_in_a: a_libs.difference(&b_libs).cloned().collect(),
+        only_in_b: b_libs.difference(&a_libs).cloned().collect(),
+        common: a_libs.intersection(&b_libs).cloned().collect(),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::binary_kind::BinaryKind;
+    use crate::delta::library_delta;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::query::tests::small_dag;
+    use crate::{get_topologically_sorted_result, AnalysisOptions};
+
+    #[test]
+    fn library_delta_should_compare_closures_by_soname() {
+        let a = small_dag();
+        // D is found somewhere else for the second binary, which still counts as the same library
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("G")
+            .add_library("B", &["D"])
+            .add_library_at("D", "/opt/lib/D", &[])
+            .add_library("G", &[])
+            .build();
+        let b = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default()).unwrap();
+        let delta = library_delta("A", &a, "A", &b).unwrap();
+        assert_eq!(vec!["C", "E", "F"], delta.only_in_a);
+        assert_eq!(vec!["G"], delta.only_in_b);
+        assert_eq!(vec!["B", "D"], delta.common);
+
+        let delta = library_delta("B", &a, "C", &a).unwrap();
+        assert!(delta.only_in_a.is_empty() && delta.only_in_b.is_empty());
+        assert_eq!(vec!["D", "E", "F"], delta.common);
+        assert!(library_delta("Z", &a, "A", &a).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use lddtree::{DependencyTree, Library};
+
+use std::path::PathBuf;
+
+/// Fluent construction of a synthetic `DependencyTree`, for exercising the analysis without real ELF files.
+///
+/// ```ignore
+/// let deps = DependencyTreeBuilder::new()
+///     .add_needed("libB.so")
+///     .add_library("libB.so", &["libC.so"])
+///     .add_missing_library("libC.so")
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct DependencyTreeBuilder {
+    tree: DependencyTree,
+}
+
+impl Default for DependencyTreeBuilder {
+    fn default() -> Self {
+        DependencyTreeBuilder::new()
+    }
+}
+
+impl DependencyTreeBuilder {
+    pub fn new() -> DependencyTreeBuilder {
+        DependencyTreeBuilder {
+            tree: DependencyTree {
+                interpreter: None,
+                needed: vec![],
+                libraries: Default::default(),
+                rpath: vec![],
+                runpath: vec![],
+            }
+        }
+    }
+
+    /// Sets the interpreter of the analyzed binary. Unlike lddtree, no library entry is added for it
+    pub fn set_interpreter(mut self, interpreter: &str) -> Self {
+        self.tree.interpreter = Some(interpreter.to_string());
+        self
+    }
+
+    /// Adds a direct (NEEDED) dependency of the analyzed binary
+    pub fn add_needed(mut self, name: &str) -> Self {
+        self.tree.needed.push(name.to_string());
+        self
+    }
+
+    pub fn set_rpath(mut self, rpath: &[&str]) -> Self {
+        self.tree.rpath = rpath.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    pub fn set_runpath(mut self, runpath: &[&str]) -> Self {
+        self.tree.runpath = runpath.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Adds a library found at `/usr/lib/<name>`
+    pub fn add_library(self, name: &str, needed: &[&str]) -> Self {
+        let path = PathBuf::from("/usr/lib").join(name);
+        self.add_library_at(name, path, needed)
+    }
+
+    /// Adds a library found at `path`
+    pub fn add_library_at(mut self, name: &str, path: impl Into<PathBuf>, needed: &[&str]) -> Self {
+        let path = path.into();
+        self.tree.libraries.insert(name.to_string(), Library {
+            name: name.to_string(),
+            path: path.clone(),
+            realpath: Some(path),
+            needed: needed.iter().map(|n| n.to_string()).collect(),
+            rpath: vec![],
+            runpath: vec![],
+        });
+        self
+    }
+
+    /// Adds a library that could not be found, the way lddtree reports it
+    pub fn add_missing_library(mut self, name: &str) -> Self {
+        self.tree.libraries.insert(name.to_string(), Library {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            realpath: None,
+            needed: vec![],
+            rpath: vec![],
+            runpath: vec![],
+        });
+        self
+    }
+
+    /// Sets the RPATH of a library added before
+    pub fn set_library_rpath(mut self, name: &str, rpath: &[&str]) -> Self {
+        let lib = self.tree.libraries.get_mut(name).unwrap_or_else(|| panic!("{} was not added", name));
+        lib.rpath = rpath.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Sets the RUNPATH of a library added before
+    pub fn set_library_runpath(mut self, name: &str, runpath: &[&str]) -> Self {
+        let lib = self.tree.libraries.get_mut(name).unwrap_or_else(|| panic!("{} was not added", name));
+        lib.runpath = runpath.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    pub fn build(self) -> DependencyTree {
+        self.tree
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+
+    #[test]
+    fn build_should_produce_tree_with_added_entries() {
+        let deps = DependencyTreeBuilder::new()
+            .set_interpreter("/lib64/ld-linux-x86-64.so.2")
+            .add_needed("B")
+            .set_rpath(&["/opt/app/lib"])
+            .set_runpath(&["$ORIGIN/../lib"])
+            .add_library("B", &["C"])
+            .set_library_rpath("B", &["/opt/lib"])
+            .set_library_runpath("B", &["$ORIGIN"])
+            .add_missing_library("C")
+            .build();
+        assert_eq!(Some("/lib64/ld-linux-x86-64.so.2".to_string()), deps.interpreter);
+        assert_eq!(vec!["B"], deps.needed);
+        assert_eq!(vec!["/opt/app/lib"], deps.rpath);
+        assert_eq!(vec!["$ORIGIN/../lib"], deps.runpath);
+        let b = &deps.libraries["B"];
+        assert!(b.found());
+        assert_eq!(PathBuf::from("/usr/lib/B"), b.path);
+        assert_eq!(vec!["C"], b.needed);
+        assert_eq!(vec!["/opt/lib"], b.rpath);
+        assert_eq!(vec!["$ORIGIN"], b.runpath);
+        assert!(!deps.libraries["C"].found());
+    }
+}
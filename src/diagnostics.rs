@@ -0,0 +1,83 @@
+use log::{error, warn};
+
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    /// The run fails
+    Error,
+}
+
+/// A warning or error about the analysis, in the form written to `--warnings-file`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Stable identifier of the kind of problem, e.g. `unresolved_libraries`
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    /// Libraries the diagnostic is about, if any
+    pub libraries: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn warning(code: &str, message: String, libraries: Vec<String>) -> Diagnostic {
+        Diagnostic { code: code.to_string(), severity: Severity::Warning, message, libraries }
+    }
+
+    pub fn error(code: &str, message: String, libraries: Vec<String>) -> Diagnostic {
+        Diagnostic { code: code.to_string(), severity: Severity::Error, message, libraries }
+    }
+}
+
+/// Where diagnostics go: always to the log, and with `--warnings-file` also to that file as JSON lines
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    file: Option<File>,
+}
+
+impl Diagnostics {
+    pub fn new(warnings_file: Option<&Path>) -> std::io::Result<Diagnostics> {
+        let file = warnings_file.map(File::create).transpose()?;
+        Ok(Diagnostics { file })
+    }
+
+    /// Logs `diagnostic` and appends it to the warnings file. Every line is written right away so that
+    /// the file is complete even when the run exits because of an error.
+    pub fn report(&self, diagnostic: Diagnostic) {
+        match diagnostic.severity {
+            Severity::Warning => warn!("{}", diagnostic.message),
+            Severity::Error => error!("{}", diagnostic.message),
+        }
+        if let Some(mut file) = self.file.as_ref() {
+            let line = serde_json::to_string(&diagnostic).unwrap();
+            if let Err(err) = writeln!(file, "{}", line) {
+                error!("Cannot write to the warnings file: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::diagnostics::{Diagnostic, Diagnostics};
+
+    #[test]
+    fn report_should_append_json_lines() {
+        let path = std::env::temp_dir().join(format!("lddtopo-rs-warnings-{}.jsonl", std::process::id()));
+        let diagnostics = Diagnostics::new(Some(&path)).unwrap();
+        diagnostics.report(Diagnostic::warning("unresolved_libraries", "1 libraries are unresolved".to_string(), vec!["libz.so.1".to_string()]));
+        diagnostics.report(Diagnostic::error("isolated_main", "app resolves nothing".to_string(), vec![]));
+        let expected = "\
+{\"code\":\"unresolved_libraries\",\"severity\":\"warning\",\"message\":\"1 libraries are unresolved\",\"libraries\":[\"libz.so.1\"]}
+{\"code\":\"isolated_main\",\"severity\":\"error\",\"message\":\"app resolves nothing\",\"libraries\":[]}
+";
+        assert_eq!(expected, std::fs::read_to_string(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+}
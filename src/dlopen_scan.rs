@@ -0,0 +1,107 @@
+use goblin::elf::section_header::SHN_UNDEF;
+use goblin::elf::Elf;
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Whether `s` looks like a soname a library may pass to `dlopen`: `lib<name>.so` with an optional
+/// version suffix, e.g. `libGL.so.1` or `libnss_files.so.2`
+pub fn is_soname_like(s: &str) -> bool {
+    let rest = match s.strip_prefix("lib") {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let so_idx = match rest.find(".so") {
+        Some(idx) => idx,
+        None => return false,
+    };
+    let (stem, suffix) = (&rest[..so_idx], &rest[so_idx + ".so".len()..]);
+    let valid_stem = !stem.is_empty() && stem.chars().all(|c| c.is_ascii_alphanumeric() || "_-+.".contains(c));
+    let valid_suffix = suffix.is_empty()
+        || (suffix.starts_with('.') && suffix[1..].split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())));
+    valid_stem && valid_suffix
+}
+
+/// Whether `elf` imports `dlopen` or `dlmopen`, i.e. may load libraries at runtime
+pub fn imports_dlopen(elf: &Elf) -> bool {
+    elf.dynsyms.iter()
+        .filter(|sym| sym.st_shndx == SHN_UNDEF as usize)
+        .any(|sym| matches!(elf.dynstrtab.get_at(sym.st_name), Some("dlopen" | "dlmopen")))
+}
+
+/// Soname-like NUL terminated strings in the `.rodata` section of the ELF file `elf` parsed from `bytes`, sorted
+/// and deduplicated
+pub fn soname_strings(elf: &Elf, bytes: &[u8]) -> Vec<String> {
+    let mut sonames: BTreeSet<String> = BTreeSet::new();
+    for sh in &elf.section_headers {
+        if elf.shdr_strtab.get_at(sh.sh_name) != Some(".rodata") {
+            continue;
+        }
+        let start = sh.sh_offset as usize;
+        let rodata = match start.checked_add(sh.sh_size as usize).and_then(|end| bytes.get(start..end)) {
+            Some(rodata) => rodata,
+            None => continue,
+        };
+        for candidate in rodata.split(|b| *b == 0) {
+            if let Ok(s) = std::str::from_utf8(candidate) {
+                if is_soname_like(s) {
+                    sonames.insert(s.to_string());
+                }
+            }
+        }
+    }
+    sonames.into_iter().collect()
+}
+
+/// Reads the library at `path` and returns the sonames found in its `.rodata`, empty if it cannot be read or
+/// does not import `dlopen`, in which case the strings cannot name a library it loads
+pub fn scan(path: &Path) -> Vec<String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Vec::new(),
+    };
+    match Elf::parse(&bytes) {
+        Ok(elf) if imports_dlopen(&elf) => soname_strings(&elf, &bytes),
+        _ => Vec::new(),
+    }
+}
+
+/// Directories a guessed soname is looked up in: the extra library paths, then the directories the
+/// graph's libraries were resolved from, then the default system directories under `root`
+pub fn search_dirs(root: &Path, library_paths: &[PathBuf], resolved_paths: &[&str]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = library_paths.to_vec();
+    dirs.extend(resolved_paths.iter().filter_map(|path| Path::new(path).parent()).map(Path::to_path_buf));
+    dirs.extend(["lib64", "lib", "usr/lib64", "usr/lib"].iter().map(|dir| root.join(dir)));
+    let mut seen: BTreeSet<PathBuf> = BTreeSet::new();
+    dirs.retain(|dir| seen.insert(dir.clone()));
+    dirs
+}
+
+/// The first file called `soname` in `dirs`
+pub fn resolve(soname: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter().map(|dir| dir.join(soname)).find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::dlopen_scan::{is_soname_like, search_dirs};
+
+    #[test]
+    fn is_soname_like_should_accept_versioned_and_unversioned_sonames() {
+        for s in ["libGL.so", "libGL.so.1", "libnss_files.so.2", "libstdc++.so.6.0.30", "libgtk-3.so.0"] {
+            assert!(is_soname_like(s), "{}", s);
+        }
+        for s in ["lib", "lib.so", "libfoo", "libfoo.so.", "libfoo.so.x", "libfoo.so.1 ", "/usr/lib/libfoo.so", "foo.so", "libfoo.sox"] {
+            assert!(!is_soname_like(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn search_dirs_should_prefer_library_paths_then_resolved_dirs_without_duplicates() {
+        let dirs = search_dirs(Path::new("/root"), &[PathBuf::from("/opt/lib")], &["/opt/lib/libA.so", "/root/usr/lib/libB.so"]);
+        let expected: Vec<PathBuf> = ["/opt/lib", "/root/usr/lib", "/root/lib64", "/root/lib", "/root/usr/lib64"]
+            .iter().map(PathBuf::from).collect();
+        assert_eq!(expected, dirs);
+    }
+}
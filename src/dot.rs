@@ -0,0 +1,410 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::collapse::CollapsedView;
+use crate::html_label::{html_label, NodeDetails};
+use crate::label_template::{LabelFields, LabelTemplate};
+use crate::{Edge, EdgeKind, Lib, TopoSortResult};
+
+/// How `render` draws the graph
+#[derive(Debug, Default)]
+pub struct DotOptions {
+    /// Group the nodes into clusters by the directory each library was resolved from, see `clusters_by_dir`
+    pub cluster_by_dir: bool,
+    /// HTML-like labels with the path and system/leaf/unresolved badges, they win over `label_template`
+    pub html_labels: bool,
+    /// Format of the node labels, the name when there is none
+    pub label_template: Option<LabelTemplate>,
+    /// Root the libraries were resolved under, the badges tell system paths below it apart
+    pub root: PathBuf,
+    /// Label of the whole graph, e.g. why it is truncated
+    pub note: Option<String>,
+}
+
+/// Badges of every library in the graph for `html_labels`
+fn node_details(result: &TopoSortResult, root: &Path) -> HashMap<String, NodeDetails> {
+    let with_dependencies: HashSet<&str> = result.edges.iter().map(|e| e.dst.as_str()).collect();
+    result.topo_sorted_libs.iter().chain(result.dlopen_libs.iter()).chain(result.runtime_libs.iter())
+        .map(|lib| {
+            let details = NodeDetails {
+                path: lib.path.clone(),
+                system: lib.path.as_deref().map(|path| NodeDetails::is_system_path(root, path)).unwrap_or(false),
+                leaf: !with_dependencies.contains(lib.name.as_str()),
+                unresolved: result.unresolved.contains(&lib.name),
+                truncated: lib.truncated,
+            };
+            (lib.name.clone(), details)
+        })
+        .collect()
+}
+
+/// Values for `label_template` of every library in the graph
+fn label_fields(result: &TopoSortResult) -> HashMap<String, LabelFields> {
+    result.topo_sorted_libs.iter().chain(result.dlopen_libs.iter()).chain(result.runtime_libs.iter())
+        .map(|lib| {
+            let fields = LabelFields {
+                path: lib.path.clone(),
+                version: LabelFields::version_of(&lib.name, lib.path.as_deref()),
+                package: lib.package.as_ref().map(|package| package.name.clone()),
+                depth: lib.min_depth,
+            };
+            (lib.name.clone(), fields)
+        })
+        .collect()
+}
+
+/// The plain text label of every library in `result` for the exporters other than DOT: `template` rendered with the
+/// values of the library, or its name when there is no template
+pub fn text_label<'a>(result: &TopoSortResult, template: Option<&'a LabelTemplate>) -> impl Fn(&str) -> String + 'a {
+    let fields = label_fields(result);
+    move |v| match template {
+        Some(template) => template.render(v, fields.get(v)),
+        None => v.to_string(),
+    }
+}
+
+/// The graph as displayed, i.e. after collapsing and `--node-limit`, in DOT with the labels and clusters `options` ask for
+pub fn render(result: &TopoSortResult, view: &CollapsedView, options: &DotOptions) -> String {
+    let clusters = if options.cluster_by_dir {
+        clusters_by_dir(&view.vertices, &result.topo_sorted_libs)
+    } else {
+        BTreeMap::new()
+    };
+    let details = node_details(result, &options.root);
+    let fields = label_fields(result);
+    let truncated: HashSet<&str> = result.topo_sorted_libs.iter().filter(|lib| lib.truncated).map(|lib| lib.name.as_str()).collect();
+    let label: Box<dyn Fn(&str) -> String> = match &options.label_template {
+        _ if options.html_labels => Box::new(|v| html_label(v, details.get(v))),
+        Some(template) => Box::new(|v| quoted_label(&template.render(v, fields.get(v)))),
+        None if !truncated.is_empty() => Box::new(|v| match truncated.contains(v) {
+            true => quoted_label(&format!("{}\n(truncated)", v)),
+            false => quoted_label(v),
+        }),
+        None => Box::new(quoted_label),
+    };
+    to_dot(&view.vertices, &view.edges, &clusters, &label, options.note.as_deref())
+}
+
+/// Writes the `render`ed graph to `dot_path`
+pub fn export_to_dot(result: &TopoSortResult, view: &CollapsedView, options: &DotOptions, dot_path: &Path) -> std::io::Result<()> {
+    std::fs::write(dot_path, render(result, view, options))
+}
+
+/// Groups vertices by the directory their library was resolved from. Vertices without a known path
+/// (unresolved or synthetic nodes) are left out and rendered outside of any cluster.
+pub fn clusters_by_dir(vertices: &[String], libs: &[Lib]) -> BTreeMap<String, Vec<String>> {
+    let name_to_path: HashMap<&str, &str> = libs.iter()
+        .filter_map(|lib| lib.path.as_deref().map(|path| (lib.name.as_str(), path)))
+        .collect();
+    let mut clusters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for v in vertices {
+        let dir = name_to_path.get(v.as_str())
+            .and_then(|path| Path::new(path).parent())
+            .and_then(|dir| dir.to_str());
+        if let Some(dir) = dir {
+            clusters.entry(dir.to_string()).or_default().push(v.clone());
+        }
+    }
+    clusters.values_mut().for_each(|members| members.sort());
+    clusters
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// `s` as a quoted DOT string, the default node label
+pub fn quoted_label(s: &str) -> String {
+    format!("\"{}\"", escape_dot(s))
+}
+
+/// Renders the graph in DOT. Nodes are numbered in `vertices` order; `clusters` (directory -> members)
+/// is a `BTreeMap` with sorted members so that the emitted text is byte-stable between runs.
+/// `edges` maps each displayed edge to the number of original edges it stands for, edges merged by
+/// collapsing are drawn thicker and labelled with that count, every other `EdgeKind` has a style of its own.
+/// Both ends of every edge are in `vertices`, `collapse_by_prefix` leaves out the other edges.
+/// `label` renders the label attribute of a vertex, e.g. `quoted_label` or an HTML-like label.
+/// `note` becomes the label of the whole graph, shown at the top.
+pub fn to_dot(vertices: &[String], edges: &BTreeMap<Edge, usize>, clusters: &BTreeMap<String, Vec<String>>,
+          label: &dyn Fn(&str) -> String, note: Option<&str>) -> String {
+    let vertex_to_index: HashMap<&str, usize> = vertices.iter().enumerate().map(|(idx, v)| (v.as_str(), idx)).collect();
+    let mut dot = String::from("digraph {\n");
+    if let Some(note) = note {
+        dot.push_str(&format!("    label = {}\n    labelloc = t\n", quoted_label(note)));
+    }
+    let mut clustered: HashSet<&str> = HashSet::new();
+    for (cluster_idx, (dir, members)) in clusters.iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{} {{\n", cluster_idx));
+        dot.push_str(&format!("        label = \"{}\"\n", escape_dot(dir)));
+        for member in members {
+            if let Some(idx) = vertex_to_index.get(member.as_str()) {
+                dot.push_str(&format!("        {} [ label = {} ]\n", idx, label(member)));
+                clustered.insert(member.as_str());
+            }
+        }
+        dot.push_str("    }\n");
+    }
+    for (idx, v) in vertices.iter().enumerate() {
+        if !clustered.contains(v.as_str()) {
+            dot.push_str(&format!("    {} [ label = {} ]\n", idx, label(v)));
+        }
+    }
+    for (edge, multiplicity) in edges {
+        let from_idx = vertex_to_index.get(edge.src.as_str()).unwrap();
+        let to_idx = vertex_to_index.get(edge.dst.as_str()).unwrap();
+        let attributes = match edge.kind {
+            EdgeKind::Dlopen => "style = dashed".to_string(),
+            EdgeKind::Runtime => "style = dotted".to_string(),
+            EdgeKind::Interpreter => "color = gray, style = bold".to_string(),
+            EdgeKind::Weak => "style = dashed, arrowhead = empty".to_string(),
+            EdgeKind::Needed if *multiplicity > 1 => format!("penwidth = {}, label = \"{}\"", multiplicity, multiplicity),
+            EdgeKind::Needed => String::new(),
+        };
+        if attributes.is_empty() {
+            dot.push_str(&format!("    {} -> {} [ ]\n", from_idx, to_idx));
+        } else {
+            dot.push_str(&format!("    {} -> {} [ {} ]\n", from_idx, to_idx, attributes));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Renders a cyclic graph in DOT with every strongly connected component in `components` as a
+/// `cluster_*` subgraph. Edges inside a component (the cycles) are drawn red; between components
+/// only one edge per pair of components is drawn, clipped at the cluster borders, so that the
+/// edges outside of the clusters form the condensed DAG.
+pub fn scc_to_dot(vertices: &[String], edges: &[Edge], components: &[Vec<String>]) -> String {
+    let vertex_to_index: HashMap<&str, usize> = vertices.iter().enumerate().map(|(idx, v)| (v.as_str(), idx)).collect();
+    let component_of: HashMap<&str, usize> = components.iter().enumerate()
+        .flat_map(|(component_idx, members)| members.iter().map(move |m| (m.as_str(), component_idx)))
+        .collect();
+    let mut dot = String::from("digraph {\n    compound = true\n");
+    for (component_idx, members) in components.iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{} {{\n", component_idx));
+        dot.push_str(&format!("        label = \"SCC {} ({} libraries)\"\n", component_idx, members.len()));
+        dot.push_str("        color = red\n");
+        for member in members {
+            dot.push_str(&format!("        {} [ label = {} ]\n", vertex_to_index[member.as_str()], quoted_label(member)));
+        }
+        dot.push_str("    }\n");
+    }
+    for (idx, v) in vertices.iter().enumerate() {
+        if !component_of.contains_key(v.as_str()) {
+            dot.push_str(&format!("    {} [ label = {} ]\n", idx, quoted_label(v)));
+        }
+    }
+    // A node is identified by its vertex, or by its component when it has one
+    let node_of = |v: &str| component_of.get(v).map(|c| (true, *c)).unwrap_or((false, vertex_to_index[v]));
+    let mut condensed: HashSet<((bool, usize), (bool, usize))> = HashSet::new();
+    for edge in edges {
+        let (src, dst) = (node_of(&edge.src), node_of(&edge.dst));
+        let (from_idx, to_idx) = (vertex_to_index[edge.src.as_str()], vertex_to_index[edge.dst.as_str()]);
+        if src == dst {
+            dot.push_str(&format!("    {} -> {} [ color = red ]\n", from_idx, to_idx));
+        } else if condensed.insert((src, dst)) {
+            let mut attributes: Vec<String> = Vec::new();
+            if src.0 {
+                attributes.push(format!("ltail = cluster_{}", src.1));
+            }
+            if dst.0 {
+                attributes.push(format!("lhead = cluster_{}", dst.1));
+            }
+            dot.push_str(&format!("    {} -> {} [ {} ]\n", from_idx, to_idx, attributes.join(", ")));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::path::PathBuf;
+
+    use crate::binary_kind::BinaryKind;
+    use crate::collapse::{collapse_by_prefix, collapse_result};
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::dot::{clusters_by_dir, quoted_label, render, scc_to_dot, to_dot, DotOptions};
+    use crate::focus::limit_depth;
+    use crate::html_label::{html_label, NodeDetails};
+    use crate::label_template::LabelTemplate;
+    use crate::query::tests::small_dag;
+    use crate::{get_topologically_sorted_result, AnalysisOptions, Edge, EdgeKind, Lib};
+
+    #[test]
+    fn render_should_apply_the_options() {
+        let result = small_dag();
+        let view = collapse_by_prefix(&result.vertices, &result.edges, &[]);
+        let plain = render(&result, &view, &DotOptions::default());
+        assert!(plain.contains("    3 [ label = \"D\" ]\n"));
+        let options = DotOptions {
+            cluster_by_dir: true,
+            label_template: Some("{name} {depth}".parse().unwrap()),
+            root: PathBuf::from("/"),
+            note: Some("note".to_string()),
+            ..Default::default()
+        };
+        let dot = render(&result, &view, &options);
+        assert!(dot.contains("    label = \"note\"\n"));
+        assert!(dot.contains("        label = \"/usr/lib\"\n"));
+        assert!(dot.contains("        3 [ label = \"D 2\" ]\n"));
+
+        let mut cut = small_dag();
+        limit_depth(&mut cut, 2);
+        let view = collapse_by_prefix(&cut.vertices, &cut.edges, &[]);
+        assert!(render(&cut, &view, &DotOptions::default()).contains("    3 [ label = \"D\\n(truncated)\" ]\n"));
+    }
+
+    #[test]
+    fn to_dot_without_clusters_should_number_nodes_in_vertex_order() {
+        let vertices = vec!["A".to_string(), "B".to_string()];
+        let edges = BTreeMap::from([(Edge { src: "B".to_string(), dst: "A".to_string(), kind: EdgeKind::Needed, ..Default::default() }, 1)]);
+        let expected = "digraph {\n    0 [ label = \"A\" ]\n    1 [ label = \"B\" ]\n    1 -> 0 [ ]\n}\n";
+        assert_eq!(expected, to_dot(&vertices, &edges, &Default::default(), &quoted_label, None));
+    }
+
+    #[test]
+    fn to_dot_when_edges_were_merged_should_weight_them() {
+        let vertices = vec!["A".to_string(), "libLLVM*".to_string()];
+        let edges = BTreeMap::from([(Edge { src: "libLLVM*".to_string(), dst: "A".to_string(), kind: EdgeKind::Needed, ..Default::default() }, 3)]);
+        assert!(to_dot(&vertices, &edges, &Default::default(), &quoted_label, None).contains("    1 -> 0 [ penwidth = 3, label = \"3\" ]\n"));
+    }
+
+    #[test]
+    fn to_dot_when_edge_is_dlopen_guess_should_draw_it_dashed() {
+        let vertices = vec!["A".to_string(), "libplugin.so".to_string()];
+        let edges = BTreeMap::from([(Edge { src: "libplugin.so".to_string(), dst: "A".to_string(), kind: EdgeKind::Dlopen, ..Default::default() }, 1)]);
+        assert!(to_dot(&vertices, &edges, &Default::default(), &quoted_label, None).contains("    1 -> 0 [ style = dashed ]\n"));
+    }
+
+    #[test]
+    fn to_dot_should_draw_interpreter_and_weak_edges_in_their_own_style() {
+        let vertices = vec!["A".to_string(), "ld-linux-x86-64.so.2".to_string(), "libopt.so".to_string()];
+        let edges = BTreeMap::from([
+            (Edge { src: "ld-linux-x86-64.so.2".to_string(), dst: "A".to_string(), kind: EdgeKind::Interpreter, ..Default::default() }, 1),
+            (Edge { src: "libopt.so".to_string(), dst: "A".to_string(), kind: EdgeKind::Weak, ..Default::default() }, 1),
+        ]);
+        let dot = to_dot(&vertices, &edges, &Default::default(), &quoted_label, None);
+        assert!(dot.contains("    1 -> 0 [ color = gray, style = bold ]\n"));
+        assert!(dot.contains("    2 -> 0 [ style = dashed, arrowhead = empty ]\n"));
+    }
+
+    #[test]
+    fn render_should_draw_the_interpreter_edges_of_an_analysis_in_their_own_style() {
+        let dt = DependencyTreeBuilder::new()
+            .set_interpreter("/lib64/ld-linux-x86-64.so.2")
+            .add_needed("B")
+            .add_library("B", &["C"])
+            .add_library("C", &["ld-linux-x86-64.so.2"])
+            .build();
+        let options = AnalysisOptions { include_interpreter: true, ..Default::default() };
+        let result = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::PieExecutable, &dt, &options).unwrap();
+        let view = collapse_result(&result, &["B".to_string()]);
+        let dot = render(&result, &view, &DotOptions::default());
+        let index = |name: &str| view.vertices.iter().position(|v| v == name).unwrap();
+        let (interpreter, a, b, c) = (index("ld-linux-x86-64.so.2"), index("A"), index("B*"), index("C"));
+        assert!(dot.contains(&format!("    {} -> {} [ color = gray, style = bold ]\n", interpreter, a)));
+        assert!(dot.contains(&format!("    {} -> {} [ color = gray, style = bold ]\n", interpreter, b)));
+        assert!(dot.contains(&format!("    {} -> {} [ ]\n", interpreter, c)));
+    }
+
+    #[test]
+    fn to_dot_with_note_should_label_the_graph() {
+        let vertices = vec!["A".to_string()];
+        let dot = to_dot(&vertices, &BTreeMap::new(), &Default::default(), &quoted_label, Some("Truncated by --node-limit: 1 of 9 libraries shown"));
+        assert!(dot.starts_with("digraph {\n    label = \"Truncated by --node-limit: 1 of 9 libraries shown\"\n    labelloc = t\n"));
+    }
+
+    #[test]
+    fn to_dot_with_html_labels_should_render_details_and_fall_back_to_name() {
+        let vertices = vec!["A".to_string(), "libLLVM*".to_string()];
+        let details = HashMap::from([("A".to_string(), NodeDetails { leaf: true, ..Default::default() })]);
+        let dot = to_dot(&vertices, &BTreeMap::new(), &Default::default(), &|v| html_label(v, details.get(v)), None);
+        assert!(dot.contains("    0 [ label = <<B>A</B><BR/><FONT POINT-SIZE=\"8\"><FONT COLOR=\"darkgreen\">leaf</FONT></FONT>> ]\n"));
+        assert!(dot.contains("    1 [ label = <<B>libLLVM*</B>> ]\n"));
+    }
+
+    #[test]
+    fn to_dot_with_label_template_should_render_every_node() {
+        let vertices = vec!["libz.so.1".to_string()];
+        let template: LabelTemplate = "{name}\n{version}".parse().unwrap();
+        let dot = to_dot(&vertices, &BTreeMap::new(), &Default::default(), &|v| quoted_label(&template.render(v, None)), None);
+        assert!(dot.contains("    0 [ label = \"libz.so.1\\n\" ]\n"));
+    }
+
+    #[test]
+    fn to_dot_with_clusters_by_dir_should_be_sorted_regardless_of_input_order() {
+        let vertices: Vec<String> = ["A", "B", "C", "D"].iter().map(|s| s.to_string()).collect();
+        let lib = |name: &str, path: Option<&str>| Lib { name: name.to_string(), path: path.map(String::from), ..Default::default() };
+        let libs = vec![
+            lib("C", Some("/usr/lib/C")),
+            lib("A", Some("/opt/app/A")),
+            lib("D", None),
+            lib("B", Some("/usr/lib/B")),
+        ];
+        let mut reversed_libs: Vec<Lib> = libs.iter().map(|l| lib(&l.name, l.path.as_deref())).collect();
+        reversed_libs.reverse();
+
+        let clusters = clusters_by_dir(&vertices, &libs);
+        assert_eq!(clusters, clusters_by_dir(&vertices, &reversed_libs));
+        assert_eq!(vec!["/opt/app", "/usr/lib"], clusters.keys().collect::<Vec<_>>());
+        assert_eq!(vec!["B", "C"], clusters["/usr/lib"]);
+
+        let expected = "digraph {\n\
+            \x20   subgraph cluster_0 {\n\
+            \x20       label = \"/opt/app\"\n\
+            \x20       0 [ label = \"A\" ]\n\
+            \x20   }\n\
+            \x20   subgraph cluster_1 {\n\
+            \x20       label = \"/usr/lib\"\n\
+            \x20       1 [ label = \"B\" ]\n\
+            \x20       2 [ label = \"C\" ]\n\
+            \x20   }\n\
+            \x20   3 [ label = \"D\" ]\n\
+            }\n";
+        assert_eq!(expected, to_dot(&vertices, &BTreeMap::new(), &clusters, &quoted_label, None));
+    }
+
+    #[test]
+    fn scc_to_dot_should_cluster_components_and_condense_edges_between_them() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("C")
+            .add_needed("D")
+            .add_library("B", &["C"])
+            .add_library("C", &["B"])
+            .add_library("D", &["E"])
+            .add_library("E", &["F"])
+            .add_library("F", &["D"])
+            .build();
+        let err = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default()).unwrap_err();
+        assert_eq!(vec![vec!["B", "C"], vec!["D", "E", "F"]], err.components);
+        let expected = r#"digraph {
+    compound = true
+    subgraph cluster_0 {
+        label = "SCC 0 (2 libraries)"
+        color = red
+        1 [ label = "B" ]
+        2 [ label = "C" ]
+    }
+    subgraph cluster_1 {
+        label = "SCC 1 (3 libraries)"
+        color = red
+        3 [ label = "D" ]
+        4 [ label = "E" ]
+        5 [ label = "F" ]
+    }
+    0 [ label = "A" ]
+    1 -> 0 [ ltail = cluster_0 ]
+    1 -> 2 [ color = red ]
+    2 -> 1 [ color = red ]
+    3 -> 0 [ ltail = cluster_1 ]
+    3 -> 5 [ color = red ]
+    4 -> 3 [ color = red ]
+    5 -> 4 [ color = red ]
+}
+"#;
+        assert_eq!(expected, scc_to_dot(&err.vertices, &err.edges, &err.components));
+    }
+}
@@ -0,0 +1,126 @@
+use goblin::elf::header::{self, EI_CLASS, EI_OSABI, ELFCLASS64};
+use goblin::elf::Elf;
+
+use std::path::Path;
+
+use crate::debug_info::read_build_id;
+use crate::{Lib, TopoSortResult};
+
+/// Names of the ELF OS ABIs, others are written as their number
+const OS_ABIS: [(u8, &str); 13] = [
+    (header::ELFOSABI_NONE, "sysv"),
+    (header::ELFOSABI_HPUX, "hpux"),
+    (header::ELFOSABI_NETBSD, "netbsd"),
+    (header::ELFOSABI_GNU, "gnu"),
+    (header::ELFOSABI_SOLARIS, "solaris"),
+    (header::ELFOSABI_AIX, "aix"),
+    (header::ELFOSABI_IRIX, "irix"),
+    (header::ELFOSABI_FREEBSD, "freebsd"),
+    (header::ELFOSABI_TRU64, "tru64"),
+    (header::ELFOSABI_OPENBSD, "openbsd"),
+    (header::ELFOSABI_ARM_AEABI, "arm_aeabi"),
+    (header::ELFOSABI_ARM, "arm"),
+    (header::ELFOSABI_STANDALONE, "standalone"),
+];
+
+/// What the ELF header and dynamic section of a library tell about it, see `--elf-metadata`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfMetadata {
+    pub soname: Option<String>,
+    /// `e_machine` by its goblin name, e.g. `X86_64`
+    pub machine: String,
+    /// 32 or 64
+    pub class: u8,
+    pub os_abi: String,
+    /// GNU build-id, hex encoded
+    pub build_id: Option<String>,
+}
+
+pub fn os_abi_name(os_abi: u8) -> String {
+    OS_ABIS.iter()
+        .find(|(value, _)| *value == os_abi)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| os_abi.to_string())
+}
+
+impl ElfMetadata {
+    pub fn from_elf(elf: &Elf, bytes: &[u8]) -> ElfMetadata {
+        ElfMetadata {
+            soname: elf.soname.map(String::from),
+            machine: header::machine_to_str(elf.header.e_machine).to_string(),
+            class: if elf.header.e_ident[EI_CLASS] == ELFCLASS64 { 64 } else { 32 },
+            os_abi: os_abi_name(elf.header.e_ident[EI_OSABI]),
+            build_id: read_build_id(elf, bytes),
+        }
+    }
+
+    /// The metadata of the ELF file at `path`, `None` when it cannot be read or parsed
+    pub fn read(path: &Path) -> Option<ElfMetadata> {
+        let bytes = std::fs::read(path).ok()?;
+        let elf = Elf::parse(&bytes).ok()?;
+        Some(ElfMetadata::from_elf(&elf, &bytes))
+    }
+
+    pub fn apply_to(&self, lib: &mut Lib) {
+        lib.soname = self.soname.clone();
+        lib.machine = Some(self.machine.clone());
+        lib.class = Some(self.class);
+        lib.os_abi = Some(self.os_abi.clone());
+        lib.build_id = self.build_id.clone();
+    }
+}
+
+/// Libraries whose ELF machine or class, as recorded by `--elf-metadata`, differ from those of `main`, e.g. an
+/// i386 library resolved for an x86_64 binary through a bad library path. Empty when `main` has no metadata.
+pub fn mixed_architectures<'a>(result: &'a TopoSortResult, main: &str) -> Vec<&'a Lib> {
+    let arch = |lib: &Lib| lib.machine.clone().zip(lib.class);
+    let expected = match result.topo_sorted_libs.iter().find(|lib| lib.name == main).and_then(arch) {
+        Some(expected) => expected,
+        None => return Vec::new(),
+    };
+    result.topo_sorted_libs.iter()
+        .filter(|lib| arch(lib).map(|found| found != expected).unwrap_or(false))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::elf_metadata::{mixed_architectures, os_abi_name, ElfMetadata};
+    use crate::query::tests::small_dag;
+    use goblin::elf::header::{ELFOSABI_GNU, ELFOSABI_NONE};
+    use std::path::Path;
+
+    #[test]
+    fn os_abi_name_should_fall_back_to_the_number() {
+        assert_eq!("sysv", os_abi_name(ELFOSABI_NONE));
+        assert_eq!("gnu", os_abi_name(ELFOSABI_GNU));
+        assert_eq!("42", os_abi_name(42));
+    }
+
+    #[test]
+    fn read_should_describe_the_test_binary() {
+        let metadata = ElfMetadata::read(&std::env::current_exe().unwrap()).unwrap();
+        assert_eq!(8 * std::mem::size_of::<usize>(), metadata.class as usize);
+        assert!(!metadata.machine.is_empty());
+        assert!(ElfMetadata::read(Path::new("/nonexistent/libfoo.so")).is_none());
+    }
+
+    #[test]
+    fn mixed_architectures_should_list_libraries_of_another_machine_or_class() {
+        let mut result = small_dag();
+        assert!(mixed_architectures(&result, "A").is_empty());
+        for lib in &mut result.topo_sorted_libs {
+            let (machine, class) = match lib.name.as_str() {
+                "D" => ("386", 32),
+                "E" => ("X86_64", 32),
+                _ => ("X86_64", 64),
+            };
+            lib.machine = Some(machine.to_string());
+            lib.class = Some(class);
+        }
+        // F could not be parsed
+        result.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "F").for_each(|lib| lib.machine = None);
+        let mixed: Vec<&str> = mixed_architectures(&result, "A").iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(vec!["E", "D"], mixed);
+    }
+}
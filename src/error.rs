@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use crate::cycles::cycle_members;
+use crate::diagnostics::Diagnostic;
+
+/// Failures that end a run, each with its own exit code so that callers can tell them apart.
+/// Exit code 1 is left for the other errors reported as diagnostics, 2 is for invalid arguments as with clap.
+#[derive(Debug, thiserror::Error)]
+pub enum LddTopoError {
+    /// The binary or one of its libraries could not be parsed
+    #[error("Cannot analyze {}: {reason}", path.display())]
+    Analysis { path: PathBuf, reason: String },
+    /// The dependency graph is not a DAG, `cycles` holds the cycles that were reported
+    #[error("The graph is not DAG, it contains {}", describe_cycles(cycles))]
+    Cycle { cycles: Vec<Vec<String>> },
+    #[error("{context}: {source}")]
+    Io { context: String, source: std::io::Error },
+    #[error("Path {} is not valid UTF-8", .0.display())]
+    NonUtf8Path(PathBuf),
+    /// Libraries stayed unresolved while `--fail-on-missing` was set
+    #[error("{} libraries are unresolved: {:?}", .0.len(), .0)]
+    MissingLibrary(Vec<String>),
+    /// A library named on the command line, e.g. with `--focus` or `--impact-of`, is not in the graph
+    #[error("{reason}")]
+    UnknownLibrary { library: Option<String>, reason: String },
+    /// An input other than the binaries cannot be read or parsed
+    #[error("Cannot read {} {}: {reason}", input.description(), path.display())]
+    UnreadableInput { input: Input, path: PathBuf, reason: String },
+    /// The result does not conform to the JSON Schema, every violation was reported
+    #[error("The result does not conform to the JSON Schema: {} violations", .0.len())]
+    SchemaViolation(Vec<String>),
+    /// The result breaks its own invariants, every violation was reported
+    #[error("The result is inconsistent: {} violations", .0.len())]
+    InconsistentResult(Vec<String>),
+    /// A check of `check` or `analyze` failed, every finding was reported
+    #[error("The {check} failed for {}", libraries.join(", "))]
+    CheckFailed { check: String, libraries: Vec<String> },
+    /// The binary or its libraries are built for another architecture than expected, `code` says which check found it
+    #[error("{message}")]
+    ArchMismatch { code: &'static str, message: String, libraries: Vec<String> },
+    /// A dynamically linked binary resolves none of its direct dependencies and `--fail-on-isolated-main` was set
+    #[error("{binary} is dynamically linked but none of its {needed} direct dependencies was resolved, check --root-path and --library-paths")]
+    IsolatedMain { binary: String, needed: usize },
+    /// The results cannot be merged with the `--merge-strategy`
+    #[error("Cannot merge: {0}")]
+    Merge(String),
+    /// A query of `query` failed
+    #[error("{0}")]
+    Query(String),
+    /// Options that cannot be combined, found only once the run has started, e.g. a single-binary option with a merged graph
+    #[error("{0}")]
+    Usage(String),
+}
+
+/// The inputs of `LddTopoError::UnreadableInput`, each with its own diagnostic code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// The output of an earlier run
+    Result,
+    /// The file of `check --policy`
+    Policy,
+    /// The database of `--osv`
+    Osv,
+    /// The log of `--runtime-trace`
+    RuntimeTrace,
+    /// The file of `--license-map`
+    LicenseMap,
+}
+
+impl Input {
+    fn description(self) -> &'static str {
+        match self {
+            Input::Result => "the lddtopo-rs result",
+            Input::Policy => "the policy",
+            Input::Osv => "the OSV database",
+            Input::RuntimeTrace => "the runtime trace",
+            Input::LicenseMap => "the license map",
+        }
+    }
+
+    fn code(self) -> &'static str {
+        match self {
+            Input::Result => "unreadable_result",
+            Input::Policy => "unreadable_policy",
+            Input::Osv => "unreadable_osv",
+            Input::RuntimeTrace => "unreadable_trace",
+            Input::LicenseMap => "unreadable_license_map",
+        }
+    }
+}
+
+fn describe_cycles(cycles: &[Vec<String>]) -> String {
+    match cycles {
+        [cycle] => format!("cycle {}", cycle.join(" -> ")),
+        _ => format!("{} cycles", cycles.len()),
+    }
+}
+
+impl LddTopoError {
+    pub fn io(context: String, source: std::io::Error) -> LddTopoError {
+        LddTopoError::Io { context, source }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LddTopoError::Analysis { .. } => 3,
+            LddTopoError::Cycle { .. } => 4,
+            LddTopoError::Io { .. } => 5,
+            LddTopoError::NonUtf8Path(_) => 6,
+            LddTopoError::MissingLibrary(_) => 7,
+            LddTopoError::UnknownLibrary { .. } => 8,
+            LddTopoError::UnreadableInput { .. } => 9,
+            LddTopoError::SchemaViolation(_) => 10,
+            LddTopoError::InconsistentResult(_) => 11,
+            LddTopoError::CheckFailed { .. } => 12,
+            LddTopoError::ArchMismatch { .. } => 13,
+            LddTopoError::IsolatedMain { .. } => 14,
+            LddTopoError::Merge(_) => 15,
+            LddTopoError::Query(_) => 16,
+            LddTopoError::Usage(_) => 2,
+        }
+    }
+
+    /// The error as it is reported to the log and `--warnings-file`
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let (code, libraries) = match self {
+            LddTopoError::Analysis { path, .. } => ("analysis_failed", vec![path.to_string_lossy().into_owned()]),
+            LddTopoError::Cycle { cycles } => {
+                let mut members: Vec<String> = cycles.iter().flat_map(|cycle| cycle_members(cycle)).collect();
+                members.sort();
+                members.dedup();
+                ("dependency_cycle", members)
+            }
+            LddTopoError::Io { .. } => ("io_error", vec![]),
+            LddTopoError::NonUtf8Path(_) => ("non_utf8_path", vec![]),
+            LddTopoError::MissingLibrary(libraries) => ("missing_libraries", libraries.clone()),
+            LddTopoError::UnknownLibrary { library, .. } => ("unknown_library", library.iter().cloned().collect()),
+            LddTopoError::UnreadableInput { input, .. } => (input.code(), vec![]),
+            LddTopoError::SchemaViolation(_) => ("schema_violation", vec![]),
+            LddTopoError::InconsistentResult(_) => ("inconsistent_result", vec![]),
+            LddTopoError::CheckFailed { libraries, .. } => ("check_failed", libraries.clone()),
+            LddTopoError::ArchMismatch { code, libraries, .. } => (*code, libraries.clone()),
+            LddTopoError::IsolatedMain { binary, .. } => ("isolated_main", vec![binary.clone()]),
+            LddTopoError::Merge(_) => ("merge_failed", vec![]),
+            LddTopoError::Query(_) => ("query_failed", vec![]),
+            LddTopoError::Usage(_) => ("invalid_arguments", vec![]),
+        };
+        Diagnostic::error(code, self.to_string(), libraries)
+    }
+}
+
+/// `path` as a `&str`, or `LddTopoError::NonUtf8Path`
+pub fn utf8(path: &Path) -> Result<&str, LddTopoError> {
+    path.to_str().ok_or_else(|| LddTopoError::NonUtf8Path(path.to_path_buf()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::error::{Input, LddTopoError};
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn every_error_should_have_its_own_exit_code_and_diagnostic() {
+        let errors = [
+            LddTopoError::Analysis { path: PathBuf::from("/tmp/A"), reason: "Malformed entity".to_string() },
+            LddTopoError::Cycle { cycles: vec![vec!["A".to_string(), "B".to_string(), "A".to_string()]] },
+            LddTopoError::io("Cannot write /tmp/out.json".to_string(), std::io::Error::from(std::io::ErrorKind::PermissionDenied)),
+            LddTopoError::NonUtf8Path(PathBuf::from("/tmp/A")),
+            LddTopoError::MissingLibrary(vec!["libz.so.1".to_string()]),
+            LddTopoError::UnknownLibrary { library: Some("libfoo.so".to_string()), reason: "libfoo.so is not in the graph".to_string() },
+            LddTopoError::UnreadableInput { input: Input::Osv, path: PathBuf::from("/tmp/osv"), reason: "No such file or directory".to_string() },
+            LddTopoError::SchemaViolation(vec!["\"edges\" is a required property at ''".to_string()]),
+            LddTopoError::InconsistentResult(vec!["A is listed twice".to_string()]),
+            LddTopoError::CheckFailed { check: "policy /tmp/policy.yaml".to_string(), libraries: vec!["libfoo.so".to_string()] },
+            LddTopoError::ArchMismatch { code: "target_mismatch", message: "A is a 64-bit x86_64 binary".to_string(), libraries: vec!["A".to_string()] },
+            LddTopoError::IsolatedMain { binary: "A".to_string(), needed: 2 },
+            LddTopoError::Merge("nothing to merge".to_string()),
+            LddTopoError::Query("unknown command".to_string()),
+        ];
+        let codes: HashSet<i32> = errors.iter().map(LddTopoError::exit_code).collect();
+        assert_eq!(errors.len(), codes.len());
+        assert!(!codes.contains(&0) && !codes.contains(&1) && !codes.contains(&2));
+        let diagnostics: HashSet<String> = errors.iter().map(|err| err.to_diagnostic().code).collect();
+        assert_eq!(errors.len(), diagnostics.len());
+        assert_eq!("Cannot read the OSV database /tmp/osv: No such file or directory", errors[6].to_string());
+        assert_eq!("unreadable_osv", errors[6].to_diagnostic().code);
+
+        assert_eq!("The graph is not DAG, it contains cycle A -> B -> A", errors[1].to_string());
+        assert_eq!(vec!["A", "B"], errors[1].to_diagnostic().libraries);
+        let two = LddTopoError::Cycle { cycles: vec![vec![], vec![]] };
+        assert_eq!("The graph is not DAG, it contains 2 cycles", two.to_string());
+
+        // the exit code of invalid arguments, as clap uses it
+        let usage = LddTopoError::Usage("--repl needs a single binary".to_string());
+        assert_eq!((2, "invalid_arguments".to_string()), (usage.exit_code(), usage.to_diagnostic().code));
+    }
+}
@@ -0,0 +1,104 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+use serde::Deserialize;
+
+/// A pattern of `--exclude` and `--only`, matched against the name and the path of a library. `re:REGEX` is a
+/// regular expression that matches anywhere, anything else is a glob that has to match the whole name or path:
+/// `*` is any run of characters including `/`, `?` one character and `[...]` a character class.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LibraryPattern {
+    regex: Regex,
+}
+
+impl LibraryPattern {
+    pub fn matches(&self, name: &str, path: Option<&str>) -> bool {
+        self.regex.is_match(name) || path.map(|p| self.regex.is_match(p)).unwrap_or(false)
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut in_class = false;
+    for c in glob.chars() {
+        match c {
+            '[' if !in_class => {
+                in_class = true;
+                regex.push('[');
+            }
+            ']' if in_class => {
+                in_class = false;
+                regex.push(']');
+            }
+            '!' if in_class && regex.ends_with('[') => regex.push('^'),
+            _ if in_class => regex.push(c),
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+impl FromStr for LibraryPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = match s.strip_prefix("re:") {
+            Some(regex) => regex.to_string(),
+            None => glob_to_regex(s),
+        };
+        Regex::new(&regex)
+            .map(|regex| LibraryPattern { regex })
+            .map_err(|err| format!("{} is not a valid pattern: {}", s, err))
+    }
+}
+
+impl TryFrom<String> for LibraryPattern {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Whether a library stays in the graph: it matches none of `exclude` and, unless `only` is empty, one of `only`
+pub fn is_included(name: &str, path: Option<&str>, exclude: &[LibraryPattern], only: &[LibraryPattern]) -> bool {
+    !exclude.iter().any(|pattern| pattern.matches(name, path))
+        && (only.is_empty() || only.iter().any(|pattern| pattern.matches(name, path)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::filter::{is_included, LibraryPattern};
+
+    fn pattern(s: &str) -> LibraryPattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn library_pattern_should_match_globs_and_regexes_against_name_or_path() {
+        assert!(pattern("libc.so.*").matches("libc.so.6", None));
+        assert!(!pattern("libc.so.*").matches("libcrypto.so.3", None));
+        assert!(pattern("lib[cm].so.?").matches("libm.so.6", None));
+        assert!(!pattern("lib[!cm].so.?").matches("libm.so.6", None));
+        assert!(pattern("/lib/x86_64-linux-gnu/*").matches("libz.so.1", Some("/lib/x86_64-linux-gnu/libz.so.1")));
+        assert!(pattern("re:^lib(c|m)\\.so").matches("libm.so.6", None));
+        assert!(pattern("re:ssl").matches("libssl.so.3", None));
+        assert!("re:(".parse::<LibraryPattern>().is_err());
+    }
+
+    #[test]
+    fn is_included_should_apply_exclude_after_only() {
+        let exclude = vec![pattern("libc.so.*"), pattern("libm.so.*")];
+        let only = vec![pattern("/opt/app/*")];
+        assert!(is_included("libz.so.1", None, &exclude, &[]));
+        assert!(!is_included("libc.so.6", None, &exclude, &[]));
+        assert!(is_included("libfoo.so", Some("/opt/app/lib/libfoo.so"), &exclude, &only));
+        assert!(!is_included("libz.so.1", Some("/usr/lib/libz.so.1"), &exclude, &only));
+        assert!(!is_included("libc.so.6", Some("/opt/app/lib/libc.so.6"), &exclude, &only));
+    }
+}
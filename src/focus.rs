@@ -0,0 +1,191 @@
+use petgraph::graphmap::DiGraphMap;
+
+use std::collections::HashSet;
+
+use crate::id_gen::IdGen;
+use crate::query::{dependencies_of, impact_of};
+use crate::toposort::levels;
+use crate::{Edge, Lib, TopoSortResult};
+
+/// Restricts `result` to `lib`, everything it (transitively) depends on and everything that (transitively) depends
+/// on it, see `--focus`. The other libraries are dropped from the graph, the load order and every list derived from
+/// them, the load order of the remaining ones is kept. Returns how many libraries were dropped.
+pub fn focus(result: &mut TopoSortResult, lib: &str) -> Result<usize, String> {
+    let mut kept: HashSet<String> = dependencies_of(result, lib)?.into_iter().collect();
+    kept.extend(impact_of(result, lib)?.dependents);
+    kept.insert(lib.to_string());
+    let dropped = retain(result, &kept);
+    result.focus = Some(lib.to_string());
+    Ok(dropped)
+}
+
+/// Truncates `result` at `max_depth` NEEDED hops from the main library, see `--max-depth`. Libraries at the cut
+/// whose dependencies were dropped are marked `truncated`. Returns how many libraries were dropped.
+pub fn limit_depth(result: &mut TopoSortResult, max_depth: usize) -> usize {
+    let kept: HashSet<String> = result.topo_sorted_libs.iter()
+        .filter(|lib| lib.min_depth.map(|depth| depth <= max_depth).unwrap_or(false))
+        .map(|lib| lib.name.clone())
+        .collect();
+    let truncated: HashSet<String> = result.edges.iter()
+        .filter(|edge| !kept.contains(&edge.src) && kept.contains(&edge.dst))
+        .map(|edge| edge.dst.clone())
+        .collect();
+    let dropped = retain(result, &kept);
+    for lib in result.topo_sorted_libs.iter_mut().chain(result.library_map.values_mut()) {
+        lib.truncated = truncated.contains(&lib.name);
+    }
+    result.truncated_at_depth = Some(max_depth);
+    dropped
+}
+
+/// Drops every library not in `kept` from the graph, the load order and every list derived from them
+fn retain(result: &mut TopoSortResult, kept: &HashSet<String>) -> usize {
+    let total = result.vertices.len();
+    result.vertices.retain(|v| kept.contains(v));
+    result.edges.retain(|edge| kept.contains(&edge.src) && kept.contains(&edge.dst));
+    result.library_map.retain(|name, _| kept.contains(name));
+    result.topo_sorted_libs.retain(|l| kept.contains(&l.name));
+    result.unresolved.retain(|name| kept.contains(name));
+    result.unresolved_chains.retain(|chain| chain.chain.iter().all(|name| kept.contains(name)));
+    result.deepest_unresolved_chain = result.deepest_unresolved_chain.take()
+        .filter(|chain| chain.chain.iter().all(|name| kept.contains(name)));
+    result.unreachable.retain(|name| kept.contains(name));
+    retain_extra(&mut result.dlopen_edges, &mut result.dlopen_libs, kept);
+    retain_extra(&mut result.runtime_edges, &mut result.runtime_libs, kept);
+    for group in &mut result.load_order_by_dir {
+        group.libs.retain(|name| kept.contains(name));
+    }
+    result.load_order_by_dir.retain(|group| !group.libs.is_empty());
+    // the runs of a directory around a dropped group are one run now
+    result.load_order_by_dir.dedup_by(|group, previous| group.dir == previous.dir && {
+        previous.libs.append(&mut group.libs);
+        true
+    });
+    for group in &mut result.cycle_groups {
+        group.retain(|name| kept.contains(name));
+    }
+    result.cycle_groups.retain(|group| !group.is_empty());
+    result.levels = levels_of(result);
+    total - result.vertices.len()
+}
+
+/// Drops the `edges` with an end that is neither in `kept` nor one of `extra_libs`, and the `extra_libs` that are no
+/// longer the source of any edge, until nothing changes: dropping an extra library can cut the edges loading another.
+fn retain_extra(edges: &mut Vec<Edge>, extra_libs: &mut Vec<Lib>, kept: &HashSet<String>) {
+    loop {
+        let extra: HashSet<String> = extra_libs.iter().map(|l| l.name.clone()).collect();
+        edges.retain(|edge| [&edge.src, &edge.dst].iter().all(|name| kept.contains(*name) || extra.contains(*name)));
+        let sources: HashSet<&str> = edges.iter().map(|edge| edge.src.as_str()).collect();
+        let before = extra_libs.len();
+        extra_libs.retain(|l| sources.contains(l.name.as_str()));
+        if extra_libs.len() == before {
+            return;
+        }
+    }
+}
+
+/// The `levels` of the remaining graph, a library can move down when its dependencies were dropped
+fn levels_of(result: &TopoSortResult) -> Vec<Vec<String>> {
+    let mut di_graph_map: DiGraphMap<u32, ()> = DiGraphMap::new();
+    let mut id_gen = IdGen::new();
+    for lib in &result.topo_sorted_libs {
+        di_graph_map.add_node(id_gen.get_next_id(&lib.name));
+    }
+    let loaded: HashSet<&str> = result.topo_sorted_libs.iter().map(|lib| lib.name.as_str()).collect();
+    for edge in result.edges.iter().filter(|edge| loaded.contains(edge.src.as_str()) && loaded.contains(edge.dst.as_str())) {
+        di_graph_map.add_edge(id_gen.get_next_id(&edge.src), id_gen.get_next_id(&edge.dst), ());
+    }
+    // the members of a cycle group are adjacent in the load order and share a level
+    let mut units: Vec<Vec<u32>> = Vec::new();
+    for lib in &result.topo_sorted_libs {
+        let id = id_gen.get_next_id(&lib.name);
+        if units.iter().any(|unit| unit.contains(&id)) {
+            continue;
+        }
+        match result.cycle_groups.iter().find(|group| group.contains(&lib.name)) {
+            Some(group) => units.push(group.iter().map(|name| id_gen.get_next_id(name)).collect()),
+            None => units.push(vec![id]),
+        }
+    }
+    levels(&di_graph_map, &units).into_iter()
+        .map(|level| level.into_iter().map(|id| String::from(id_gen.get_by_id(id).unwrap())).collect())
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::collapse::collapse_result;
+    use crate::dot::{render, DotOptions};
+    use crate::focus::{focus, limit_depth};
+    use crate::query::tests::small_dag;
+    use crate::{Edge, EdgeKind, Lib};
+
+    #[test]
+    fn focus_should_keep_dependencies_and_dependents_of_the_library() {
+        let mut result = small_dag();
+        // B needs D, E and F, and only A needs B; C is neither
+        assert_eq!(Ok(1), focus(&mut result, "B"));
+        assert_eq!(vec!["A", "B", "D", "E", "F"], result.vertices);
+        assert!(result.edges.iter().all(|edge| edge.src != "C" && edge.dst != "C"));
+        let order: Vec<&str> = result.topo_sorted_libs.iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(vec!["F", "E", "D", "B", "A"], order);
+        assert_eq!(vec![vec!["F"], vec!["E"], vec!["D"], vec!["B"], vec!["A"]], result.levels);
+        assert_eq!(Some("B".to_string()), result.focus);
+        assert!(focus(&mut result, "C").is_err());
+    }
+
+    #[test]
+    fn limit_depth_should_drop_deeper_libraries_and_mark_the_cut() {
+        let mut result = small_dag();
+        // D is at depth 2 and E at 3; F is kept at depth 1 although E needs it
+        assert_eq!(1, limit_depth(&mut result, 2));
+        assert_eq!(vec!["A", "B", "C", "D", "F"], result.vertices);
+        let truncated: Vec<&str> = result.topo_sorted_libs.iter().filter(|lib| lib.truncated).map(|lib| lib.name.as_str()).collect();
+        assert_eq!(vec!["D"], truncated);
+        assert!(result.library_map["D"].truncated);
+        assert_eq!(Some(2), result.truncated_at_depth);
+        // D lost its only dependency and moves down to F
+        assert_eq!(vec![vec!["F", "D"], vec!["B", "C"], vec!["A"]], result.levels);
+        assert_eq!(4, limit_depth(&mut result, 0));
+        assert_eq!(vec!["A"], result.vertices);
+    }
+
+    #[test]
+    fn focus_should_drop_dlopen_guesses_of_dropped_libraries() {
+        let dlopen = |src: &str, dst: &str| Edge { src: src.to_string(), dst: dst.to_string(), kind: EdgeKind::Dlopen, ..Default::default() };
+        let with_guesses = || {
+            let mut result = small_dag();
+            // C, a vertex of the graph, and libplugin.so, found by the scan only, are guessed to be dlopened by B
+            result.dlopen_libs = vec![Lib { name: "libplugin.so".to_string(), ..Default::default() }];
+            result.dlopen_edges = vec![dlopen("C", "B"), dlopen("libplugin.so", "B")];
+            result
+        };
+        let mut result = with_guesses();
+        focus(&mut result, "B").unwrap();
+        assert_eq!(vec![dlopen("libplugin.so", "B")], result.dlopen_edges);
+        let view = collapse_result(&result, &[]);
+        let dot = render(&result, &view, &DotOptions::default());
+        assert_eq!(1, dot.matches("style = dashed").count());
+        assert!(!dot.contains("\"C\""));
+
+        let mut result = with_guesses();
+        focus(&mut result, "C").unwrap();
+        assert!(result.dlopen_edges.is_empty());
+        assert!(result.dlopen_libs.is_empty());
+    }
+
+    #[test]
+    fn limit_depth_should_drop_traced_loads_of_dropped_libraries() {
+        let mut result = small_dag();
+        let runtime = |src: &str, dst: &str| Edge { src: src.to_string(), dst: dst.to_string(), kind: EdgeKind::Runtime, ..Default::default() };
+        // E, a vertex of the graph, was loaded by D at runtime; libgtk-3.so.0 by E, and libatk by libgtk-3.so.0
+        result.runtime_libs = ["libatk-1.0.so.0", "libgtk-3.so.0"].iter().map(|name| Lib { name: name.to_string(), ..Default::default() }).collect();
+        result.runtime_edges = vec![runtime("E", "D"), runtime("libatk-1.0.so.0", "libgtk-3.so.0"), runtime("libgtk-3.so.0", "E")];
+        // E is at depth 3, the libraries it loaded go with it
+        limit_depth(&mut result, 2);
+        assert!(result.runtime_edges.is_empty());
+        assert!(result.runtime_libs.is_empty());
+        let view = collapse_result(&result, &[]);
+        assert!(!render(&result, &view, &DotOptions::default()).contains("style = dotted"));
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::html_label::escape_html;
+use crate::{Lib, TopoSortResult};
+
+/// Node attributes, in the order of their ids
+const NODE_ATTRIBUTES: [(&str, &str); 4] = [("path", "string"), ("min_depth", "integer"), ("max_depth", "integer"), ("unresolved", "boolean")];
+
+/// The NEEDED graph in GEXF 1.3 for Gephi. Nodes are numbered in `vertices` order and carry the path,
+/// depths and whether the library is unresolved as attributes; attributes without a value are left out.
+/// Edges point from the dependency to its dependent, as in `edges`.
+pub fn to_gexf(result: &TopoSortResult) -> String {
+    let libs: HashMap<&str, &Lib> = result.topo_sorted_libs.iter().map(|lib| (lib.name.as_str(), lib)).collect();
+    let index: HashMap<&str, usize> = result.vertices.iter().enumerate().map(|(idx, v)| (v.as_str(), idx)).collect();
+    let mut gexf = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gexf.push_str("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n");
+    gexf.push_str("  <graph mode=\"static\" defaultedgetype=\"directed\">\n");
+    gexf.push_str("    <attributes class=\"node\">\n");
+    for (id, (title, kind)) in NODE_ATTRIBUTES.iter().enumerate() {
+        writeln!(gexf, "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>", id, title, kind).unwrap();
+    }
+    gexf.push_str("    </attributes>\n");
+    gexf.push_str("    <nodes>\n");
+    for (idx, v) in result.vertices.iter().enumerate() {
+        let lib = libs.get(v.as_str());
+        let values = [
+            lib.and_then(|lib| lib.path.as_deref()).map(escape_html),
+            lib.and_then(|lib| lib.min_depth).map(|depth| depth.to_string()),
+            lib.and_then(|lib| lib.max_depth).map(|depth| depth.to_string()),
+            Some(result.unresolved.contains(v).to_string()),
+        ];
+        writeln!(gexf, "      <node id=\"{}\" label=\"{}\">", idx, escape_html(v)).unwrap();
+        gexf.push_str("        <attvalues>\n");
+        for (id, value) in values.iter().enumerate() {
+            if let Some(value) = value {
+                writeln!(gexf, "          <attvalue for=\"{}\" value=\"{}\"/>", id, value).unwrap();
+            }
+        }
+        gexf.push_str("        </attvalues>\n");
+        gexf.push_str("      </node>\n");
+    }
+    gexf.push_str("    </nodes>\n");
+    gexf.push_str("    <edges>\n");
+    for (idx, edge) in result.edges.iter().enumerate() {
+        if let (Some(src), Some(dst)) = (index.get(edge.src.as_str()), index.get(edge.dst.as_str())) {
+            writeln!(gexf, "      <edge id=\"{}\" source=\"{}\" target=\"{}\"/>", idx, src, dst).unwrap();
+        }
+    }
+    gexf.push_str("    </edges>\n");
+    gexf.push_str("  </graph>\n");
+    gexf.push_str("</gexf>\n");
+    gexf
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::gexf::to_gexf;
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn to_gexf_should_describe_nodes_with_attributes_and_edges() {
+        let mut result = small_dag();
+        result.vertices[1] = "lib<B>".to_string();
+        result.edges.retain(|edge| edge.src != "B" && edge.dst != "B");
+        let gexf = to_gexf(&result);
+        assert!(gexf.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n"));
+        assert!(gexf.contains("\
+      <node id=\"3\" label=\"D\">
+        <attvalues>
+          <attvalue for=\"0\" value=\"/usr/lib/D\"/>
+          <attvalue for=\"1\" value=\"2\"/>
+          <attvalue for=\"2\" value=\"2\"/>
+          <attvalue for=\"3\" value=\"false\"/>
+        </attvalues>
+      </node>
+"));
+        // Not in the libraries, so only the unresolved flag is known
+        assert!(gexf.contains("      <node id=\"1\" label=\"lib&lt;B&gt;\">\n        <attvalues>\n          <attvalue for=\"3\" value=\"false\"/>\n"));
+        assert_eq!(result.edges.len(), gexf.matches("<edge ").count());
+        assert!(gexf.contains("source=\"4\" target=\"3\"/>"));
+        assert!(gexf.ends_with("    </edges>\n  </graph>\n</gexf>\n"));
+    }
+}
@@ -0,0 +1,170 @@
+use clap::ValueEnum;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::order_diff::{diff_order, OrderDiff};
+use crate::{Edge, EdgeKind, TopoSortResult};
+
+/// Output of the `diff` subcommand
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// `GraphDiff` as JSON
+    Json,
+    /// One line per change, see `to_text`
+    Text,
+}
+
+/// A change against an approved baseline that `check --fail-on` fails on
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Regression {
+    /// A library that is not in the baseline
+    NewDeps,
+    /// A library of the baseline that is gone
+    RemovedDeps,
+    /// A library that resolves to a different file than in the baseline
+    PathChanges,
+}
+
+impl Regression {
+    /// Diagnostic code of the regression
+    pub fn code(&self) -> &'static str {
+        match self {
+            Regression::NewDeps => "new_dependencies",
+            Regression::RemovedDeps => "removed_dependencies",
+            Regression::PathChanges => "path_changes",
+        }
+    }
+}
+
+/// Everything that changed between two results of the same binary, for release-to-release regression checks
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct GraphDiff {
+    pub added_vertices: Vec<String>,
+    pub removed_vertices: Vec<String>,
+    pub added_edges: Vec<Edge>,
+    pub removed_edges: Vec<Edge>,
+    /// Libraries present in both results that resolve to a different file, or that were only resolved in one of them
+    pub path_changes: Vec<PathChange>,
+    /// The comparison of the load order, as written by `--diff-order`
+    #[serde(flatten)]
+    pub order: OrderDiff,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PathChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl GraphDiff {
+    /// Whether the two results have the same graph, paths and load order
+    pub fn is_empty(&self) -> bool {
+        self.added_vertices.is_empty() && self.removed_vertices.is_empty() && self.added_edges.is_empty()
+            && self.removed_edges.is_empty() && self.path_changes.is_empty() && self.order.identical
+    }
+
+    /// The libraries that make up `regression`, sorted, empty when there is none
+    pub fn libraries_of(&self, regression: Regression) -> Vec<String> {
+        match regression {
+            Regression::NewDeps => self.added_vertices.clone(),
+            Regression::RemovedDeps => self.removed_vertices.clone(),
+            Regression::PathChanges => self.path_changes.iter().map(|change| change.name.clone()).collect(),
+        }
+    }
+}
+
+/// The edges of `result` without their symbols, which `--symbols` may or may not have recorded
+fn edge_keys(result: &TopoSortResult) -> BTreeSet<(&str, &str, EdgeKind)> {
+    result.edges.iter().map(|edge| (edge.src.as_str(), edge.dst.as_str(), edge.kind)).collect()
+}
+
+fn copy_edges(edges: BTreeSet<&(&str, &str, EdgeKind)>) -> Vec<Edge> {
+    edges.into_iter().map(|(src, dst, kind)| Edge { src: src.to_string(), dst: dst.to_string(), kind: *kind, ..Default::default() }).collect()
+}
+
+/// Compares `old` with `new`: the vertices and edges of every kind added and removed, the libraries whose path changed and
+/// the load order of the libraries in both. Every list is sorted.
+pub fn diff_graphs(old: &TopoSortResult, new: &TopoSortResult) -> GraphDiff {
+    let old_vertices: BTreeSet<&String> = old.vertices.iter().collect();
+    let new_vertices: BTreeSet<&String> = new.vertices.iter().collect();
+    let old_edges = edge_keys(old);
+    let new_edges = edge_keys(new);
+    let old_paths: BTreeMap<&str, Option<&str>> = old.topo_sorted_libs.iter().map(|lib| (lib.name.as_str(), lib.path.as_deref())).collect();
+    let mut path_changes: Vec<PathChange> = new.topo_sorted_libs.iter()
+        .filter_map(|lib| old_paths.get(lib.name.as_str()).map(|old_path| (lib, *old_path)))
+        .filter(|(lib, old_path)| lib.path.as_deref() != *old_path)
+        .map(|(lib, old_path)| PathChange { name: lib.name.clone(), old: old_path.map(String::from), new: lib.path.clone() })
+        .collect();
+    path_changes.sort_by(|a, b| a.name.cmp(&b.name));
+    GraphDiff {
+        added_vertices: new_vertices.difference(&old_vertices).map(|v| v.to_string()).collect(),
+        removed_vertices: old_vertices.difference(&new_vertices).map(|v| v.to_string()).collect(),
+        added_edges: copy_edges(new_edges.difference(&old_edges).collect()),
+        removed_edges: copy_edges(old_edges.difference(&new_edges).collect()),
+        path_changes,
+        order: diff_order(old, new),
+    }
+}
+
+/// `diff` as lines for a human: `+`/`-` for added and removed libraries and edges, each edge with its kind, `~` for
+/// path changes, and the verdict on the load order
+pub fn to_text(diff: &GraphDiff) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    lines.extend(diff.added_vertices.iter().map(|v| format!("+ {}", v)));
+    lines.extend(diff.removed_vertices.iter().map(|v| format!("- {}", v)));
+    lines.extend(diff.added_edges.iter().map(|edge| format!("+ {} needs {} ({})", edge.dst, edge.src, edge.kind.as_str())));
+    lines.extend(diff.removed_edges.iter().map(|edge| format!("- {} needs {} ({})", edge.dst, edge.src, edge.kind.as_str())));
+    let path = |path: &Option<String>| path.clone().unwrap_or_else(|| "unresolved".to_string());
+    lines.extend(diff.path_changes.iter().map(|change| format!("~ {}: {} -> {}", change.name, path(&change.old), path(&change.new))));
+    if diff.order.identical {
+        lines.push(format!("order identical for the {} common libraries", diff.order.common));
+    } else {
+        let moved: Vec<&str> = diff.order.moved.iter().map(|lib| lib.name.as_str()).collect();
+        lines.push(format!("order changed for {} of the {} common libraries: {}", moved.len(), diff.order.common, moved.join(", ")));
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::graph_diff::{diff_graphs, to_text, PathChange, Regression};
+    use crate::query::tests::small_dag;
+    use crate::{Edge, EdgeKind, Lib};
+
+    #[test]
+    fn diff_graphs_should_report_vertices_edges_and_paths() {
+        let old = small_dag();
+        assert!(diff_graphs(&old, &small_dag()).is_empty());
+
+        // C no longer needs D but the new G, A dlopens E, and D moved to /opt/lib
+        let mut new = small_dag();
+        new.vertices.push("G".to_string());
+        new.edges.retain(|edge| !(edge.src == "D" && edge.dst == "C"));
+        new.edges.push(Edge { src: "G".to_string(), dst: "C".to_string(), kind: EdgeKind::Needed, ..Default::default() });
+        new.edges.push(Edge { src: "E".to_string(), dst: "A".to_string(), kind: EdgeKind::Dlopen, ..Default::default() });
+        new.topo_sorted_libs.insert(0, Lib { name: "G".to_string(), path: Some("/usr/lib/G".to_string()), ..Default::default() });
+        new.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "D").for_each(|lib| lib.path = Some("/opt/lib/D".to_string()));
+
+        let diff = diff_graphs(&old, &new);
+        assert!(!diff.is_empty());
+        assert_eq!(vec!["G"], diff.added_vertices);
+        assert!(diff.removed_vertices.is_empty());
+        assert_eq!(vec![("E", "A", EdgeKind::Dlopen), ("G", "C", EdgeKind::Needed)], diff.added_edges.iter().map(|e| (e.src.as_str(), e.dst.as_str(), e.kind)).collect::<Vec<_>>());
+        assert_eq!(vec![("D", "C")], diff.removed_edges.iter().map(|e| (e.src.as_str(), e.dst.as_str())).collect::<Vec<_>>());
+        assert_eq!(vec![PathChange { name: "D".to_string(), old: Some("/usr/lib/D".to_string()), new: Some("/opt/lib/D".to_string()) }], diff.path_changes);
+        assert!(diff.order.identical);
+        assert_eq!("+ G\n+ A needs E (dlopen)\n+ C needs G (needed)\n- C needs D (needed)\n~ D: /usr/lib/D -> /opt/lib/D\norder identical for the 6 common libraries\n", to_text(&diff));
+
+        assert_eq!(vec!["G"], diff.libraries_of(Regression::NewDeps));
+        assert!(diff.libraries_of(Regression::RemovedDeps).is_empty());
+        assert_eq!(vec!["D"], diff.libraries_of(Regression::PathChanges));
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(serde_json::json!(true), json["identical"]);
+    }
+}
@@ -0,0 +1,146 @@
+use goblin::elf::dynamic::{DF_1_NOW, DF_BIND_NOW, DT_BIND_NOW};
+use goblin::elf::header::ET_DYN;
+use goblin::elf::program_header::{PF_X, PT_GNU_RELRO, PT_GNU_STACK};
+use goblin::elf::Elf;
+
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+use crate::TopoSortResult;
+
+/// Symbols only code built with a stack protector refers to
+const STACK_CHK_SYMBOLS: [&str; 3] = ["__stack_chk_fail", "__stack_chk_guard", "__intel_security_cookie"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Relro {
+    None,
+    /// PT_GNU_RELRO: the sections written during relocation are made read-only afterwards, but not the GOT of lazily
+    /// bound functions
+    Partial,
+    /// Partial RELRO and BIND_NOW, the whole GOT is read-only
+    Full,
+}
+
+/// The exploit mitigations an ELF file was built with, see `--hardening-report`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hardening {
+    /// Position independent, i.e. ET_DYN, which every shared library is
+    pub pie: bool,
+    pub relro: Relro,
+    /// Refers to `__stack_chk_fail` or a similar symbol of a stack protector
+    pub stack_canary: bool,
+    /// PT_GNU_STACK without the execute flag. Without PT_GNU_STACK the stack is executable
+    pub nx: bool,
+    /// DT_BIND_NOW or the NOW flag: every symbol is resolved at load time
+    pub bind_now: bool,
+}
+
+impl Hardening {
+    pub fn from_elf(elf: &Elf) -> Hardening {
+        let bind_now = elf.dynamic.as_ref().is_some_and(|dynamic| {
+            dynamic.dyns.iter().any(|d| d.d_tag == DT_BIND_NOW)
+                || dynamic.info.flags & DF_BIND_NOW != 0
+                || dynamic.info.flags_1 & DF_1_NOW != 0
+        });
+        let relro = match elf.program_headers.iter().any(|header| header.p_type == PT_GNU_RELRO) {
+            false => Relro::None,
+            true if bind_now => Relro::Full,
+            true => Relro::Partial,
+        };
+        let stack_canary = elf.dynsyms.iter().filter_map(|sym| elf.dynstrtab.get_at(sym.st_name))
+            .chain(elf.syms.iter().filter_map(|sym| elf.strtab.get_at(sym.st_name)))
+            .any(|name| STACK_CHK_SYMBOLS.contains(&name));
+        let nx = elf.program_headers.iter()
+            .find(|header| header.p_type == PT_GNU_STACK)
+            .is_some_and(|header| header.p_flags & PF_X == 0);
+        Hardening { pie: elf.header.e_type == ET_DYN, relro, stack_canary, nx, bind_now }
+    }
+
+    /// The hardening of the ELF file at `path`, `None` when it cannot be read or parsed
+    pub fn read(path: &Path) -> Option<Hardening> {
+        let bytes = std::fs::read(path).ok()?;
+        let elf = Elf::parse(&bytes).ok()?;
+        Some(Hardening::from_elf(&elf))
+    }
+}
+
+/// A mitigation of the summary, by name and how to tell whether a library has it
+type Feature = (&'static str, fn(&Hardening) -> bool);
+
+/// One row of the hardening summary: how many libraries have a mitigation, and which do not
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HardeningRow {
+    /// `pie`, `full_relro`, `stack_canary`, `nx` or `bind_now`
+    pub feature: String,
+    /// Number of inspected libraries that have it
+    pub enabled: usize,
+    /// Number of inspected libraries
+    pub total: usize,
+    /// Inspected libraries without it, in load order
+    pub missing: Vec<String>,
+}
+
+/// Summarizes the `hardening` of the libraries of `result` with one row per mitigation, libraries that could not
+/// be inspected are left out
+pub fn summary(result: &TopoSortResult) -> Vec<HardeningRow> {
+    let features: [Feature; 5] = [
+        ("pie", |h| h.pie),
+        ("full_relro", |h| h.relro == Relro::Full),
+        ("stack_canary", |h| h.stack_canary),
+        ("nx", |h| h.nx),
+        ("bind_now", |h| h.bind_now),
+    ];
+    let inspected: Vec<(&str, &Hardening)> = result.topo_sorted_libs.iter()
+        .filter_map(|lib| lib.hardening.as_ref().map(|hardening| (lib.name.as_str(), hardening)))
+        .collect();
+    features.iter()
+        .map(|(feature, has)| {
+            let missing: Vec<String> = inspected.iter().filter(|(_, h)| !has(h)).map(|(name, _)| name.to_string()).collect();
+            HardeningRow { feature: feature.to_string(), enabled: inspected.len() - missing.len(), total: inspected.len(), missing }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::hardening::{summary, Hardening, Relro};
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn summary_should_count_the_mitigations() {
+        let mut result = small_dag();
+        let hardened = Hardening { pie: true, relro: Relro::Full, stack_canary: true, nx: true, bind_now: true };
+        for lib in &mut result.topo_sorted_libs {
+            lib.hardening = match lib.name.as_str() {
+                "A" => Some(hardened),
+                "D" => Some(Hardening { relro: Relro::Partial, stack_canary: false, bind_now: false, ..hardened }),
+                "E" => Some(Hardening { nx: false, ..hardened }),
+                _ => None,
+            };
+        }
+        let rows = summary(&result);
+        let table: Vec<(&str, usize, usize, Vec<&str>)> = rows.iter()
+            .map(|row| (row.feature.as_str(), row.enabled, row.total, row.missing.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(vec![
+            ("pie", 3, 3, vec![]),
+            ("full_relro", 2, 3, vec!["D"]),
+            ("stack_canary", 2, 3, vec!["D"]),
+            ("nx", 2, 3, vec!["E"]),
+            ("bind_now", 2, 3, vec!["D"]),
+        ], table);
+    }
+
+    #[test]
+    fn read_should_inspect_the_test_binary() {
+        let hardening = Hardening::read(&std::env::current_exe().unwrap()).unwrap();
+        if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+            // rustc links with a non-executable stack and RELRO
+            assert!(hardening.nx);
+            assert_ne!(Relro::None, hardening.relro);
+        }
+        assert!(Hardening::read(std::path::Path::new("/nonexistent/libfoo.so")).is_none());
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::Read;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of the file at `path` in lowercase hex, read in chunks so that large libraries are not loaded at once
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::hash::sha256_file;
+
+    #[test]
+    fn sha256_file_should_hash_the_content() {
+        let path = std::env::temp_dir().join(format!("lddtopo-rs-hash-{}", std::process::id()));
+        std::fs::write(&path, "hello world").unwrap();
+        assert_eq!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9", sha256_file(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(sha256_file(&path).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+/// What a node of the DOT output is known to be, rendered as badges by `--html-labels`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NodeDetails {
+    pub path: Option<String>,
+    /// Resolved from one of the default system library directories of the root
+    pub system: bool,
+    /// Has no dependencies of its own
+    pub leaf: bool,
+    pub unresolved: bool,
+    /// Some of its dependencies were cut off by `--max-depth`
+    pub truncated: bool,
+}
+
+impl NodeDetails {
+    /// Whether `path` lives under `lib`, `lib64`, `usr/lib` or `usr/lib64` (or a multiarch directory below them) of `root`
+    pub fn is_system_path(root: &Path, path: &str) -> bool {
+        match Path::new(path).strip_prefix(root) {
+            Ok(relative) => ["lib", "lib64", "usr/lib", "usr/lib64"].iter().any(|dir| relative.starts_with(dir)),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Escapes text for use inside a Graphviz HTML-like label, where `&`, `<` and `>` are markup
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// HTML-like label (including the enclosing `<>`) with the name in bold, the path in a smaller
+/// font and a badge for every flag that is set
+pub fn html_label(name: &str, details: Option<&NodeDetails>) -> String {
+    let mut label = format!("<<B>{}</B>", escape_html(name));
+    if let Some(details) = details {
+        if let Some(path) = &details.path {
+            label.push_str(&format!("<BR/><FONT POINT-SIZE=\"9\">{}</FONT>", escape_html(path)));
+        }
+        let badges: Vec<&str> = [
+            (details.system, "<FONT COLOR=\"gray40\">system</FONT>"),
+            (details.leaf, "<FONT COLOR=\"darkgreen\">leaf</FONT>"),
+            (details.unresolved, "<FONT COLOR=\"red\">unresolved</FONT>"),
+            (details.truncated, "<FONT COLOR=\"orange3\">truncated</FONT>"),
+        ].iter().filter(|(set, _)| *set).map(|(_, badge)| *badge).collect();
+        if !badges.is_empty() {
+            label.push_str(&format!("<BR/><FONT POINT-SIZE=\"8\">{}</FONT>", badges.join(" ")));
+        }
+    }
+    label.push('>');
+    label
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::Path;
+    use crate::html_label::{escape_html, html_label, NodeDetails};
+
+    /// Checks the parts of Graphviz's HTML-like label grammar the labels rely on: every tag is
+    /// closed in order, `<BR/>` is the only self-closing one and `&` always starts an entity
+    fn assert_well_formed(label: &str) {
+        let inner = label.strip_prefix('<').and_then(|l| l.strip_suffix('>')).expect("label is enclosed in <>");
+        let mut open: Vec<String> = Vec::new();
+        let mut rest = inner;
+        while let Some(idx) = rest.find(['<', '&']) {
+            let (text, markup) = rest.split_at(idx);
+            assert!(!text.contains('>'), "unescaped > in {}", label);
+            if markup.starts_with('&') {
+                let end = markup.find(';').expect("entity is terminated");
+                assert!(["&amp;", "&lt;", "&gt;", "&quot;"].contains(&&markup[..=end]), "unknown entity in {}", label);
+                rest = &markup[end + 1..];
+                continue;
+            }
+            let end = markup.find('>').expect("tag is terminated");
+            let tag = &markup[1..end];
+            if tag == "BR/" {
+            } else if let Some(closing) = tag.strip_prefix('/') {
+                assert_eq!(open.pop().as_deref(), Some(closing), "mismatched tag in {}", label);
+            } else {
+                open.push(tag.split(' ').next().unwrap().to_string());
+            }
+            rest = &markup[end + 1..];
+        }
+        assert!(!rest.contains('>'), "unescaped > in {}", label);
+        assert!(open.is_empty(), "unclosed tags {:?} in {}", open, label);
+    }
+
+    #[test]
+    fn escape_html_should_escape_markup_characters() {
+        assert_eq!("a &amp; b &lt;c&gt; &quot;d&quot;", escape_html("a & b <c> \"d\""));
+    }
+
+    #[test]
+    fn html_label_should_render_name_path_and_badges() {
+        let details = NodeDetails { path: Some("/usr/lib/libz.so.1".to_string()), system: true, leaf: true, unresolved: false, truncated: false };
+        let label = html_label("libz.so.1", Some(&details));
+        assert_eq!("<<B>libz.so.1</B><BR/><FONT POINT-SIZE=\"9\">/usr/lib/libz.so.1</FONT><BR/><FONT POINT-SIZE=\"8\">\
+            <FONT COLOR=\"gray40\">system</FONT> <FONT COLOR=\"darkgreen\">leaf</FONT></FONT>>", label);
+        assert_well_formed(&label);
+        assert_eq!("<<B>libLLVM*</B>>", html_label("libLLVM*", None));
+    }
+
+    #[test]
+    fn html_label_when_name_has_markup_characters_should_stay_well_formed() {
+        let details = NodeDetails { path: Some("/opt/a&b/<lib>.so".to_string()), unresolved: true, ..Default::default() };
+        assert_well_formed(&html_label("lib<weird>&co.so", Some(&details)));
+    }
+
+    #[test]
+    fn is_system_path_should_only_match_default_directories_under_root() {
+        assert!(NodeDetails::is_system_path(Path::new("/"), "/usr/lib/x86_64-linux-gnu/libc.so.6"));
+        assert!(NodeDetails::is_system_path(Path::new("/sysroot"), "/sysroot/lib64/libm.so.6"));
+        assert!(!NodeDetails::is_system_path(Path::new("/sysroot"), "/usr/lib/libm.so.6"));
+        assert!(!NodeDetails::is_system_path(Path::new("/"), "/opt/app/lib/libapp.so"));
+        assert!(!NodeDetails::is_system_path(Path::new("/"), "/usr/library/libx.so"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+
+use crate::TopoSortResult;
+
+/// Page of the report, the result goes in place of `/*DATA*/`
+const TEMPLATE: &str = include_str!("html_report.html");
+
+/// A single HTML page to explore `result` in a browser without any network access: the result is embedded
+/// as JSON and drawn by a small force-directed layout, next to the topological order with a search box.
+/// Clicking a library highlights its direct dependencies and dependents.
+pub fn to_html(result: &TopoSortResult) -> String {
+    // `</` would end the script element early, `<\/` is the same string in JSON
+    let json = serde_json::to_string(result).unwrap().replace("</", "<\\/");
+    TEMPLATE.replace("/*DATA*/", &json)
+}
+
+/// Writes the report as `index.html` in `dir`, which is created when missing, and returns its path
+pub fn write_report(result: &TopoSortResult, dir: &Path) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join("index.html");
+    std::fs::write(&path, to_html(result))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::html_report::to_html;
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn to_html_should_embed_the_result_as_json() {
+        let mut result = small_dag();
+        result.vertices[5] = "lib</script>F".to_string();
+        let html = to_html(&result);
+        assert!(!html.contains("/*DATA*/"));
+        assert_eq!(2, html.matches("</script>").count());
+        let start = html.find("type=\"application/json\">").unwrap() + "type=\"application/json\">".len();
+        let end = start + html[start..].find("</script>").unwrap();
+        let embedded: serde_json::Value = serde_json::from_str(&html[start..end]).unwrap();
+        assert_eq!(serde_json::to_value(&result).unwrap(), embedded);
+    }
+}
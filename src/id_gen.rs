@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+/// Hands out dense `u32` ids for names, the node weights of the `DiGraphMap` the graph is sorted in
+#[derive(Default)]
 pub struct IdGen<'a> {
     next_id: u32,
     id_to_str: HashMap<u32, &'a str>,
@@ -15,6 +17,7 @@ impl<'a> IdGen<'a> {
         }
     }
 
+    /// The id of `str`, assigning the next free one on first sight
     pub fn get_next_id(&mut self, str: &'a str) -> u32 {
         let id = match self.str_to_id.get(str) {
             None => {
@@ -31,7 +34,7 @@ impl<'a> IdGen<'a> {
     }
 
     pub fn get_by_id(&self, id: u32) -> Option<&'a str> {
-        self.id_to_str.get(&id).map(|r| { *r })
+        self.id_to_str.get(&id).copied()
     }
 }
 
@@ -0,0 +1,152 @@
+use std::path::Path;
+use std::str::FromStr;
+
+/// A value a label template can refer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Path,
+    Version,
+    Package,
+    Depth,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 5] = [
+        ("name", Field::Name),
+        ("path", Field::Path),
+        ("version", Field::Version),
+        ("package", Field::Package),
+        ("depth", Field::Depth),
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// Node label format of `--label-template`, e.g. `{name}\n{path}`. Placeholders are `{name}`,
+/// `{path}`, `{version}`, `{package}` and `{depth}`; `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelTemplate {
+    parts: Vec<Part>,
+}
+
+/// What a node label can show, a missing value renders empty
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LabelFields {
+    pub path: Option<String>,
+    pub version: Option<String>,
+    pub package: Option<String>,
+    pub depth: Option<usize>,
+}
+
+impl LabelFields {
+    /// The version suffix of a soname, taken from the file the library was resolved to when possible:
+    /// `libz.so.1.2.13` -> `1.2.13`
+    pub fn version_of(name: &str, path: Option<&str>) -> Option<String> {
+        let file_name = path.and_then(|p| Path::new(p).file_name()).and_then(|f| f.to_str());
+        [file_name, Some(name)].into_iter().flatten()
+            .find_map(|n| n.split_once(".so.").map(|(_, version)| version.to_string()))
+            .filter(|version| !version.is_empty())
+    }
+}
+
+impl FromStr for LabelTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<Part> = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(format!("unterminated placeholder {{{}", placeholder)),
+                        }
+                    }
+                    let field = Field::ALL.iter()
+                        .find(|(name, _)| *name == placeholder)
+                        .map(|(_, field)| *field)
+                        .ok_or_else(|| format!("unknown placeholder {{{}}}, expected one of {}", placeholder,
+                            Field::ALL.iter().map(|(name, _)| format!("{{{}}}", name)).collect::<Vec<_>>().join(", ")))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err("unmatched } in template, use }} for a literal brace".to_string()),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(LabelTemplate { parts })
+    }
+}
+
+impl LabelTemplate {
+    /// Whether the template shows `{package}`, which needs the packages of the libraries to be resolved
+    pub fn uses_package(&self) -> bool {
+        self.parts.contains(&Part::Field(Field::Package))
+    }
+
+    pub fn render(&self, name: &str, fields: Option<&LabelFields>) -> String {
+        let empty = LabelFields::default();
+        let fields = fields.unwrap_or(&empty);
+        self.parts.iter().map(|part| match part {
+            Part::Literal(literal) => literal.clone(),
+            Part::Field(Field::Name) => name.to_string(),
+            Part::Field(Field::Path) => fields.path.clone().unwrap_or_default(),
+            Part::Field(Field::Version) => fields.version.clone().unwrap_or_default(),
+            Part::Field(Field::Package) => fields.package.clone().unwrap_or_default(),
+            Part::Field(Field::Depth) => fields.depth.map(|d| d.to_string()).unwrap_or_default(),
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::label_template::{LabelFields, LabelTemplate};
+
+    #[test]
+    fn render_should_substitute_placeholders_and_leave_missing_fields_empty() {
+        let template: LabelTemplate = "{name} ({version}) {{{depth}}} [{package}]".parse().unwrap();
+        let fields = LabelFields { version: Some("1.2.13".to_string()), depth: Some(2), ..Default::default() };
+        assert!(template.uses_package());
+        assert!(!"{name} {{package}}".parse::<LabelTemplate>().unwrap().uses_package());
+        assert_eq!("libz.so.1 (1.2.13) {2} []", template.render("libz.so.1", Some(&fields)));
+        assert_eq!("libLLVM* () {} []", template.render("libLLVM*", None));
+    }
+
+    #[test]
+    fn parse_should_reject_unknown_and_malformed_placeholders() {
+        let err = "{name} {soname}".parse::<LabelTemplate>().unwrap_err();
+        assert_eq!("unknown placeholder {soname}, expected one of {name}, {path}, {version}, {package}, {depth}", err);
+        assert!("{name".parse::<LabelTemplate>().is_err());
+        assert!("name}".parse::<LabelTemplate>().is_err());
+    }
+
+    #[test]
+    fn version_of_should_prefer_resolved_file_name() {
+        assert_eq!(Some("1.2.13".to_string()), LabelFields::version_of("libz.so.1", Some("/usr/lib/libz.so.1.2.13")));
+        assert_eq!(Some("1".to_string()), LabelFields::version_of("libz.so.1", Some("/usr/lib/libz.so")));
+        assert_eq!(None, LabelFields::version_of("libz.so", None));
+    }
+}
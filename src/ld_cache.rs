@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// The format of glibc 2.32 and later, which earlier releases also append after the old one
+const MAGIC_NEW: &[u8] = b"glibc-ld.so.cache1.1";
+/// The format of libc5 and glibc before 2.32
+const MAGIC_OLD: &[u8] = b"ld.so-1.7.0";
+const HEADER_NEW_SIZE: usize = 48;
+const ENTRY_NEW_SIZE: usize = 24;
+const HEADER_OLD_SIZE: usize = 16;
+const ENTRY_OLD_SIZE: usize = 12;
+
+/// The sonames of an `ld.so.cache` and the files they resolve to, see `--use-ldcache`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LdCache {
+    /// Paths inside the root by soname, in the order of the cache, which ldconfig sorts by preference
+    entries: HashMap<String, Vec<String>>,
+}
+
+/// Reads the integers of a cache in the byte order it was written in
+struct Reader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn u32_at(&self, offset: usize) -> Result<u32, String> {
+        let bytes: [u8; 4] = self.bytes.get(offset..offset + 4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| format!("truncated at offset {}", offset))?;
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    /// The NUL-terminated string at `offset`
+    fn str_at(&self, offset: usize) -> Result<String, String> {
+        let rest = self.bytes.get(offset..).ok_or_else(|| format!("string offset {} is out of bounds", offset))?;
+        let end = rest.iter().position(|b| *b == 0).ok_or_else(|| format!("string at offset {} is not terminated", offset))?;
+        Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+    }
+}
+
+impl LdCache {
+    /// Parses an `ld.so.cache` in the new format, the old one, or both one after the other
+    pub fn parse(bytes: &[u8]) -> Result<LdCache, String> {
+        if bytes.starts_with(MAGIC_NEW) {
+            return LdCache::parse_new(bytes);
+        }
+        if !bytes.starts_with(MAGIC_OLD) {
+            return Err("not an ld.so.cache".to_string());
+        }
+        let reader = Reader { bytes, big_endian: cfg!(target_endian = "big") };
+        let nlibs = reader.u32_at(MAGIC_OLD.len() + 1)? as usize;
+        let strings = HEADER_OLD_SIZE + nlibs * ENTRY_OLD_SIZE;
+        // the new format follows aligned to 8 bytes, its entries are the same and more precise
+        let new = strings.next_multiple_of(8);
+        if bytes.get(new..).is_some_and(|rest| rest.starts_with(MAGIC_NEW)) {
+            return LdCache::parse_new(&bytes[new..]);
+        }
+        let mut cache = LdCache::default();
+        for index in 0..nlibs {
+            let entry = HEADER_OLD_SIZE + index * ENTRY_OLD_SIZE;
+            let key = reader.str_at(strings + reader.u32_at(entry + 4)? as usize)?;
+            let value = reader.str_at(strings + reader.u32_at(entry + 8)? as usize)?;
+            cache.entries.entry(key).or_default().push(value);
+        }
+        Ok(cache)
+    }
+
+    /// Parses the new format, whose string offsets are relative to its header
+    fn parse_new(bytes: &[u8]) -> Result<LdCache, String> {
+        // the flags tell the byte order since glibc 2.33: 2 is little, 3 is big endian, 0 unknown
+        let big_endian = match bytes.get(28).map(|flags| flags & 3) {
+            Some(3) => true,
+            Some(2) => false,
+            _ => cfg!(target_endian = "big"),
+        };
+        let reader = Reader { bytes, big_endian };
+        let nlibs = reader.u32_at(MAGIC_NEW.len())? as usize;
+        let mut cache = LdCache::default();
+        for index in 0..nlibs {
+            let entry = HEADER_NEW_SIZE + index * ENTRY_NEW_SIZE;
+            let key = reader.str_at(reader.u32_at(entry + 4)? as usize)?;
+            let value = reader.str_at(reader.u32_at(entry + 8)? as usize)?;
+            cache.entries.entry(key).or_default().push(value);
+        }
+        Ok(cache)
+    }
+
+    /// The `etc/ld.so.cache` of `root`
+    pub fn load(root: &Path) -> io::Result<LdCache> {
+        let bytes = std::fs::read(root.join("etc/ld.so.cache"))?;
+        LdCache::parse(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// The files `soname` resolves to, most preferred first. They are absolute paths inside the root the cache was
+    /// built for, and may be for other architectures.
+    pub fn lookup(&self, soname: &str) -> &[String] {
+        self.entries.get(soname).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Number of sonames in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::ld_cache::{LdCache, ENTRY_NEW_SIZE, HEADER_NEW_SIZE, MAGIC_NEW, MAGIC_OLD};
+
+    /// A little-endian cache in the new format with `entries` in order
+    pub(crate) fn new_format(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut strings: Vec<u8> = Vec::new();
+        let strings_start = HEADER_NEW_SIZE + entries.len() * ENTRY_NEW_SIZE;
+        let mut table: Vec<u8> = Vec::new();
+        for (key, value) in entries {
+            let key_offset = strings_start + strings.len();
+            strings.extend(key.as_bytes());
+            strings.push(0);
+            let value_offset = strings_start + strings.len();
+            strings.extend(value.as_bytes());
+            strings.push(0);
+            table.extend(0x0303_i32.to_le_bytes());
+            table.extend((key_offset as u32).to_le_bytes());
+            table.extend((value_offset as u32).to_le_bytes());
+            table.extend([0; 12]);
+        }
+        let mut bytes = MAGIC_NEW.to_vec();
+        bytes.extend((entries.len() as u32).to_le_bytes());
+        bytes.extend((strings.len() as u32).to_le_bytes());
+        bytes.extend([2, 0, 0, 0]);
+        bytes.extend([0; 16]);
+        bytes.extend(table);
+        bytes.extend(strings);
+        bytes
+    }
+
+    #[test]
+    fn parse_should_read_the_new_format_alone_and_after_the_old_one() {
+        let new = new_format(&[
+            ("libz.so.1", "/usr/lib/x86_64-linux-gnu/libz.so.1"),
+            ("libc.so.6", "/lib/x86_64-linux-gnu/libc.so.6"),
+            ("libc.so.6", "/lib/i386-linux-gnu/libc.so.6"),
+        ]);
+        let cache = LdCache::parse(&new).unwrap();
+        assert_eq!(2, cache.len());
+        assert_eq!(["/lib/x86_64-linux-gnu/libc.so.6", "/lib/i386-linux-gnu/libc.so.6"], cache.lookup("libc.so.6"));
+        assert!(cache.lookup("libssl.so.3").is_empty());
+
+        // an old header without entries, then the new format at the next multiple of 8
+        let mut combined = MAGIC_OLD.to_vec();
+        combined.extend([0, 0, 0, 0, 0]);
+        combined.extend(new);
+        assert_eq!(cache, LdCache::parse(&combined).unwrap());
+
+        assert!(LdCache::parse(b"not a cache").is_err());
+        assert!(LdCache::parse(&combined[..60]).is_err());
+    }
+
+    #[test]
+    fn load_should_read_the_cache_of_the_host() {
+        if let Ok(cache) = LdCache::load(std::path::Path::new("/")) {
+            if cfg!(all(target_os = "linux", target_env = "gnu")) {
+                assert!(cache.lookup("libc.so.6").iter().any(|path| path.ends_with("/libc.so.6")));
+            }
+        }
+    }
+}
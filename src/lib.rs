@@ -0,0 +1,1279 @@
+//! Topological sort of the shared libraries an ELF binary loads.
+//!
+//! `get_topologically_sorted_result` turns the dependency tree of a binary, as found by `resolver::Resolver`,
+//! into a `TopoSortResult`: the libraries in the order they can be loaded (every library after all of its
+//! dependencies), the graph itself and everything that went wrong while resolving it. The modules hold the
+//! optional analyses the `lddtopo-rs` binary offers on top of it.
+
+pub mod appimage;
+pub mod bazel;
+pub mod binary_kind;
+pub mod candidate_roots;
+pub mod collapse;
+pub mod compression;
+pub mod cycles;
+pub mod cypher;
+pub mod debug_info;
+pub mod delta;
+pub mod diagnostics;
+pub mod dep_tree_builder;
+pub mod dot;
+pub mod elf_metadata;
+pub mod error;
+pub mod filter;
+pub mod focus;
+pub mod gexf;
+pub mod graph_diff;
+pub mod dlopen_scan;
+pub mod hardening;
+pub mod hash;
+pub mod html_label;
+pub mod html_report;
+pub mod label_template;
+pub mod ld_cache;
+pub mod license;
+pub mod matrix;
+pub mod merge;
+pub mod mermaid;
+pub mod multiarch;
+pub mod ndjson;
+pub mod nix_store;
+pub mod node_limit;
+pub mod id_gen;
+pub mod normalize;
+pub mod oci_image;
+pub mod order_diff;
+pub mod osv;
+pub mod output;
+pub mod package_archive;
+pub mod packages;
+pub mod path_budget;
+pub mod plantuml;
+pub mod platform_policy;
+pub mod policy;
+pub mod provided;
+pub mod render;
+pub mod query;
+pub mod repl;
+pub mod resolver;
+pub mod rpath_audit;
+pub mod runtime_trace;
+pub mod sbom;
+pub mod scan_dir;
+pub mod self_check;
+pub mod sqlite;
+pub mod symbol_versions;
+pub mod symbols;
+pub mod toposort;
+pub mod validate;
+pub mod wheel;
+
+use crate::binary_kind::BinaryKind;
+use crate::cycles::{condensed_order, cyclic_components, find_cycles};
+use crate::debug_info::DebugInfo;
+use crate::error::{utf8, LddTopoError};
+use crate::filter::{is_included, LibraryPattern};
+use crate::hardening::{Hardening, HardeningRow};
+use crate::license::LicenseSummary;
+use crate::id_gen::IdGen;
+use crate::merge::MergeConflict;
+use crate::multiarch::ArchMismatch;
+use crate::normalize::NameNormalization;
+use crate::packages::Package;
+use crate::query::{AllPaths, DirGroup, Impact};
+use crate::resolver::{Resolution, Resolver, SkippedLib};
+use crate::symbol_versions::CxxRequirements;
+use crate::symbols::{SymbolConflict, Underlinked};
+use crate::toposort::{levels, lexicographic_toposort};
+use crate::wheel::WheelReport;
+
+use lddtree::{DependencyTree, Library};
+
+use petgraph::Direction;
+use petgraph::graphmap::DiGraphMap;
+
+use serde::{Serialize, Deserialize};
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::path::Path;
+
+
+/// `dst` depends on `src`, so `src` has to be loaded first
+#[derive(Serialize, Deserialize, Debug, Default, PartialOrd, Ord, PartialEq, Eq)]
+pub struct Edge {
+    pub src: String,
+    pub dst: String,
+    #[serde(default, skip_serializing_if = "EdgeKind::is_needed")]
+    pub kind: EdgeKind,
+    /// How many undefined symbols of `dst` are defined by `src`, see `--symbols`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_symbols_count: Option<usize>,
+    /// The symbols counted in `resolved_symbols_count`, sorted, see `--symbol-map`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resolved_symbols: Vec<String>,
+    /// `dst` lists `src` in its NEEDED entries but uses none of its symbols, see `--report-overlinking`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub overlinked: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// `dst` lists `src` in its NEEDED entries
+    #[default]
+    Needed,
+    /// `dst` imports `dlopen` and carries a string that looks like the soname of `src`, see `--scan-dlopen`.
+    /// Written as `dlopen_guess` before, which is still read
+    #[serde(alias = "dlopen_guess")]
+    Dlopen,
+    /// `dst` was seen loading `src` in a runtime trace, see `--runtime-trace`
+    Runtime,
+    /// `src` is the ELF interpreter, which the kernel maps before `dst` and which loads everything else
+    Interpreter,
+    /// `dst` can do without `src`, e.g. it only refers to weak symbols of it
+    Weak,
+}
+
+impl EdgeKind {
+    fn is_needed(&self) -> bool {
+        *self == EdgeKind::Needed
+    }
+
+    /// The name of the kind in every output, e.g. `dlopen`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::Needed => "needed",
+            EdgeKind::Dlopen => "dlopen",
+            EdgeKind::Runtime => "runtime",
+            EdgeKind::Interpreter => "interpreter",
+            EdgeKind::Weak => "weak",
+        }
+    }
+}
+
+/// A library of the graph and the file it was resolved to, `path` is `None` when it was not found
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Lib {
+    pub name: String,
+    pub path: Option<String>,
+    /// Build-id and symbols found under `--debug-root`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<DebugInfo>,
+    /// Length of the shortest NEEDED chain from the main library, which is at depth 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_depth: Option<usize>,
+    /// Length of the longest NEEDED chain from the main library. A large gap to `min_depth` means the
+    /// library is pulled in both directly and deep down in the tree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    /// License from `--license-map`, or of the package found by `--resolve-packages`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// SHA-256 of the file in lowercase hex, see `--hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Size of the file, see `--hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+    /// DT_SONAME of the file, which can differ from the NEEDED name it was found by, see `--elf-metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soname: Option<String>,
+    /// ELF machine, e.g. `X86_64`, see `--elf-metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine: Option<String>,
+    /// ELF class, 32 or 64, see `--elf-metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<u8>,
+    /// ELF OS ABI, e.g. `sysv` or `gnu`, see `--elf-metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_abi: Option<String>,
+    /// GNU build-id in hex, see `--elf-metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<String>,
+    /// A NEEDED entry that could not be resolved, kept in the graph as a placeholder without a path
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub missing: bool,
+    /// Unresolved, but expected to be provided by the runtime, see `--assume-provided`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub assumed_provided: bool,
+    /// Some of its dependencies were cut off by `--max-depth`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Newest glibc release of the GLIBC symbol versions it needs, e.g. `2.34`, see `--glibc-versions`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glibc_version: Option<String>,
+    /// `GLIBCXX_*` and `CXXABI_*` symbol versions it needs from libstdc++, see `--cxx-versions`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cxx_versions: Vec<String>,
+    /// Directories of its DT_RPATH, as searched: `$ORIGIN` expanded, under the root, those that do not exist dropped.
+    /// Ignored by the dynamic linker when there is a DT_RUNPATH
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rpath: Vec<String>,
+    /// Directories of its DT_RUNPATH, as searched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runpath: Vec<String>,
+    /// Exploit mitigations of the file, see `--hardening-report`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardening: Option<Hardening>,
+    /// The installed package the file belongs to, see `--resolve-packages` and `--osv`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<Package>,
+    /// Known vulnerabilities of `package`, CVE ids where the OSV record has one, see `--osv`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vulnerabilities: Vec<String>,
+}
+
+/// The analyzed binary and the directories it adds to the library search, which its dependencies are found in first
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MainBinary {
+    pub name: String,
+    /// See `Lib::rpath`, unlike the RPATH of a library it also applies to the dependencies of the dependencies
+    #[serde(default)]
+    pub rpath: Vec<String>,
+    /// See `Lib::runpath`
+    #[serde(default)]
+    pub runpath: Vec<String>,
+}
+
+/// Dependency graph of a binary and its libraries in load order, the JSON written by `lddtopo-rs`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TopoSortResult {
+    pub kind: BinaryKind,
+    pub interpreter: Option<String>,
+    /// RPATH and RUNPATH of the analyzed binary, `None` for a merged result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_binary: Option<MainBinary>,
+    pub vertices: Vec<String>,
+    pub edges: Vec<Edge>,
+    pub library_map: BTreeMap<String, Lib>,
+    pub topo_sorted_libs: Vec<Lib>,
+    /// Libraries that could not be found under the root and library paths
+    #[serde(default)]
+    pub unresolved: Vec<String>,
+    /// For every unresolved library reachable from the main library, the shortest chain of NEEDED
+    /// entries leading to it: `[main, ..., declaring library, unresolved library]`
+    #[serde(default)]
+    pub unresolved_chains: Vec<UnresolvedChain>,
+    /// The longest of `unresolved_chains`, i.e. the deepest point where resolution breaks
+    #[serde(default)]
+    pub deepest_unresolved_chain: Option<UnresolvedChain>,
+    /// Libraries in the dependency tree that the main library does not (transitively) depend on.
+    /// They are removed from the graph when `--prune-unreachable` is set.
+    #[serde(default)]
+    pub unreachable: Vec<String>,
+    /// Original library name -> normalized name, for every name changed by `--normalize-names`
+    #[serde(default)]
+    pub normalized_names: BTreeMap<String, String>,
+    /// Libraries folded together by `--collapse-by-prefix` in the visual output, keyed by the synthetic node name
+    #[serde(default)]
+    pub collapsed_groups: BTreeMap<String, Vec<String>>,
+    /// Answer to `--impact-of`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impact: Option<Impact>,
+    /// Answer to `--all-paths-to`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub all_paths: Option<AllPaths>,
+    /// Possible runtime dependencies found by `--scan-dlopen`, never part of `edges` or the load order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dlopen_edges: Vec<Edge>,
+    /// Libraries guessed by `--scan-dlopen` that are not in the graph, with the path they resolve to if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dlopen_libs: Vec<Lib>,
+    /// Loads seen by `--runtime-trace` that `edges` misses, never part of `edges` or the load order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runtime_edges: Vec<Edge>,
+    /// Libraries loaded in the runtime trace that are not in the graph, with the path they were loaded from if known
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runtime_libs: Vec<Lib>,
+    /// `topo_sorted_libs` grouped by resolution directory, see `--group-by-dir`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub load_order_by_dir: Vec<DirGroup>,
+    /// Library files that were found but not analyzed, their own dependencies are missing from the graph
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedLib>,
+    /// Libraries resolved from the multiarch directory of another architecture, e.g. `/usr/lib/i386-linux-gnu`
+    /// for an x86-64 binary
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arch_mismatches: Vec<ArchMismatch>,
+    /// Libraries resolved to different files by the merged results, with the path `--merge-strategy` picked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merge_conflicts: Vec<MergeConflict>,
+    /// License summary of the dependencies, see `--license-map`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub licenses: Option<LicenseSummary>,
+    /// Topological generations of `topo_sorted_libs`: every library is one level above the highest of its dependencies,
+    /// so the libraries of a level do not depend on each other and can be loaded or processed in parallel once the
+    /// levels before it are done. Members of a `cycle_groups` group share a level.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub levels: Vec<Vec<String>>,
+    /// Libraries that depend on each other, in load order. With `--allow-cycles` every group is loaded as a unit and
+    /// its members are adjacent in `topo_sorted_libs`, in name order since no order among them satisfies the graph
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycle_groups: Vec<Vec<String>>,
+    /// Libraries dropped from the graph by `--exclude` or `--only`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<String>,
+    /// The library the graph was restricted to by `--focus`, the result then is not the whole closure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<String>,
+    /// The depth the graph was truncated at by `--max-depth`, the result then is not the whole closure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated_at_depth: Option<usize>,
+    /// Newest `glibc_version` of the libraries, the oldest glibc the binary runs on, see `--glibc-versions`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_glibc_version: Option<String>,
+    /// Summary of the `cxx_versions` of the libraries, see `--cxx-versions`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cxx_requirements: Option<CxxRequirements>,
+    /// Libraries with undefined symbols no library defines, see `--report-underlinking`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub underlinked: Vec<Underlinked>,
+    /// Symbols exported by more than one library, see `--report-interposition`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symbol_conflicts: Vec<SymbolConflict>,
+    /// How many libraries have each exploit mitigation, see `--hardening-report`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hardening_summary: Vec<HardeningRow>,
+    /// Package -> the packages it depends on, see `--resolve-packages`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub package_dependencies: BTreeMap<String, Vec<String>>,
+    /// The bundled and external libraries of the analyzed wheel, see `--wheel`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wheel: Option<WheelReport>,
+}
+
+/// How the main library ends up needing the library `missing` that could not be found
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedChain {
+    pub missing: String,
+    pub chain: Vec<String>,
+}
+
+/// The dependency graph is not a DAG
+#[derive(Debug)]
+pub struct CycleError {
+    /// One representative cycle per strongly connected component, see `cycles::find_cycles`
+    pub cycles: Vec<Vec<String>>,
+    /// Members of every strongly connected component with a cycle, see `cycles::cyclic_components`
+    pub components: Vec<Vec<String>>,
+    pub vertices: Vec<String>,
+    pub edges: Vec<Edge>,
+}
+
+/// The JSON written instead of a `TopoSortResult` when the graph has cycles
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CycleReport {
+    pub cycles: Vec<Vec<String>>,
+    /// The strongly connected component of every cycle in `cycles`, with all the edges that form its loops
+    #[serde(default)]
+    pub components: Vec<CycleComponent>,
+}
+
+/// Libraries that (transitively) depend on each other, and the edges among them
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct CycleComponent {
+    pub libraries: Vec<String>,
+    pub edges: Vec<Edge>,
+}
+
+impl CycleReport {
+    /// The first cycle of `err` and its component, or all of them with `all`
+    pub fn from_error(err: &CycleError, all: bool) -> CycleReport {
+        let count = if all { err.cycles.len() } else { 1 };
+        let cycles: Vec<Vec<String>> = err.cycles.iter().take(count).cloned().collect();
+        let components = cycles.iter()
+            .filter_map(|cycle| cycle.first().and_then(|start| err.components.iter().find(|component| component.contains(start))))
+            .map(|component| CycleComponent {
+                libraries: component.clone(),
+                edges: err.edges.iter()
+                    .filter(|edge| component.contains(&edge.src) && component.contains(&edge.dst))
+                    .map(|edge| Edge { src: edge.src.clone(), dst: edge.dst.clone(), kind: edge.kind, ..Default::default() })
+                    .collect(),
+            })
+            .collect();
+        CycleReport { cycles, components }
+    }
+}
+
+/// Knobs that change how the dependency tree is turned into a graph
+#[derive(Debug, Default)]
+pub struct AnalysisOptions {
+    pub prune_unreachable: bool,
+    pub normalize_names: Option<NameNormalization>,
+    /// Sort the condensation of a cyclic graph instead of failing, see `TopoSortResult::cycle_groups`
+    pub allow_cycles: bool,
+    pub exclude: Vec<LibraryPattern>,
+    pub only: Vec<LibraryPattern>,
+    /// Add the ELF interpreter of an executable as a vertex every other library depends on, see `EdgeKind::Interpreter`
+    pub include_interpreter: bool,
+    /// Unresolved libraries treated as provided by the runtime by `analyze_file`, see `provided::assume_provided`
+    pub assume_provided: Vec<String>,
+}
+
+
+/// Resolves and sorts the dependencies of the file at `path` without any of the enrichments of the
+/// single-library mode but `AnalysisOptions::assume_provided`. A cycle fails with the first cycle found.
+pub fn analyze_file(path: &Path, resolver: &Resolver, options: &AnalysisOptions) -> Result<TopoSortResult, LddTopoError> {
+    let analysis_error = |reason: String| LddTopoError::Analysis { path: path.to_path_buf(), reason };
+    let kind = BinaryKind::detect(path).map_err(|err| analysis_error(err.to_string()))?;
+    let resolution = resolver.analyze(path).map_err(|err| analysis_error(err.to_string()))?;
+    sort_resolution(path, kind, resolution, options)
+}
+
+/// The part of `analyze_file` after the file at `path` was resolved to `resolution`
+fn sort_resolution(path: &Path, kind: BinaryKind, resolution: Resolution, options: &AnalysisOptions) -> Result<TopoSortResult, LddTopoError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    check_utf8_paths(&resolution.tree)?;
+    let mut result = get_topologically_sorted_result(&name, utf8(path)?, kind, &resolution.tree, options)
+        .map_err(|err| LddTopoError::Cycle { cycles: err.cycles.into_iter().take(1).collect() })?;
+    result.skipped = resolution.skipped;
+    provided::assume_provided(&mut result, &options.assume_provided);
+    Ok(result)
+}
+
+
+/// Fails with `LddTopoError::NonUtf8Path` for the first library of `deps` found at a path that is not valid UTF-8,
+/// which the output cannot hold; `get_topologically_sorted_result` would write it lossily
+pub fn check_utf8_paths(deps: &DependencyTree) -> Result<(), LddTopoError> {
+    deps.libraries.values().filter(|lib| lib.found()).try_for_each(|lib| utf8(&lib.path).map(drop))
+}
+
+/// Whether `main_lib_name` has no edge from a resolved dependency, i.e. resolution found nothing it links against
+pub fn is_isolated(result: &TopoSortResult, main_lib_name: &str) -> bool {
+    !result.edges.iter().any(|edge| edge.dst == main_lib_name && !result.unresolved.contains(&edge.src))
+}
+
+
+/// Vertices the main library does not transitively depend on. Edges point from a dependency to its
+/// dependent, so these are the vertices from which `main_lib_id` cannot be reached.
+fn unreachable_from(di_graph_map: &DiGraphMap<u32, ()>, main_lib_id: u32) -> Vec<u32> {
+    if !di_graph_map.contains_node(main_lib_id) {
+        return Vec::new();
+    }
+    let mut visited: HashSet<u32> = HashSet::new();
+    let mut stack = vec![main_lib_id];
+    while let Some(id) = stack.pop() {
+        if visited.insert(id) {
+            stack.extend(di_graph_map.neighbors_directed(id, Direction::Incoming));
+        }
+    }
+    di_graph_map.nodes().filter(|id| !visited.contains(id)).collect()
+}
+
+/// BFS from the main library in the `depends on` direction, returning the shortest chain from the main
+/// library to every vertex in `targets` that can be reached
+fn shortest_chains_from(di_graph_map: &DiGraphMap<u32, ()>, main_lib_id: u32, targets: &HashSet<u32>) -> HashMap<u32, Vec<u32>> {
+    let mut chains: HashMap<u32, Vec<u32>> = HashMap::new();
+    if !di_graph_map.contains_node(main_lib_id) {
+        return chains;
+    }
+    let mut parent: HashMap<u32, u32> = HashMap::new();
+    let mut queue: VecDeque<u32> = VecDeque::from([main_lib_id]);
+    let mut visited: HashSet<u32> = HashSet::from([main_lib_id]);
+    while let Some(id) = queue.pop_front() {
+        if targets.contains(&id) {
+            let mut chain = vec![id];
+            let mut current = id;
+            while let Some(p) = parent.get(&current) {
+                chain.push(*p);
+                current = *p;
+            }
+            chain.reverse();
+            chains.insert(id, chain);
+        }
+        let mut dependencies: Vec<u32> = di_graph_map.neighbors_directed(id, Direction::Incoming).collect();
+        dependencies.sort();
+        for dep in dependencies {
+            if visited.insert(dep) {
+                parent.insert(dep, id);
+                queue.push_back(dep);
+            }
+        }
+    }
+    chains
+}
+
+/// Minimum (BFS) and maximum (longest path) depth of every vertex reachable from the main library in
+/// the `depends on` direction. `topological_sorted` must be a topological order of the graph, which
+/// puts dependents after their dependencies.
+fn depths_from(di_graph_map: &DiGraphMap<u32, ()>, main_lib_id: u32, topological_sorted: &[u32]) -> HashMap<u32, (usize, usize)> {
+    if !di_graph_map.contains_node(main_lib_id) {
+        return HashMap::new();
+    }
+    let mut min_depth: HashMap<u32, usize> = HashMap::from([(main_lib_id, 0)]);
+    let mut queue: VecDeque<u32> = VecDeque::from([main_lib_id]);
+    while let Some(id) = queue.pop_front() {
+        let depth = min_depth[&id];
+        for dep in di_graph_map.neighbors_directed(id, Direction::Incoming) {
+            if let Entry::Vacant(e) = min_depth.entry(dep) {
+                e.insert(depth + 1);
+                queue.push_back(dep);
+            }
+        }
+    }
+    // Walking from the main library down, every dependent of a vertex has its final depth already
+    let mut max_depth: HashMap<u32, usize> = HashMap::new();
+    for id in topological_sorted.iter().rev().filter(|id| min_depth.contains_key(id)) {
+        let depth = di_graph_map.neighbors_directed(*id, Direction::Outgoing)
+            .filter_map(|dependent| max_depth.get(&dependent).map(|d| d + 1))
+            .max()
+            .unwrap_or(0);
+        max_depth.insert(*id, depth);
+    }
+    min_depth.into_iter().map(|(id, min)| (id, (min, max_depth[&id]))).collect()
+}
+
+fn normalized_name<'a>(name_table: &'a HashMap<&str, String>, raw: &'a str) -> &'a str {
+    name_table.get(raw).map(String::as_str).unwrap_or(raw)
+}
+
+/// Builds the graph of `deps`, the resolved dependency tree of the binary at `main_lib_path`, and sorts it
+/// so that every library comes after all of its dependencies. Fails with every cycle when it is not a DAG.
+pub fn get_topologically_sorted_result(main_lib_name: &str, main_lib_path: &str, main_kind: BinaryKind, deps: &DependencyTree, options: &AnalysisOptions) -> Result<TopoSortResult, CycleError> {
+    // Imagine we have 6 libraries, A, B, C, D, E and F
+    // A depends on B
+    // A depends on C
+    // A depends on F
+    // B depends on D
+    // C depends on D
+    // D depends on E
+    // E depends on F
+    // The following direct acyclic graph represents the dependency between libraries, the edge means `depends`, A -> B means A depends on B
+    /*
+          ┌─────────────┐
+          │             │
+   ┌──────A──────┐      │
+   │             │      │
+   │             │      │
+   ▼             ▼      │
+   B             C      │
+   │             │      │
+   └─────►D◄─────┘      │
+          │             │
+          │             │
+          ▼             ▼
+          E───────────► F
+    */
+    // The usage of topological sorting from Wiki:
+    // The canonical application of topological sorting is in scheduling a sequence of jobs or tasks based on their dependencies.
+    // The jobs are represented by vertices, and there is an edge from x to y if job x must be completed before job y can be started
+
+    // If library A depends on library B, B must come before A (B must be loaded first).
+    // In terms of DAG it means we should swap the edge between vertices, the graph will become
+    /*
+
+  ┌──────F───────┐
+  │              │
+  ▼              ▼
+  E       ┌─────►A◄─────┐
+  │       │             │
+  │       B             C
+  │       ▲             ▲
+  │       └──────D──────┘
+  │              ▲
+  └──────────────┘
+     */
+
+    // Names are normalized up front so that NEEDED entries, library names and library map keys all
+    // agree on the same vertex.
+    let mut name_table: HashMap<&str, String> = HashMap::new();
+    if let Some(normalization) = options.normalize_names {
+        let raw_names = std::iter::once(main_lib_name)
+            .chain(deps.needed.iter().map(String::as_str))
+            .chain(deps.libraries.iter().flat_map(|(key, lib)| {
+                std::iter::once(key).chain(std::iter::once(&lib.name)).chain(lib.needed.iter()).map(String::as_str)
+            }));
+        for raw in raw_names {
+            if let Cow::Owned(normalized) = normalization.apply(raw) {
+                name_table.insert(raw, normalized);
+            }
+        }
+    }
+    let normalized_names: BTreeMap<String, String> = name_table.iter()
+        .filter(|(raw, normalized)| *raw != normalized)
+        .map(|(raw, normalized)| (raw.to_string(), normalized.clone()))
+        .collect();
+    let main_lib_name = normalized_name(&name_table, main_lib_name);
+
+    // Normalization can map several entries onto one name, prefer the one that was actually found
+    let mut libraries: HashMap<&str, &Library> = HashMap::new();
+    for (key, lib) in &deps.libraries {
+        let entry = libraries.entry(normalized_name(&name_table, key)).or_insert(lib);
+        if !entry.found() && lib.found() {
+            *entry = lib;
+        }
+    }
+    // `--exclude` and `--only` drop libraries with all their edges before the graph is built, the main library stays
+    let mut excluded: Vec<String> = Vec::new();
+    if !options.exclude.is_empty() || !options.only.is_empty() {
+        let names: BTreeSet<&str> = deps.needed.iter()
+            .chain(deps.libraries.iter().flat_map(|(key, lib)| std::iter::once(key).chain(lib.needed.iter())))
+            .map(|name| normalized_name(&name_table, name))
+            .collect();
+        excluded = names.into_iter()
+            .filter(|name| *name != main_lib_name)
+            .filter(|name| {
+                let path = libraries.get(name).filter(|lib| lib.found()).and_then(|lib| lib.path.to_str());
+                !is_included(name, path, &options.exclude, &options.only)
+            })
+            .map(String::from)
+            .collect();
+        libraries.retain(|name, _| excluded.binary_search_by(|e| e.as_str().cmp(name)).is_err());
+    }
+    let is_excluded = |name: &str| excluded.binary_search_by(|e| e.as_str().cmp(name)).is_ok();
+
+    let mut di_graph_map = DiGraphMap::new();
+    let mut id_gen = IdGen::new();
+
+    let main_lib_id: u32 = id_gen.get_next_id(main_lib_name);
+    // The main library is part of any non-empty graph, even when it has no direct dependency edges
+    // (e.g. `needed` is empty but the analyzer still reported libraries such as the interpreter).
+    if !deps.needed.is_empty() || !deps.libraries.is_empty() {
+        di_graph_map.add_node(main_lib_id);
+    }
+    for direct_dep in deps.needed.iter().filter(|needed| !is_excluded(normalized_name(&name_table, needed))) {
+        let direct_lib_id = id_gen.get_next_id(normalized_name(&name_table, direct_dep));
+        if !di_graph_map.contains_node(direct_lib_id) {
+            di_graph_map.add_node(direct_lib_id);
+        }
+        // `main_lib_id` depends on `direct_lib_id`, but the edge points that `direct_lib_id` must come before `main_lib_id`
+        di_graph_map.add_edge(direct_lib_id, main_lib_id, ());
+    }
+    // `deps.libraries` is a `HashMap`, walk it in name order so that vertex ids do not change from run to run
+    let mut sorted_libraries: Vec<(&String, &Library)> = deps.libraries.iter().collect();
+    sorted_libraries.sort_by_key(|(name, _)| *name);
+    for (_, lib) in sorted_libraries.into_iter().filter(|(_, lib)| !is_excluded(normalized_name(&name_table, &lib.name))) {
+        let lib_id = id_gen.get_next_id(normalized_name(&name_table, &lib.name));
+        if !di_graph_map.contains_node(lib_id) {
+            di_graph_map.add_node(lib_id);
+        }
+        for needed in &lib.needed {
+            // A NEEDED entry lddtree did not report at all becomes a missing placeholder under its own name
+            let dep_lib_name = libraries.get(normalized_name(&name_table, needed))
+                .map(|dep_lib| normalized_name(&name_table, &dep_lib.name))
+                .unwrap_or_else(|| normalized_name(&name_table, needed));
+            if is_excluded(dep_lib_name) {
+                continue;
+            }
+            let dep_lib_id = id_gen.get_next_id(dep_lib_name);
+            if !di_graph_map.contains_node(dep_lib_id) {
+                di_graph_map.add_node(dep_lib_id);
+            }
+            // `lib_id` depends on `dep_lib_id`, but the edge points that `dep_lib_id` must come before `lib_id`
+            di_graph_map.add_edge(dep_lib_id, lib_id, ());
+        }
+    }
+    // The interpreter is mapped before anything else, so every vertex depends on it, except for those it needs itself
+    let interpreter_path = deps.interpreter.as_deref().filter(|_| options.include_interpreter && main_kind.is_executable());
+    let interpreter_name: Option<&str> = interpreter_path
+        .map(|path| deps.libraries.get(path).map(|lib| lib.name.as_str())
+            .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(path)))
+        .map(|name| normalized_name(&name_table, name))
+        .filter(|name| *name != main_lib_name && !is_excluded(name));
+    let mut interpreter_edges: HashSet<(u32, u32)> = HashSet::new();
+    if let (Some(path), Some(name)) = (interpreter_path, interpreter_name) {
+        if let Some(lib) = deps.libraries.get(path) {
+            libraries.entry(name).or_insert(lib);
+        }
+        let interpreter_id = id_gen.get_next_id(name);
+        if !di_graph_map.contains_node(interpreter_id) {
+            di_graph_map.add_node(interpreter_id);
+        }
+        let mut own_dependencies: HashSet<u32> = HashSet::new();
+        let mut stack = vec![interpreter_id];
+        while let Some(id) = stack.pop() {
+            if own_dependencies.insert(id) {
+                stack.extend(di_graph_map.neighbors_directed(id, Direction::Incoming));
+            }
+        }
+        let dependents: Vec<u32> = di_graph_map.nodes().filter(|id| !own_dependencies.contains(id)).collect();
+        for id in dependents {
+            if !di_graph_map.contains_edge(interpreter_id, id) {
+                di_graph_map.add_edge(interpreter_id, id, ());
+                interpreter_edges.insert((interpreter_id, id));
+            }
+        }
+    }
+    let mut unreachable: Vec<String> = Vec::new();
+    for id in unreachable_from(&di_graph_map, main_lib_id) {
+        unreachable.push(String::from(id_gen.get_by_id(id).unwrap()));
+        if options.prune_unreachable {
+            di_graph_map.remove_node(id);
+        }
+    }
+    unreachable.sort();
+
+    let mut vertices: Vec<String> = Vec::with_capacity(di_graph_map.node_count());
+    di_graph_map.nodes().for_each(|vertex_id| {
+        let v = String::from(id_gen.get_by_id(vertex_id).unwrap());
+        vertices.push(v.clone());
+    });
+    vertices.sort();
+
+    let mut edges: Vec<Edge> = Vec::with_capacity(di_graph_map.edge_count());
+    di_graph_map.all_edges().for_each(|(from, to, _)| {
+        let kind = if interpreter_edges.contains(&(from, to)) { EdgeKind::Interpreter } else { EdgeKind::Needed };
+        let from = String::from(id_gen.get_by_id(from).unwrap());
+        let to = String::from(id_gen.get_by_id(to).unwrap());
+        edges.push(Edge { src: from, dst: to, kind, ..Default::default() });
+    });
+    edges.sort();
+
+    // Libraries lddtree could not find, and NEEDED entries it did not report, are missing: they stay in the
+    // graph as placeholders without a path
+    let is_missing = |name: &str| libraries.get(name).map(|lib| !lib.found()).unwrap_or(name != main_lib_name && Some(name) != interpreter_name);
+    let mut library_map: BTreeMap<String, Lib> = BTreeMap::new();
+    for (name, lib) in &libraries {
+        let path = if lib.found() { Some(lib.path.to_string_lossy().into_owned()) } else { None };
+        library_map.insert(name.to_string(), Lib {
+            name: name.to_string(),
+            path,
+            missing: !lib.found(),
+            rpath: lib.rpath.clone(),
+            runpath: lib.runpath.clone(),
+            ..Default::default()
+        });
+    }
+    for v in vertices.iter().filter(|v| !libraries.contains_key(v.as_str()) && is_missing(v)) {
+        library_map.insert(v.clone(), Lib { name: v.clone(), path: None, missing: true, ..Default::default() });
+    }
+    if let Some(name) = interpreter_name.filter(|name| !libraries.contains_key(name)) {
+        library_map.insert(name.to_string(), Lib { name: name.to_string(), path: interpreter_path.map(String::from), ..Default::default() });
+    }
+    let mut unresolved: Vec<String> = libraries.values()
+        .filter(|lib| !lib.found())
+        .map(|lib| normalized_name(&name_table, &lib.name).to_string())
+        .chain(vertices.iter().filter(|v| !libraries.contains_key(v.as_str()) && is_missing(v)).cloned())
+        .collect();
+    unresolved.sort();
+    unresolved.dedup();
+
+    let unresolved_ids: HashSet<u32> = di_graph_map.nodes()
+        .filter(|id| unresolved.binary_search_by(|u| u.as_str().cmp(id_gen.get_by_id(*id).unwrap())).is_ok())
+        .collect();
+    let mut unresolved_chains: Vec<UnresolvedChain> = shortest_chains_from(&di_graph_map, main_lib_id, &unresolved_ids)
+        .into_iter()
+        .map(|(id, chain)| UnresolvedChain {
+            missing: String::from(id_gen.get_by_id(id).unwrap()),
+            chain: chain.into_iter().map(|id| String::from(id_gen.get_by_id(id).unwrap())).collect(),
+        })
+        .collect();
+    unresolved_chains.sort_by(|a, b| a.missing.cmp(&b.missing));
+    let deepest_unresolved_chain = unresolved_chains.iter()
+        .max_by(|a, b| a.chain.len().cmp(&b.chain.len()).then_with(|| b.missing.cmp(&a.missing)))
+        .cloned();
+
+    let mut cycle_groups: Vec<Vec<String>> = Vec::new();
+    // Libraries that have to be loaded together, a single library unless `--allow-cycles` groups a cycle
+    let load_units: Vec<Vec<u32>> = match lexicographic_toposort(&di_graph_map, |id| String::from(id_gen.get_by_id(id).unwrap())) {
+        Some(topological_sorted) => topological_sorted.into_iter().map(|id| vec![id]).collect(),
+        None if options.allow_cycles => {
+            let components = condensed_order(&di_graph_map, |id| String::from(id_gen.get_by_id(id).unwrap()));
+            cycle_groups = components.iter()
+                .filter(|component| component.len() > 1 || di_graph_map.contains_edge(component[0], component[0]))
+                .map(|component| component.iter().map(|id| String::from(id_gen.get_by_id(*id).unwrap())).collect())
+                .collect();
+            components
+        }
+        None => {
+            let name_of = |id| String::from(id_gen.get_by_id(id).unwrap());
+            return Err(CycleError {
+                cycles: find_cycles(&di_graph_map, name_of),
+                components: cyclic_components(&di_graph_map, name_of),
+                vertices,
+                edges,
+            });
+        }
+    };
+    let topological_sorted: Vec<u32> = load_units.iter().flatten().copied().collect();
+    let levels: Vec<Vec<String>> = levels(&di_graph_map, &load_units).into_iter()
+        .map(|level| level.into_iter().map(|id| String::from(id_gen.get_by_id(id).unwrap())).collect())
+        .collect();
+    let depths = depths_from(&di_graph_map, main_lib_id, &topological_sorted);
+    let mut topo_sorted_libs: Vec<Lib> = Vec::with_capacity(topological_sorted.len());
+    for id in &topological_sorted {
+        let lib_name = id_gen.get_by_id(*id).unwrap();
+        let lib_path = if lib_name == main_lib_name {
+            Some(String::from(main_lib_path))
+        } else if !libraries.contains_key(lib_name) && Some(lib_name) == interpreter_name {
+            interpreter_path.map(String::from)
+        } else {
+            libraries.get(lib_name).filter(|lib| lib.found()).map(|lib| lib.path.to_string_lossy().into_owned())
+        };
+        let (rpath, runpath) = match libraries.get(lib_name) {
+            _ if lib_name == main_lib_name => (deps.rpath.clone(), deps.runpath.clone()),
+            Some(lib) => (lib.rpath.clone(), lib.runpath.clone()),
+            None => (Vec::new(), Vec::new()),
+        };
+        topo_sorted_libs.push(Lib {
+            name: String::from(lib_name),
+            path: lib_path,
+            missing: is_missing(lib_name),
+            min_depth: depths.get(id).map(|(min, _)| *min),
+            max_depth: depths.get(id).map(|(_, max)| *max),
+            rpath,
+            runpath,
+            ..Default::default()
+        });
+    }
+    for lib in &topo_sorted_libs {
+        if let Some(entry) = library_map.get_mut(&lib.name) {
+            entry.min_depth = lib.min_depth;
+            entry.max_depth = lib.max_depth;
+        }
+    }
+    // The interpreter only matters when the main binary is executed; a library that happens to carry
+    // `PT_INTERP` (like libc.so.6) is loaded by whoever loads it.
+    let interpreter = if main_kind.is_executable() { deps.interpreter.clone() } else { None };
+    Ok(TopoSortResult {
+        kind: main_kind,
+        interpreter,
+        main_binary: Some(MainBinary { name: main_lib_name.to_string(), rpath: deps.rpath.clone(), runpath: deps.runpath.clone() }),
+        vertices,
+        edges,
+        library_map,
+        topo_sorted_libs,
+        unresolved,
+        unresolved_chains,
+        deepest_unresolved_chain,
+        unreachable,
+        normalized_names,
+        collapsed_groups: BTreeMap::new(),
+        impact: None,
+        all_paths: None,
+        dlopen_edges: Vec::new(),
+        dlopen_libs: Vec::new(),
+        runtime_edges: Vec::new(),
+        runtime_libs: Vec::new(),
+        load_order_by_dir: Vec::new(),
+        skipped: Vec::new(),
+        arch_mismatches: Vec::new(),
+        merge_conflicts: Vec::new(),
+        licenses: None,
+        cycle_groups,
+        levels,
+        excluded,
+        focus: None,
+        truncated_at_depth: None,
+        min_glibc_version: None,
+        cxx_requirements: None,
+        underlinked: Vec::new(),
+        symbol_conflicts: Vec::new(),
+        hardening_summary: Vec::new(),
+        package_dependencies: BTreeMap::new(),
+        wheel: None,
+    })
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::binary_kind::BinaryKind;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::error::LddTopoError;
+    use crate::normalize::NameNormalization;
+    use crate::resolver::Resolution;
+    use crate::{check_utf8_paths, sort_resolution, get_topologically_sorted_result, is_isolated, AnalysisOptions, CycleError, CycleReport, Edge, EdgeKind};
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    type RetType = Result<(), CycleError>;
+
+    #[test]
+    fn analyze_file_should_apply_assume_provided() {
+        let tree = DependencyTreeBuilder::new()
+            .add_needed("libc.so.6")
+            .add_missing_library("libc.so.6")
+            .build();
+        let options = AnalysisOptions { assume_provided: vec!["libc.so.6".to_string()], ..AnalysisOptions::default() };
+        let result = sort_resolution(Path::new("/opt/app"), BinaryKind::PieExecutable, Resolution { tree, skipped: vec![] }, &options).unwrap();
+        assert!(result.library_map["libc.so.6"].assumed_provided);
+        assert!(!result.unresolved.contains(&"libc.so.6".to_string()));
+        // nothing is left for --fail-on-missing to fail on
+        assert!(result.unresolved.is_empty());
+    }
+
+    #[test]
+    fn check_utf8_paths_should_reject_a_library_found_at_a_non_utf8_path() {
+        let path = PathBuf::from(OsStr::from_bytes(b"/opt/\xffapp/libB.so"));
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_library_at("B", path.clone(), &[])
+            .build();
+        assert!(matches!(check_utf8_paths(&dt), Err(LddTopoError::NonUtf8Path(p)) if p == path));
+        // the analysis itself does not panic on it
+        let result = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default()).unwrap();
+        assert_eq!(Some("/opt/\u{FFFD}app/libB.so"), result.library_map["B"].path.as_deref());
+        assert!(check_utf8_paths(&DependencyTreeBuilder::new().add_needed("B").add_library("B", &[]).build()).is_ok());
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_input_is_empty_dag_should_work() -> RetType {
+        let dt = DependencyTreeBuilder::new().build();
+        let main_lib = "A";
+        let main_lib_path = "/tmp/A";
+        let toposorted = get_topologically_sorted_result(main_lib, main_lib_path, BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(0, toposorted.vertices.len());
+        assert_eq!(0, toposorted.edges.len());
+        assert_eq!(0, toposorted.topo_sorted_libs.len());
+        // like the other empty lists, no levels are written
+        assert!(!serde_json::to_string(&toposorted).unwrap().contains("\"levels\""));
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_input_is_dag_with_two_vertices_should_work() -> RetType {
+        let dt = DependencyTreeBuilder::new().add_needed("B").build();
+        let main_lib = "A";
+        let main_lib_path = "/tmp/A";
+
+        let toposorted = get_topologically_sorted_result(main_lib, main_lib_path, BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(2, toposorted.vertices.len());
+        assert_eq!(1, toposorted.edges.len());
+        assert_eq!(2, toposorted.topo_sorted_libs.len());
+
+        assert_eq!("B", toposorted.topo_sorted_libs[0].name);
+        assert_eq!("A", toposorted.topo_sorted_libs[1].name);
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_should_report_interpreter_only_for_executables() -> RetType {
+        let dt = DependencyTreeBuilder::new().set_interpreter("/lib64/ld-linux-x86-64.so.2").build();
+        let exe = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::PieExecutable, &dt, &AnalysisOptions::default())?;
+        assert_eq!(BinaryKind::PieExecutable, exe.kind);
+        assert_eq!(Some("/lib64/ld-linux-x86-64.so.2".to_string()), exe.interpreter);
+
+        let lib = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(BinaryKind::SharedLibrary, lib.kind);
+        assert!(lib.interpreter.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_should_keep_rpath_and_runpath() -> RetType {
+        let dt = DependencyTreeBuilder::new()
+            .set_rpath(&["/opt/app/lib"])
+            .add_needed("B")
+            .add_library("B", &[])
+            .set_library_runpath("B", &["/opt/b/lib", "/usr/lib/b"])
+            .build();
+        let result = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::PieExecutable, &dt, &AnalysisOptions::default())?;
+        let main_binary = result.main_binary.as_ref().unwrap();
+        assert_eq!(("A", vec!["/opt/app/lib".to_string()]), (main_binary.name.as_str(), main_binary.rpath.clone()));
+        assert!(main_binary.runpath.is_empty());
+        assert_eq!(vec!["/opt/b/lib".to_string(), "/usr/lib/b".to_string()], result.library_map["B"].runpath);
+        let by_name = |name: &str| result.topo_sorted_libs.iter().find(|lib| lib.name == name).unwrap();
+        assert_eq!(vec!["/opt/b/lib".to_string(), "/usr/lib/b".to_string()], by_name("B").runpath);
+        assert_eq!(vec!["/opt/app/lib".to_string()], by_name("A").rpath);
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_with_include_interpreter_should_load_it_first() -> RetType {
+        // C needs the interpreter itself, so that edge stays a NEEDED one
+        let dt = DependencyTreeBuilder::new()
+            .set_interpreter("/lib64/ld-linux-x86-64.so.2")
+            .add_needed("B")
+            .add_library("B", &["C"])
+            .add_library("C", &["ld-linux-x86-64.so.2"])
+            .build();
+        let options = AnalysisOptions { include_interpreter: true, ..Default::default() };
+        let exe = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::PieExecutable, &dt, &options)?;
+        let order: Vec<&str> = exe.topo_sorted_libs.iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(vec!["ld-linux-x86-64.so.2", "C", "B", "A"], order);
+        assert_eq!(Some("/lib64/ld-linux-x86-64.so.2"), exe.topo_sorted_libs[0].path.as_deref());
+        assert!(!exe.topo_sorted_libs[0].missing);
+        assert!(exe.unresolved.is_empty());
+        let kinds: Vec<(&str, EdgeKind)> = exe.edges.iter().filter(|e| e.src == "ld-linux-x86-64.so.2").map(|e| (e.dst.as_str(), e.kind)).collect();
+        assert_eq!(vec![("A", EdgeKind::Interpreter), ("B", EdgeKind::Interpreter), ("C", EdgeKind::Needed)], kinds);
+
+        let lib = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &options)?;
+        assert!(lib.edges.iter().all(|edge| edge.kind == EdgeKind::Needed));
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_needed_is_empty_but_libraries_are_not_should_keep_root() -> RetType {
+        let dt = DependencyTreeBuilder::new()
+            .add_library("B", &["C"])
+            .add_library("C", &[])
+            .build();
+
+        let toposorted = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(vec!["A", "B", "C"], toposorted.vertices);
+        assert_eq!(1, toposorted.edges.len());
+        assert_eq!(3, toposorted.topo_sorted_libs.len());
+        assert_eq!(vec!["B", "C"], toposorted.unreachable);
+
+        let options = AnalysisOptions { prune_unreachable: true, ..Default::default() };
+        let pruned = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &options)?;
+        assert_eq!(vec!["A"], pruned.vertices);
+        assert!(pruned.edges.is_empty());
+        assert_eq!(1, pruned.topo_sorted_libs.len());
+        assert_eq!("A", pruned.topo_sorted_libs[0].name);
+        assert_eq!(Some("/tmp/A".to_string()), pruned.topo_sorted_libs[0].path);
+        assert_eq!(vec!["B", "C"], pruned.unreachable);
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_needed_has_trailing_whitespace_and_names_are_normalized_should_merge_vertices() -> RetType {
+        // lddtree could not find a file called "C " and reports it as an unresolved library
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("C ")
+            .add_library("B", &["C"])
+            .add_library("C", &[])
+            .add_missing_library("C ")
+            .build();
+
+        let raw = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(vec!["A", "B", "C", "C "], raw.vertices);
+        assert_eq!(vec!["C "], raw.unresolved);
+        assert!(raw.normalized_names.is_empty());
+
+        let options = AnalysisOptions { normalize_names: Some(NameNormalization::Trim), ..Default::default() };
+        let normalized = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &options)?;
+        assert_eq!(vec!["A", "B", "C"], normalized.vertices);
+        assert_eq!(3, normalized.edges.len());
+        assert!(normalized.unresolved.is_empty());
+        assert_eq!(Some(&"C".to_string()), normalized.normalized_names.get("C "));
+        assert_eq!(1, normalized.normalized_names.len());
+        assert_eq!(vec!["C", "B", "A"], normalized.topo_sorted_libs.iter().map(|l| l.name.as_str()).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_input_is_small_dag_should_work() -> RetType {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("C")
+            .add_needed("F")
+            .add_library("B", &["D"])
+            .add_library("C", &["D"])
+            .add_library("D", &["E"])
+            .add_library("E", &["F"])
+            .add_library("F", &[])
+            .build();
+        let main_lib = "A";
+        let main_lib_path = "/tmp/A";
+        let toposorted = get_topologically_sorted_result(main_lib, main_lib_path, BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(6, toposorted.vertices.len());
+        assert_eq!(7, toposorted.edges.len());
+        assert_eq!(6, toposorted.topo_sorted_libs.len());
+
+        assert_eq!("F", toposorted.topo_sorted_libs[0].name);
+        assert_eq!("E", toposorted.topo_sorted_libs[1].name);
+        assert_eq!("D", toposorted.topo_sorted_libs[2].name);
+        assert_eq!("B", toposorted.topo_sorted_libs[3].name);
+        assert_eq!("C", toposorted.topo_sorted_libs[4].name);
+        assert_eq!("A", toposorted.topo_sorted_libs[5].name);
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_should_group_libraries_into_levels() {
+        let result = crate::query::tests::small_dag();
+        assert_eq!(vec![vec!["F"], vec!["E"], vec!["D"], vec!["B", "C"], vec!["A"]], result.levels);
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_should_report_min_and_max_depth() {
+        let result = crate::query::tests::small_dag();
+        let depths: Vec<(&str, Option<usize>, Option<usize>)> = result.topo_sorted_libs.iter()
+            .map(|lib| (lib.name.as_str(), lib.min_depth, lib.max_depth))
+            .collect();
+        assert_eq!(vec![
+            ("F", Some(1), Some(4)),
+            ("E", Some(3), Some(3)),
+            ("D", Some(2), Some(2)),
+            ("B", Some(1), Some(1)),
+            ("C", Some(1), Some(1)),
+            ("A", Some(0), Some(0)),
+        ], depths);
+        assert_eq!(Some(4), result.library_map["F"].max_depth);
+    }
+
+    #[test]
+    fn is_isolated_should_only_count_resolved_direct_dependencies() -> RetType {
+        let analyze = |builder: DependencyTreeBuilder| {
+            get_topologically_sorted_result("A", "/tmp/A", BinaryKind::PieExecutable, &builder.build(), &AnalysisOptions::default())
+        };
+        let builder = || DependencyTreeBuilder::new().set_interpreter("/lib64/ld-linux-x86-64.so.2");
+        assert!(is_isolated(&analyze(builder())?, "A"));
+        assert!(is_isolated(&analyze(builder().add_needed("libc.so.6").add_missing_library("libc.so.6"))?, "A"));
+        assert!(!is_isolated(&analyze(builder()
+            .add_needed("libc.so.6")
+            .add_needed("libm.so.6")
+            .add_library("libc.so.6", &[])
+            .add_missing_library("libm.so.6"))?, "A"));
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_input_is_not_dag_should_fail() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_library("A", &["B"])
+            .add_library("B", &["A"])
+            .build();
+        let main_lib = "A";
+        let main_lib_path = "/tmp/A";
+
+        if let Ok(x) = get_topologically_sorted_result(main_lib, main_lib_path, BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default()) {
+            panic!("Should not find any topo sort, but found {:?}", x)
+        }
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_input_has_two_disjoint_cycles_should_report_both() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("D")
+            .add_library("B", &["C"])
+            .add_library("C", &["B"])
+            .add_library("D", &["E"])
+            .add_library("E", &["F"])
+            .add_library("F", &["D"])
+            .build();
+
+        match get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default()) {
+            Ok(x) => panic!("Should not find any topo sort, but found {:?}", x),
+            Err(err) => {
+                assert_eq!(vec![vec!["B", "C", "B"], vec!["D", "E", "F", "D"]], err.cycles);
+            }
+        }
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_cycles_are_allowed_should_group_cycle_members() -> RetType {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("C")
+            .add_library("B", &["C"])
+            .add_library("C", &["B", "D"])
+            .add_library("D", &[])
+            .build();
+
+        let options = AnalysisOptions { allow_cycles: true, ..Default::default() };
+        let toposorted = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &options)?;
+        let order: Vec<&str> = toposorted.topo_sorted_libs.iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(vec!["D", "B", "C", "A"], order);
+        assert_eq!(vec![vec!["B", "C"]], toposorted.cycle_groups);
+        assert_eq!(vec![vec!["D"], vec!["B", "C"], vec!["A"]], toposorted.levels);
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_filtered_should_drop_libraries_with_their_edges() -> RetType {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("libc.so.6")
+            .add_library_at("B", "/opt/app/lib/B", &["libc.so.6", "D"])
+            .add_library_at("libc.so.6", "/usr/lib/libc.so.6", &[])
+            .add_library_at("D", "/usr/lib/D", &[])
+            .build();
+
+        let options = AnalysisOptions { exclude: vec!["libc.so.*".parse().unwrap()], ..Default::default() };
+        let toposorted = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &options)?;
+        assert_eq!(vec!["A", "B", "D"], toposorted.vertices);
+        assert!(toposorted.edges.iter().all(|edge| edge.src != "libc.so.6"));
+        assert_eq!(vec!["libc.so.6"], toposorted.excluded);
+        assert!(toposorted.unresolved.is_empty());
+
+        let options = AnalysisOptions { only: vec!["/opt/app/*".parse().unwrap()], ..Default::default() };
+        let toposorted = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &options)?;
+        assert_eq!(vec!["A", "B"], toposorted.vertices);
+        assert_eq!(vec!["D", "libc.so.6"], toposorted.excluded);
+        Ok(())
+    }
+
+    #[test]
+    fn cycle_report_from_error_should_hold_the_component_edges_of_the_reported_cycles() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("D")
+            .add_library("B", &["C"])
+            .add_library("C", &["B"])
+            .add_library("D", &["E", "F"])
+            .add_library("E", &["F"])
+            .add_library("F", &["D"])
+            .build();
+
+        let err = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default()).unwrap_err();
+        let first = CycleReport::from_error(&err, false);
+        assert_eq!(vec![vec!["B", "C", "B"]], first.cycles);
+        assert_eq!(1, first.components.len());
+        assert_eq!(2, first.components[0].edges.len());
+        let all = CycleReport::from_error(&err, true);
+        assert_eq!(2, all.components.len());
+        let mut libraries = all.components[1].libraries.clone();
+        libraries.sort();
+        assert_eq!(vec!["D", "E", "F"], libraries);
+        // D -> E -> F -> D and D -> F -> D
+        assert_eq!(4, all.components[1].edges.len());
+        assert!(all.components[1].edges.iter().all(|edge| edge.src != "A" && edge.dst != "A"));
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_libraries_are_unresolved_should_report_deepest_chain() -> RetType {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("E")
+            .add_library("B", &["C"])
+            .add_library("C", &["D"])
+            .add_missing_library("D")
+            .add_missing_library("E")
+            .build();
+
+        let toposorted = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(vec!["D", "E"], toposorted.unresolved);
+        assert_eq!(2, toposorted.unresolved_chains.len());
+        assert_eq!(vec!["A", "E"], toposorted.unresolved_chains[1].chain);
+        let deepest = toposorted.deepest_unresolved_chain.unwrap();
+        assert_eq!("D", deepest.missing);
+        assert_eq!(vec!["A", "B", "C", "D"], deepest.chain);
+        let d = toposorted.topo_sorted_libs.iter().find(|lib| lib.name == "D").unwrap();
+        assert!(d.missing && d.path.is_none());
+        assert!(toposorted.library_map["E"].missing && toposorted.library_map["E"].path.is_none());
+        assert!(!toposorted.library_map["C"].missing);
+        Ok(())
+    }
+
+    #[test]
+    fn get_topologically_sorted_result_when_needed_entry_is_not_reported_should_add_missing_placeholder() -> RetType {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_library("B", &["X"])
+            .build();
+
+        let toposorted = get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default())?;
+        assert_eq!(vec!["A", "B", "X"], toposorted.vertices);
+        assert!(toposorted.edges.contains(&Edge { src: "X".to_string(), dst: "B".to_string(), kind: EdgeKind::Needed, ..Default::default() }));
+        assert_eq!(vec!["X"], toposorted.unresolved);
+        assert_eq!(vec!["B", "X"], toposorted.deepest_unresolved_chain.unwrap().chain[1..]);
+        let x = &toposorted.topo_sorted_libs[0];
+        assert_eq!(("X", None, true), (x.name.as_str(), x.path.as_deref(), x.missing));
+        assert!(toposorted.library_map["X"].missing);
+        assert!(!toposorted.topo_sorted_libs.iter().any(|lib| lib.name != "X" && lib.missing));
+        Ok(())
+    }
+
+    #[test]
+    fn edge_kind_should_read_the_former_dlopen_guess() {
+        let edge: Edge = serde_json::from_str(r#"{"src": "libplugin.so", "dst": "A", "kind": "dlopen_guess"}"#).unwrap();
+        assert_eq!(EdgeKind::Dlopen, edge.kind);
+        assert_eq!(r#"{"src":"libplugin.so","dst":"A","kind":"dlopen"}"#, serde_json::to_string(&edge).unwrap());
+    }
+
+    #[test]
+    fn edge_kind_should_serialize_as_its_name() {
+        for kind in [EdgeKind::Needed, EdgeKind::Dlopen, EdgeKind::Runtime, EdgeKind::Interpreter, EdgeKind::Weak] {
+            assert_eq!(format!("\"{}\"", kind.as_str()), serde_json::to_string(&kind).unwrap());
+            assert_eq!(kind, serde_json::from_str(&serde_json::to_string(&kind).unwrap()).unwrap());
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::Command;
+
+use crate::packages::{Package, PackageSource};
+use crate::TopoSortResult;
+
+/// Licenses found in the dependency closure of the analyzed library, see `--license-map`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LicenseSummary {
+    /// Every distinct license of a dependency, sorted
+    pub licenses: Vec<String>,
+    /// Dependencies without an entry in the map, sorted
+    pub unlicensed: Vec<String>,
+}
+
+/// Reads a JSON object mapping sonames to licenses, e.g. `{"libz.so.1": "Zlib"}`
+pub fn read_license_map(path: &Path) -> Result<BTreeMap<String, String>, String> {
+    let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    serde_json::from_str(&content).map_err(|err| err.to_string())
+}
+
+/// Sets the `license` of every library found in `map`, replacing the one of its package, and summarizes the
+/// licenses of all libraries but `main_lib_name`
+pub fn annotate(result: &mut TopoSortResult, map: &BTreeMap<String, String>, main_lib_name: &str) -> LicenseSummary {
+    for lib in result.topo_sorted_libs.iter_mut().chain(result.library_map.values_mut()) {
+        if let Some(license) = map.get(&lib.name) {
+            lib.license = Some(license.clone());
+        }
+    }
+    summarize(result, main_lib_name)
+}
+
+/// The distinct licenses of all libraries but `main_lib_name` and the libraries without one
+pub fn summarize(result: &TopoSortResult, main_lib_name: &str) -> LicenseSummary {
+    let dependencies = result.topo_sorted_libs.iter().filter(|lib| lib.name != main_lib_name);
+    let mut licenses: BTreeSet<String> = BTreeSet::new();
+    let mut unlicensed: BTreeSet<String> = BTreeSet::new();
+    for lib in dependencies {
+        match &lib.license {
+            Some(license) => licenses.insert(license.clone()),
+            None => unlicensed.insert(lib.name.clone()),
+        };
+    }
+    LicenseSummary {
+        licenses: licenses.into_iter().collect(),
+        unlicensed: unlicensed.into_iter().collect(),
+    }
+}
+
+/// The license of the files of a package from its machine-readable Debian copyright file, the distinct `License`
+/// names of its `Files` paragraphs joined with ` AND `. The paragraphs about the packaging in `debian/` are left
+/// out. `None` for copyright files in free form, which many packages still have.
+pub fn copyright_license(copyright: &str) -> Option<String> {
+    if !copyright.starts_with("Format:") {
+        return None;
+    }
+    let mut licenses: Vec<&str> = Vec::new();
+    for paragraph in copyright.split("\n\n") {
+        let field = |name: &str| paragraph.lines()
+            .find_map(|line| line.strip_prefix(name).and_then(|rest| rest.strip_prefix(':')))
+            .map(str::trim);
+        let files = match field("Files") {
+            Some(files) => files,
+            None => continue,
+        };
+        if files.starts_with("debian/") {
+            continue;
+        }
+        if let Some(license) = field("License").filter(|license| !license.is_empty()) {
+            if !licenses.contains(&license) {
+                licenses.push(license);
+            }
+        }
+    }
+    (!licenses.is_empty()).then(|| licenses.join(" AND "))
+}
+
+/// The license of the dpkg package `name` under `root`, see `copyright_license`
+pub fn dpkg_license(root: &Path, name: &str) -> Option<String> {
+    copyright_license(&std::fs::read_to_string(root.join("usr/share/doc").join(name).join("copyright")).ok()?)
+}
+
+/// The license tag of the rpm package `name` under `root`, `None` without rpm or when the package is not installed
+pub fn rpm_license(root: &Path, name: &str) -> Option<String> {
+    let output = Command::new("rpm")
+        .arg("--root").arg(root)
+        .args(["-q", "--queryformat", "%{LICENSE}", name])
+        .output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let license = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!license.is_empty() && license != "(none)").then_some(license)
+}
+
+/// The license `package` declares under `root`, see `--resolve-packages`. Packages guessed from a version string
+/// have none.
+pub fn package_license(root: &Path, package: &Package) -> Option<String> {
+    match package.source {
+        PackageSource::Dpkg => dpkg_license(root, &package.name),
+        PackageSource::Rpm => rpm_license(root, &package.name),
+        PackageSource::Embedded => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::license::{annotate, copyright_license, summarize, LicenseSummary};
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn annotate_should_set_licenses_and_report_unmapped_dependencies() {
+        let mut result = small_dag();
+        let map = BTreeMap::from([
+            ("A".to_string(), "Proprietary".to_string()),
+            ("B".to_string(), "MIT".to_string()),
+            ("D".to_string(), "MIT".to_string()),
+            ("F".to_string(), "LGPL-2.1-or-later".to_string()),
+        ]);
+        let summary = annotate(&mut result, &map, "A");
+        assert_eq!(LicenseSummary {
+            licenses: vec!["LGPL-2.1-or-later".to_string(), "MIT".to_string()],
+            unlicensed: vec!["C".to_string(), "E".to_string()],
+        }, summary);
+        assert_eq!(Some("MIT".to_string()), result.library_map["D"].license);
+        assert_eq!(Some("Proprietary".to_string()), result.topo_sorted_libs.last().unwrap().license);
+    }
+
+    #[test]
+    fn annotate_should_keep_the_licenses_of_packages_the_map_does_not_cover() {
+        let mut result = small_dag();
+        for lib in result.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "C" || lib.name == "E") {
+            lib.license = Some("Zlib".to_string());
+        }
+        assert_eq!(vec!["B", "D", "F"], summarize(&result, "A").unlicensed);
+        let map = BTreeMap::from([("C".to_string(), "MIT".to_string())]);
+        let summary = annotate(&mut result, &map, "A");
+        assert_eq!(vec!["MIT".to_string(), "Zlib".to_string()], summary.licenses);
+    }
+
+    #[test]
+    fn copyright_license_should_read_the_files_paragraphs() {
+        let copyright = "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/\n\
+            Upstream-Name: tiff\n\n\
+            Files: *\nCopyright: 1988-1997 Sam Leffler\nLicense: libtiff\n\n\
+            Files: libtiff/tif_jbig.c\n tools/*\nCopyright: 2001 Silicon Graphics\nLicense: BSD-3-clause\n\n\
+            Files: debian/*\nCopyright: 2004 Debian\nLicense: GPL-2+\n\n\
+            License: libtiff\n Permission to use, copy, modify...\n";
+        assert_eq!(Some("libtiff AND BSD-3-clause".to_string()), copyright_license(copyright));
+        assert_eq!(None, copyright_license("This is the Debian GNU/Linux prepackaged version of the GNU C Library.\n"));
+    }
+}
@@ -1,379 +1,1925 @@
-mod id_gen;
+mod response_file;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-use crate::id_gen::IdGen;
+use goblin::elf::header::machine_to_str;
 
-use lddtree::{DependencyAnalyzer, DependencyTree};
+use lddtopo_rs::{analyze_file, check_utf8_paths, get_topologically_sorted_result, is_isolated};
+use lddtopo_rs::{AnalysisOptions, CycleReport, Edge, EdgeKind, Lib, TopoSortResult};
+use lddtopo_rs::{appimage, bazel, cypher, debug_info, delta, dot, dlopen_scan, elf_metadata, focus, gexf, graph_diff, hardening, hash, html_report, license, matrix, merge, mermaid, multiarch, ndjson, nix_store, node_limit, oci_image, package_archive, order_diff, osv, output, packages, plantuml, platform_policy, provided, query, render, repl, rpath_audit, runtime_trace, sbom, scan_dir, self_check, sqlite, symbol_versions, symbols, validate, wheel};
+use lddtopo_rs::binary_kind::BinaryKind;
+use lddtopo_rs::candidate_roots::{best_root, evaluate_roots, format_table};
+use lddtopo_rs::collapse::collapse_result;
+use lddtopo_rs::debug_info::DebugInfo;
+use lddtopo_rs::delta::LibraryDelta;
+use lddtopo_rs::cycles::cycle_members;
+use lddtopo_rs::diagnostics::{Diagnostic, Diagnostics, Severity};
+use lddtopo_rs::elf_metadata::ElfMetadata;
+use lddtopo_rs::hardening::Hardening;
+use lddtopo_rs::osv::OsvDatabase;
+use lddtopo_rs::packages::{Package, PackageResolver};
+use lddtopo_rs::error::{utf8, Input, LddTopoError};
+use lddtopo_rs::filter::LibraryPattern;
+use lddtopo_rs::graph_diff::{DiffFormat, Regression};
+use lddtopo_rs::dot::{scc_to_dot, DotOptions};
+use lddtopo_rs::label_template::LabelTemplate;
+use lddtopo_rs::render::RenderTarget;
+use lddtopo_rs::ld_cache::LdCache;
+use lddtopo_rs::merge::MergeStrategy;
+use lddtopo_rs::multiarch::Target;
+use lddtopo_rs::nix_store::{NixStore, NIX_STORE};
+use lddtopo_rs::normalize::NameNormalization;
+use lddtopo_rs::order_diff::OrderDiff;
+use lddtopo_rs::output::OutputFormat;
+use lddtopo_rs::path_budget::{PathBudget, DEFAULT_PATH_BUDGET};
+use lddtopo_rs::platform_policy::PlatformPolicy;
+use lddtopo_rs::policy::Policy;
+use lddtopo_rs::query::{all_paths, impact_of, load_order_by_dir};
+use lddtopo_rs::resolver::Resolver;
+use lddtopo_rs::sbom::SbomFormat;
+use lddtopo_rs::symbols::DynamicSymbols;
+use lddtopo_rs::scan_dir::{Sample, ScanReport, ScannedBinary};
 
-use petgraph::algo::{Cycle, toposort};
-use petgraph::graphmap::DiGraphMap;
-use petgraph::dot::{Dot, Config};
+use lddtree::DependencyTree;
 
-use serde::{Serialize, Deserialize};
-use serde_json;
+use serde::Serialize;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
 use log::{error, info};
-use petgraph::Graph;
-use petgraph::graph::NodeIndex;
 
+/// The temporary directory --oci-image or --package unpacked into, removed by `exit`
+static TEMPORARY_ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
-struct Args {
-    /// Path to shared library to analyze
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Analyze a library and write its load order and dependency graph. This is the default, options given
+    /// without a subcommand go to `analyze`
+    Analyze(Args),
+    /// Analyze a library and write only its dependency graph in DOT, same as `analyze --format dot`
+    Dot(Args),
+    /// Compare two earlier outputs: added and removed libraries and edges, path changes and the load order as `analyze --diff-order`
+    Diff(DiffArgs),
+    /// Answer a query against an earlier output, not a binary, or start a prompt when no query is given
+    Query(QueryArgs),
+    /// Check an earlier output against the JSON Schema and its own invariants
+    Check(CheckArgs),
+    /// Convert an earlier output into a CycloneDX 1.5 or SPDX 2.3 JSON SBOM with the SHA-256 of every library file
+    Sbom(SbomArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    old: PathBuf,
+    new: PathBuf,
+
+    /// Where to write the comparison, stdout when it is omitted or `-`
+    #[clap(long)]
+    output_file: Option<PathBuf>,
+
+    /// Write compact JSON instead of pretty-printed
     #[clap(long)]
-    shared_library_path: PathBuf,
+    compact: bool,
+
+    /// JSON, or one line per change for a human
+    #[clap(long, value_enum, default_value_t = DiffFormat::Json)]
+    format: DiffFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct QueryArgs {
+    /// Output of an earlier run. A binary is not analyzed here, run `analyze` on it first with the resolution
+    /// options it needs and query its output
+    result: PathBuf,
+
+    /// The query in the syntax of the prompt, e.g. `deps libfoo.so` or `why --from A --to F`, see `help`.
+    /// Everything after its first word belongs to the query, options of `query` go before it
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    query: Vec<String>,
+
+    /// The library the result was computed for, found by its depth when omitted
+    #[clap(long, value_name = "LIB")]
+    main: Option<String>,
+
+    /// Maximum number of paths a `paths` query enumerates
+    #[clap(long, value_name = "N", default_value_t = DEFAULT_PATH_BUDGET)]
+    path_budget: usize,
+}
+
+#[derive(clap::Args, Debug)]
+struct CheckArgs {
+    /// Output of an earlier run
+    result: PathBuf,
+
+    /// Approved snapshot to compare the result with, see --fail-on
+    #[clap(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
+    /// Changes against the baseline that fail the check, comma separated
+    #[clap(long, value_enum, value_delimiter = ',', requires = "baseline", default_values_t = [Regression::NewDeps])]
+    fail_on: Vec<Regression>,
+
+    /// YAML or TOML file with the `allowed` and `denied` library name patterns and the `allowed_path_prefixes` every
+    /// dependency has to follow, or a built-in platform policy such as `manylinux_2_28` or `musllinux_1_2`
+    #[clap(long, value_name = "FILE|PLATFORM")]
+    policy: Option<PathBuf>,
+
+    /// Flag insecure RPATH and RUNPATH entries of the libraries: relative paths, empty components and world-writable
+    /// directories fail the check, `$ORIGIN` entries leaving the install prefix are warned about. The files are read
+    /// on this machine
+    #[clap(long)]
+    rpath_audit: bool,
+
+    /// The directory `$ORIGIN` entries have to stay in, the parent of the directory of the analyzed binary by default
+    #[clap(long, value_name = "DIR", requires = "rpath_audit")]
+    install_prefix: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SbomArgs {
+    /// Output of an earlier run, the libraries without the `sha256` of `--hash` are hashed so they have to exist on this machine
+    result: PathBuf,
+
+    /// Where to write the SBOM, stdout when it is omitted or `-`
+    #[clap(long)]
+    output_file: Option<PathBuf>,
+
+    /// Standard of the SBOM
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = SbomFormat::Cyclonedx)]
+    format: SbomFormat,
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Path to shared library to analyze. Repeat it to analyze several binaries, e.g. an application and its
+    /// plugins, and merge their graphs into one load order as --merge does
+    #[clap(long, required_unless_present_any = ["diff_order", "scan_dir", "delta", "merge", "package", "wheel", "appimage"])]
+    shared_library_path: Vec<PathBuf>,
 
     /// Root path
     #[clap(long)]
     root_path: Option<PathBuf>,
 
+    /// OCI image layout or `docker save` output, a directory read in place or a tar archive, whose layers are overlaid
+    /// into the root path before the analysis: --shared-library-path is then a path inside the image. The layers go to
+    /// --root-path when it is given and is kept, it has to be new or empty, or to a temporary directory removed at the end
+    /// of the run
+    #[clap(long, value_name = "IMAGE", conflicts_with_all = ["candidate_root", "diff_order", "merge"])]
+    oci_image: Option<PathBuf>,
+
+    /// .deb or .rpm package whose files are extracted to a temporary directory, without running its scripts, to analyze
+    /// every executable and shared library it contains into one merged graph, or only the members named with
+    /// --shared-library-path. Their dependencies are resolved under the root path, and in the package after it
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["oci_image", "diff_order", "scan_dir", "delta", "merge"])]
+    package: Option<PathBuf>,
+
+    /// Python wheel whose extension modules and bundled libraries are analyzed into one merged graph the way auditwheel
+    /// sees it: the libraries of the wheel are only found through the RPATH of the modules, the others are resolved
+    /// under the root path. Members can be named with --shared-library-path, e.g. `pkg/_core.so`. The output lists the
+    /// bundled and external libraries under `wheel`, and the external ones the policy of the platform tag does not
+    /// guarantee are reported as `unbundled_library`
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["package", "oci_image", "diff_order", "scan_dir", "delta", "merge"])]
+    wheel: Option<PathBuf>,
+
+    /// AppImage, or the squashfs image of an AppDir, whose payload is extracted to a temporary directory and becomes the
+    /// root path, to analyze the binary AppRun starts and every bundled shared library into one merged graph, or only the
+    /// members named with --shared-library-path. The libraries needed that are neither bundled nor on the AppImage
+    /// excludelist of libraries the host provides are reported as `unbundled_library`
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["root_path", "package", "wheel", "oci_image", "diff_order", "scan_dir", "delta", "merge"])]
+    appimage: Option<PathBuf>,
+
     /// Additional library paths are treated as absolute paths, not relative to root
     #[clap(long)]
     library_paths: Option<Vec<PathBuf>>,
 
-    /// The path to output file with topologically sorted dependency graph
+    /// Colon-separated directories searched like LD_LIBRARY_PATH at runtime: after the RPATH of the binary and before
+    /// its RUNPATH. Absolute directories are inside the root path
+    #[clap(long, value_name = "PATHS")]
+    ld_library_path: Option<String>,
+
+    /// Search the LD_LIBRARY_PATH of this environment, as the binary would when launched from it
+    #[clap(long, conflicts_with = "ld_library_path")]
+    env: bool,
+
+    /// Value of the `$LIB` token in RPATH, RUNPATH and --ld-library-path entries, e.g. `lib/x86_64-linux-gnu` for
+    /// Debian. `lib64` for 64-bit and `lib` for 32-bit binaries by default
+    #[clap(long, value_name = "DIR")]
+    lib_token: Option<String>,
+
+    /// Value of the `$PLATFORM` token, the architecture of the binary by default for x86 and AArch64. Entries with a
+    /// token without a value are skipped, like the dynamic loader does
+    #[clap(long, value_name = "NAME")]
+    platform_token: Option<String>,
+
+    /// Target triple the binary is built for, e.g. `aarch64-unknown-linux-gnu`, to analyze a cross-compiled binary
+    /// against a foreign sysroot: the binary has to match its architecture, its multiarch directories are searched,
+    /// `$LIB` defaults to them and the search paths of its C library are used
+    #[clap(long, value_name = "TRIPLE", value_parser = Target::parse)]
+    target: Option<Target>,
+
+    /// glibc-hwcaps levels the CPU the binary runs on supports, most preferred first, e.g. `x86-64-v3,x86-64-v2`.
+    /// Libraries in a `glibc-hwcaps/<level>` subdirectory of a search directory are then found before the generic ones,
+    /// as glibc 2.33 and later load them
+    #[clap(long, value_name = "LEVELS", value_delimiter = ',')]
+    hwcaps: Vec<String>,
+
+    /// Resolve the libraries through the etc/ld.so.cache of the root, like the dynamic loader does, instead of
+    /// searching the directories of its ld.so.conf. Falls back to the directories when the root has no cache
+    #[clap(long)]
+    use_ldcache: bool,
+
+    /// Resolve a Nix-built binary, whose RUNPATH and interpreter are absolute `/nix/store` paths: they are looked up in
+    /// DIR, the nix/store of the root by default, and the system directories of the root are not searched
+    #[clap(long, value_name = "DIR", num_args = 0..=1, conflicts_with = "use_ldcache")]
+    nix_store: Option<Option<PathBuf>>,
+
+    /// Look store paths under the prefix FROM up in the directory TO instead, e.g. `/gnu/store=/mnt/gnu/store` for
+    /// Guix or a single store path copied elsewhere, can be repeated
+    #[clap(long, value_name = "FROM=TO", value_parser = nix_store::parse_rewrite, requires = "nix_store")]
+    store_rewrite: Vec<(PathBuf, PathBuf)>,
+
+    /// The path to output file with topologically sorted dependency graph, written to stdout in --machine mode when omitted.
+    /// `-` writes it to stdout in any mode, without the DOT file that is otherwise written next to it
+    #[clap(long, required_unless_present_any = ["machine", "repl", "diff_order", "scan_dir", "delta", "merge"])]
+    output_file: Option<PathBuf>,
+
+    /// Fold all libraries whose name starts with PREFIX into a single node in the DOT, Mermaid and SVG output, can be repeated
+    #[clap(long, value_name = "PREFIX")]
+    collapse_by_prefix: Vec<String>,
+
+    /// Resolve the dependencies under each candidate root and report how many stay unresolved, can be repeated
+    #[clap(long, value_name = "DIR")]
+    candidate_root: Vec<PathBuf>,
+
+    /// Run the analysis under the candidate root with the fewest unresolved dependencies instead of --root-path
+    #[clap(long, requires = "candidate_root")]
+    auto_best: bool,
+
+    /// Group the nodes of the DOT output into clusters by the directory each library was resolved from
+    #[clap(long)]
+    dot_cluster_by_dir: bool,
+
+    /// Use HTML-like labels in the DOT output: the name in bold, the path in a smaller font and system/leaf/unresolved badges
+    #[clap(long, conflicts_with = "label_template")]
+    html_labels: bool,
+
+    /// Format of the node labels in the DOT, Mermaid, PlantUML and SVG output with the placeholders {name}, {path}, {version}, {package} and {depth}.
+    /// Values that are not known render empty
+    #[clap(long, value_name = "TEMPLATE")]
+    label_template: Option<LabelTemplate>,
+
+    /// Drop libraries that the analyzed library does not (transitively) depend on from the graph
+    #[clap(long)]
+    prune_unreachable: bool,
+
+    /// Add the ELF interpreter of an executable, e.g. /lib64/ld-linux-x86-64.so.2, as a vertex every other library
+    /// depends on, since it is loaded first
+    #[clap(long)]
+    include_interpreter: bool,
+
+    /// Drop libraries whose name or path matches PATTERN from the graph before sorting it, e.g. `libc.so.*` or
+    /// `/lib/x86_64-linux-gnu/*`. A glob, or a regular expression with a `re:` prefix. Can be repeated
+    #[clap(long, value_name = "PATTERN")]
+    exclude: Vec<LibraryPattern>,
+
+    /// Keep only the libraries whose name or path matches PATTERN, in the syntax of --exclude. Can be repeated
+    #[clap(long, value_name = "PATTERN")]
+    only: Vec<LibraryPattern>,
+
+    /// Canonicalize library names before building the graph so that e.g. `libfoo.so.1 ` and `libfoo.so.1` become one vertex.
+    /// Defaults to `trim`; `lowercase` is only safe for libraries coming from a case-insensitive filesystem
+    #[clap(long, value_enum, value_name = "MODE", num_args = 0..=1, default_missing_value = "trim")]
+    normalize_names: Option<NameNormalization>,
+
+    /// When the graph is not a DAG, sort the libraries anyway: every group of libraries that depend on each other is loaded
+    /// as a unit, after the libraries the group depends on, and listed in `cycle_groups`
+    #[clap(long)]
+    allow_cycles: bool,
+
+    /// When the graph is not a DAG, report every dependency cycle and its component instead of the first one
+    #[clap(long)]
+    report_cycles_all: bool,
+
+    /// Script-friendly preset for CI: compact JSON to stdout, no DOT file and no colors. Explicit flags still win
+    #[clap(long, alias = "ci")]
+    machine: bool,
+
+    /// Write the JSON output on a single line
+    #[clap(long, overrides_with = "pretty")]
+    compact: bool,
+
+    /// Indent the JSON output (default unless --machine)
+    #[clap(long, overrides_with = "compact")]
+    pretty: bool,
+
+    /// Do not write the DOT file next to the output file
+    #[clap(long, overrides_with = "dot")]
+    no_dot: bool,
+
+    /// Write the DOT file to PATH instead of next to the output file, also when the output goes to stdout
+    #[clap(long, value_name = "PATH", conflicts_with = "no_dot")]
+    dot_output: Option<PathBuf>,
+
+    /// Lay out the graph of the DOT output without Graphviz and write it as an image, e.g. `svg=graph.svg`.
+    /// Only SVG is supported
+    #[clap(long, value_name = "FORMAT=PATH")]
+    render: Option<RenderTarget>,
+
+    /// Write a self-contained HTML page to explore the graph in a browser, with search, highlighting of a
+    /// library's neighbours and the topological order, as DIR/index.html
+    #[clap(long, value_name = "DIR")]
+    html_report: Option<PathBuf>,
+
+    /// Write the DOT file next to the output file (default unless --machine)
+    #[clap(long, overrides_with = "no_dot")]
+    dot: bool,
+
+    /// Disable colors in log messages
+    #[clap(long, overrides_with = "color")]
+    no_color: bool,
+
+    /// Enable colors in log messages when the terminal supports them (default unless --machine)
+    #[clap(long, overrides_with = "no_color")]
+    color: bool,
+
+    /// Separate debug sysroot consulted for the build-id debug files of the resolved libraries, it is never used for resolution
+    #[clap(long, value_name = "PATH")]
+    debug_root: Option<PathBuf>,
+
+    /// Record the SHA-256 and size of the file of every resolved library, for integrity checks and artifact pinning
+    #[clap(long)]
+    hash: bool,
+
+    /// Record the soname, machine, class, OS ABI and build-id from the ELF header and dynamic section of every resolved library
+    #[clap(long)]
+    elf_metadata: bool,
+
+    /// Inspect every resolved library for PIE, RELRO, a stack canary, a non-executable stack and BIND_NOW, and add a
+    /// summary table of the mitigations to the result
+    #[clap(long)]
+    hardening_report: bool,
+
+    /// Record the dpkg or rpm package under the root that owns every resolved library, its license from the Debian
+    /// copyright file or the rpm license tag, and the package-level dependencies
+    #[clap(long)]
+    resolve_packages: bool,
+
+    /// Directory with a local dump of the OSV vulnerability database, e.g. the unzipped all.zip of the Debian ecosystem.
+    /// Maps every resolved library to its package and version through dpkg, rpm or a version string in the file, and
+    /// records the known vulnerabilities of that version
+    #[clap(long, value_name = "DIR")]
+    osv: Option<PathBuf>,
+
+    /// Record the newest GLIBC symbol version every resolved library outside of glibc needs, and the newest of them all, i.e. the oldest glibc the binary runs on
+    #[clap(long)]
+    glibc_versions: bool,
+
+    /// Record the GLIBCXX and CXXABI symbol versions every resolved library needs from libstdc++, and the newest of them all
+    #[clap(long)]
+    cxx_versions: bool,
+
+    /// Record on every NEEDED edge how many undefined symbols of the dependent the dependency defines, from their dynamic symbol tables
+    #[clap(long)]
+    symbols: bool,
+
+    /// With --symbols, also record the names of the symbols on every edge
+    #[clap(long, requires = "symbols")]
+    symbol_map: bool,
+
+    /// Report the NEEDED entries whose library provides none of the symbols the dependent uses, implies --symbols
+    #[clap(long)]
+    report_overlinking: bool,
+
+    /// Report the undefined symbols of the libraries that no library defines, or only the analyzed executable does
+    #[clap(long)]
+    report_underlinking: bool,
+
+    /// Report the symbols exported by more than one library and which definition wins
+    #[clap(long)]
+    report_interposition: bool,
+
+    /// Only warn when --elf-metadata finds libraries of another machine or class than the analyzed binary, instead of failing
+    #[clap(long, requires = "elf_metadata")]
+    allow_arch_mismatch: bool,
+
+    /// Report every library that (transitively) depends on SONAME, i.e. the blast radius of changing it
+    #[clap(long, value_name = "SONAME")]
+    impact_of: Option<String>,
+
+    /// Restrict the output, JSON and DOT, to SONAME, everything it depends on and everything that depends on it
+    #[clap(long, value_name = "SONAME")]
+    focus: Option<String>,
+
+    /// Truncate the output, JSON and DOT, at N NEEDED hops from the analyzed library. Libraries whose dependencies
+    /// were cut off are marked `truncated`
+    #[clap(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Validate the output against the bundled JSON Schema and its internal invariants before writing it, fail if it does not conform
+    #[clap(long)]
+    self_check: bool,
+
+    /// Heuristically look for sonames in the read-only data of every library that imports `dlopen` and report them as possible `dlopen` dependencies.
+    /// They are kept apart from the NEEDED graph and drawn dashed in the DOT output
+    #[clap(long)]
+    scan_dlopen: bool,
+
+    /// `LD_DEBUG=libs` or `LD_DEBUG=files` log, or `ltrace -e dlopen` log, of a run of the binary. The libraries it
+    /// loaded that the static graph misses are added as `runtime` edges, apart from the NEEDED graph
+    #[clap(long, value_name = "FILE")]
+    runtime_trace: Option<PathBuf>,
+
+    /// Also write the load order split into groups by the directory each library was resolved from
+    #[clap(long)]
+    group_by_dir: bool,
+
+    /// Fail when a dynamically linked binary (one with an interpreter) resolves no direct dependency at all,
+    /// which almost always means a wrong --root-path or missing --library-paths. Static binaries are exempt
+    #[clap(long)]
+    fail_on_isolated_main: bool,
+
+    /// After the analysis, read queries (deps, rdeps, path, why, explain, stats) from stdin until `quit`
+    #[clap(long)]
+    repl: bool,
+
+    /// Write a Bazel .bzl fragment defining `declare_libraries()`, with a `cc_import` per library whose `deps` are its dependencies
+    #[clap(long, value_name = "FILE")]
+    bazel: Option<PathBuf>,
+
+    /// Skip any library file whose reading and parsing takes longer than MS milliseconds instead of stalling the run,
+    /// skipped libraries are listed under `skipped`
+    #[clap(long, value_name = "MS")]
+    resolve_timeout_per_lib: Option<u64>,
+
+    /// Report every NEEDED chain from the analyzed library to SONAME, not just the shortest one
+    #[clap(long, value_name = "SONAME")]
+    all_paths_to: Option<String>,
+
+    /// Maximum number of path expansions spent by the path-enumerating analyses (--all-paths-to, `paths` in --repl).
+    /// When it runs out, the results are truncated and the run reports how far it got
+    #[clap(long, value_name = "N", default_value_t = DEFAULT_PATH_BUDGET)]
+    path_budget: usize,
+
+    /// Instead of analyzing a library, compare the load order of two earlier outputs, ignoring paths and other metadata.
+    /// The comparison goes to --output-file, or stdout when it is omitted
+    #[clap(long, num_args = 2, value_names = ["OLD", "NEW"], conflicts_with = "shared_library_path")]
+    diff_order: Option<Vec<PathBuf>>,
+
+    /// When the graph is not a DAG, write a DOT file next to the output file with one cluster per strongly
+    /// connected component, so that the cycles and the acyclic structure between them can be told apart
     #[clap(long)]
-    output_file: PathBuf,
+    dot_scc: bool,
+
+    /// Instead of a single library, analyze every ELF file found under DIR (recursively, symlinks are not followed).
+    /// The report with one result per file goes to --output-file, or stdout when it is omitted
+    #[clap(long, value_name = "DIR", conflicts_with_all = ["shared_library_path", "diff_order"])]
+    scan_dir: Option<PathBuf>,
+
+    /// Only analyze a deterministic pseudo-random FRACTION (in (0, 1]) of the files found by --scan-dir,
+    /// the report then says it is a sample
+    #[clap(long, value_name = "FRACTION", requires = "scan_dir", value_parser = scan_dir::parse_fraction)]
+    sample: Option<f64>,
+
+    /// Seed of --sample, the same seed and fraction pick the same files
+    #[clap(long, value_name = "SEED", requires = "sample", default_value_t = 0)]
+    sample_seed: u64,
+
+    /// Analyze two binaries with the same settings and report the libraries only one of them (transitively) depends on,
+    /// compared by soname. The report goes to --output-file, or stdout when it is omitted
+    #[clap(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["shared_library_path", "diff_order", "scan_dir"])]
+    delta: Option<Vec<PathBuf>>,
+
+    /// Instead of analyzing a library, merge two or more earlier outputs into one graph and sort it again.
+    /// The merged result goes to --output-file, or stdout when it is omitted
+    #[clap(long, num_args = 2.., value_name = "FILE", conflicts_with_all = ["shared_library_path", "diff_order", "scan_dir", "delta"])]
+    merge: Option<Vec<PathBuf>>,
+
+    /// How to pick the path of a library that the merged outputs or libraries resolved to different files: keep the first or the last one,
+    /// prefer the one closest to its root, or fail. Every conflict is listed under `merge_conflicts`
+    #[clap(long, value_enum, value_name = "STRATEGY", default_value_t = MergeStrategy::Error)]
+    merge_strategy: MergeStrategy,
+
+    /// Only draw the N libraries with the most edges, plus the analyzed one, and the edges among them in the DOT, Mermaid and SVG output.
+    /// The diagram says it is truncated; the JSON output stays complete
+    #[clap(long, value_name = "N")]
+    node_limit: Option<usize>,
+
+    /// Serialization of the output file: the full result as JSON, YAML, TOML or MessagePack, as JSON Lines with a
+    /// record per vertex, edge and library, the edges or the N×N adjacency matrix of the graph as CSV, the graph in
+    /// GEXF for Gephi, Mermaid for Markdown or a PlantUML component diagram, the graph and libraries as tables of a
+    /// SQLite database or a Cypher script for Neo4j, or the graph in DOT instead of the JSON and its sibling DOT file
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+
+    /// Only draw the libraries at most N NEEDED hops away from the analyzed one with `--format mermaid`,
+    /// which keeps diagrams of large graphs readable
+    #[clap(long, value_name = "N")]
+    mermaid_depth: Option<usize>,
+
+    /// Put the components of `--format plantuml` in a package per directory the libraries were resolved from
+    #[clap(long)]
+    plantuml_group_by_dir: bool,
+
+    /// JSON object mapping sonames to licenses. Every library found in it gets a `license`, overriding the one of
+    /// its package, and the output lists the distinct licenses of the dependencies along with the dependencies
+    /// without one
+    #[clap(long, value_name = "FILE")]
+    license_map: Option<PathBuf>,
+
+    /// Also write every warning and error to FILE, one JSON object per line with its code, severity, message and libraries
+    #[clap(long, value_name = "FILE")]
+    warnings_file: Option<PathBuf>,
+
+    /// Look up and parse library files on N threads. The output is identical to that of a serial run
+    #[clap(long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    /// Treat SONAME as provided by the runtime when it cannot be resolved, e.g. a host-provided libGL. It stays in the graph,
+    /// marked `assumed_provided`, but does not count as unresolved. Can be repeated
+    #[clap(long, value_name = "SONAME")]
+    assume_provided: Vec<String>,
+
+    /// Fail when a library stays unresolved, libraries listed in --assume-provided excepted
+    #[clap(long)]
+    fail_on_missing: bool,
+}
+
+/// Output behaviour once the `--machine` preset and the explicit flags overriding it are applied
+#[derive(Debug, PartialEq, Eq)]
+struct OutputSettings {
+    pretty: bool,
+    dot: bool,
+    color: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialOrd, Ord, PartialEq, Eq)]
-struct Edge {
-    src: String,
-    dst: String,
+impl OutputSettings {
+    fn from_args(args: &Args) -> OutputSettings {
+        let resolve = |on: bool, off: bool, default: bool| if on { true } else if off { false } else { default };
+        OutputSettings {
+            pretty: resolve(args.pretty, args.compact, !args.machine),
+            dot: resolve(args.dot, args.no_dot, !args.machine),
+            color: resolve(args.color, args.no_color, !args.machine),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Lib {
-    name: String,
-    path: Option<String>,
+fn analysis_options(args: &Args) -> AnalysisOptions {
+    AnalysisOptions {
+        prune_unreachable: args.prune_unreachable,
+        normalize_names: args.normalize_names,
+        allow_cycles: args.allow_cycles,
+        exclude: args.exclude.clone(),
+        only: args.only.clone(),
+        include_interpreter: args.include_interpreter,
+        assume_provided: args.assume_provided.clone(),
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TopoSortResult {
-    vertices: Vec<String>,
-    edges: Vec<Edge>,
-    library_map: BTreeMap<String, Lib>,
-    topo_sorted_libs: Vec<Lib>,
+/// Inserts `analyze` when the first argument is an option, so that invocations from before the
+/// subcommands keep working
+fn with_default_command(mut args: Vec<OsString>) -> Vec<OsString> {
+    let implicit = match args.get(1).and_then(|arg| arg.to_str()) {
+        Some(arg) => arg.starts_with('-') && !matches!(arg, "-h" | "--help" | "-V" | "--version"),
+        None => false,
+    };
+    if implicit {
+        args.insert(1, OsString::from("analyze"));
+    }
+    args
+}
+
+/// `--output-file -` means stdout, which is what no output file means everywhere else
+fn stdout_for_dash(output_file: &mut Option<PathBuf>) {
+    if output_file.as_deref() == Some(Path::new("-")) {
+        *output_file = None;
+    }
 }
 
 fn main() {
-    env_logger::init();
+    let mut cli = match response_file::expand(std::env::args_os()) {
+        Ok(args) => Cli::parse_from(with_default_command(args)),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+    match &mut cli.command {
+        Command::Analyze(args) => stdout_for_dash(&mut args.output_file),
+        Command::Dot(args) => {
+            args.format = OutputFormat::Dot;
+            stdout_for_dash(&mut args.output_file);
+        }
+        Command::Diff(args) => stdout_for_dash(&mut args.output_file),
+        Command::Sbom(args) => stdout_for_dash(&mut args.output_file),
+        Command::Query(_) | Command::Check(_) => {}
+    }
+    let (color, warnings_file) = match &cli.command {
+        Command::Analyze(args) | Command::Dot(args) => (OutputSettings::from_args(args).color, args.warnings_file.clone()),
+        _ => (true, None),
+    };
+    env_logger::Builder::from_default_env()
+        .write_style(if color { env_logger::WriteStyle::Auto } else { env_logger::WriteStyle::Never })
+        .init();
+    let diagnostics = match Diagnostics::new(warnings_file.as_deref()) {
+        Ok(diagnostics) => diagnostics,
+        Err(err) => {
+            let err = LddTopoError::io(format!("Cannot create the warnings file {}", warnings_file.unwrap_or_default().display()), err);
+            error!("{}", err);
+            std::process::exit(err.exit_code());
+        }
+    };
+    if let Command::Analyze(args) | Command::Dot(args) = &mut cli.command {
+        let opened = if args.package.is_some() || args.wheel.is_some() {
+            open_package(args)
+        } else if args.appimage.is_some() {
+            open_appimage(args)
+        } else {
+            open_oci_image(args)
+        };
+        match opened {
+            Ok(root) => TEMPORARY_ROOT.set(root).expect("the root is opened once"),
+            Err(err) => {
+                diagnostics.report(err.to_diagnostic());
+                std::process::exit(err.exit_code());
+            }
+        }
+    }
+    let outcome = match &cli.command {
+        Command::Analyze(args) | Command::Dot(args) => run(args, &OutputSettings::from_args(args), &diagnostics),
+        Command::Diff(args) => diff(args, &diagnostics),
+        Command::Query(args) => query(args, &diagnostics),
+        Command::Check(args) => check(args, &diagnostics),
+        Command::Sbom(args) => sbom(args, &diagnostics),
+    };
+    if let Err(err) = outcome {
+        diagnostics.report(err.to_diagnostic());
+        exit(err.exit_code());
+    }
+    exit(0);
+}
+
+/// Ends the run with `code`, after removing the directory --oci-image, --package or --appimage unpacked into unless it
+/// is kept
+fn exit(code: i32) -> ! {
+    if let Some(Some(root)) = TEMPORARY_ROOT.get() {
+        let _ = std::fs::remove_dir_all(root);
+    }
+    std::process::exit(code)
+}
+
+/// Creates a new directory named `prefix` and a random suffix in the temporary directory, only accessible to the user.
+/// A directory that already exists is never reused, someone else may have created it
+fn temporary_dir(prefix: &str) -> std::io::Result<PathBuf> {
+    use std::hash::{BuildHasher, Hasher};
+    use std::os::unix::fs::DirBuilderExt;
+    let mut attempts = 0;
+    loop {
+        // the keys of every RandomState are random
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        let dir = std::env::temp_dir().join(format!("{}{:016x}", prefix, hasher.finish()));
+        match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && attempts < 8 => attempts += 1,
+            created => return created.map(|_| dir),
+        }
+    }
+}
+
+/// Overlays the layers of --oci-image into the root path and points --shared-library-path into it. Returns the
+/// temporary root to remove at the end of the run
+fn open_oci_image(args: &mut Args) -> Result<Option<PathBuf>, LddTopoError> {
+    let image = match &args.oci_image {
+        Some(image) => image.clone(),
+        None => return Ok(None),
+    };
+    let temporary = args.root_path.is_none();
+    let root = match &args.root_path {
+        Some(root) => root.clone(),
+        None => temporary_dir("lddtopo-oci-").map_err(|err| LddTopoError::io("Cannot create a temporary directory".to_string(), err))?,
+    };
+    let layers = oci_image::unpack(&image, &root).map_err(|err| {
+        if temporary {
+            let _ = std::fs::remove_dir_all(&root);
+        }
+        LddTopoError::io(format!("Cannot unpack the image {}", image.display()), err)
+    })?;
+    info!("Unpacked {} layers of {} into {}", layers, image.display(), root.display());
+    args.shared_library_path = args.shared_library_path.iter().map(|path| root.join(path.strip_prefix("/").unwrap_or(path))).collect();
+    args.root_path = Some(root.clone());
+    Ok(temporary.then_some(root))
+}
 
-    let args = Args::parse();
-    assert!(args.shared_library_path.exists(), "Provided shared library at {} does not exist", args.shared_library_path.to_str().unwrap());
+/// Extracts --package or --wheel into a temporary directory and points --shared-library-path at the binaries inside it,
+/// all of them unless members are named. For a package, --library-paths gets the directories of its libraries as well,
+/// the libraries of a wheel have to be found through its RPATH. Returns the directory to remove at the end of the run
+fn open_package(args: &mut Args) -> Result<Option<PathBuf>, LddTopoError> {
+    let package = match args.package.as_ref().or(args.wheel.as_ref()) {
+        Some(package) => package.clone(),
+        None => return Ok(None),
+    };
+    let dir = temporary_dir("lddtopo-package-").map_err(|err| LddTopoError::io("Cannot create a temporary directory".to_string(), err))?;
+    let fail = |err: LddTopoError| {
+        let _ = std::fs::remove_dir_all(&dir);
+        err
+    };
+    let extracted = match args.wheel.is_some() {
+        true => wheel::extract(&package, &dir),
+        false => package_archive::extract(&package, &dir),
+    }.map_err(|err| fail(LddTopoError::io(format!("Cannot extract the package {}", package.display()), err)))?;
+    let binaries: Vec<(PathBuf, BinaryKind)> = scan_dir::discover(&dir).into_iter()
+        .filter_map(|path| BinaryKind::detect(&path).ok().filter(|kind| *kind != BinaryKind::Other).map(|kind| (path, kind)))
+        .collect();
+    if args.shared_library_path.is_empty() {
+        args.shared_library_path = binaries.iter().map(|(path, _)| path.clone()).collect();
+    } else {
+        args.shared_library_path = members(&dir, &package, &args.shared_library_path).map_err(fail)?;
+    }
+    if args.shared_library_path.is_empty() {
+        return Err(fail(LddTopoError::Analysis { path: package, reason: "the package contains no executable or shared library".to_string() }));
+    }
+    info!("Extracted {} files of {}, analyzing {} binaries", extracted, package.display(), args.shared_library_path.len());
+    let mut library_dirs: Vec<PathBuf> = binaries.iter()
+        .filter(|(_, kind)| *kind == BinaryKind::SharedLibrary)
+        .filter_map(|(path, _)| path.parent().map(Path::to_path_buf))
+        .collect();
+    library_dirs.sort();
+    library_dirs.dedup();
+    if args.package.is_some() {
+        args.library_paths.get_or_insert_with(Vec::new).extend(library_dirs);
+    }
+    Ok(Some(dir))
+}
 
-    let root = args.root_path.unwrap_or(PathBuf::from("/"));
-    let analyzer = match args.library_paths {
-        None => DependencyAnalyzer::new(root),
-        Some(library_paths) => DependencyAnalyzer::new(root).library_paths(library_paths),
+/// The files of `members` in the archive `archive` extracted to `dir`. Every member has to be a file in the archive
+fn members(dir: &Path, archive: &Path, members: &[PathBuf]) -> Result<Vec<PathBuf>, LddTopoError> {
+    members.iter()
+        .map(|member| package_archive::member(dir, member).ok_or_else(|| LddTopoError::Analysis {
+            path: archive.to_path_buf(), reason: format!("{} is not a file of the archive", member.display()) }))
+        .collect()
+}
+
+/// Extracts the payload of --appimage into a temporary directory that becomes the root path, and points
+/// --shared-library-path at the binary AppRun starts and the bundled shared libraries, unless members are named.
+/// --ld-library-path starts with the directories of the bundled libraries, as AppRun puts them in LD_LIBRARY_PATH.
+/// Returns the directory to remove at the end of the run
+fn open_appimage(args: &mut Args) -> Result<Option<PathBuf>, LddTopoError> {
+    let image = match &args.appimage {
+        Some(image) => image.clone(),
+        None => return Ok(None),
+    };
+    let dir = temporary_dir("lddtopo-appimage-").map_err(|err| LddTopoError::io("Cannot create a temporary directory".to_string(), err))?;
+    let fail = |err: LddTopoError| {
+        let _ = std::fs::remove_dir_all(&dir);
+        err
     };
-    let main_file_name = String::from(args.shared_library_path.file_name().unwrap().to_str().unwrap());
-    let main_file_path = String::from(args.shared_library_path.to_str().unwrap());
+    let extracted = appimage::extract(&image, &dir)
+        .map_err(|err| fail(LddTopoError::io(format!("Cannot extract the AppImage {}", image.display()), err)))?;
+    let libraries: Vec<PathBuf> = scan_dir::discover(&dir).into_iter()
+        .filter(|path| BinaryKind::detect(path).is_ok_and(|kind| kind == BinaryKind::SharedLibrary))
+        .collect();
+    if args.shared_library_path.is_empty() {
+        let target = appimage::apprun_target(&dir).ok_or_else(|| fail(LddTopoError::Analysis {
+            path: image.clone(), reason: "AppRun is neither an ELF file nor a script with a desktop file naming one".to_string() }))?;
+        args.shared_library_path = std::iter::once(target.clone()).chain(libraries.iter().filter(|path| **path != target).cloned()).collect();
+    } else {
+        args.shared_library_path = members(&dir, &image, &args.shared_library_path).map_err(fail)?;
+    }
+    info!("Extracted {} files of {}, analyzing {} binaries", extracted, image.display(), args.shared_library_path.len());
+    // the directories under the root path, which the resolver joins them with
+    let mut library_dirs: Vec<String> = libraries.iter()
+        .filter_map(|path| path.parent()?.strip_prefix(&dir).ok())
+        .map(|relative| Path::new("/").join(relative).to_string_lossy().into_owned())
+        .collect();
+    library_dirs.sort();
+    library_dirs.dedup();
+    if !library_dirs.is_empty() {
+        args.ld_library_path = Some(library_dirs.into_iter().chain(args.ld_library_path.take()).collect::<Vec<_>>().join(":"));
+    }
+    args.root_path = Some(dir.clone());
+    Ok(Some(dir))
+}
 
-    let deps: DependencyTree = analyzer.analyze(args.shared_library_path).unwrap();
-    info!("{} has {} dependencies", main_file_name, deps.libraries.len());
+fn run(args: &Args, settings: &OutputSettings, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    if let Some(files) = &args.diff_order {
+        let diff = order_diff::diff_order(&read_result(&files[0], diagnostics)?, &read_result(&files[1], diagnostics)?);
+        report_order_diff(&diff, diagnostics);
+        write_json(&args.output_file, &diff, settings.pretty)?;
+        return Ok(());
+    }
+    if let Some(dir) = &args.scan_dir {
+        let report = scan(dir, args, diagnostics);
+        if args.self_check {
+            self_check(&report, diagnostics)?;
+        }
+        write_json(&args.output_file, &report, settings.pretty)?;
+        return Ok(());
+    }
+    if args.merge.is_some() || args.wheel.is_some() || args.appimage.is_some() || args.shared_library_path.len() > 1 {
+        reject_single_binary_options(args)?;
+    }
+    if let Some(files) = &args.merge {
+        let results = files.iter().map(|file| read_result(file, diagnostics)).collect::<Result<Vec<TopoSortResult>, LddTopoError>>()?;
+        let main_file_name = query::main_library(&results[0]).unwrap_or_default().to_string();
+        return write_merged(merged(&results, args, diagnostics)?, &main_file_name, args, settings, diagnostics);
+    }
+    if let Some(files) = &args.delta {
+        let delta = delta(files, args, diagnostics)?;
+        info!("{} libraries only in {}, {} only in {}, {} in common",
+            delta.only_in_a.len(), delta.a, delta.only_in_b.len(), delta.b, delta.common.len());
+        write_json(&args.output_file, &delta, settings.pretty)?;
+        return Ok(());
+    }
+    if let Some(wheel) = &args.wheel {
+        return write_wheel(wheel, args, settings, diagnostics);
+    }
+    if let Some(image) = &args.appimage {
+        return write_appimage(image, args, settings, diagnostics);
+    }
+    if args.shared_library_path.len() > 1 {
+        return write_merged(analyze_and_merge(args, diagnostics)?, &library_name(&args.shared_library_path[0]), args, settings, diagnostics);
+    }
+    let shared_library_path = args.shared_library_path.first().cloned().expect("clap requires --shared-library-path in this mode");
+    std::fs::metadata(&shared_library_path)
+        .map_err(|err| LddTopoError::io(format!("Cannot read the shared library {}", shared_library_path.display()), err))?;
+    if let Some(target) = &args.target {
+        if let Some((machine, is_64, little_endian)) = multiarch::arch_of_file(&shared_library_path) {
+            if !target.matches(machine, is_64, little_endian) {
+                return Err(LddTopoError::ArchMismatch { code: "target_mismatch", message: format!("{} is a {}-bit {} binary, not one for {}",
+                    shared_library_path.display(), if is_64 { 64 } else { 32 }, machine_to_str(machine), target.triple),
+                    libraries: vec![shared_library_path.to_string_lossy().into_owned()] });
+            }
+        }
+    }
 
-    match get_topologically_sorted_result(&main_file_name, &main_file_path, &deps) {
+    let mut root = args.root_path.clone().unwrap_or(PathBuf::from("/"));
+    if !args.candidate_root.is_empty() {
+        let evaluations = evaluate_roots(&args.candidate_root, |candidate| {
+            new_resolver(candidate.to_path_buf(), args, diagnostics).analyze(&shared_library_path).map(|resolution| resolution.tree)
+        });
+        let best = best_root(&evaluations);
+        eprint!("{}", format_table(&evaluations, best));
+        match best {
+            None => diagnostics.report(Diagnostic::error("no_candidate_root", "None of the candidate roots could be analyzed".to_string(), vec![])),
+            Some(best) => {
+                info!("Recommended root is {} with {} unresolved dependencies", best.root.display(), best.unresolved.as_ref().map_or(0, Vec::len));
+                if args.auto_best {
+                    root = best.root.clone();
+                }
+            }
+        }
+    }
+    let resolver = new_resolver(root.clone(), args, diagnostics);
+    let main_file_path = utf8(&shared_library_path)?.to_string();
+    let main_file_name = shared_library_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+    let analysis_error = |reason: String| LddTopoError::Analysis { path: shared_library_path.clone(), reason };
+    let kind = BinaryKind::detect(&shared_library_path).map_err(|err| analysis_error(err.to_string()))?;
+    let resolution = resolver.analyze(&shared_library_path).map_err(|err| analysis_error(err.to_string()))?;
+    let deps: DependencyTree = resolution.tree;
+    check_utf8_paths(&deps)?;
+    for skipped in &resolution.skipped {
+        diagnostics.report(Diagnostic::warning("skipped_library",
+            format!("Skipped {} at {}: {}", skipped.name, skipped.path, skipped.reason), vec![skipped.name.clone()]));
+    }
+    info!("{} is a {} with {} dependencies", main_file_name, kind.description(), deps.libraries.len());
+
+    let options = analysis_options(args);
+    match get_topologically_sorted_result(&main_file_name, &main_file_path, kind, &deps, &options) {
         Err(err) => {
-            error!("The graph is not DAG, it contains cycle at {:?}", err);
+            let report = CycleReport::from_error(&err, args.report_cycles_all);
+            if args.report_cycles_all {
+                for cycle in &report.cycles {
+                    diagnostics.report(Diagnostic::error("dependency_cycle", format!("Dependency cycle {}", cycle.join(" -> ")), cycle_members(cycle)));
+                }
+            }
+            if args.self_check {
+                self_check(&report, diagnostics)?;
+            }
+            write_json(&args.output_file, &report, settings.pretty)?;
+            let cycles = report.cycles;
+            match (&args.dot_output, &args.output_file) {
+                _ if !args.dot_scc => {}
+                (Some(dot_path), _) => write_dot(&scc_to_dot(&err.vertices, &err.edges, &err.components), dot_path)?,
+                (None, Some(output_file)) => write_dot(&scc_to_dot(&err.vertices, &err.edges, &err.components), &dot_path_for(output_file))?,
+                (None, None) => diagnostics.report(Diagnostic::warning("dot_skipped",
+                    "The DOT file is written next to --output-file, skipping it because there is none and --dot-output is not set".to_string(), vec![])),
+            }
+            Err(LddTopoError::Cycle { cycles })
         }
-        Ok(result) => {
-            serde_json::to_writer_pretty(&File::create(args.output_file.clone()).unwrap(), &result).unwrap();
-            let dot_path = Path::new(&args.output_file).parent().unwrap().join(format!("{}.dot", Path::new(&args.output_file).file_stem().unwrap().to_str().unwrap()));
-            export_to_dot(&result, dot_path);
+        Ok(mut result) => {
+            result.skipped = resolution.skipped;
+            let assumed = provided::assume_provided(&mut result, &options.assume_provided);
+            if !assumed.is_empty() {
+                info!("Assuming {} unresolved libraries are provided by the runtime: {:?}", assumed.len(), assumed);
+            }
+            if !result.excluded.is_empty() {
+                info!("Excluded {} libraries by --exclude and --only: {:?}", result.excluded.len(), result.excluded);
+            }
+            if !result.unreachable.is_empty() {
+                diagnostics.report(Diagnostic::warning("unreachable_libraries", format!("{} libraries are not reachable from {}{}: {:?}",
+                    result.unreachable.len(), main_file_name, if options.prune_unreachable { " and were pruned" } else { "" }, result.unreachable),
+                    result.unreachable.clone()));
+            }
+            for group in &result.cycle_groups {
+                diagnostics.report(Diagnostic::warning("dependency_cycle", format!("Loading the cyclic group {} as a unit", group.join(", ")), group.clone()));
+            }
+            if let Some(deepest) = &result.deepest_unresolved_chain {
+                diagnostics.report(Diagnostic::warning("unresolved_libraries", format!("{} libraries are unresolved, resolution fails at depth {} under {}",
+                    result.unresolved.len(), deepest.chain.len() - 1, deepest.chain.join(" -> ")), result.unresolved.clone()));
+            }
+            if args.fail_on_missing && !result.unresolved.is_empty() {
+                return Err(LddTopoError::MissingLibrary(result.unresolved.clone()));
+            }
+            let expected_tuples = match args.target.as_ref().and_then(|target| target.multiarch) {
+                Some(tuple) => vec![tuple],
+                None => multiarch::tuples_of_file(&shared_library_path).unwrap_or_default(),
+            };
+            result.arch_mismatches = multiarch::arch_mismatches(&result, &expected_tuples);
+            for mismatch in &result.arch_mismatches {
+                diagnostics.report(Diagnostic::warning("arch_mismatch", format!("{} was resolved from the multiarch directory {}, which does not match the {} architecture of {}",
+                    mismatch.library, mismatch.multiarch_dir, mismatch.expected.join("/"), main_file_name), vec![mismatch.library.clone()]));
+            }
+            if args.fail_on_isolated_main && deps.interpreter.is_some() && is_isolated(&result, &main_file_name) {
+                return Err(LddTopoError::IsolatedMain { binary: main_file_name.clone(), needed: deps.needed.len() });
+            }
+            finish(result, &main_file_name, &root, args, settings, diagnostics)
         }
     }
 }
 
-fn export_to_dot(result: &TopoSortResult, dot_path: PathBuf) {
-    let mut graph_to_export = Graph::<_, i32>::new();
-    let mut vertex_to_index: HashMap::<String, NodeIndex> = HashMap::new();
-    result.vertices.iter().for_each(|v| {
-        let idx: NodeIndex = graph_to_export.add_node(v.clone());
-        vertex_to_index.insert(v.clone(), idx);
-    });
-    result.edges.iter().for_each(|edge| {
-        let from_idx = vertex_to_index.get(&edge.src).unwrap().clone();
-        let to_idx = vertex_to_index.get(&edge.dst).unwrap().clone();
-        graph_to_export.add_edge(from_idx, to_idx, 0);
-    });
-    std::fs::write(dot_path, format!("{}", Dot::with_config(&graph_to_export, &[Config::EdgeNoLabel])))
-        .expect("Unable to write file");
-}
-
-fn get_topologically_sorted_result(main_lib_name: &str, main_lib_path: &str, deps: &DependencyTree) -> Result<TopoSortResult, Cycle<u32>> {
-    // Imagine we have 6 libraries, A, B, C, D, E and F
-    // A depends on B
-    // A depends on C
-    // A depends on F
-    // B depends on D
-    // C depends on D
-    // D depends on E
-    // E depends on F
-    // The following direct acyclic graph represents the dependency between libraries, the edge means `depends`, A -> B means A depends on B
-    /*
-          ┌─────────────┐
-          │             │
-   ┌──────A──────┐      │
-   │             │      │
-   │             │      │
-   ▼             ▼      │
-   B             C      │
-   │             │      │
-   └─────►D◄─────┘      │
-          │             │
-          │             │
-          ▼             ▼
-          E───────────► F
-    */
-    // The usage of topological sorting from Wiki:
-    // The canonical application of topological sorting is in scheduling a sequence of jobs or tasks based on their dependencies.
-    // The jobs are represented by vertices, and there is an edge from x to y if job x must be completed before job y can be started
-
-    // If library A depends on library B, B must come before A (B must be loaded first).
-    // In terms of DAG it means we should swap the edge between vertices, the graph will become
-    /*
-
-  ┌──────F───────┐
-  │              │
-  ▼              ▼
-  E       ┌─────►A◄─────┐
-  │       │             │
-  │       B             C
-  │       ▲             ▲
-  │       └──────D──────┘
-  │              ▲
-  └──────────────┘
-     */
-
-    let mut di_graph_map = DiGraphMap::new();
-    let mut id_gen = IdGen::new();
-
-    let main_lib_id: u32 = id_gen.get_next_id(main_lib_name);
-    for direct_dep in &deps.needed {
-        let direct_lib_id = id_gen.get_next_id(direct_dep.as_str());
-        if !di_graph_map.contains_node(direct_lib_id) {
-            di_graph_map.add_node(direct_lib_id);
-        }
-        // `main_lib_id` depends on `direct_lib_id`, but the edge points that `direct_lib_id` must come before `main_lib_id`
-        di_graph_map.add_edge(direct_lib_id, main_lib_id, ());
-    }
-    for (_, lib) in &deps.libraries {
-        let lib_id = id_gen.get_next_id(lib.name.as_str());
-        if !di_graph_map.contains_node(lib_id) {
-            di_graph_map.add_node(lib_id);
-        }
-        for needed in &lib.needed {
-            if let Some(dep_lib) = deps.libraries.get(needed) {
-                let dep_lib_id = id_gen.get_next_id(dep_lib.name.as_str());
-                if !di_graph_map.contains_node(dep_lib_id) {
-                    di_graph_map.add_node(dep_lib_id);
+/// The steps that do not depend on how `result` was obtained: adding what the options of `args` ask for, then checking
+/// and writing it. `main_file_name` is the analyzed binary, the first one when several were merged
+fn finish(mut result: TopoSortResult, main_file_name: &str, root: &Path, args: &Args, settings: &OutputSettings, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    if let Some(debug_root) = &args.debug_root {
+        add_debug_info(&mut result, debug_root);
+    }
+    if args.hash {
+        add_hashes(&mut result, diagnostics);
+    }
+    if args.elf_metadata {
+        add_elf_metadata(&mut result);
+        let mixed = elf_metadata::mixed_architectures(&result, main_file_name);
+        if !mixed.is_empty() {
+            let described: Vec<String> = mixed.iter()
+                .map(|lib| format!("{} ({} {}-bit)", lib.name, lib.machine.as_deref().unwrap_or_default(), lib.class.unwrap_or_default()))
+                .collect();
+            let message = format!("{} libraries do not match the architecture of {}: {}", mixed.len(), main_file_name, described.join(", "));
+            let libraries = mixed.iter().map(|lib| lib.name.clone()).collect();
+            if !args.allow_arch_mismatch {
+                return Err(LddTopoError::ArchMismatch { code: "mixed_architectures", message, libraries });
+            }
+            diagnostics.report(Diagnostic::warning("mixed_architectures", message, libraries));
+        }
+    }
+    if args.hardening_report {
+        add_hardening(&mut result);
+        result.hardening_summary = hardening::summary(&result);
+        for row in result.hardening_summary.iter().filter(|row| !row.missing.is_empty()) {
+            info!("{} of {} libraries lack {}: {}", row.missing.len(), row.total, row.feature, row.missing.join(", "));
+        }
+    }
+    if args.resolve_packages {
+        // with --osv as well, the version strings of the files stand in for the packages that are not found
+        add_packages(&mut result, &PackageResolver::new(root), args.osv.is_some());
+        result.package_dependencies = packages::package_dependencies(&result);
+        info!("{} libraries belong to {} packages", result.topo_sorted_libs.iter().filter(|lib| lib.package.is_some()).count(),
+            result.package_dependencies.len());
+        add_package_licenses(&mut result, root);
+        let summary = license::summarize(&result, main_file_name);
+        info!("The dependencies have {} licenses, {} have none", summary.licenses.len(), summary.unlicensed.len());
+        result.licenses = Some(summary);
+    } else if args.label_template.as_ref().is_some_and(LabelTemplate::uses_package) {
+        add_packages(&mut result, &PackageResolver::new(root), false);
+    }
+    if let Some(osv_dir) = &args.osv {
+        let database = match OsvDatabase::load(osv_dir) {
+            Ok((database, skipped)) => {
+                if skipped > 0 {
+                    info!("Skipped {} files of {} that are not OSV records", skipped, osv_dir.display());
+                }
+                database.with_release(osv::os_release(root))
+            }
+            Err(err) => return Err(LddTopoError::UnreadableInput { input: Input::Osv, path: osv_dir.clone(), reason: err.to_string() }),
+        };
+        if !args.resolve_packages {
+            add_packages(&mut result, &PackageResolver::new(root), true);
+        }
+        add_vulnerabilities(&mut result, &database);
+        let unmapped: Vec<String> = result.topo_sorted_libs.iter()
+            .filter(|lib| lib.path.is_some() && lib.package.is_none())
+            .map(|lib| lib.name.clone())
+            .collect();
+        if !unmapped.is_empty() {
+            info!("No package found for {} libraries, they are not checked: {:?}", unmapped.len(), unmapped);
+        }
+        for lib in result.topo_sorted_libs.iter().filter(|lib| !lib.vulnerabilities.is_empty()) {
+            let package = lib.package.as_ref().map(|package| format!("{} {}", package.name, package.version)).unwrap_or_default();
+            diagnostics.report(Diagnostic::warning("known_vulnerabilities", format!("{} from {} has {} known vulnerabilities: {}",
+                lib.name, package, lib.vulnerabilities.len(), lib.vulnerabilities.join(", ")), vec![lib.name.clone()]));
+        }
+    }
+    if args.glibc_versions {
+        add_glibc_versions(&mut result);
+        if let Some((version, libraries)) = symbol_versions::min_glibc_version(&result) {
+            info!("{} needs glibc {} or newer, because of {}", main_file_name, version, libraries.join(", "));
+            result.min_glibc_version = Some(version);
+        }
+    }
+    if args.cxx_versions {
+        add_cxx_versions(&mut result);
+        let requirements = symbol_versions::cxx_requirements(&result);
+        if let Some(glibcxx) = &requirements.glibcxx {
+            info!("{} needs a libstdc++ with {}, {} libraries need libstdc++ versions: {}", main_file_name, glibcxx,
+                requirements.libraries.len(), requirements.libraries.join(", "));
+        }
+        result.cxx_requirements = Some(requirements);
+    }
+    if args.symbols || args.report_overlinking || args.report_underlinking || args.report_interposition {
+        let symbols: HashMap<String, DynamicSymbols> = result.topo_sorted_libs.iter()
+            .filter_map(|lib| lib.path.as_ref().map(|path| (lib, path)))
+            .filter_map(|(lib, path)| DynamicSymbols::read(Path::new(path)).map(|symbols| (lib.name.clone(), symbols)))
+            .collect();
+        if args.symbols || args.report_overlinking {
+            symbols::annotate_edges(&mut result, &symbols, args.symbol_map);
+        }
+        if args.report_interposition {
+            result.symbol_conflicts = symbols::interposition(&result, &symbols);
+            for conflict in &result.symbol_conflicts {
+                diagnostics.report(Diagnostic::warning("symbol_interposition", format!("{} is exported by {}, {} wins",
+                    conflict.symbol, conflict.libraries.join(", "), conflict.winner), conflict.libraries.clone()));
+            }
+        }
+        if args.report_underlinking {
+            result.underlinked = symbols::underlinking(&result, &symbols, main_file_name);
+            for underlinked in &result.underlinked {
+                if !underlinked.unresolved.is_empty() {
+                    diagnostics.report(Diagnostic::warning("unresolved_symbols", format!("No library defines the symbols {} of {}",
+                        underlinked.unresolved.join(", "), underlinked.library), vec![underlinked.library.clone()]));
+                }
+                if !underlinked.from_executable.is_empty() {
+                    diagnostics.report(Diagnostic::warning("symbols_from_executable", format!("{} relies on {} to define {}",
+                        underlinked.library, main_file_name, underlinked.from_executable.join(", ")), vec![underlinked.library.clone()]));
                 }
-                // `lib_id` depends on `dep_lib_id`, but the edge points that `dep_lib_id` must come before `lib_id`
-                di_graph_map.add_edge(dep_lib_id, lib_id, ());
             }
         }
     }
-    let mut vertices: Vec<String> = Vec::with_capacity(di_graph_map.node_count());
-    di_graph_map.nodes().for_each(|vertex_id| {
-        let v = String::from(id_gen.get_by_id(vertex_id).unwrap());
-        vertices.push(v.clone());
+    if args.report_overlinking {
+        for edge in symbols::mark_overlinking(&mut result) {
+            diagnostics.report(Diagnostic::warning("overlinking", format!("{} lists {} in its NEEDED entries but uses none of its symbols", edge.dst, edge.src),
+                vec![edge.dst.clone(), edge.src.clone()]));
+        }
+    }
+    if args.scan_dlopen {
+        add_dlopen_guesses(&mut result, root, args.library_paths.as_deref().unwrap_or_default())?;
+    }
+    if let Some(trace) = &args.runtime_trace {
+        match std::fs::read_to_string(trace) {
+            Ok(content) => {
+                let loads = runtime_trace::parse_trace(&content);
+                let added = runtime_trace::merge_trace(&mut result, &loads, main_file_name);
+                info!("{} loads in {}, {} of them not in the static graph, {} new libraries", loads.len(), trace.display(), added, result.runtime_libs.len());
+            }
+            Err(err) => return Err(LddTopoError::UnreadableInput { input: Input::RuntimeTrace, path: trace.clone(), reason: err.to_string() }),
+        }
+    }
+    if let Some(target) = &args.impact_of {
+        match impact_of(&result, target) {
+            Ok(impact) => {
+                info!("{} libraries depend on {}: {:?}", impact.count, target, impact.dependents);
+                result.impact = Some(impact);
+            }
+            Err(reason) => return Err(LddTopoError::UnknownLibrary { library: Some(target.clone()), reason }),
+        }
+    }
+    let mut path_budget = PathBudget::new(args.path_budget);
+    if let Some(target) = &args.all_paths_to {
+        match all_paths(&result, main_file_name, target, &mut path_budget) {
+            Ok(paths) => {
+                if paths.truncated {
+                    diagnostics.report(Diagnostic::warning("path_budget_exceeded", path_budget.exceeded_message(), vec![target.clone()]));
+                }
+                info!("Found {} NEEDED chains from {} to {}", paths.paths.len(), main_file_name, target);
+                result.all_paths = Some(paths);
+            }
+            Err(reason) => return Err(LddTopoError::UnknownLibrary { library: Some(target.clone()), reason }),
+        }
+    }
+    if let Some(license_map) = &args.license_map {
+        match license::read_license_map(license_map) {
+            Ok(map) => {
+                let summary = license::annotate(&mut result, &map, main_file_name);
+                if !summary.unlicensed.is_empty() {
+                    diagnostics.report(Diagnostic::warning("unlicensed_libraries", format!("{} dependencies have no entry in {}: {:?}",
+                        summary.unlicensed.len(), license_map.display(), summary.unlicensed), summary.unlicensed.clone()));
+                }
+                result.licenses = Some(summary);
+            }
+            Err(err) => return Err(LddTopoError::UnreadableInput { input: Input::LicenseMap, path: license_map.clone(), reason: err.to_string() }),
+        }
+    }
+    if let Some(lib) = &args.focus {
+        match focus::focus(&mut result, lib) {
+            Ok(dropped) => info!("Focusing on {}: {} of {} libraries shown", lib, result.vertices.len(), result.vertices.len() + dropped),
+            Err(reason) => return Err(LddTopoError::UnknownLibrary { library: Some(lib.clone()), reason }),
+        }
+    }
+    if let Some(max_depth) = args.max_depth {
+        let dropped = focus::limit_depth(&mut result, max_depth);
+        info!("Truncated at depth {}: {} of {} libraries shown", max_depth, result.vertices.len(), result.vertices.len() + dropped);
+    }
+    if args.group_by_dir {
+        result.load_order_by_dir = load_order_by_dir(&result);
+    }
+    let mut view = collapse_result(&result, &args.collapse_by_prefix);
+    result.collapsed_groups = view.groups.clone();
+    let note = args.node_limit.and_then(|limit| {
+        let total = view.vertices.len();
+        match node_limit::limit_nodes(&mut view, main_file_name, limit) {
+            0 => None,
+            dropped => Some(format!("Truncated by --node-limit: {} of {} libraries shown", total - dropped, total)),
+        }
     });
-    vertices.sort();
+    if args.self_check {
+        validate_result(&result, diagnostics)?;
+    }
+    if let Some(bazel_path) = &args.bazel {
+        std::fs::write(bazel_path, bazel::to_bzl(&result, root, "declare_libraries"))
+            .map_err(|err| LddTopoError::io(format!("Cannot write {}", bazel_path.display()), err))?;
+    }
+    let dot_options = DotOptions {
+        cluster_by_dir: args.dot_cluster_by_dir,
+        html_labels: args.html_labels,
+        label_template: args.label_template.clone(),
+        root: root.to_path_buf(),
+        note,
+    };
+    let text_label = dot::text_label(&result, args.label_template.as_ref());
+    if args.output_file.is_some() || !args.repl {
+        match args.format {
+            OutputFormat::Json => write_json(&args.output_file, &result, settings.pretty)?,
+            OutputFormat::Ndjson => write_ndjson(&args.output_file, &result)?,
+            OutputFormat::Yaml | OutputFormat::Toml | OutputFormat::Msgpack => {
+                write_serialized(&args.output_file, &result, args.format, settings.pretty)?;
+            }
+            OutputFormat::CsvEdges => write_text(&args.output_file, &output::csv_edges(&result))?,
+            OutputFormat::Gexf => write_text(&args.output_file, &gexf::to_gexf(&result))?,
+            OutputFormat::Mermaid => write_text(&args.output_file, &mermaid::to_mermaid(&result, &view, args.mermaid_depth, &text_label, dot_options.note.as_deref()))?,
+            OutputFormat::Plantuml => write_text(&args.output_file, &plantuml::to_plantuml(&result, args.plantuml_group_by_dir, &text_label))?,
+            OutputFormat::Sqlite => write_sqlite(&args.output_file, &result)?,
+            OutputFormat::Cypher => write_text(&args.output_file, &cypher::to_cypher(&result))?,
+            OutputFormat::Matrix => {
+                if result.vertices.len() > matrix::LARGE_MATRIX {
+                    diagnostics.report(Diagnostic::warning("large_matrix",
+                        format!("The adjacency matrix of {} libraries has {} cells", result.vertices.len(), result.vertices.len() * result.vertices.len()), vec![]));
+                }
+                write_text(&args.output_file, &matrix::to_csv(&result))?;
+            }
+            OutputFormat::Dot => write_text(&args.output_file, &dot::render(&result, &view, &dot_options))?,
+        }
+    }
+    let dot_path = match (&args.dot_output, &args.output_file) {
+        _ if args.format == OutputFormat::Dot => None,
+        (Some(dot_path), _) => Some(dot_path.clone()),
+        (None, Some(output_file)) if settings.dot => Some(dot_path_for(output_file)),
+        (None, None) if args.dot => {
+            diagnostics.report(Diagnostic::warning("dot_skipped",
+                "The DOT file is written next to --output-file, skipping it because the output goes to stdout and --dot-output is not set".to_string(), vec![]));
+            None
+        }
+        _ => None,
+    };
+    if let Some(dot_path) = dot_path {
+        dot::export_to_dot(&result, &view, &dot_options, &dot_path)
+            .map_err(|err| LddTopoError::io(format!("Cannot write {}", dot_path.display()), err))?;
+    }
+    if let Some(target) = &args.render {
+        std::fs::write(&target.path, render::to_svg(&view, &text_label, dot_options.note.as_deref()))
+            .map_err(|err| LddTopoError::io(format!("Cannot write {}", target.path.display()), err))?;
+    }
+    if let Some(dir) = &args.html_report {
+        html_report::write_report(&result, dir)
+            .map_err(|err| LddTopoError::io(format!("Cannot write the HTML report to {}", dir.display()), err))?;
+    }
+    if args.repl {
+        let stdin = std::io::stdin();
+        repl::Repl::new(&result, main_file_name, path_budget.limit()).run(stdin.lock(), std::io::stdout().lock())
+            .map_err(|err| LddTopoError::io("Cannot run the prompt".to_string(), err))?;
+    }
+    Ok(())
+}
 
-    let mut edges: Vec<Edge> = Vec::with_capacity(di_graph_map.edge_count());
-    di_graph_map.all_edges().for_each(|(from, to, _)| {
-        let from = String::from(id_gen.get_by_id(from).unwrap());
-        let to = String::from(id_gen.get_by_id(to).unwrap());
-        edges.push(Edge { src: from, dst: to });
-    });
-    edges.sort();
-
-    let mut library_map: BTreeMap<String, Lib> = BTreeMap::new();
-    for (name, lib) in &deps.libraries {
-        let path = String::from(lib.path.as_path().to_str().unwrap());
-        library_map.insert(name.clone(), Lib { name: name.clone(), path: Some(path) });
-    }
-
-    let topological_sorted = toposort(&di_graph_map, None)?;
-    let mut topo_sorted_libs: Vec<Lib> = Vec::with_capacity(topological_sorted.len());
-    for id in &topological_sorted {
-        let lib_name = id_gen.get_by_id(*id).unwrap();
-        let lib_path = if lib_name != main_lib_name {
-            deps.libraries.get(lib_name).map(|lib| {
-                String::from(lib.path.clone().as_path().to_str().unwrap())
-            })
-        } else { Some(String::from(main_lib_path)) };
-        topo_sorted_libs.push(Lib {
-            name: String::from(lib_name),
-            path: lib_path,
+/// Fails the run when an option is set that needs the graph of a single binary, as a merged graph has several roots
+fn reject_single_binary_options(args: &Args) -> Result<(), LddTopoError> {
+    let options: Vec<&str> = [
+        ("--target", args.target.is_some()),
+        ("--candidate-root", !args.candidate_root.is_empty()),
+        ("--fail-on-isolated-main", args.fail_on_isolated_main),
+        ("--runtime-trace", args.runtime_trace.is_some()),
+        ("--all-paths-to", args.all_paths_to.is_some()),
+        ("--repl", args.repl),
+    ].into_iter().filter(|(_, set)| *set).map(|(option, _)| option).collect();
+    match options.as_slice() {
+        [] => Ok(()),
+        _ => Err(LddTopoError::Usage(format!("{} cannot be used with a merged graph, analyze a single binary instead", options.join(", ")))),
+    }
+}
+
+/// Writes the `merged` graph with every option of `args` applied to it, as for a single binary
+fn write_merged(merged: TopoSortResult, main_file_name: &str, args: &Args, settings: &OutputSettings, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    if args.fail_on_missing && !merged.unresolved.is_empty() {
+        return Err(LddTopoError::MissingLibrary(merged.unresolved.clone()));
+    }
+    let root = args.root_path.clone().unwrap_or(PathBuf::from("/"));
+    finish(merged, main_file_name, &root, args, settings, diagnostics)
+}
+
+/// The file name of `path`, which names the library in the graph
+fn library_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// `results` merged with `--merge-strategy`, every conflict is reported
+fn merged(results: &[TopoSortResult], args: &Args, diagnostics: &Diagnostics) -> Result<TopoSortResult, LddTopoError> {
+    let merged = merge::merge(results, args.merge_strategy).map_err(LddTopoError::Merge)?;
+    for conflict in &merged.merge_conflicts {
+        diagnostics.report(Diagnostic::warning("merge_conflict", format!("{} resolves to {}, picked {}", conflict.library,
+            std::iter::once(&conflict.chosen).chain(&conflict.alternatives).map(String::as_str).collect::<Vec<_>>().join(", "), conflict.chosen),
+            vec![conflict.library.clone()]));
+    }
+    Ok(merged)
+}
+
+/// Analyzes every --shared-library-path on its own and merges the graphs with `merged`
+fn analyze_and_merge(args: &Args, diagnostics: &Diagnostics) -> Result<TopoSortResult, LddTopoError> {
+    let resolver = new_resolver(args.root_path.clone().unwrap_or(PathBuf::from("/")), args, diagnostics);
+    let options = analysis_options(args);
+    let results = args.shared_library_path.iter()
+        .map(|path| analyze_file(path, &resolver, &options))
+        .collect::<Result<Vec<TopoSortResult>, LddTopoError>>()?;
+    info!("Merging the graphs of {} libraries", results.len());
+    merged(&results, args, diagnostics)
+}
+
+/// Analyzes the binaries of --wheel into one graph and sorts its libraries into the bundled and external ones
+fn write_wheel(wheel: &Path, args: &Args, settings: &OutputSettings, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let dir = TEMPORARY_ROOT.get().cloned().flatten().expect("the wheel is extracted before the run");
+    let mut merged = analyze_and_merge(args, diagnostics)?;
+    let file_name = wheel.file_name().unwrap_or_default().to_string_lossy();
+    let policy = wheel::policy_of(&file_name);
+    let report = wheel::report(&merged, &dir, &file_name, policy);
+    info!("{} libraries of {} are bundled, {} are external", report.bundled.len(), file_name, report.external.len());
+    for library in &report.unbundled {
+        diagnostics.report(Diagnostic::warning("unbundled_library", format!("{} needs {}, which is neither bundled nor one of the libraries of {}",
+            file_name, library, policy.map(|policy| policy.name).unwrap_or_default()), vec![library.clone()]));
+    }
+    merged.wheel = Some(report);
+    write_merged(merged, &library_name(&args.shared_library_path[0]), args, settings, diagnostics)
+}
+
+/// Analyzes the binaries of --appimage into one graph and reports the libraries the bundle lacks
+fn write_appimage(image: &Path, args: &Args, settings: &OutputSettings, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let merged = analyze_and_merge(args, diagnostics)?;
+    let unbundled = appimage::unbundled(&merged);
+    for library in &unbundled {
+        diagnostics.report(Diagnostic::warning("unbundled_library", format!("{} needs {}, which is neither bundled nor on the AppImage excludelist",
+            image.display(), library), vec![library.clone()]));
+    }
+    if unbundled.is_empty() {
+        info!("{} is self-contained, {} libraries are bundled", image.display(), merged.topo_sorted_libs.iter().filter(|lib| !lib.missing).count());
+    }
+    write_merged(merged, &library_name(&args.shared_library_path[0]), args, settings, diagnostics)
+}
+
+/// Analyzes the ELF files under `dir`, or a sample of them with `--sample`, each one on its own
+fn scan(dir: &Path, args: &Args, diagnostics: &Diagnostics) -> ScanReport {
+    let discovered = scan_dir::discover(dir);
+    let (selected, sample) = match args.sample {
+        Some(fraction) => {
+            let selected = scan_dir::sample(&discovered, dir, fraction, args.sample_seed);
+            diagnostics.report(Diagnostic::warning("sampled_scan", format!("Analyzing a sample of {} of the {} ELF files under {} (seed {}), the report is not exhaustive",
+                selected.len(), discovered.len(), dir.display(), args.sample_seed), vec![]));
+            let sample = Sample { fraction, seed: args.sample_seed, selected: selected.len() };
+            (selected, Some(sample))
+        }
+        None => {
+            info!("Analyzing {} ELF files under {}", discovered.len(), dir.display());
+            (discovered.clone(), None)
+        }
+    };
+    let resolver = new_resolver(args.root_path.clone().unwrap_or(PathBuf::from("/")), args, diagnostics);
+    let options = analysis_options(args);
+    let binaries = selected.iter().map(|path| {
+        let (result, error) = match analyze_file(path, &resolver, &options) {
+            Ok(result) => (Some(result), None),
+            // Only analysis errors name the file
+            Err(err @ LddTopoError::Analysis { .. }) => (None, Some(err.to_string())),
+            Err(err) => (None, Some(format!("Cannot analyze {}: {}", path.display(), err))),
+        };
+        if let Some(error) = &error {
+            diagnostics.report(Diagnostic::warning("unanalyzable_binary", error.clone(), vec![path.to_string_lossy().into_owned()]));
+        }
+        ScannedBinary { path: path.to_string_lossy().into_owned(), result, error }
+    }).collect();
+    ScanReport { dir: dir.to_string_lossy().into_owned(), discovered: discovered.len(), sample, binaries }
+}
+
+/// Analyzes both binaries of `--delta` and compares their dependency closures
+fn delta(files: &[PathBuf], args: &Args, diagnostics: &Diagnostics) -> Result<LibraryDelta, LddTopoError> {
+    let resolver = new_resolver(args.root_path.clone().unwrap_or(PathBuf::from("/")), args, diagnostics);
+    let options = analysis_options(args);
+    let analyzed: Vec<(String, TopoSortResult)> = files.iter().map(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let result = analyze_file(path, &resolver, &options)?;
+        Ok((options.normalize_names.map(|mode| mode.apply(&name).into_owned()).unwrap_or(name), result))
+    }).collect::<Result<_, LddTopoError>>()?;
+    delta::library_delta(&analyzed[0].0, &analyzed[0].1, &analyzed[1].0, &analyzed[1].1)
+        .map_err(|reason| LddTopoError::UnknownLibrary { library: None, reason })
+}
+
+/// Compares two earlier outputs
+fn diff(args: &DiffArgs, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let diff = graph_diff::diff_graphs(&read_result(&args.old, diagnostics)?, &read_result(&args.new, diagnostics)?);
+    info!("{} libraries added, {} removed, {} edges added, {} removed, {} paths changed", diff.added_vertices.len(), diff.removed_vertices.len(),
+        diff.added_edges.len(), diff.removed_edges.len(), diff.path_changes.len());
+    report_order_diff(&diff.order, diagnostics);
+    match args.format {
+        DiffFormat::Json => write_json(&args.output_file, &diff, !args.compact),
+        DiffFormat::Text => write_text(&args.output_file, &graph_diff::to_text(&diff)),
+    }
+}
+
+/// Checks an earlier output against the schema, its invariants and the baseline, policy and RPATH audit of `args`
+fn check(args: &CheckArgs, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let result = read_result(&args.result, diagnostics)?;
+    validate_result(&result, diagnostics)?;
+    info!("{} is a valid result", args.result.display());
+    if let Some(baseline) = &args.baseline {
+        check_baseline(&read_result(baseline, diagnostics)?, &result, &args.fail_on, diagnostics)?;
+        info!("{} has no regression against {}", args.result.display(), baseline.display());
+    }
+    if let Some(policy) = &args.policy {
+        match policy.to_str().and_then(platform_policy::find) {
+            Some(platform) => check_platform_policy(platform, &result, diagnostics)?,
+            None => check_policy(policy, &result, diagnostics)?,
+        }
+        info!("{} follows the policy {}", args.result.display(), policy.display());
+    }
+    if args.rpath_audit {
+        check_rpaths(&result, args.install_prefix.as_deref(), diagnostics)?;
+        info!("{} has no insecure RPATH or RUNPATH entry", args.result.display());
+    }
+    Ok(())
+}
+
+/// Answers `args.query`, or all queries read from stdin when there is none. The result can also be a binary to analyze.
+fn query(args: &QueryArgs, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let result = read_result(&args.result, diagnostics)?;
+    let main_lib_name = args.main.as_deref().or_else(|| query::main_library(&result)).map(str::to_string).ok_or_else(|| LddTopoError::UnknownLibrary {
+        library: None, reason: format!("Cannot tell the main library of {}, pass it with --main", args.result.display()) })?;
+    let repl = repl::Repl::new(&result, &main_lib_name, args.path_budget);
+    if args.query.is_empty() {
+        let stdin = std::io::stdin();
+        return repl.run(stdin.lock(), std::io::stdout().lock())
+            .map_err(|err| LddTopoError::io("Cannot run the prompt".to_string(), err));
+    }
+    let answer = repl.execute(&args.query.join(" ")).map_err(LddTopoError::Query)?;
+    write_text(&None, &format!("{}\n", answer))
+}
+
+/// Hashes the libraries of an earlier output and writes it as an SBOM
+fn sbom(args: &SbomArgs, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let result = read_result(&args.result, diagnostics)?;
+    let mut sha256: BTreeMap<String, String> = BTreeMap::new();
+    let mut unhashed: Vec<String> = Vec::new();
+    for lib in &result.topo_sorted_libs {
+        if let Some(digest) = &lib.sha256 {
+            sha256.insert(lib.name.clone(), digest.clone());
+        } else if let Some(path) = &lib.path {
+            match hash::sha256_file(Path::new(path)) {
+                Ok(digest) => {
+                    sha256.insert(lib.name.clone(), digest);
+                }
+                Err(_) => unhashed.push(lib.name.clone()),
+            }
+        }
+    }
+    if !unhashed.is_empty() {
+        diagnostics.report(Diagnostic::warning("unhashed_libraries",
+            format!("{} libraries cannot be read to compute their SHA-256, they are listed without hashes: {:?}", unhashed.len(), unhashed), unhashed));
+    }
+    match args.format {
+        SbomFormat::Cyclonedx => write_json(&args.output_file, &sbom::to_cyclonedx(&result, &sha256), true),
+        SbomFormat::Spdx => {
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+            write_json(&args.output_file, &sbom::to_spdx(&result, &sha256, &sbom::utc_timestamp(now.as_secs())), true)
+        }
+    }
+}
+
+fn report_order_diff(diff: &OrderDiff, diagnostics: &Diagnostics) {
+    if diff.identical {
+        info!("Order identical for the {} common libraries", diff.common);
+    } else {
+        diagnostics.report(Diagnostic::warning("order_changed",
+            format!("Order changed for {} of the {} common libraries", diff.moved.len(), diff.common),
+            diff.moved.iter().map(|lib| lib.name.clone()).collect()));
+    }
+}
+
+/// Fails the run when `result` changed against `baseline` in one of the `fail_on` ways, after reporting every such change
+fn check_baseline(baseline: &TopoSortResult, result: &TopoSortResult, fail_on: &[Regression], diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let diff = graph_diff::diff_graphs(baseline, result);
+    let mut failed = Vec::new();
+    for regression in [Regression::NewDeps, Regression::RemovedDeps, Regression::PathChanges] {
+        let libraries = diff.libraries_of(regression);
+        if libraries.is_empty() {
+            continue;
+        }
+        let message = format!("{} against the baseline: {:?}", regression.code().replace('_', " "), libraries);
+        if fail_on.contains(&regression) {
+            diagnostics.report(Diagnostic::error(regression.code(), message, libraries.clone()));
+            failed.extend(libraries);
+        } else {
+            diagnostics.report(Diagnostic::warning(regression.code(), message, libraries));
+        }
+    }
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(LddTopoError::CheckFailed { check: "comparison with the baseline".to_string(), libraries: failed }),
+    }
+}
+
+/// Fails the run when a dependency of `result` breaks a rule of the policy at `path`, after reporting every violation
+fn check_policy(path: &Path, result: &TopoSortResult, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let policy = Policy::read(path)
+        .map_err(|err| LddTopoError::UnreadableInput { input: Input::Policy, path: path.to_path_buf(), reason: err.to_string() })?;
+    let main_lib_name = query::main_library(result).unwrap_or_default();
+    let violations = policy.violations(result, main_lib_name);
+    for violation in &violations {
+        diagnostics.report(Diagnostic::error("policy_violation", violation.describe(), vec![violation.library.clone()]));
+    }
+    failed_check(format!("policy {}", path.display()), violations.iter().map(|violation| violation.library.clone()))
+}
+
+/// Fails the run when the analyzed library or an external library it pulls in breaks the platform policy, after
+/// reporting every violation. The symbol versions are read from the libraries on this machine.
+fn check_platform_policy(policy: &PlatformPolicy, result: &TopoSortResult, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let main_lib_name = query::main_library(result).unwrap_or_default();
+    let needed_versions = |lib: &Lib| {
+        let versions = lib.path.as_ref().and_then(|path| symbol_versions::read_needed_versions(Path::new(path)));
+        if versions.is_none() && !lib.missing {
+            diagnostics.report(Diagnostic::warning("unreadable_library", format!("Cannot read the symbol versions of {}", lib.name), vec![lib.name.clone()]));
+        }
+        versions.unwrap_or_default()
+    };
+    let violations = policy.violations(result, main_lib_name, needed_versions);
+    for violation in &violations {
+        diagnostics.report(Diagnostic::error("policy_violation", violation.describe(policy), vec![violation.library().to_string()]));
+    }
+    failed_check(format!("policy {}", policy.name), violations.iter().map(|violation| violation.library().to_string()))
+}
+
+/// Fails the run when a library of `result` has an insecure RPATH or RUNPATH entry, after reporting every finding
+/// with the severity of its problem
+fn check_rpaths(result: &TopoSortResult, install_prefix: Option<&Path>, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    let main_lib_name = query::main_library(result).unwrap_or_default();
+    let prefix = install_prefix.map(Path::to_path_buf)
+        .or_else(|| rpath_audit::default_install_prefix(result, main_lib_name))
+        .unwrap_or_else(|| PathBuf::from("/"));
+    let entries = |lib: &Lib| {
+        let entries = lib.path.as_ref().and_then(|path| rpath_audit::read_search_path_entries(Path::new(path)));
+        if entries.is_none() && !lib.missing {
+            diagnostics.report(Diagnostic::warning("unreadable_library", format!("Cannot read the search paths of {}", lib.name), vec![lib.name.clone()]));
+        }
+        entries
+    };
+    let findings = rpath_audit::audit(result, &prefix, entries, rpath_audit::is_world_writable);
+    for finding in &findings {
+        diagnostics.report(Diagnostic {
+            code: finding.problem.code().to_string(),
+            severity: finding.problem.severity(),
+            message: finding.describe(),
+            libraries: vec![finding.library.clone()],
         });
     }
-    return Result::Ok(TopoSortResult {
-        vertices: vertices,
-        edges: edges,
-        library_map: library_map,
-        topo_sorted_libs: topo_sorted_libs,
+    let errors = findings.iter().filter(|finding| finding.problem.severity() == Severity::Error);
+    failed_check("RPATH audit".to_string(), errors.map(|finding| finding.library.clone()))
+}
+
+/// `LddTopoError::CheckFailed` for `check` when it found `libraries` at fault, each one once
+fn failed_check(check: String, libraries: impl Iterator<Item = String>) -> Result<(), LddTopoError> {
+    let mut libraries: Vec<String> = libraries.collect();
+    libraries.sort();
+    libraries.dedup();
+    match libraries.is_empty() {
+        true => Ok(()),
+        false => Err(LddTopoError::CheckFailed { check, libraries }),
+    }
+}
+
+/// Fails the run when `result` does not conform to the JSON Schema or breaks its own invariants, see `validate::validate`,
+/// after reporting every violation
+fn validate_result(result: &TopoSortResult, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    self_check(result, diagnostics)?;
+    if let Err(violations) = validate::validate(result) {
+        error!("Self-check failed, the result is inconsistent:");
+        for violation in &violations {
+            diagnostics.report(Diagnostic::error("inconsistent_result", format!("Inconsistent result: {}", violation), vec![]));
+        }
+        return Err(LddTopoError::InconsistentResult(violations));
+    }
+    Ok(())
+}
+
+/// Fails the run when `value` does not conform to the JSON Schema, after reporting every violation
+fn self_check<T: Serialize>(value: &T, diagnostics: &Diagnostics) -> Result<(), LddTopoError> {
+    if let Err(violations) = self_check::validate_against_schema(value) {
+        error!("Self-check failed, the output does not conform to the JSON Schema:");
+        for violation in &violations {
+            diagnostics.report(Diagnostic::error("schema_violation", format!("Schema violation: {}", violation), vec![]));
+        }
+        return Err(LddTopoError::SchemaViolation(violations));
+    }
+    Ok(())
+}
+
+/// Reads a `TopoSortResult` written by an earlier run, which has to conform to the JSON Schema. Every violation is
+/// reported before the run fails
+fn read_result(path: &Path, diagnostics: &Diagnostics) -> Result<TopoSortResult, LddTopoError> {
+    let unreadable = |reason: String| LddTopoError::UnreadableInput { input: Input::Result, path: path.to_path_buf(), reason };
+    let value: serde_json::Value = File::open(path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| serde_json::from_reader(std::io::BufReader::new(file)).map_err(|err| err.to_string()))
+        .map_err(unreadable)?;
+    if let Err(violations) = self_check::validate_against_definition(&value, "TopoSortResult") {
+        for violation in &violations {
+            diagnostics.report(Diagnostic::error("schema_violation", format!("Schema violation in {}: {}", path.display(), violation), vec![]));
+        }
+        return Err(LddTopoError::SchemaViolation(violations));
+    }
+    serde_json::from_value(value).map_err(|err| unreadable(err.to_string()))
+}
+
+/// Writes `value` as JSON to `output_file`, or to stdout when there is none
+fn write_json<T: Serialize>(output_file: &Option<PathBuf>, value: &T, pretty: bool) -> Result<(), LddTopoError> {
+    let io_error = |err: std::io::Error| LddTopoError::io(format!("Cannot write {}", destination(output_file)), err);
+    let mut writer: BufWriter<Box<dyn Write>> = match output_file {
+        None => BufWriter::new(Box::new(std::io::stdout().lock())),
+        Some(path) => BufWriter::new(Box::new(File::create(path).map_err(io_error)?)),
+    };
+    if pretty {
+        serde_json::to_writer_pretty(&mut writer, value).map_err(|err| io_error(err.into()))?;
+    } else {
+        serde_json::to_writer(&mut writer, value).map_err(|err| io_error(err.into()))?;
+    }
+    if output_file.is_none() {
+        writeln!(writer).map_err(io_error)?;
+    }
+    writer.flush().map_err(io_error)
+}
+
+/// Writes `value` in `format` to `output_file`, or to stdout when there is none
+fn write_serialized<T: Serialize>(output_file: &Option<PathBuf>, value: &T, format: OutputFormat, pretty: bool) -> Result<(), LddTopoError> {
+    let io_error = |err: std::io::Error| LddTopoError::io(format!("Cannot write {}", destination(output_file)), err);
+    let mut bytes = output::serialize(value, format, pretty)
+        .map_err(|err| io_error(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))?;
+    if output_file.is_none() && format.is_text() && !bytes.ends_with(b"\n") {
+        bytes.push(b'\n');
+    }
+    match output_file {
+        None => std::io::stdout().lock().write_all(&bytes),
+        Some(path) => std::fs::write(path, bytes),
+    }
+    .map_err(io_error)
+}
+
+/// Streams `result` as JSON Lines to `output_file`, or to stdout when there is none
+fn write_ndjson(output_file: &Option<PathBuf>, result: &TopoSortResult) -> Result<(), LddTopoError> {
+    match output_file {
+        None => ndjson::write_ndjson(result, std::io::BufWriter::new(std::io::stdout().lock())),
+        Some(path) => std::fs::File::create(path).and_then(|file| ndjson::write_ndjson(result, std::io::BufWriter::new(file))),
+    }
+    .map_err(|err| LddTopoError::io(format!("Cannot write {}", destination(output_file)), err))
+}
+
+/// Adds `result` to the SQLite database `output_file`, a database cannot go to stdout
+fn write_sqlite(output_file: &Option<PathBuf>, result: &TopoSortResult) -> Result<(), LddTopoError> {
+    match output_file {
+        None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "--format sqlite needs an --output-file")),
+        Some(path) => sqlite::write_sqlite(result, path).map_err(std::io::Error::other),
+    }
+    .map_err(|err| LddTopoError::io(format!("Cannot write {}", destination(output_file)), err))
+}
+
+/// Writes `text` to `output_file`, or to stdout when there is none
+fn write_text(output_file: &Option<PathBuf>, text: &str) -> Result<(), LddTopoError> {
+    match output_file {
+        None => std::io::stdout().lock().write_all(text.as_bytes()),
+        Some(path) => std::fs::write(path, text),
+    }
+    .map_err(|err| LddTopoError::io(format!("Cannot write {}", destination(output_file)), err))
+}
+
+/// `output_file` for messages, stdout when there is none
+fn destination(output_file: &Option<PathBuf>) -> String {
+    output_file.as_ref().map(|path| path.display().to_string()).unwrap_or_else(|| "to stdout".to_string())
+}
+
+/// Attaches the debug information found under `debug_root` to every library with a path. Only the
+/// output is enriched, the graph stays exactly as resolved under the main root.
+fn add_debug_info(result: &mut TopoSortResult, debug_root: &Path) {
+    let mut cache: HashMap<String, Option<DebugInfo>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(path) = &lib.path {
+            lib.debug = cache.entry(path.clone())
+                .or_insert_with(|| debug_info::lookup(Path::new(path), debug_root))
+                .clone();
+        }
+    }
+    let with_debug_file = result.topo_sorted_libs.iter()
+        .filter(|lib| lib.debug.as_ref().map(|d| d.debug_file.is_some()).unwrap_or(false))
+        .count();
+    info!("Found debug files for {} of {} libraries under {}", with_debug_file, result.topo_sorted_libs.len(), debug_root.display());
+}
+
+/// Records the ELF metadata of every library with a path, the files that cannot be parsed are left without it
+fn add_elf_metadata(result: &mut TopoSortResult) {
+    let mut cache: HashMap<String, Option<ElfMetadata>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(path) = &lib.path {
+            if let Some(metadata) = cache.entry(path.clone()).or_insert_with(|| ElfMetadata::read(Path::new(path))).clone() {
+                metadata.apply_to(lib);
+            }
+        }
+    }
+}
+
+/// Records the exploit mitigations of every library with a path, the files that cannot be parsed are left without them
+fn add_hardening(result: &mut TopoSortResult) {
+    let mut cache: HashMap<String, Option<Hardening>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(path) = &lib.path {
+            lib.hardening = *cache.entry(path.clone()).or_insert_with(|| Hardening::read(Path::new(path)));
+        }
+    }
+}
+
+/// Records the package of every library with a path, the owning dpkg or rpm package, or with `embedded` the version
+/// string of the file when there is none
+fn add_packages(result: &mut TopoSortResult, packages: &PackageResolver, embedded: bool) {
+    let mut cache: HashMap<String, Option<Package>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(path) = &lib.path {
+            lib.package = cache.entry(path.clone())
+                .or_insert_with(|| if embedded { packages.package_of(&lib.name, path) } else { packages.owner(path) })
+                .clone();
+        }
+    }
+}
+
+/// Records the license of the package of every library, see `license::package_license`
+fn add_package_licenses(result: &mut TopoSortResult, root: &Path) {
+    let mut cache: HashMap<String, Option<String>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(package) = &lib.package {
+            lib.license = cache.entry(package.name.clone())
+                .or_insert_with(|| license::package_license(root, package))
+                .clone();
+        }
+    }
+}
+
+/// Records the known vulnerabilities of the package of every library
+fn add_vulnerabilities(result: &mut TopoSortResult, database: &OsvDatabase) {
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        lib.vulnerabilities = lib.package.as_ref().map(|package| database.vulnerabilities(package)).unwrap_or_default();
+    }
+}
+
+/// Records the glibc release every library with a path requires, the files that cannot be parsed and the libraries of
+/// glibc itself are left without it
+fn add_glibc_versions(result: &mut TopoSortResult) {
+    let mut cache: HashMap<String, Option<String>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(path) = &lib.path {
+            lib.glibc_version = cache.entry(path.clone())
+                .or_insert_with(|| symbol_versions::read_glibc_version(Path::new(path)))
+                .clone();
+        }
+    }
+}
+
+/// Records the libstdc++ versions every library with a path needs, the files that cannot be parsed are left without them
+fn add_cxx_versions(result: &mut TopoSortResult) {
+    let mut cache: HashMap<String, Vec<String>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(path) = &lib.path {
+            lib.cxx_versions = cache.entry(path.clone())
+                .or_insert_with(|| symbol_versions::read_needed_versions(Path::new(path))
+                    .map(|versions| symbol_versions::cxx_versions(versions.iter().map(String::as_str)))
+                    .unwrap_or_default())
+                .clone();
+        }
+    }
+}
+
+/// Records the SHA-256 and size of the file of every library with a path, the files that cannot be
+/// read are reported and left without them
+fn add_hashes(result: &mut TopoSortResult, diagnostics: &Diagnostics) {
+    let mut cache: HashMap<String, Option<(String, u64)>> = HashMap::new();
+    for lib in result.library_map.values_mut().chain(result.topo_sorted_libs.iter_mut()) {
+        if let Some(path) = &lib.path {
+            let digest = cache.entry(path.clone())
+                .or_insert_with(|| {
+                    let path = Path::new(path);
+                    hash::sha256_file(path).and_then(|sha256| Ok((sha256, std::fs::metadata(path)?.len()))).ok()
+                })
+                .clone();
+            (lib.sha256, lib.size_bytes) = digest.map(|(sha256, size)| (Some(sha256), Some(size))).unwrap_or_default();
+        }
+    }
+    let unhashed: Vec<String> = result.topo_sorted_libs.iter()
+        .filter(|lib| lib.path.is_some() && lib.sha256.is_none())
+        .map(|lib| lib.name.clone())
+        .collect();
+    if !unhashed.is_empty() {
+        diagnostics.report(Diagnostic::warning("unhashed_libraries",
+            format!("{} libraries cannot be read to compute their SHA-256: {:?}", unhashed.len(), unhashed), unhashed));
+    }
+}
+
+/// Scans every library with a path that imports `dlopen` for soname-like strings and records those that are not already
+/// NEEDED by it as `Dlopen` edges. Guessed libraries outside of the graph are looked up but not
+/// analyzed any further.
+fn add_dlopen_guesses(result: &mut TopoSortResult, root: &Path, library_paths: &[PathBuf]) -> Result<(), LddTopoError> {
+    let resolved_paths: Vec<&str> = result.topo_sorted_libs.iter().filter_map(|lib| lib.path.as_deref()).collect();
+    let dirs = dlopen_scan::search_dirs(root, library_paths, &resolved_paths);
+    let vertices: HashSet<&str> = result.vertices.iter().map(String::as_str).collect();
+    let needed: HashSet<(&str, &str)> = result.edges.iter().map(|e| (e.src.as_str(), e.dst.as_str())).collect();
+    let mut dlopen_edges: Vec<Edge> = Vec::new();
+    let mut dlopen_libs: BTreeMap<String, Lib> = BTreeMap::new();
+    for lib in &result.topo_sorted_libs {
+        let path = match &lib.path {
+            Some(path) => path,
+            None => continue,
+        };
+        for soname in dlopen_scan::scan(Path::new(path)) {
+            if soname == lib.name || needed.contains(&(soname.as_str(), lib.name.as_str())) {
+                continue;
+            }
+            if !vertices.contains(soname.as_str()) && !dlopen_libs.contains_key(&soname) {
+                let path = dlopen_scan::resolve(&soname, &dirs).map(|p| utf8(&p).map(str::to_string)).transpose()?;
+                dlopen_libs.insert(soname.clone(), Lib { name: soname.clone(), path, ..Default::default() });
+            }
+            dlopen_edges.push(Edge { src: soname, dst: lib.name.clone(), kind: EdgeKind::Dlopen, ..Default::default() });
+        }
+    }
+    dlopen_edges.sort();
+    info!("Found {} possible dlopen dependencies, {} of them on libraries outside of the graph", dlopen_edges.len(), dlopen_libs.len());
+    result.dlopen_edges = dlopen_edges;
+    result.dlopen_libs = dlopen_libs.into_values().collect();
+    Ok(())
+}
+
+/// The resolver of the options in `args` under `root`, an unreadable ld.so.cache is reported as `ld_cache_unreadable`
+fn new_resolver(root: PathBuf, args: &Args, diagnostics: &Diagnostics) -> Resolver {
+    let ld_cache = match args.use_ldcache {
+        true => match LdCache::load(&root) {
+            Ok(cache) => {
+                info!("Read {} sonames from the ld.so.cache of {}", cache.len(), root.display());
+                Some(Arc::new(cache))
+            }
+            Err(err) => {
+                diagnostics.report(Diagnostic::warning("ld_cache_unreadable",
+                    format!("Cannot read the ld.so.cache of {}, searching the ld.so.conf directories instead: {}", root.display(), err), vec![]));
+                None
+            }
+        },
+        false => None,
+    };
+    let nix_store = args.nix_store.as_ref().map(|location| {
+        let location = location.clone().unwrap_or_else(|| root.join(&NIX_STORE[1..]));
+        args.store_rewrite.iter().fold(NixStore::new(location), |store, (from, to)| store.rewrite(from.clone(), to.clone()))
     });
+    Resolver::new(root)
+        .library_paths(args.library_paths.clone().unwrap_or_default())
+        .ld_cache(ld_cache)
+        .tokens(args.lib_token.clone(), args.platform_token.clone())
+        .hwcaps(args.hwcaps.clone())
+        .target(args.target.clone())
+        .nix_store(nix_store)
+        .ld_library_path(args.ld_library_path.clone().or_else(|| std::env::var("LD_LIBRARY_PATH").ok().filter(|_| args.env)))
+        .parse_timeout(args.resolve_timeout_per_lib.map(Duration::from_millis))
+        .jobs(args.jobs)
+}
+
+/// `<dir>/<stem>.dot` for an output file `<dir>/<stem>.json`
+fn dot_path_for(output_file: &Path) -> PathBuf {
+    output_file.with_extension("dot")
+}
+
+fn write_dot(dot: &str, dot_path: &Path) -> Result<(), LddTopoError> {
+    std::fs::write(dot_path, dot).map_err(|err| LddTopoError::io(format!("Cannot write {}", dot_path.display()), err))
 }
 
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use std::collections::HashMap;
-    use lddtree::{DependencyTree, Library};
-    use petgraph::algo::Cycle;
-    use crate::get_topologically_sorted_result;
-
-    type RetType = Result<(), Cycle<u32>>;
+    use clap::Parser;
+    use crate::{reject_single_binary_options, stdout_for_dash, temporary_dir, with_default_command, Args, Cli, Command, OutputSettings};
+    use lddtopo_rs::graph_diff::Regression;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
 
     #[test]
-    fn get_topologically_sorted_result_when_input_is_empty_dag_should_work() -> RetType {
-        let dt = DependencyTree {
-            interpreter: None,
-            needed: vec![],
-            libraries: Default::default(),
-            rpath: vec![],
-            runpath: vec![],
+    fn output_settings_when_machine_mode_should_apply_preset_unless_overridden() {
+        let parse = |extra: &[&str]| {
+            let args = Args::try_parse_from(["lddtopo-rs", "--shared-library-path", "/tmp/A"].iter().chain(extra)).unwrap();
+            OutputSettings::from_args(&args)
         };
-        let main_lib = "A";
-        let main_lib_path = "/tmp/A";
-        let toposorted = get_topologically_sorted_result(main_lib, main_lib_path, &dt)?;
-        assert_eq!(0, toposorted.vertices.len());
-        assert_eq!(0, toposorted.edges.len());
-        assert_eq!(0, toposorted.topo_sorted_libs.len());
-        Ok(())
+        assert_eq!(OutputSettings { pretty: true, dot: true, color: true }, parse(&["--output-file", "/tmp/out.json"]));
+        assert_eq!(OutputSettings { pretty: false, dot: false, color: false }, parse(&["--machine"]));
+        assert_eq!(OutputSettings { pretty: true, dot: false, color: false }, parse(&["--ci", "--pretty"]));
+        assert_eq!(OutputSettings { pretty: false, dot: true, color: true }, parse(&["--output-file", "/tmp/out.json", "--machine", "--dot", "--color"]));
+        assert_eq!(OutputSettings { pretty: true, dot: true, color: true }, parse(&["--output-file", "/tmp/out.json", "--compact", "--pretty"]));
+        assert!(Args::try_parse_from(["lddtopo-rs", "--shared-library-path", "/tmp/A"]).is_err());
     }
 
     #[test]
-    fn get_topologically_sorted_result_when_input_is_dag_with_two_vertices_should_work() -> RetType {
-        let dt = DependencyTree {
-            interpreter: None,
-            needed: vec!["B".to_string()],
-            libraries: Default::default(),
-            rpath: vec![],
-            runpath: vec![],
-        };
-        let main_lib = "A";
-        let main_lib_path = "/tmp/A";
+    fn with_default_command_should_insert_analyze_before_options_only() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(args(&["lddtopo-rs", "analyze", "--shared-library-path", "/tmp/A"]), with_default_command(args(&["lddtopo-rs", "--shared-library-path", "/tmp/A"])));
+        assert_eq!(args(&["lddtopo-rs", "diff", "a.json", "b.json"]), with_default_command(args(&["lddtopo-rs", "diff", "a.json", "b.json"])));
+        assert_eq!(args(&["lddtopo-rs", "--help"]), with_default_command(args(&["lddtopo-rs", "--help"])));
+        assert_eq!(args(&["lddtopo-rs"]), with_default_command(args(&["lddtopo-rs"])));
 
-        let toposorted = get_topologically_sorted_result(main_lib, main_lib_path, &dt)?;
-        assert_eq!(2, toposorted.vertices.len());
-        assert_eq!(1, toposorted.edges.len());
-        assert_eq!(2, toposorted.topo_sorted_libs.len());
+        let cli = Cli::try_parse_from(with_default_command(args(&["lddtopo-rs", "--shared-library-path", "/tmp/A", "--machine"]))).unwrap();
+        assert!(matches!(cli.command, Command::Analyze(args) if args.shared_library_path == vec![PathBuf::from("/tmp/A")]));
+        let cli = Cli::try_parse_from(args(&["lddtopo-rs", "query", "/tmp/out.json", "deps", "libz.so.1"])).unwrap();
+        assert!(matches!(cli.command, Command::Query(args) if args.query == vec!["deps", "libz.so.1"]));
+        assert!(Cli::try_parse_from(args(&["lddtopo-rs", "query", "--root-path", "/image", "/usr/bin/curl", "rdeps", "libssl.so.3"])).is_err());
+        let cli = Cli::try_parse_from(args(&["lddtopo-rs", "query", "/tmp/out.json", "why", "--from", "A", "--to", "F", "--all-paths"])).unwrap();
+        assert!(matches!(cli.command, Command::Query(args) if args.query == vec!["why", "--from", "A", "--to", "F", "--all-paths"]));
+    }
 
-        assert_eq!("B", toposorted.topo_sorted_libs[0].name);
-        assert_eq!("A", toposorted.topo_sorted_libs[1].name);
-        Ok(())
+    #[test]
+    fn check_fail_on_should_default_to_new_deps_and_split_on_commas() {
+        let cli = Cli::try_parse_from(["lddtopo-rs", "check", "/tmp/out.json", "--baseline", "/tmp/base.json"]).unwrap();
+        assert!(matches!(cli.command, Command::Check(args) if args.fail_on == vec![Regression::NewDeps]));
+        let cli = Cli::try_parse_from(["lddtopo-rs", "check", "/tmp/out.json", "--baseline", "/tmp/base.json", "--fail-on", "removed-deps,path-changes"]).unwrap();
+        assert!(matches!(cli.command, Command::Check(args) if args.fail_on == vec![Regression::RemovedDeps, Regression::PathChanges]));
+        assert!(Cli::try_parse_from(["lddtopo-rs", "check", "/tmp/out.json", "--fail-on", "new-deps"]).is_err());
     }
 
     #[test]
-    fn get_topologically_sorted_result_when_input_is_small_dag_should_work() -> RetType {
-        let mut libraries: HashMap<String, Library> = HashMap::new();
-        libraries.insert("B".to_string(), Library {
-            name: "B".to_string(),
-            path: Default::default(),
-            realpath: None,
-            needed: vec!["D".to_string()],
-            rpath: vec![],
-            runpath: vec![],
-        });
-        libraries.insert("C".to_string(), Library {
-            name: "C".to_string(),
-            path: Default::default(),
-            realpath: None,
-            needed: vec!["D".to_string()],
-            rpath: vec![],
-            runpath: vec![],
-        });
-        libraries.insert("D".to_string(), Library {
-            name: "D".to_string(),
-            path: Default::default(),
-            realpath: None,
-            needed: vec!["E".to_string()],
-            rpath: vec![],
-            runpath: vec![],
-        });
-        libraries.insert("E".to_string(), Library {
-            name: "E".to_string(),
-            path: Default::default(),
-            realpath: None,
-            needed: vec!["F".to_string()],
-            rpath: vec![],
-            runpath: vec![],
-        });
-        libraries.insert("F".to_string(), Library {
-            name: "F".to_string(),
-            path: Default::default(),
-            realpath: None,
-            needed: vec![],
-            rpath: vec![],
-            runpath: vec![],
-        });
-        let dt = DependencyTree {
-            interpreter: None,
-            needed: vec!["B".to_string(), "C".to_string(), "F".to_string()],
-            libraries: libraries,
-            rpath: vec![],
-            runpath: vec![],
-        };
-        let main_lib = "A";
-        let main_lib_path = "/tmp/A";
-        let toposorted = get_topologically_sorted_result(main_lib, main_lib_path, &dt)?;
-        assert_eq!(6, toposorted.vertices.len());
-        assert_eq!(7, toposorted.edges.len());
-        assert_eq!(6, toposorted.topo_sorted_libs.len());
-
-        assert_eq!("F", toposorted.topo_sorted_libs[0].name);
-        assert_eq!("E", toposorted.topo_sorted_libs[1].name);
-        assert_eq!("D", toposorted.topo_sorted_libs[2].name);
-        assert_eq!("C", toposorted.topo_sorted_libs[3].name);
-        assert_eq!("B", toposorted.topo_sorted_libs[4].name);
-        assert_eq!("A", toposorted.topo_sorted_libs[5].name);
-        Ok(())
+    fn reject_single_binary_options_should_name_every_such_option() {
+        let parse = |extra: &[&str]| Args::try_parse_from(["lddtopo-rs", "--machine", "--shared-library-path", "/tmp/A", "--shared-library-path", "/tmp/B"].iter().chain(extra)).unwrap();
+        assert!(reject_single_binary_options(&parse(&["--hash", "--format", "dot", "--focus", "libz.so.1"])).is_ok());
+        let err = reject_single_binary_options(&parse(&["--repl", "--all-paths-to", "libz.so.1"])).unwrap_err();
+        assert_eq!("--all-paths-to, --repl cannot be used with a merged graph, analyze a single binary instead", err.to_string());
+        assert_eq!(2, err.exit_code());
     }
 
     #[test]
-    fn get_topologically_sorted_result_when_input_is_not_dag_should_fail() {
-        let mut libraries: HashMap<String, Library> = HashMap::new();
-        libraries.insert("A".to_string(), Library {
-            name: "A".to_string(),
-            path: Default::default(),
-            realpath: None,
-            needed: vec!["B".to_string()],
-            rpath: vec![],
-            runpath: vec![],
-        });
-        libraries.insert("B".to_string(), Library {
-            name: "B".to_string(),
-            path: Default::default(),
-            realpath: None,
-            needed: vec!["A".to_string()],
-            rpath: vec![],
-            runpath: vec![],
-        });
+    fn stdout_for_dash_should_clear_only_dash() {
+        let mut output_file = Some(PathBuf::from("-"));
+        stdout_for_dash(&mut output_file);
+        assert_eq!(None, output_file);
+        let mut output_file = Some(PathBuf::from("./-"));
+        stdout_for_dash(&mut output_file);
+        assert_eq!(Some(PathBuf::from("./-")), output_file);
+        // `-` satisfies the requirement of an output file outside of --machine mode
+        assert!(Args::try_parse_from(["lddtopo-rs", "--shared-library-path", "/tmp/A", "--output-file", "-"]).is_ok());
+    }
 
-        let dt = DependencyTree {
-            interpreter: None,
-            needed: vec!["B".to_string()],
-            libraries: libraries,
-            rpath: vec![],
-            runpath: vec![],
-        };
-        let main_lib = "A";
-        let main_lib_path = "/tmp/A";
+    #[test]
+    fn shared_library_path_should_be_repeatable() {
+        let args = Args::try_parse_from(["lddtopo-rs", "--shared-library-path", "/opt/app/bin/app", "--shared-library-path", "/opt/app/plugins/libfoo.so", "--machine"]).unwrap();
+        assert_eq!(vec![PathBuf::from("/opt/app/bin/app"), PathBuf::from("/opt/app/plugins/libfoo.so")], args.shared_library_path);
+        assert!(Args::try_parse_from(["lddtopo-rs", "--shared-library-path", "/tmp/A", "--merge", "/tmp/a.json", "/tmp/b.json"]).is_err());
+    }
 
-        match get_topologically_sorted_result(main_lib, main_lib_path, &dt) {
-            Ok(x) => {
-                panic!("Should not find any topo sort, but found {:?}", x)
-            }
-            Err(_) => {}
-        }
+    #[test]
+    fn temporary_dir_should_create_a_new_private_directory_every_time() {
+        use std::os::unix::fs::PermissionsExt;
+        let (first, second) = (temporary_dir("lddtopo-test-").unwrap(), temporary_dir("lddtopo-test-").unwrap());
+        let mode = std::fs::metadata(&first).unwrap().permissions().mode() & 0o777;
+        std::fs::remove_dir(&first).unwrap();
+        std::fs::remove_dir(&second).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(0o700, mode);
     }
-}
\ No newline at end of file
+}
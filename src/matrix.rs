@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{EdgeKind, TopoSortResult};
+
+/// Above this many libraries the matrix is large enough to warn about, it grows with the square of the count
+pub const LARGE_MATRIX: usize = 1000;
+
+pub fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The adjacency matrix of the NEEDED graph as CSV, with a header row and column of library names in `vertices`
+/// order. Like `edges`, the cell in row `src` and column `dst` is 1 when `src` must be loaded before `dst`,
+/// i.e. when `dst` needs `src`; every other cell is 0.
+pub fn to_csv(result: &TopoSortResult) -> String {
+    let index: HashMap<&str, usize> = result.vertices.iter().enumerate().map(|(idx, v)| (v.as_str(), idx)).collect();
+    let n = result.vertices.len();
+    let mut cells = vec![false; n * n];
+    for edge in result.edges.iter().filter(|edge| edge.kind == EdgeKind::Needed) {
+        if let (Some(src), Some(dst)) = (index.get(edge.src.as_str()), index.get(edge.dst.as_str())) {
+            cells[src * n + dst] = true;
+        }
+    }
+    let mut csv = String::new();
+    for v in &result.vertices {
+        write!(csv, ",{}", escape_csv(v)).unwrap();
+    }
+    csv.push('\n');
+    for (row, v) in result.vertices.iter().enumerate() {
+        csv.push_str(&escape_csv(v));
+        for cell in &cells[row * n..(row + 1) * n] {
+            csv.push_str(if *cell { ",1" } else { ",0" });
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::matrix::{escape_csv, to_csv};
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn to_csv_should_emit_adjacency_matrix_in_vertex_order() {
+        let expected = "\
+,A,B,C,D,E,F
+A,0,0,0,0,0,0
+B,1,0,0,0,0,0
+C,1,0,0,0,0,0
+D,0,1,1,0,0,0
+E,0,0,0,1,0,0
+F,1,0,0,0,1,0
+";
+        assert_eq!(expected, to_csv(&small_dag()));
+    }
+
+    #[test]
+    fn escape_csv_should_quote_special_characters() {
+        assert_eq!("libz.so.1", escape_csv("libz.so.1"));
+        assert_eq!("\"lib,odd\"\"name\"", escape_csv("lib,odd\"name"));
+    }
+}
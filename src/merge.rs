@@ -0,0 +1,386 @@
+use clap::ValueEnum;
+
+use petgraph::graphmap::DiGraphMap;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::cycles::find_cycles;
+use crate::id_gen::IdGen;
+use crate::toposort::{levels, lexicographic_toposort};
+use crate::{hardening, packages};
+use crate::{Edge, EdgeKind, Lib, TopoSortResult, UnresolvedChain};
+
+/// Which path wins when a soname is resolved to different files by the merged results
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The path of the first result that resolved the library
+    First,
+    /// The path of the last result that resolved the library
+    Last,
+    /// Fail and list every conflict
+    #[default]
+    Error,
+    /// The path with the shortest NEEDED chain from its root, the first one on ties
+    PreferShortestPath,
+}
+
+/// A soname resolved to more than one file by the merged results
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub library: String,
+    pub chosen: String,
+    /// The other paths, in the order the results were given
+    pub alternatives: Vec<String>,
+}
+
+/// A resolved path of a library with the smallest depth it was seen at
+struct Candidate<'a> {
+    path: &'a str,
+    min_depth: Option<usize>,
+}
+
+fn choose<'a>(candidates: &[Candidate<'a>], strategy: MergeStrategy) -> &'a str {
+    match strategy {
+        MergeStrategy::First | MergeStrategy::Error => candidates[0].path,
+        MergeStrategy::Last => candidates[candidates.len() - 1].path,
+        MergeStrategy::PreferShortestPath => candidates.iter()
+            .min_by_key(|c| c.min_depth.unwrap_or(usize::MAX))
+            .unwrap().path,
+    }
+}
+
+/// Merges several results into one graph: the union of their vertices and edges, sorted again.
+/// A library resolved in any of the results counts as resolved, when they disagree on its path
+/// `strategy` decides and the decision is recorded in `merge_conflicts`. Depths are the extremes over
+/// all results, each measured from its own root; `kind` and `interpreter`, and the kind of an edge found
+/// in several results, are those of the first result. The RPATH and RUNPATH of each root stay with its `Lib`.
+pub fn merge(results: &[TopoSortResult], strategy: MergeStrategy) -> Result<TopoSortResult, String> {
+    let first = results.first().ok_or("nothing to merge")?;
+
+    let mut candidates: BTreeMap<&str, Vec<Candidate>> = BTreeMap::new();
+    let mut fallback_paths: HashMap<&str, &str> = HashMap::new();
+    let mut depths: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut licenses: HashMap<&str, &str> = HashMap::new();
+    let mut assumed_provided: HashSet<&str> = HashSet::new();
+    let mut missing: HashSet<&str> = HashSet::new();
+    // Truncated only when every result cut its dependencies off, otherwise they are in the union
+    let mut truncated: HashMap<&str, bool> = HashMap::new();
+    for result in results {
+        for lib in &result.topo_sorted_libs {
+            if lib.missing {
+                missing.insert(&lib.name);
+            }
+            *truncated.entry(&lib.name).or_insert(true) &= lib.truncated;
+            if let Some(license) = &lib.license {
+                licenses.entry(&lib.name).or_insert(license);
+            }
+            if let (Some(min), Some(max)) = (lib.min_depth, lib.max_depth) {
+                let entry = depths.entry(&lib.name).or_insert((min, max));
+                *entry = (entry.0.min(min), entry.1.max(max));
+            }
+            let path = match &lib.path {
+                Some(path) => path.as_str(),
+                None => continue,
+            };
+            if lib.assumed_provided {
+                assumed_provided.insert(&lib.name);
+            }
+            if result.unresolved.contains(&lib.name) || lib.assumed_provided {
+                fallback_paths.entry(&lib.name).or_insert(path);
+                continue;
+            }
+            let lib_candidates = candidates.entry(&lib.name).or_default();
+            match lib_candidates.iter_mut().find(|c| c.path == path) {
+                Some(candidate) => candidate.min_depth = [candidate.min_depth, lib.min_depth].into_iter().flatten().min(),
+                None => lib_candidates.push(Candidate { path, min_depth: lib.min_depth }),
+            }
+        }
+    }
+    let conflicting: Vec<String> = candidates.iter()
+        .filter(|(_, c)| c.len() > 1)
+        .map(|(name, c)| format!("{} at {}", name, c.iter().map(|c| c.path).collect::<Vec<_>>().join(", ")))
+        .collect();
+    if strategy == MergeStrategy::Error && !conflicting.is_empty() {
+        return Err(format!("{} libraries resolve to different paths, pick a --merge-strategy: {}", conflicting.len(), conflicting.join("; ")));
+    }
+    let chosen: HashMap<&str, &str> = candidates.iter().map(|(name, c)| (*name, choose(c, strategy))).collect();
+    let merge_conflicts: Vec<MergeConflict> = candidates.iter()
+        .filter(|(_, c)| c.len() > 1)
+        .map(|(name, c)| MergeConflict {
+            library: name.to_string(),
+            chosen: chosen[name].to_string(),
+            alternatives: c.iter().map(|c| c.path).filter(|p| *p != chosen[name]).map(str::to_string).collect(),
+        })
+        .collect();
+
+    let vertices: Vec<String> = results.iter().flat_map(|r| r.vertices.iter().cloned()).collect::<BTreeSet<_>>().into_iter().collect();
+    let mut edge_kinds: BTreeMap<(&str, &str), EdgeKind> = BTreeMap::new();
+    for edge in results.iter().flat_map(|r| r.edges.iter()) {
+        edge_kinds.entry((&edge.src, &edge.dst)).or_insert(edge.kind);
+    }
+    let edges: Vec<Edge> = edge_kinds.into_iter()
+        .map(|((src, dst), kind)| Edge { src: src.to_string(), dst: dst.to_string(), kind, ..Default::default() })
+        .collect();
+
+    let mut di_graph_map: DiGraphMap<u32, ()> = DiGraphMap::new();
+    let mut id_gen = IdGen::new();
+    for v in &vertices {
+        di_graph_map.add_node(id_gen.get_next_id(v));
+    }
+    for edge in &edges {
+        di_graph_map.add_edge(id_gen.get_next_id(&edge.src), id_gen.get_next_id(&edge.dst), ());
+    }
+    let topological_sorted = lexicographic_toposort(&di_graph_map, |id| String::from(id_gen.get_by_id(id).unwrap())).ok_or_else(|| {
+        let cycles = find_cycles(&di_graph_map, |id| String::from(id_gen.get_by_id(id).unwrap()));
+        format!("the merged graph is not DAG, it contains cycle {}", cycles.first().map(|c| c.join(" -> ")).unwrap_or_default())
+    })?;
+
+    let unresolved: Vec<String> = results.iter()
+        .flat_map(|r| r.unresolved.iter())
+        .filter(|name| !chosen.contains_key(name.as_str()))
+        .cloned()
+        .collect::<BTreeSet<_>>().into_iter().collect();
+    // Everything known about the file comes from a result that resolved the library to the chosen one
+    let path_of = |name: &str| chosen.get(name).or_else(|| fallback_paths.get(name)).copied();
+    let file_of = |name: &str| results.iter()
+        .flat_map(|r| r.topo_sorted_libs.iter().chain(r.library_map.values()))
+        .find(|lib| lib.name == name && lib.path.is_some() && lib.path.as_deref() == path_of(name));
+    let lib_of = |name: &str| {
+        let file = file_of(name);
+        Lib {
+            name: name.to_string(),
+            path: path_of(name).map(str::to_string),
+            debug: file.and_then(|lib| lib.debug.clone()),
+            min_depth: depths.get(name).map(|(min, _)| *min),
+            max_depth: depths.get(name).map(|(_, max)| *max),
+            license: licenses.get(name).map(|l| l.to_string()),
+            sha256: file.and_then(|lib| lib.sha256.clone()),
+            size_bytes: file.and_then(|lib| lib.size_bytes),
+            soname: file.and_then(|lib| lib.soname.clone()),
+            machine: file.and_then(|lib| lib.machine.clone()),
+            class: file.and_then(|lib| lib.class),
+            os_abi: file.and_then(|lib| lib.os_abi.clone()),
+            build_id: file.and_then(|lib| lib.build_id.clone()),
+            missing: missing.contains(name) && path_of(name).is_none(),
+            assumed_provided: assumed_provided.contains(name) && !chosen.contains_key(name) && !unresolved.iter().any(|u| u == name),
+            truncated: truncated.get(name).copied().unwrap_or_default(),
+            glibc_version: file.and_then(|lib| lib.glibc_version.clone()),
+            cxx_versions: file.map(|lib| lib.cxx_versions.clone()).unwrap_or_default(),
+            rpath: file.map(|lib| lib.rpath.clone()).unwrap_or_default(),
+            runpath: file.map(|lib| lib.runpath.clone()).unwrap_or_default(),
+            hardening: file.and_then(|lib| lib.hardening),
+            package: file.and_then(|lib| lib.package.clone()),
+            vulnerabilities: file.map(|lib| lib.vulnerabilities.clone()).unwrap_or_default(),
+        }
+    };
+    let topo_sorted_libs: Vec<Lib> = topological_sorted.iter().map(|id| lib_of(id_gen.get_by_id(*id).unwrap())).collect();
+    let load_units: Vec<Vec<u32>> = topological_sorted.iter().map(|id| vec![*id]).collect();
+    let levels: Vec<Vec<String>> = levels(&di_graph_map, &load_units).into_iter()
+        .map(|level| level.into_iter().map(|id| String::from(id_gen.get_by_id(id).unwrap())).collect())
+        .collect();
+    let library_map: BTreeMap<String, Lib> = results.iter()
+        .flat_map(|r| r.library_map.keys())
+        .map(|name| (name.clone(), lib_of(name)))
+        .collect();
+
+    let mut shortest_chains: BTreeMap<&str, &UnresolvedChain> = BTreeMap::new();
+    for chain in results.iter().flat_map(|r| r.unresolved_chains.iter()).filter(|c| unresolved.contains(&c.missing)) {
+        let entry = shortest_chains.entry(&chain.missing).or_insert(chain);
+        if chain.chain.len() < entry.chain.len() {
+            *entry = chain;
+        }
+    }
+    let unresolved_chains: Vec<UnresolvedChain> = shortest_chains.into_values().cloned().collect();
+    let deepest_unresolved_chain = unresolved_chains.iter()
+        .max_by(|a, b| a.chain.len().cmp(&b.chain.len()).then_with(|| b.missing.cmp(&a.missing)))
+        .cloned();
+
+    // Unreachable only when no result reaches it
+    let reachable: HashSet<&str> = results.iter()
+        .flat_map(|r| r.vertices.iter().filter(|v| !r.unreachable.contains(v)))
+        .map(String::as_str)
+        .collect();
+    let unreachable: Vec<String> = results.iter()
+        .flat_map(|r| r.unreachable.iter())
+        .filter(|name| !reachable.contains(name.as_str()))
+        .cloned()
+        .collect::<BTreeSet<_>>().into_iter().collect();
+
+    let mut skipped = Vec::new();
+    for lib in results.iter().flat_map(|r| r.skipped.iter()) {
+        if !skipped.contains(lib) {
+            skipped.push(lib.clone());
+        }
+    }
+    let mut arch_mismatches = Vec::new();
+    for mismatch in results.iter().flat_map(|r| r.arch_mismatches.iter()) {
+        if chosen.get(mismatch.library.as_str()) == Some(&mismatch.path.as_str()) && !arch_mismatches.contains(mismatch) {
+            arch_mismatches.push(mismatch.clone());
+        }
+    }
+
+    let mut merged = TopoSortResult {
+        kind: first.kind,
+        interpreter: first.interpreter.clone(),
+        main_binary: None,
+        vertices,
+        edges,
+        library_map,
+        topo_sorted_libs,
+        unresolved,
+        unresolved_chains,
+        deepest_unresolved_chain,
+        unreachable,
+        normalized_names: results.iter().flat_map(|r| r.normalized_names.clone()).collect(),
+        collapsed_groups: BTreeMap::new(),
+        impact: None,
+        all_paths: None,
+        dlopen_edges: Vec::new(),
+        dlopen_libs: Vec::new(),
+        runtime_edges: Vec::new(),
+        runtime_libs: Vec::new(),
+        load_order_by_dir: Vec::new(),
+        skipped,
+        arch_mismatches,
+        merge_conflicts,
+        licenses: None,
+        cycle_groups: Vec::new(),
+        levels,
+        excluded: results.iter().flat_map(|r| r.excluded.iter().cloned()).collect::<BTreeSet<_>>().into_iter().collect(),
+        focus: None,
+        truncated_at_depth: None,
+        min_glibc_version: None,
+        cxx_requirements: None,
+        underlinked: Vec::new(),
+        symbol_conflicts: Vec::new(),
+        hardening_summary: Vec::new(),
+        package_dependencies: BTreeMap::new(),
+        wheel: None,
+    };
+    // Summaries of the inputs are computed again over the merged libraries
+    if results.iter().any(|r| !r.hardening_summary.is_empty()) {
+        merged.hardening_summary = hardening::summary(&merged);
+    }
+    if results.iter().any(|r| !r.package_dependencies.is_empty()) {
+        merged.package_dependencies = packages::package_dependencies(&merged);
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::binary_kind::BinaryKind;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::merge::{merge, MergeConflict, MergeStrategy};
+    use crate::packages::{Package, PackageSource};
+    use crate::{get_topologically_sorted_result, AnalysisOptions, EdgeKind, TopoSortResult};
+    use std::collections::BTreeSet;
+
+    /// `app` needs libz directly and libssl, which needs libcrypto; `tool` needs libcrypto directly
+    /// and libz through libfoo. libz and libcrypto are found in different places for the two.
+    fn app_and_tool() -> Vec<TopoSortResult> {
+        let app = DependencyTreeBuilder::new()
+            .add_needed("libz.so.1")
+            .add_needed("libssl.so.3")
+            .add_library_at("libz.so.1", "/usr/lib/libz.so.1", &[])
+            .add_library("libssl.so.3", &["libcrypto.so.3"])
+            .add_library_at("libcrypto.so.3", "/usr/lib/libcrypto.so.3", &[])
+            .build();
+        let tool = DependencyTreeBuilder::new()
+            .add_needed("libcrypto.so.3")
+            .add_needed("libfoo.so")
+            .add_library_at("libcrypto.so.3", "/opt/tool/libcrypto.so.3", &[])
+            .add_library("libfoo.so", &["libz.so.1"])
+            .add_library_at("libz.so.1", "/opt/tool/libz.so.1", &[])
+            .add_missing_library("libbar.so")
+            .build();
+        vec![
+            get_topologically_sorted_result("app", "/opt/app", BinaryKind::Executable, &app, &AnalysisOptions::default()).unwrap(),
+            get_topologically_sorted_result("tool", "/opt/tool/tool", BinaryKind::Executable, &tool, &AnalysisOptions::default()).unwrap(),
+        ]
+    }
+
+    fn path_of<'a>(result: &'a TopoSortResult, name: &str) -> &'a str {
+        result.topo_sorted_libs.iter().find(|lib| lib.name == name).unwrap().path.as_deref().unwrap()
+    }
+
+    #[test]
+    fn merge_when_strategy_is_error_should_list_every_conflict() {
+        let err = merge(&app_and_tool(), MergeStrategy::Error).unwrap_err();
+        assert_eq!("2 libraries resolve to different paths, pick a --merge-strategy: \
+            libcrypto.so.3 at /usr/lib/libcrypto.so.3, /opt/tool/libcrypto.so.3; libz.so.1 at /usr/lib/libz.so.1, /opt/tool/libz.so.1", err);
+    }
+
+    #[test]
+    fn merge_should_resolve_conflicts_by_strategy_and_record_them() {
+        let results = app_and_tool();
+        let first = merge(&results, MergeStrategy::First).unwrap();
+        assert_eq!("/usr/lib/libz.so.1", path_of(&first, "libz.so.1"));
+        assert_eq!(vec![
+            MergeConflict { library: "libcrypto.so.3".to_string(), chosen: "/usr/lib/libcrypto.so.3".to_string(), alternatives: vec!["/opt/tool/libcrypto.so.3".to_string()] },
+            MergeConflict { library: "libz.so.1".to_string(), chosen: "/usr/lib/libz.so.1".to_string(), alternatives: vec!["/opt/tool/libz.so.1".to_string()] },
+        ], first.merge_conflicts);
+
+        let last = merge(&results, MergeStrategy::Last).unwrap();
+        assert_eq!("/opt/tool/libz.so.1", path_of(&last, "libz.so.1"));
+        assert_eq!("/opt/tool/libcrypto.so.3", path_of(&last, "libcrypto.so.3"));
+
+        // libz is direct for app, libcrypto is direct for tool
+        let shortest = merge(&results, MergeStrategy::PreferShortestPath).unwrap();
+        assert_eq!("/usr/lib/libz.so.1", path_of(&shortest, "libz.so.1"));
+        assert_eq!("/opt/tool/libcrypto.so.3", path_of(&shortest, "libcrypto.so.3"));
+        assert_eq!(Some(1), shortest.library_map["libcrypto.so.3"].min_depth);
+        assert_eq!(Some(2), shortest.library_map["libcrypto.so.3"].max_depth);
+    }
+
+    #[test]
+    fn merge_should_sort_the_union_of_both_graphs() {
+        let merged = merge(&app_and_tool(), MergeStrategy::First).unwrap();
+        assert_eq!(vec!["app", "libbar.so", "libcrypto.so.3", "libfoo.so", "libssl.so.3", "libz.so.1", "tool"], merged.vertices);
+        let position = |name: &str| merged.topo_sorted_libs.iter().position(|lib| lib.name == name).unwrap();
+        for edge in &merged.edges {
+            assert!(position(&edge.src) < position(&edge.dst), "{} must load before {}", edge.src, edge.dst);
+        }
+        assert_eq!(vec!["libbar.so"], merged.unresolved);
+        assert_eq!(vec!["libbar.so"], merged.unreachable);
+        // the levels are those of the merged graph, not of either input
+        let levels: Vec<BTreeSet<&str>> = merged.levels.iter().map(|level| level.iter().map(String::as_str).collect()).collect();
+        assert_eq!(vec![BTreeSet::from(["libbar.so", "libcrypto.so.3", "libz.so.1"]), BTreeSet::from(["libfoo.so", "libssl.so.3"]),
+            BTreeSet::from(["app", "tool"])], levels);
+        // Merging is deterministic
+        let again = merge(&app_and_tool(), MergeStrategy::First).unwrap();
+        assert_eq!(serde_json::to_string(&merged).unwrap(), serde_json::to_string(&again).unwrap());
+    }
+
+    #[test]
+    fn merge_should_keep_the_fields_of_the_chosen_file_and_the_kind_of_edges() {
+        let mut results = app_and_tool();
+        let app = &mut results[0];
+        for lib in app.topo_sorted_libs.iter_mut().chain(app.library_map.values_mut()).filter(|lib| lib.name == "libz.so.1") {
+            lib.sha256 = Some("00ff".to_string());
+            lib.soname = Some("libz.so.1".to_string());
+            lib.build_id = Some("abcd".to_string());
+            lib.cxx_versions = vec!["GLIBCXX_3.4".to_string()];
+            lib.package = Some(Package { name: "zlib1g".to_string(), version: "1:1.3".to_string(), source_name: None, source: PackageSource::Dpkg });
+        }
+        for lib in results[1].topo_sorted_libs.iter_mut().filter(|lib| lib.name == "libz.so.1") {
+            lib.sha256 = Some("ff00".to_string());
+            lib.truncated = true;
+        }
+        results[0].package_dependencies.insert("zlib1g".to_string(), Vec::new());
+        results[1].edges.iter_mut().find(|edge| edge.src == "libz.so.1").unwrap().kind = EdgeKind::Weak;
+
+        let merged = merge(&results, MergeStrategy::First).unwrap();
+        let libz = &merged.library_map["libz.so.1"];
+        assert_eq!((Some("00ff"), Some("libz.so.1"), Some("abcd")), (libz.sha256.as_deref(), libz.soname.as_deref(), libz.build_id.as_deref()));
+        assert_eq!(vec!["GLIBCXX_3.4"], libz.cxx_versions);
+        assert!(!libz.truncated);
+        assert!(merged.library_map["libbar.so"].missing);
+        let kind_of = |src: &str, dst: &str| merged.edges.iter().find(|edge| edge.src == src && edge.dst == dst).unwrap().kind;
+        assert_eq!(EdgeKind::Needed, kind_of("libz.so.1", "app"));
+        assert_eq!(EdgeKind::Weak, kind_of("libz.so.1", "libfoo.so"));
+        assert_eq!(vec!["zlib1g"], merged.package_dependencies.keys().collect::<Vec<_>>());
+    }
+}
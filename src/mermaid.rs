@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::collapse::CollapsedView;
+use crate::{EdgeKind, TopoSortResult};
+
+/// Mermaid label text in double quotes, where `"` has to be written as an entity and a line break as `<br>`
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "#quot;").replace('\n', "<br>"))
+}
+
+/// The graph of `view`, the same nodes and edges as the DOT output, as a Mermaid `graph TD` for Markdown documents.
+/// Nodes are named `n<index>` in `vertices` order and labelled by `label`, e.g. `dot::text_label`, edges point from
+/// the dependency to its dependent. Merged edges are labelled with their multiplicity and the other kinds than NEEDED are dotted.
+/// With `max_depth` only the nodes at most that many NEEDED hops away from the main library are drawn, by their
+/// shortest chain, and a comment says how many were left out. A collapsed node is as deep as its shallowest member.
+/// `note`, e.g. why the graph is truncated, is written as a comment at the top.
+pub fn to_mermaid(result: &TopoSortResult, view: &CollapsedView, max_depth: Option<usize>, label: &dyn Fn(&str) -> String,
+                  note: Option<&str>) -> String {
+    let mut depths: HashMap<&str, usize> = result.topo_sorted_libs.iter()
+        .filter_map(|lib| lib.min_depth.map(|depth| (lib.name.as_str(), depth)))
+        .collect();
+    for (group, members) in &view.groups {
+        if let Some(depth) = members.iter().filter_map(|member| depths.get(member.as_str()).copied()).min() {
+            depths.insert(group.as_str(), depth);
+        }
+    }
+    let shown: HashSet<&str> = view.vertices.iter()
+        .map(String::as_str)
+        .filter(|v| max_depth.map(|max| depths.get(v).map(|depth| *depth <= max).unwrap_or(false)).unwrap_or(true))
+        .collect();
+    let index: HashMap<&str, usize> = view.vertices.iter().enumerate().map(|(idx, v)| (v.as_str(), idx)).collect();
+    let mut mermaid = String::from("graph TD\n");
+    if let Some(note) = note {
+        writeln!(mermaid, "    %% {}", note.replace('\n', " ")).unwrap();
+    }
+    if let Some(max) = max_depth.filter(|_| shown.len() < view.vertices.len()) {
+        writeln!(mermaid, "    %% Limited to depth {}: {} of {} libraries shown", max, shown.len(), view.vertices.len()).unwrap();
+    }
+    for (idx, v) in view.vertices.iter().enumerate() {
+        if shown.contains(v.as_str()) {
+            writeln!(mermaid, "    n{}[{}]", idx, quoted(&label(v))).unwrap();
+        }
+    }
+    for (edge, multiplicity) in &view.edges {
+        if !shown.contains(edge.src.as_str()) || !shown.contains(edge.dst.as_str()) {
+            continue;
+        }
+        let arrow = match edge.kind {
+            EdgeKind::Needed if *multiplicity > 1 => format!("-->|{}|", multiplicity),
+            EdgeKind::Needed => "-->".to_string(),
+            EdgeKind::Interpreter => "-->|interpreter|".to_string(),
+            EdgeKind::Weak => "-.->|weak|".to_string(),
+            EdgeKind::Dlopen | EdgeKind::Runtime => "-.->".to_string(),
+        };
+        writeln!(mermaid, "    n{} {} n{}", index[edge.src.as_str()], arrow, index[edge.dst.as_str()]).unwrap();
+    }
+    mermaid
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::collapse::collapse_result;
+    use crate::dot::text_label;
+    use crate::label_template::LabelTemplate;
+    use crate::mermaid::to_mermaid;
+    use crate::node_limit::limit_nodes;
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn to_mermaid_should_draw_every_library_and_edge() {
+        let mut result = small_dag();
+        result.vertices[5] = "lib\"F\"".to_string();
+        result.edges.retain(|edge| edge.src != "F");
+        let view = collapse_result(&result, &[]);
+        let mermaid = to_mermaid(&result, &view, None, &str::to_string, None);
+        assert!(mermaid.starts_with("graph TD\n    n0[\"A\"]\n"));
+        assert!(mermaid.contains("    n5[\"lib#quot;F#quot;\"]\n"));
+        assert!(mermaid.contains("    n3 --> n1\n"));
+        assert_eq!(result.edges.len(), mermaid.matches(" --> ").count());
+    }
+
+    #[test]
+    fn to_mermaid_with_max_depth_should_keep_libraries_up_to_that_depth() {
+        // A at depth 0; B, C and F at 1; D at 2; E at 3
+        let result = small_dag();
+        let view = collapse_result(&result, &[]);
+        let mermaid = to_mermaid(&result, &view, Some(1), &str::to_string, None);
+        let expected = "\
+graph TD
+    %% Limited to depth 1: 4 of 6 libraries shown
+    n0[\"A\"]
+    n1[\"B\"]
+    n2[\"C\"]
+    n5[\"F\"]
+    n1 --> n0
+    n2 --> n0
+    n5 --> n0
+";
+        assert_eq!(expected, mermaid);
+        assert!(!to_mermaid(&result, &view, Some(3), &str::to_string, None).contains("%%"));
+    }
+
+    #[test]
+    fn to_mermaid_should_draw_the_collapsed_graph() {
+        let result = small_dag();
+        // B and C are both needed by A and need D
+        let view = collapse_result(&result, &["B".to_string(), "C".to_string(), "D".to_string()]);
+        let mermaid = to_mermaid(&result, &view, None, &str::to_string, None);
+        assert!(mermaid.contains("    n1[\"B*\"]\n    n2[\"C*\"]\n    n3[\"D*\"]\n"));
+        assert!(!mermaid.contains("[\"B\"]"));
+        assert!(mermaid.contains("    n3 --> n1\n"));
+        // the groups are as deep as their members
+        let mermaid = to_mermaid(&result, &view, Some(1), &str::to_string, None);
+        assert!(mermaid.contains("    n1 --> n0\n"));
+        assert!(!mermaid.contains("D*"));
+    }
+
+    #[test]
+    fn to_mermaid_should_draw_only_the_nodes_kept_by_the_node_limit() {
+        let result = small_dag();
+        let mut view = collapse_result(&result, &[]);
+        // A and D have the highest degree, F is the main library here
+        limit_nodes(&mut view, "F", 2);
+        let mermaid = to_mermaid(&result, &view, None, &str::to_string, Some("Truncated by --node-limit: 3 of 6 libraries shown"));
+        let expected = "\
+graph TD
+    %% Truncated by --node-limit: 3 of 6 libraries shown
+    n0[\"A\"]
+    n1[\"D\"]
+    n2[\"F\"]
+    n2 --> n0
+";
+        assert_eq!(expected, mermaid);
+    }
+
+    #[test]
+    fn to_mermaid_with_label_template_should_label_every_library() {
+        let result = small_dag();
+        let view = collapse_result(&result, &[]);
+        let template: LabelTemplate = "{name}\n{depth}".parse().unwrap();
+        let mermaid = to_mermaid(&result, &view, None, &text_label(&result, Some(&template)), None);
+        assert!(mermaid.contains("    n0[\"A<br>0\"]\n"));
+        assert!(mermaid.contains("    n4[\"E<br>3\"]\n"));
+    }
+}
@@ -0,0 +1,226 @@
+use goblin::elf::header::{self, EI_CLASS, EI_DATA, ELFCLASS64, ELFDATA2LSB};
+use goblin::elf::Elf;
+
+use serde::{Deserialize, Serialize};
+
+use std::io::Read;
+use std::path::{Component, Path};
+
+use crate::TopoSortResult;
+
+/// Debian multiarch tuples with the ELF machine, class (64-bit or not) and endianness (little or not) of their binaries
+const TUPLES: [(&str, u16, bool, bool); 18] = [
+    ("x86_64-linux-gnu", header::EM_X86_64, true, true),
+    ("x86_64-linux-gnux32", header::EM_X86_64, false, true),
+    ("i386-linux-gnu", header::EM_386, false, true),
+    ("aarch64-linux-gnu", header::EM_AARCH64, true, true),
+    ("arm-linux-gnueabihf", header::EM_ARM, false, true),
+    ("arm-linux-gnueabi", header::EM_ARM, false, true),
+    ("powerpc64le-linux-gnu", header::EM_PPC64, true, true),
+    ("powerpc64-linux-gnu", header::EM_PPC64, true, false),
+    ("powerpc-linux-gnu", header::EM_PPC, false, false),
+    ("s390x-linux-gnu", header::EM_S390, true, false),
+    ("riscv64-linux-gnu", header::EM_RISCV, true, true),
+    ("mips64el-linux-gnuabi64", header::EM_MIPS, true, true),
+    ("mipsel-linux-gnu", header::EM_MIPS, false, true),
+    ("mips-linux-gnu", header::EM_MIPS, false, false),
+    ("sparc64-linux-gnu", header::EM_SPARCV9, true, false),
+    ("hppa-linux-gnu", header::EM_PARISC, false, false),
+    ("m68k-linux-gnu", header::EM_68K, false, false),
+    ("sh4-linux-gnu", header::EM_SH, false, true),
+];
+
+/// A library resolved from the multiarch directory of another architecture than the analyzed binary's
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchMismatch {
+    pub library: String,
+    pub path: String,
+    /// The multiarch directory the library was found in, e.g. `/usr/lib/i386-linux-gnu`
+    pub multiarch_dir: String,
+    /// Multiarch tuples matching the analyzed binary, e.g. `x86_64-linux-gnu`
+    pub expected: Vec<String>,
+}
+
+/// Multiarch tuples whose binaries have the given ELF machine, class and endianness
+pub fn tuples_for(machine: u16, is_64: bool, little_endian: bool) -> Vec<&'static str> {
+    TUPLES.iter()
+        .filter(|(_, m, b, le)| *m == machine && *b == is_64 && *le == little_endian)
+        .map(|(tuple, ..)| *tuple)
+        .collect()
+}
+
+/// The ELF machine, class (64-bit or not) and endianness (little or not) of the ELF file at `path`, `None` when it
+/// cannot be read
+pub fn arch_of_file(path: &Path) -> Option<(u16, bool, bool)> {
+    let mut bytes = [0u8; header::header64::SIZEOF_EHDR];
+    std::fs::File::open(path).and_then(|mut file| file.read_exact(&mut bytes)).ok()?;
+    let header = Elf::parse_header(&bytes).ok()?;
+    Some((header.e_machine, header.e_ident[EI_CLASS] == ELFCLASS64, header.e_ident[EI_DATA] == ELFDATA2LSB))
+}
+
+/// Multiarch tuples matching the ELF file at `path`, `None` when it cannot be read
+pub fn tuples_of_file(path: &Path) -> Option<Vec<&'static str>> {
+    let (machine, is_64, little_endian) = arch_of_file(path)?;
+    Some(tuples_for(machine, is_64, little_endian))
+}
+
+/// The platform binaries are built for, from a target triple such as `aarch64-unknown-linux-gnu`, see `--target`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub triple: String,
+    pub machine: u16,
+    pub is_64: bool,
+    pub little_endian: bool,
+    /// The multiarch tuple of its libraries, e.g. `aarch64-linux-gnu`, `None` for musl
+    pub multiarch: Option<&'static str>,
+    pub musl: bool,
+}
+
+impl Target {
+    /// Parses `<arch>-<vendor>-linux-<env>` or `<arch>-linux-<env>`, the environment being `gnu` when omitted
+    pub fn parse(triple: &str) -> Result<Target, String> {
+        let parts: Vec<&str> = triple.split('-').collect();
+        let linux = parts.iter().position(|part| *part == "linux")
+            .ok_or_else(|| format!("{} is not a Linux target triple", triple))?;
+        let env = parts.get(linux + 1).copied().unwrap_or("gnu");
+        let (machine, is_64, little_endian) = match parts[0] {
+            "x86_64" | "amd64" => (header::EM_X86_64, env != "gnux32", true),
+            "i386" | "i486" | "i586" | "i686" => (header::EM_386, false, true),
+            "aarch64" | "arm64" => (header::EM_AARCH64, true, true),
+            "aarch64_be" => (header::EM_AARCH64, true, false),
+            arch if arch.starts_with("arm") || arch.starts_with("thumb") => (header::EM_ARM, false, !arch.ends_with("eb")),
+            "powerpc64le" => (header::EM_PPC64, true, true),
+            "powerpc64" => (header::EM_PPC64, true, false),
+            "powerpc" => (header::EM_PPC, false, false),
+            "s390x" => (header::EM_S390, true, false),
+            arch if arch.starts_with("riscv64") => (header::EM_RISCV, true, true),
+            "mips64el" => (header::EM_MIPS, true, true),
+            "mips64" => (header::EM_MIPS, true, false),
+            "mipsel" => (header::EM_MIPS, false, true),
+            "mips" => (header::EM_MIPS, false, false),
+            "sparc64" => (header::EM_SPARCV9, true, false),
+            "m68k" => (header::EM_68K, false, false),
+            arch => return Err(format!("Unknown architecture {} in the target {}", arch, triple)),
+        };
+        let musl = env.starts_with("musl");
+        let multiarch = match musl {
+            true => None,
+            // arm-linux-gnueabihf for armv7-unknown-linux-gnueabihf, the first tuple of the architecture otherwise
+            false => {
+                let tuples = tuples_for(machine, is_64, little_endian);
+                tuples.iter().find(|tuple| tuple.ends_with(&format!("-linux-{}", env))).or(tuples.first()).copied()
+            }
+        };
+        Ok(Target { triple: triple.to_string(), machine, is_64, little_endian, multiarch, musl })
+    }
+
+    /// Whether binaries with this ELF machine, class and endianness are built for the target
+    pub fn matches(&self, machine: u16, is_64: bool, little_endian: bool) -> bool {
+        self.machine == machine && self.is_64 == is_64 && self.little_endian == little_endian
+    }
+}
+
+/// The innermost directory of `path` named after a known multiarch tuple, e.g. `/usr/lib/i386-linux-gnu`
+/// for `/usr/lib/i386-linux-gnu/libc.so.6`, with the tuple itself
+pub fn multiarch_dir_of(path: &str) -> Option<(String, &'static str)> {
+    let parent = Path::new(path).parent()?;
+    parent.ancestors()
+        .find_map(|dir| match dir.components().next_back() {
+            Some(Component::Normal(name)) => TUPLES.iter()
+                .find(|(tuple, ..)| name.to_str() == Some(*tuple))
+                .map(|(tuple, ..)| (dir.to_string_lossy().into_owned(), *tuple)),
+            _ => None,
+        })
+}
+
+/// Every resolved library of `result` found under the multiarch directory of an architecture not in `expected`.
+/// Nothing is reported when `expected` is empty, i.e. when the architecture has no known multiarch tuple.
+pub fn arch_mismatches(result: &TopoSortResult, expected: &[&str]) -> Vec<ArchMismatch> {
+    if expected.is_empty() {
+        return Vec::new();
+    }
+    result.topo_sorted_libs.iter()
+        .filter(|lib| !result.unresolved.contains(&lib.name))
+        .filter_map(|lib| {
+            let path = lib.path.as_deref()?;
+            let (multiarch_dir, tuple) = multiarch_dir_of(path)?;
+            if expected.contains(&tuple) {
+                return None;
+            }
+            Some(ArchMismatch {
+                library: lib.name.clone(),
+                path: path.to_string(),
+                multiarch_dir,
+                expected: expected.iter().map(|t| t.to_string()).collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_X86_64};
+
+    use crate::binary_kind::BinaryKind;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::multiarch::{arch_mismatches, multiarch_dir_of, tuples_for, tuples_of_file, ArchMismatch, Target};
+    use crate::{get_topologically_sorted_result, AnalysisOptions};
+
+    #[test]
+    fn tuples_for_should_match_machine_class_and_endianness() {
+        assert_eq!(vec!["x86_64-linux-gnu"], tuples_for(EM_X86_64, true, true));
+        assert_eq!(vec!["x86_64-linux-gnux32"], tuples_for(EM_X86_64, false, true));
+        assert_eq!(vec!["i386-linux-gnu"], tuples_for(EM_386, false, true));
+        assert_eq!(vec!["arm-linux-gnueabihf", "arm-linux-gnueabi"], tuples_for(EM_ARM, false, true));
+        assert!(tuples_for(EM_ARM, true, true).is_empty());
+        assert!(!tuples_of_file(&std::env::current_exe().unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn multiarch_dir_of_should_find_innermost_tuple_directory() {
+        assert_eq!(Some(("/usr/lib/i386-linux-gnu".to_string(), "i386-linux-gnu")), multiarch_dir_of("/usr/lib/i386-linux-gnu/libc.so.6"));
+        assert_eq!(Some(("/usr/lib/aarch64-linux-gnu".to_string(), "aarch64-linux-gnu")),
+            multiarch_dir_of("/usr/lib/aarch64-linux-gnu/pulseaudio/libpulsecommon.so"));
+        assert_eq!(None, multiarch_dir_of("/usr/lib/libc.so.6"));
+        assert_eq!(None, multiarch_dir_of("/opt/x86_64-linux-gnu-tools.so"));
+    }
+
+    #[test]
+    fn arch_mismatches_should_report_libraries_from_foreign_multiarch_dirs() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("libc.so.6")
+            .add_needed("libz.so.1")
+            .add_needed("libfoo.so")
+            .add_library_at("libc.so.6", "/lib/x86_64-linux-gnu/libc.so.6", &[])
+            .add_library_at("libz.so.1", "/usr/lib/i386-linux-gnu/libz.so.1", &[])
+            .add_library_at("libfoo.so", "/opt/lib/libfoo.so", &[])
+            .build();
+        let result = get_topologically_sorted_result("app", "/opt/app", BinaryKind::PieExecutable, &dt, &AnalysisOptions::default()).unwrap();
+        assert_eq!(vec![ArchMismatch {
+            library: "libz.so.1".to_string(),
+            path: "/usr/lib/i386-linux-gnu/libz.so.1".to_string(),
+            multiarch_dir: "/usr/lib/i386-linux-gnu".to_string(),
+            expected: vec!["x86_64-linux-gnu".to_string()],
+        }], arch_mismatches(&result, &["x86_64-linux-gnu"]));
+        assert!(arch_mismatches(&result, &[]).is_empty());
+    }
+
+    #[test]
+    fn target_parse_should_map_triples_to_architectures_and_multiarch_tuples() {
+        let aarch64 = Target::parse("aarch64-unknown-linux-gnu").unwrap();
+        assert_eq!((EM_AARCH64, true, true, Some("aarch64-linux-gnu"), false),
+            (aarch64.machine, aarch64.is_64, aarch64.little_endian, aarch64.multiarch, aarch64.musl));
+        assert!(aarch64.matches(EM_AARCH64, true, true));
+        assert!(!aarch64.matches(EM_X86_64, true, true));
+        assert_eq!(Some("arm-linux-gnueabi"), Target::parse("armv5te-unknown-linux-gnueabi").unwrap().multiarch);
+        assert_eq!(Some("arm-linux-gnueabihf"), Target::parse("armv7-unknown-linux-gnueabihf").unwrap().multiarch);
+        assert_eq!(Some("i386-linux-gnu"), Target::parse("i686-linux-gnu").unwrap().multiarch);
+        assert_eq!(Some("x86_64-linux-gnux32"), Target::parse("x86_64-unknown-linux-gnux32").unwrap().multiarch);
+        assert_eq!(Some("riscv64-linux-gnu"), Target::parse("riscv64gc-unknown-linux-gnu").unwrap().multiarch);
+        let musl = Target::parse("x86_64-unknown-linux-musl").unwrap();
+        assert!(musl.musl);
+        assert_eq!(None, musl.multiarch);
+        assert!(Target::parse("x86_64-apple-darwin").is_err());
+        assert!(Target::parse("wasm32-unknown-linux-gnu").is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::{Edge, Lib, TopoSortResult};
+
+/// A line of `--format ndjson`, told apart by its `type`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Vertex { id: usize, name: &'a str },
+    Edge(&'a Edge),
+    /// An entry of `topo_sorted_libs` with its position in the load order
+    Library {
+        load_order: usize,
+        #[serde(flatten)]
+        lib: &'a Lib,
+    },
+}
+
+/// Writes `result` as JSON Lines, one record at a time so that a consumer can process the graph while it is
+/// read: every vertex, then every NEEDED, guessed `dlopen` and traced `runtime` edge, then every library in load order.
+pub fn write_ndjson<W: Write>(result: &TopoSortResult, mut out: W) -> std::io::Result<()> {
+    let vertices = result.vertices.iter().enumerate().map(|(id, name)| Record::Vertex { id, name });
+    let edges = result.edges.iter().chain(&result.dlopen_edges).chain(&result.runtime_edges).map(Record::Edge);
+    let libraries = result.topo_sorted_libs.iter().enumerate().map(|(load_order, lib)| Record::Library { load_order, lib });
+    for record in vertices.chain(edges).chain(libraries) {
+        serde_json::to_writer(&mut out, &record)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::ndjson::write_ndjson;
+    use crate::query::tests::small_dag;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn write_ndjson_should_write_one_record_per_line() {
+        let result = small_dag();
+        let mut out: Vec<u8> = Vec::new();
+        write_ndjson(&result, &mut out).unwrap();
+        let records: Vec<Value> = String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(result.vertices.len() + result.edges.len() + result.topo_sorted_libs.len(), records.len());
+        assert_eq!(json!({"type": "vertex", "id": 0, "name": "A"}), records[0]);
+        assert!(records.contains(&json!({"type": "edge", "src": "D", "dst": "B"})));
+        let last = &records[records.len() - 1];
+        assert_eq!(json!("library"), last["type"]);
+        assert_eq!(json!(result.topo_sorted_libs.len() - 1), last["load_order"]);
+        assert_eq!(json!("A"), last["name"]);
+    }
+}
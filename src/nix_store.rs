@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+/// The store directory Nix builds for
+pub const NIX_STORE: &str = "/nix/store";
+
+/// Where the store paths in the RUNPATH and interpreter of Nix or Guix built binaries are on this machine, see
+/// `--nix-store`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NixStore {
+    /// Store prefixes with the directory they are rewritten to, the longest prefix first
+    rewrites: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Parses `FROM=TO`, e.g. `/gnu/store=/mnt/guix/gnu/store`
+pub fn parse_rewrite(rewrite: &str) -> Result<(PathBuf, PathBuf), String> {
+    match rewrite.split_once('=') {
+        Some((from, to)) if from.starts_with('/') && !to.is_empty() => Ok((PathBuf::from(from), PathBuf::from(to))),
+        _ => Err(format!("{} is not of the form /store/prefix=DIR", rewrite)),
+    }
+}
+
+impl NixStore {
+    /// A store whose `/nix/store` is at `location`, which is `/nix/store` itself inside the store root and e.g. the
+    /// `nix/store` of a copied closure outside of it
+    pub fn new(location: PathBuf) -> NixStore {
+        NixStore { rewrites: vec![(PathBuf::from(NIX_STORE), location)] }
+    }
+
+    /// Rewrites paths starting with `from` to `to` as well, e.g. `/gnu/store` for Guix or a single store path
+    pub fn rewrite(mut self, from: PathBuf, to: PathBuf) -> NixStore {
+        self.rewrites.retain(|(prefix, _)| *prefix != from);
+        self.rewrites.push((from, to));
+        self.rewrites.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.components().count()));
+        self
+    }
+
+    /// The path on this machine of the store path `path`, `None` when it is in no store
+    pub fn locate(&self, path: &Path) -> Option<PathBuf> {
+        self.rewrites.iter().find_map(|(prefix, location)| {
+            path.strip_prefix(prefix).ok().map(|rest| if rest.as_os_str().is_empty() { location.clone() } else { location.join(rest) })
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::nix_store::{parse_rewrite, NixStore};
+
+    #[test]
+    fn locate_should_rewrite_the_longest_matching_prefix() {
+        let store = NixStore::new(PathBuf::from("/tmp/closure/nix/store"))
+            .rewrite(PathBuf::from("/gnu/store"), PathBuf::from("/gnu/store"))
+            .rewrite(PathBuf::from("/nix/store/abc-glibc-2.38"), PathBuf::from("/opt/glibc"));
+        assert_eq!(Some(PathBuf::from("/tmp/closure/nix/store/xyz-zlib-1.3/lib")), store.locate(Path::new("/nix/store/xyz-zlib-1.3/lib")));
+        assert_eq!(Some(PathBuf::from("/opt/glibc/lib")), store.locate(Path::new("/nix/store/abc-glibc-2.38/lib")));
+        assert_eq!(Some(PathBuf::from("/gnu/store/def-gcc/lib")), store.locate(Path::new("/gnu/store/def-gcc/lib")));
+        assert_eq!(None, store.locate(Path::new("/nix/storefront/lib")));
+        assert_eq!(None, store.locate(Path::new("/usr/lib")));
+
+        assert_eq!(Ok((PathBuf::from("/gnu/store"), PathBuf::from("/mnt/gnu/store"))), parse_rewrite("/gnu/store=/mnt/gnu/store"));
+        assert!(parse_rewrite("gnu/store=/mnt").is_err());
+        assert!(parse_rewrite("/gnu/store").is_err());
+    }
+}
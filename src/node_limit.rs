@@ -0,0 +1,49 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::collapse::CollapsedView;
+
+/// Keeps the `limit` vertices of `view` with the highest total degree, plus `main`, and the edges among them.
+/// Ties are broken by name so the cut is the same on every run. Returns how many vertices were dropped.
+pub fn limit_nodes(view: &mut CollapsedView, main: &str, limit: usize) -> usize {
+    if view.vertices.len() <= limit {
+        return 0;
+    }
+    let mut degree: HashMap<&str, usize> = HashMap::new();
+    for edge in view.edges.keys() {
+        *degree.entry(edge.src.as_str()).or_default() += 1;
+        *degree.entry(edge.dst.as_str()).or_default() += 1;
+    }
+    let mut ranked: Vec<&String> = view.vertices.iter().collect();
+    ranked.sort_by(|a, b| degree.get(b.as_str()).cmp(&degree.get(a.as_str())).then_with(|| a.cmp(b)));
+    let mut kept: BTreeSet<String> = ranked.into_iter().take(limit).cloned().collect();
+    if view.vertices.iter().any(|v| v == main) {
+        kept.insert(main.to_string());
+    }
+    let dropped = view.vertices.len() - kept.len();
+    view.vertices.retain(|v| kept.contains(v));
+    view.edges.retain(|edge, _| kept.contains(&edge.src) && kept.contains(&edge.dst));
+    dropped
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::collapse::collapse_by_prefix;
+    use crate::node_limit::limit_nodes;
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn limit_nodes_should_keep_most_connected_vertices_and_main() {
+        // Degrees: A 3, B 2, C 2, D 3, E 2, F 2
+        let result = small_dag();
+        let mut view = collapse_by_prefix(&result.vertices, &result.edges, &[]);
+        // A and D, then B wins the tie by name; F is kept as the main library
+        assert_eq!(2, limit_nodes(&mut view, "F", 3));
+        assert_eq!(vec!["A", "B", "D", "F"], view.vertices);
+        assert_eq!(vec![("B", "A"), ("D", "B"), ("F", "A")],
+            view.edges.keys().map(|e| (e.src.as_str(), e.dst.as_str())).collect::<Vec<_>>());
+
+        let mut view = collapse_by_prefix(&result.vertices, &result.edges, &[]);
+        assert_eq!(0, limit_nodes(&mut view, "A", 6));
+        assert_eq!(6, view.vertices.len());
+    }
+}
@@ -0,0 +1,40 @@
+use clap::ValueEnum;
+
+use std::borrow::Cow;
+
+/// How library names are canonicalized before they become graph vertices
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameNormalization {
+    /// Strip leading and trailing whitespace
+    Trim,
+    /// Strip whitespace and lowercase. Only safe when the libraries live on a case-insensitive filesystem
+    Lowercase,
+}
+
+impl NameNormalization {
+    pub fn apply<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let trimmed = name.trim();
+        match self {
+            NameNormalization::Trim if trimmed.len() == name.len() => Cow::Borrowed(name),
+            NameNormalization::Trim => Cow::Owned(trimmed.to_string()),
+            NameNormalization::Lowercase => Cow::Owned(trimmed.to_lowercase()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::borrow::Cow;
+    use crate::normalize::NameNormalization;
+
+    #[test]
+    fn trim_should_only_strip_whitespace() {
+        assert_eq!("libFoo.so.1", NameNormalization::Trim.apply(" libFoo.so.1\t"));
+        assert!(matches!(NameNormalization::Trim.apply("libFoo.so.1"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn lowercase_should_strip_whitespace_and_lowercase() {
+        assert_eq!("libfoo.so.1", NameNormalization::Lowercase.apply("libFoo.so.1 "));
+    }
+}
@@ -0,0 +1,286 @@
+use serde::Deserialize;
+
+use std::io::{self, Read};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::compression::decompressed;
+
+const INDEX_MEDIA_TYPES: [&str; 2] = ["application/vnd.oci.image.index.v1+json", "application/vnd.docker.distribution.manifest.list.v2+json"];
+/// A layer hides the file `<name>` of the layers below with an empty `.wh.<name>`
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// and every file of its directory with `.wh..wh..opq`
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+#[derive(Deserialize, Debug, Clone)]
+struct Platform {
+    architecture: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    #[serde(default)]
+    media_type: String,
+    digest: String,
+    platform: Option<Platform>,
+}
+
+/// `index.json` of an OCI image layout, and the image indexes it points to
+#[derive(Deserialize, Debug)]
+struct Index {
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Manifest {
+    layers: Vec<Descriptor>,
+}
+
+/// An entry of the `manifest.json` written by `docker save`
+#[derive(Deserialize, Debug)]
+struct DockerManifest {
+    #[serde(rename = "Layers")]
+    layers: Vec<String>,
+}
+
+/// The architecture of this machine as OCI platforms name it
+fn host_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        "powerpc64" => "ppc64le",
+        arch => arch,
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> io::Result<T> {
+    let bytes = std::fs::read(path)?;
+    serde_json::from_slice(&bytes).map_err(|err| invalid(format!("{}: {}", path.display(), err)))
+}
+
+/// `blobs/<algorithm>/<hex>` of the layout for the digest `<algorithm>:<hex>`
+fn blob_path(layout: &Path, digest: &str) -> io::Result<PathBuf> {
+    match digest.split_once(':') {
+        Some((algorithm, hex)) if !algorithm.contains(['/', '.']) && !hex.contains(['/', '.']) => Ok(layout.join("blobs").join(algorithm).join(hex)),
+        _ => Err(invalid(format!("{} is not a valid digest", digest))),
+    }
+}
+
+/// The layers of the OCI image layout or `docker save` directory `layout`, bottom first. For a multi-platform image,
+/// the manifest for the architecture of this machine is used, or the first one.
+pub fn layers(layout: &Path) -> io::Result<Vec<PathBuf>> {
+    if !layout.join("index.json").exists() && layout.join("manifest.json").exists() {
+        let manifests: Vec<DockerManifest> = read_json(&layout.join("manifest.json"))?;
+        let manifest = manifests.into_iter().next().ok_or_else(|| invalid("manifest.json lists no image".to_string()))?;
+        return manifest.layers.iter()
+            .map(|layer| match Path::new(layer).components().all(|component| matches!(component, Component::Normal(_))) {
+                true => Ok(layout.join(layer)),
+                false => Err(invalid(format!("layer {} is outside of the image", layer))),
+            })
+            .collect();
+    }
+    let mut index: Index = read_json(&layout.join("index.json"))?;
+    loop {
+        let host = index.manifests.iter().position(|manifest| manifest.platform.as_ref().is_some_and(|p| p.architecture == host_architecture()));
+        let manifest = index.manifests.get(host.unwrap_or(0)).ok_or_else(|| invalid("the image index lists no manifest".to_string()))?;
+        let path = blob_path(layout, &manifest.digest)?;
+        if !INDEX_MEDIA_TYPES.contains(&manifest.media_type.as_str()) {
+            let manifest: Manifest = read_json(&path)?;
+            return manifest.layers.iter().map(|layer| blob_path(layout, &layer.digest)).collect();
+        }
+        index = read_json(&path)?;
+    }
+}
+
+/// Where the archive path `path` is in `rootfs`, with the symbolic links of its parent directories followed. `None`
+/// when it leaves `rootfs`, e.g. through `..` or an absolute link, or its parent does not exist.
+pub(crate) fn inside(rootfs: &Path, path: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => relative.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    let parent = std::fs::canonicalize(rootfs.join(&relative).parent()?).ok()?;
+    match parent.starts_with(std::fs::canonicalize(rootfs).ok()?) {
+        true => Some(parent.join(relative.file_name()?)),
+        false => None,
+    }
+}
+
+/// Removes `path` whether it is a file, a link or a directory
+fn remove(path: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Applies the layer tarball `layer` on top of `rootfs`, with its whiteouts removing the files of the layers below
+fn apply_layer(layer: impl Read, rootfs: &Path) -> io::Result<()> {
+    let mut archive = tar::Archive::new(decompressed(layer)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        if name == OPAQUE_WHITEOUT {
+            let dir = path.parent().and_then(|parent| inside(rootfs, parent));
+            if let Some(dir) = dir.filter(|dir| std::fs::symlink_metadata(dir).is_ok_and(|metadata| metadata.is_dir())) {
+                for child in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+                    remove(&child.path())?;
+                }
+            }
+        } else if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            if let Some(target) = inside(rootfs, &path.with_file_name(hidden)) {
+                remove(&target)?;
+            }
+        } else {
+            // a file replaces whatever the layers below have at its path, a directory only what is not a directory
+            let is_dir = entry.header().entry_type().is_dir();
+            if let Some(target) = inside(rootfs, &path) {
+                if !(is_dir && std::fs::symlink_metadata(&target).is_ok_and(|metadata| metadata.is_dir())) {
+                    remove(&target)?;
+                }
+            }
+            entry.unpack_in(rootfs)?;
+        }
+    }
+    Ok(())
+}
+
+/// Overlays the layers of the image `image` into the directory `rootfs`, which has to be new or empty: the whiteouts of
+/// the layers delete files. `image` is an OCI image layout or `docker save` directory, read in place, or a tar archive of
+/// one, unpacked next to `rootfs` first. Returns the number of layers.
+pub fn unpack(image: &Path, rootfs: &Path) -> io::Result<usize> {
+    if std::fs::read_dir(rootfs).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is not empty, the layers are only unpacked into a new or empty directory", rootfs.display())));
+    }
+    std::fs::create_dir_all(rootfs)?;
+    if image.is_dir() {
+        return overlay(image, rootfs);
+    }
+    let mut layout = rootfs.as_os_str().to_os_string();
+    layout.push(".layout");
+    let layout = PathBuf::from(layout);
+    // never a directory someone else created
+    std::fs::DirBuilder::new().mode(0o700).create(&layout)?;
+    let unpacked = tar::Archive::new(decompressed(std::fs::File::open(image)?)?).unpack(&layout)
+        .and_then(|_| overlay(&layout, rootfs));
+    std::fs::remove_dir_all(&layout)?;
+    unpacked
+}
+
+fn overlay(layout: &Path, rootfs: &Path) -> io::Result<usize> {
+    let layers = layers(layout)?;
+    for layer in &layers {
+        apply_layer(std::fs::File::open(layer)?, rootfs)
+            .map_err(|err| io::Error::new(err.kind(), format!("layer {}: {}", layer.display(), err)))?;
+    }
+    Ok(layers.len())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+    use std::path::Path;
+
+    use flate2::write::GzEncoder;
+    use sha2::{Digest, Sha256};
+
+    use crate::oci_image::unpack;
+
+    /// A layer tarball with `files` of the given contents, and symbolic links for contents starting with `->`
+    fn layer(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            match content.strip_prefix("->") {
+                Some(target) => {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_size(0);
+                    builder.append_link(&mut header, path, target).unwrap();
+                }
+                None => {
+                    header.set_size(content.len() as u64);
+                    builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+                }
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// Writes `bytes` as a blob of the layout and returns its descriptor
+    fn blob(layout: &Path, media_type: &str, bytes: &[u8]) -> serde_json::Value {
+        let hex: String = Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect();
+        std::fs::create_dir_all(layout.join("blobs/sha256")).unwrap();
+        std::fs::write(layout.join("blobs/sha256").join(&hex), bytes).unwrap();
+        serde_json::json!({"mediaType": media_type, "digest": format!("sha256:{}", hex), "size": bytes.len()})
+    }
+
+    #[test]
+    fn unpack_should_overlay_the_layers_with_their_whiteouts() {
+        let dir = std::env::temp_dir().join(format!("lddtopo-oci-test-{}", std::process::id()));
+        let (layout, outside) = (dir.join("layout"), dir.join("outside"));
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("victim"), b"").unwrap();
+        let base = layer(&[
+            ("usr/lib/libfoo.so.1", "v1"),
+            ("etc/gone", ""),
+            ("cache/a", ""),
+            ("escape", &format!("->{}", outside.display())),
+        ]);
+        let mut gzipped = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzipped.write_all(&layer(&[
+            ("usr/lib/libfoo.so.1", "v2"),
+            ("etc/.wh.gone", ""),
+            ("cache/.wh..wh..opq", ""),
+            ("cache/b", ""),
+            ("escape/.wh.victim", ""),
+        ])).unwrap();
+        let layers = vec![
+            blob(&layout, "application/vnd.oci.image.layer.v1.tar", &base),
+            blob(&layout, "application/vnd.oci.image.layer.v1.tar+gzip", &gzipped.finish().unwrap()),
+            blob(&layout, "application/vnd.oci.image.layer.v1.tar+zstd", &ruzstd::encoding::compress_to_vec(
+                &layer(&[("usr/lib/libbar.so.1", "zstd")])[..], ruzstd::encoding::CompressionLevel::Fastest)),
+        ];
+        let manifest = serde_json::json!({"schemaVersion": 2, "layers": layers}).to_string();
+        let manifest = blob(&layout, "application/vnd.oci.image.manifest.v1+json", manifest.as_bytes());
+        std::fs::write(layout.join("index.json"), serde_json::json!({"schemaVersion": 2, "manifests": [manifest]}).to_string()).unwrap();
+        std::fs::write(layout.join("oci-layout"), r#"{"imageLayoutVersion":"1.0.0"}"#).unwrap();
+
+        let from_dir = unpack(&layout, &dir.join("rootfs"));
+        let mut archive = tar::Builder::new(Vec::new());
+        archive.append_dir_all(".", &layout).unwrap();
+        std::fs::write(dir.join("image.tar"), archive.into_inner().unwrap()).unwrap();
+        let from_archive = unpack(&dir.join("image.tar"), &dir.join("rootfs-archive"));
+        let into_non_empty = unpack(&layout, &outside);
+        let read = |path: &str| std::fs::read_to_string(dir.join(path)).ok();
+        let (libfoo, gone, a, b, victim) = (read("rootfs/usr/lib/libfoo.so.1"), read("rootfs/etc/gone"), read("rootfs/cache/a"),
+            read("rootfs/cache/b"), read("outside/victim"));
+        let from_archive_libfoo = read("rootfs-archive/usr/lib/libfoo.so.1");
+        let libbar = read("rootfs/usr/lib/libbar.so.1");
+        let layout_left = dir.join("rootfs-archive.layout").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(3, from_dir.unwrap());
+        assert_eq!(Some("v2".to_string()), libfoo);
+        assert_eq!(Some("zstd".to_string()), libbar);
+        assert_eq!((None, None, Some(String::new())), (gone, a, b));
+        // whiteouts never reach through links out of the root
+        assert_eq!(Some(String::new()), victim);
+        assert_eq!(3, from_archive.unwrap());
+        assert_eq!(Some("v2".to_string()), from_archive_libfoo);
+        assert!(!layout_left);
+        assert_eq!(std::io::ErrorKind::AlreadyExists, into_non_empty.unwrap_err().kind());
+    }
+}
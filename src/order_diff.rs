@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+
+use crate::TopoSortResult;
+
+/// Comparison of the load order of two runs, ignoring paths and any other metadata
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct OrderDiff {
+    /// Whether the libraries present in both runs are loaded in the same relative order
+    pub identical: bool,
+    /// Number of libraries present in both runs
+    pub common: usize,
+    pub only_in_old: Vec<String>,
+    pub only_in_new: Vec<String>,
+    /// Common libraries whose order relative to at least one other common library changed
+    pub moved: Vec<MovedLib>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct MovedLib {
+    pub name: String,
+    /// Position among the common libraries in the old run
+    pub old_position: usize,
+    /// Position among the common libraries in the new run
+    pub new_position: usize,
+}
+
+fn names(result: &TopoSortResult) -> Vec<&str> {
+    result.topo_sorted_libs.iter().map(|lib| lib.name.as_str()).collect()
+}
+
+pub fn diff_order(old: &TopoSortResult, new: &TopoSortResult) -> OrderDiff {
+    let old_names = names(old);
+    let new_names = names(new);
+    let old_set: HashSet<&str> = old_names.iter().copied().collect();
+    let new_set: HashSet<&str> = new_names.iter().copied().collect();
+
+    let old_common: Vec<&str> = old_names.iter().copied().filter(|n| new_set.contains(n)).collect();
+    let new_position: HashMap<&str, usize> = new_names.iter().copied()
+        .filter(|n| old_set.contains(n))
+        .enumerate()
+        .map(|(idx, n)| (n, idx))
+        .collect();
+
+    // A library moved when its order relative to some other common library flipped
+    let moved: Vec<MovedLib> = old_common.iter().enumerate()
+        .filter(|(old_idx, name)| {
+            let new_idx = new_position[*name];
+            old_common.iter().enumerate().any(|(other_old_idx, other)| {
+                other != *name && (other_old_idx < *old_idx) != (new_position[other] < new_idx)
+            })
+        })
+        .map(|(old_idx, name)| MovedLib { name: name.to_string(), old_position: old_idx, new_position: new_position[name] })
+        .collect();
+
+    let only = |names: &[&str], other: &HashSet<&str>| -> Vec<String> {
+        let mut only: Vec<String> = names.iter().filter(|n| !other.contains(*n)).map(|n| n.to_string()).collect();
+        only.sort();
+        only
+    };
+    OrderDiff {
+        identical: moved.is_empty(),
+        common: old_common.len(),
+        only_in_old: only(&old_names, &new_set),
+        only_in_new: only(&new_names, &old_set),
+        moved,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::order_diff::{diff_order, MovedLib};
+    use crate::query::tests::small_dag;
+    use crate::Lib;
+
+    #[test]
+    fn diff_order_when_only_paths_changed_should_be_identical() {
+        let old = small_dag();
+        let mut new = small_dag();
+        new.topo_sorted_libs.iter_mut().for_each(|lib| lib.path = Some(format!("/opt/{}", lib.name)));
+        let diff = diff_order(&old, &new);
+        assert!(diff.identical);
+        assert_eq!(6, diff.common);
+        assert!(diff.moved.is_empty());
+    }
+
+    #[test]
+    fn diff_order_should_report_swapped_libraries_and_ignore_added_and_removed_ones() {
+        let old = small_dag();
+        // F E D B C A -> F D E G C A
+        let mut new = small_dag();
+        new.topo_sorted_libs.swap(1, 2);
+        new.topo_sorted_libs[3] = Lib { name: "G".to_string(), ..Default::default() };
+        let diff = diff_order(&old, &new);
+        assert!(!diff.identical);
+        assert_eq!(5, diff.common);
+        assert_eq!(vec!["B"], diff.only_in_old);
+        assert_eq!(vec!["G"], diff.only_in_new);
+        assert_eq!(vec![
+            MovedLib { name: "E".to_string(), old_position: 1, new_position: 2 },
+            MovedLib { name: "D".to_string(), old_position: 2, new_position: 1 },
+        ], diff.moved);
+    }
+}
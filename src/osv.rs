@@ -0,0 +1,242 @@
+use serde::Deserialize;
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::path::Path;
+
+use crate::packages::{compare_versions, Package, PackageSource};
+
+/// Ecosystems of the OSV database whose advisories name dpkg and rpm source packages
+const DPKG_ECOSYSTEMS: [&str; 2] = ["Debian", "Ubuntu"];
+const RPM_ECOSYSTEMS: [&str; 7] = ["Red Hat", "Rocky Linux", "AlmaLinux", "openSUSE", "SUSE", "Mageia", "openEuler"];
+/// Ecosystems whose advisories name upstream projects and their releases, which the version strings embedded in files
+/// are. Distribution advisories do not apply to those: the distributions patch without changing the upstream version
+const UPSTREAM_ECOSYSTEMS: [&str; 1] = ["OSS-Fuzz"];
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct Event {
+    introduced: Option<String>,
+    fixed: Option<String>,
+    last_affected: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Range {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    events: Vec<Event>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct AffectedPackage {
+    ecosystem: String,
+    name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Affected {
+    package: Option<AffectedPackage>,
+    #[serde(default)]
+    ranges: Vec<Range>,
+    #[serde(default)]
+    versions: Vec<String>,
+}
+
+/// The part of an OSV record the lookup needs, see https://ossf.github.io/osv-schema/
+#[derive(Deserialize, Debug, Clone)]
+struct Record {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    upstream: Vec<String>,
+    withdrawn: Option<String>,
+    #[serde(default)]
+    affected: Vec<Affected>,
+}
+
+impl Record {
+    /// The CVE the record is about, its own id when it is none
+    fn cve(&self) -> String {
+        std::iter::once(&self.id).chain(self.aliases.iter()).chain(self.upstream.iter())
+            .find(|id| id.starts_with("CVE-"))
+            .unwrap_or(&self.id)
+            .clone()
+    }
+}
+
+/// Whether `version` is in `range`: the events are walked in order, `introduced` opens the affected interval,
+/// `fixed` closes it before and `last_affected` after the version. GIT ranges name commits and are never matched.
+fn in_range(version: &str, range: &Range) -> bool {
+    if range.kind == "GIT" {
+        return false;
+    }
+    let mut affected = false;
+    for event in &range.events {
+        if let Some(introduced) = &event.introduced {
+            if introduced == "0" || compare_versions(version, introduced) != Ordering::Less {
+                affected = true;
+            }
+        }
+        if let Some(fixed) = &event.fixed {
+            if compare_versions(version, fixed) != Ordering::Less {
+                affected = false;
+            }
+        }
+        if let Some(last_affected) = &event.last_affected {
+            if compare_versions(version, last_affected) == Ordering::Greater {
+                affected = false;
+            }
+        }
+    }
+    affected
+}
+
+/// A local dump of the OSV database, e.g. the unzipped `all.zip` of an ecosystem, see `--osv`
+#[derive(Debug, Default)]
+pub struct OsvDatabase {
+    /// Records by the name of the packages they affect
+    records: HashMap<String, Vec<Record>>,
+    /// `Debian:12` or `Ubuntu:22.04` of the analyzed system, advisories of other releases are ignored when it is known
+    release: Option<String>,
+}
+
+impl OsvDatabase {
+    /// Reads every `*.json` file under `dir`. Files that are not OSV records are skipped, their number is returned
+    /// with the database.
+    pub fn load(dir: &Path) -> io::Result<(OsvDatabase, usize)> {
+        let mut database = OsvDatabase::default();
+        let mut skipped = 0;
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir)?.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|extension| extension == "json") {
+                    match std::fs::read(&path).ok().and_then(|bytes| serde_json::from_slice::<Record>(&bytes).ok()) {
+                        Some(record) => database.add(record),
+                        None => skipped += 1,
+                    }
+                }
+            }
+        }
+        Ok((database, skipped))
+    }
+
+    fn add(&mut self, record: Record) {
+        if record.withdrawn.is_some() {
+            return;
+        }
+        let names: BTreeSet<String> = record.affected.iter().filter_map(|affected| affected.package.as_ref().map(|p| p.name.clone())).collect();
+        for name in names {
+            self.records.entry(name).or_default().push(record.clone());
+        }
+    }
+
+    /// Restricts distribution advisories to the release of the system, e.g. from `os_release`
+    pub fn with_release(mut self, release: Option<String>) -> OsvDatabase {
+        self.release = release;
+        self
+    }
+
+    /// Whether advisories for `ecosystem` apply to a package from `source`
+    fn applies(&self, ecosystem: &str, source: PackageSource) -> bool {
+        let family = ecosystem.split(':').next().unwrap_or(ecosystem);
+        let same_release = || match &self.release {
+            Some(release) => ecosystem == family || ecosystem == release || ecosystem.starts_with(&format!("{}:", release)),
+            None => true,
+        };
+        match source {
+            PackageSource::Dpkg => DPKG_ECOSYSTEMS.contains(&family) && same_release(),
+            PackageSource::Rpm => RPM_ECOSYSTEMS.contains(&family),
+            PackageSource::Embedded => UPSTREAM_ECOSYSTEMS.contains(&family),
+        }
+    }
+
+    /// The CVEs, or OSV ids without a CVE, of every record that affects `package`, sorted
+    pub fn vulnerabilities(&self, package: &Package) -> Vec<String> {
+        let records = match self.records.get(package.advisory_name()) {
+            Some(records) => records,
+            None => return Vec::new(),
+        };
+        let affects = |affected: &Affected| {
+            affected.package.as_ref().is_some_and(|p| p.name == package.advisory_name() && self.applies(&p.ecosystem, package.source))
+                && (affected.versions.contains(&package.version) || affected.ranges.iter().any(|range| in_range(&package.version, range)))
+        };
+        let ids: BTreeSet<String> = records.iter()
+            .filter(|record| record.affected.iter().any(affects))
+            .map(Record::cve)
+            .collect();
+        ids.into_iter().collect()
+    }
+}
+
+/// The OSV ecosystem of the system under `root` from its `/etc/os-release`, e.g. `Debian:12`, `None` for other
+/// distributions
+pub fn os_release(root: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(root.join("etc/os-release")).ok()?;
+    let field = |name: &str| content.lines()
+        .find_map(|line| line.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')))
+        .map(|value| value.trim_matches('"').to_string());
+    let family = match field("ID")?.as_str() {
+        "debian" => "Debian",
+        "ubuntu" => "Ubuntu",
+        _ => return None,
+    };
+    Some(format!("{}:{}", family, field("VERSION_ID")?))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::osv::{OsvDatabase, Record};
+    use crate::packages::{Package, PackageSource};
+
+    fn record(json: &str) -> Record {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn package(name: &str, version: &str, source: PackageSource) -> Package {
+        Package { name: format!("lib{}", name), version: version.to_string(), source_name: Some(name.to_string()), source }
+    }
+
+    #[test]
+    fn vulnerabilities_should_match_the_affected_ranges() {
+        let mut database = OsvDatabase::default();
+        database.add(record(r#"{"id": "DSA-5532-1", "upstream": ["CVE-2023-5363"], "affected": [{
+            "package": {"ecosystem": "Debian:12", "name": "openssl"},
+            "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "3.0.11-1~deb12u2"}]}]}]}"#));
+        database.add(record(r#"{"id": "DEBIAN-CVE-2024-0727", "aliases": ["CVE-2024-0727"], "affected": [{
+            "package": {"ecosystem": "Debian:12", "name": "openssl"},
+            "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "3.0.0"}, {"last_affected": "3.0.11-1~deb12u2"}]}]}]}"#));
+        database.add(record(r#"{"id": "DEBIAN-CVE-2022-0001", "affected": [{
+            "package": {"ecosystem": "Debian:11", "name": "openssl"}, "versions": ["3.0.11-1~deb12u1"]}]}"#));
+        database.add(record(r#"{"id": "OSV-2020-1", "withdrawn": "2021-01-01T00:00:00Z", "affected": [{
+            "package": {"ecosystem": "Debian", "name": "openssl"}, "versions": ["3.0.11-1~deb12u1"]}]}"#));
+        database.add(record(r#"{"id": "RHSA-2024:0001", "affected": [{
+            "package": {"ecosystem": "Red Hat:enterprise_linux:9", "name": "openssl"},
+            "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "1:3.0.7-27.el9"}]}]}]}"#));
+        let database = database.with_release(Some("Debian:12".to_string()));
+
+        assert_eq!(vec!["CVE-2023-5363", "CVE-2024-0727"], database.vulnerabilities(&package("openssl", "3.0.11-1~deb12u1", PackageSource::Dpkg)));
+        assert_eq!(vec!["CVE-2024-0727"], database.vulnerabilities(&package("openssl", "3.0.11-1~deb12u2", PackageSource::Dpkg)));
+        assert!(database.vulnerabilities(&package("openssl", "3.0.13-1~deb12u1", PackageSource::Dpkg)).is_empty());
+        assert_eq!(vec!["RHSA-2024:0001"], database.vulnerabilities(&package("openssl", "1:3.0.7-25.el9", PackageSource::Rpm)));
+        assert!(database.vulnerabilities(&package("zlib", "1.2.13", PackageSource::Dpkg)).is_empty());
+    }
+
+    #[test]
+    fn vulnerabilities_of_embedded_versions_should_only_match_upstream_advisories() {
+        let mut database = OsvDatabase::default();
+        database.add(record(r#"{"id": "DEBIAN-CVE-2023-38545", "aliases": ["CVE-2023-38545"], "affected": [{
+            "package": {"ecosystem": "Debian:12", "name": "curl"},
+            "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "7.88.1-10+deb12u4"}]}]}]}"#));
+        database.add(record(r#"{"id": "OSV-2023-1234", "affected": [{
+            "package": {"ecosystem": "OSS-Fuzz", "name": "curl"}, "versions": ["8.5.0"]}]}"#));
+        let embedded = |version: &str| Package { name: "curl".to_string(), version: version.to_string(), source_name: None, source: PackageSource::Embedded };
+        assert_eq!(vec!["OSV-2023-1234"], database.vulnerabilities(&embedded("8.5.0")));
+        assert!(database.vulnerabilities(&embedded("7.88.1")).is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use clap::ValueEnum;
+
+use serde::Serialize;
+
+use crate::matrix::escape_csv;
+use crate::TopoSortResult;
+
+/// Serialization of the output file
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    /// One JSON record per line for every vertex, edge and library, see `ndjson::write_ndjson`
+    Ndjson,
+    Yaml,
+    Toml,
+    /// MessagePack with field names, so that it decodes to the same structure as the JSON
+    Msgpack,
+    /// One `src,dst,kind` row per edge of the graph, see `csv_edges`
+    CsvEdges,
+    /// Adjacency matrix in CSV with a header row and column of library names, see `matrix::to_csv`
+    Matrix,
+    /// The dependency graph in DOT, as written next to the JSON otherwise
+    Dot,
+    /// The NEEDED graph in GEXF for Gephi, see `gexf::to_gexf`
+    Gexf,
+    /// The graph of the DOT output as a Mermaid `graph TD` for Markdown, see `mermaid::to_mermaid`
+    Mermaid,
+    /// The NEEDED graph as a PlantUML component diagram, see `plantuml::to_plantuml`
+    Plantuml,
+    /// `vertices`, `edges` and `libraries` tables in a SQLite database, see `sqlite::write_sqlite`
+    Sqlite,
+    /// A Cypher script that creates the NEEDED graph in Neo4j, see `cypher::to_cypher`
+    Cypher,
+}
+
+impl OutputFormat {
+    /// Whether the format is text, which gets a trailing newline on a terminal
+    pub fn is_text(&self) -> bool {
+        !matches!(self, OutputFormat::Msgpack | OutputFormat::Sqlite)
+    }
+}
+
+/// `value` in one of the formats that can represent any serializable value. JSON Lines, the CSV formats, the diagram formats and SQLite
+/// only describe a graph and have their own writers, for them this fails.
+pub fn serialize<T: Serialize>(value: &T, format: OutputFormat, pretty: bool) -> Result<Vec<u8>, String> {
+    match format {
+        OutputFormat::Json if pretty => serde_json::to_vec_pretty(value).map_err(|err| err.to_string()),
+        OutputFormat::Json => serde_json::to_vec(value).map_err(|err| err.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(value).map(String::into_bytes).map_err(|err| err.to_string()),
+        OutputFormat::Toml if pretty => toml::to_string_pretty(value).map(String::into_bytes).map_err(|err| err.to_string()),
+        OutputFormat::Toml => toml::to_string(value).map(String::into_bytes).map_err(|err| err.to_string()),
+        OutputFormat::Msgpack => rmp_serde::to_vec_named(value).map_err(|err| err.to_string()),
+        OutputFormat::CsvEdges | OutputFormat::Matrix | OutputFormat::Dot | OutputFormat::Gexf | OutputFormat::Mermaid
+        | OutputFormat::Plantuml | OutputFormat::Sqlite | OutputFormat::Cypher | OutputFormat::Ndjson => {
+            Err(format!("--format {} only applies to a dependency graph", format.to_possible_value().unwrap().get_name()))
+        }
+    }
+}
+
+/// Every edge as a `src,dst,kind` row under a header, NEEDED edges first, then the guessed `dlopen` ones and
+/// the `runtime` ones of a trace.
+/// Like `edges`, `src` must be loaded before `dst`.
+pub fn csv_edges(result: &TopoSortResult) -> String {
+    let mut csv = String::from("src,dst,kind\n");
+    for edge in result.edges.iter().chain(&result.dlopen_edges).chain(&result.runtime_edges) {
+        csv.push_str(&format!("{},{},{}\n", escape_csv(&edge.src), escape_csv(&edge.dst), edge.kind.as_str()));
+    }
+    csv
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::output::{csv_edges, serialize, OutputFormat};
+    use crate::query::tests::small_dag;
+    use crate::{Edge, EdgeKind, TopoSortResult};
+
+    #[test]
+    fn serialize_should_round_trip_in_every_serde_format() {
+        let result = small_dag();
+        let json = serde_json::to_value(&result).unwrap();
+        let yaml: TopoSortResult = serde_yaml::from_slice(&serialize(&result, OutputFormat::Yaml, true).unwrap()).unwrap();
+        assert_eq!(json, serde_json::to_value(&yaml).unwrap());
+        let toml: TopoSortResult = toml::from_str(&String::from_utf8(serialize(&result, OutputFormat::Toml, true).unwrap()).unwrap()).unwrap();
+        assert_eq!(json, serde_json::to_value(&toml).unwrap());
+        let msgpack: TopoSortResult = rmp_serde::from_slice(&serialize(&result, OutputFormat::Msgpack, true).unwrap()).unwrap();
+        assert_eq!(json, serde_json::to_value(&msgpack).unwrap());
+        assert!(serialize(&result, OutputFormat::CsvEdges, true).is_err());
+    }
+
+    #[test]
+    fn csv_edges_should_list_needed_then_dlopen_edges() {
+        let mut result = small_dag();
+        result.dlopen_edges.push(Edge { src: "libplugin,1.so".to_string(), dst: "A".to_string(), kind: EdgeKind::Dlopen, ..Default::default() });
+        let csv = csv_edges(&result);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!("src,dst,kind", lines[0]);
+        assert_eq!(result.edges.len() + 2, lines.len());
+        assert!(lines.contains(&"D,B,needed"));
+        assert_eq!("\"libplugin,1.so\",A,dlopen", lines[lines.len() - 1]);
+    }
+}
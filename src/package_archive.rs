@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::compression::decompressed;
+use crate::oci_image::inside;
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const AR_HEADER_SIZE: usize = 60;
+const RPM_MAGIC: &[u8] = &[0xed, 0xab, 0xee, 0xdb];
+const RPM_LEAD_SIZE: u64 = 96;
+const RPM_HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8];
+const CPIO_NEWC_MAGICS: [&[u8]; 2] = [b"070701", b"070702"];
+const CPIO_HEADER_SIZE: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The path of the member `member` of an archive extracted to `root`, e.g. `/usr/bin/app`. `None` when it is not a
+/// file, or it or the file it links to is outside of `root`, through `..` or a link.
+pub fn member(root: &Path, member: &Path) -> Option<PathBuf> {
+    let relative = member.strip_prefix("/").unwrap_or(member);
+    let file = std::fs::canonicalize(inside(root, relative)?).ok()?;
+    match file.starts_with(std::fs::canonicalize(root).ok()?) && file.is_file() {
+        true => Some(root.join(relative)),
+        false => None,
+    }
+}
+
+/// Extracts the files of the Debian or RPM package `package` into the directory `root`, as installing it would lay
+/// them out but without running its scripts. Returns the number of files and links extracted.
+pub fn extract(package: &Path, root: &Path) -> io::Result<usize> {
+    std::fs::create_dir_all(root)?;
+    let mut file = File::open(package)?;
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic)?;
+    if magic.starts_with(AR_MAGIC) {
+        extract_deb(file, root)
+    } else if magic.starts_with(RPM_MAGIC) {
+        extract_rpm(file, root)
+    } else {
+        Err(invalid(format!("{} is neither a .deb nor an .rpm package", package.display())))
+    }
+}
+
+/// The `data.tar` member of the ar archive `file`, past its magic
+fn extract_deb(mut file: File, root: &Path) -> io::Result<usize> {
+    let mut header = [0u8; AR_HEADER_SIZE];
+    loop {
+        file.read_exact(&mut header).map_err(|_| invalid("the package has no data.tar member".to_string()))?;
+        let name = String::from_utf8_lossy(&header[..16]).trim_end().trim_end_matches('/').to_string();
+        let size: u64 = String::from_utf8_lossy(&header[48..58]).trim().parse()
+            .map_err(|_| invalid(format!("the size of the member {} is not a number", name)))?;
+        if name.starts_with("data.tar") {
+            let mut archive = tar::Archive::new(decompressed((&mut file).take(size))?);
+            let mut extracted = 0;
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let is_dir = entry.header().entry_type().is_dir();
+                if entry.unpack_in(root)? && !is_dir {
+                    extracted += 1;
+                }
+            }
+            return Ok(extracted);
+        }
+        // members are aligned to 2 bytes
+        file.seek(SeekFrom::Current((size + size % 2) as i64))?;
+    }
+}
+
+/// Skips the lead and both headers of the RPM `file`, past its magic, and extracts the cpio payload that follows
+fn extract_rpm(mut file: File, root: &Path) -> io::Result<usize> {
+    file.seek(SeekFrom::Start(RPM_LEAD_SIZE))?;
+    for header in ["signature", "main"] {
+        let mut intro = [0u8; 16];
+        file.read_exact(&mut intro)?;
+        if !intro.starts_with(RPM_HEADER_MAGIC) {
+            return Err(invalid(format!("the {} header of the package is malformed", header)));
+        }
+        let index_entries = u32::from_be_bytes(intro[8..12].try_into().unwrap()) as u64;
+        let data_size = u32::from_be_bytes(intro[12..16].try_into().unwrap()) as u64;
+        let mut size = index_entries * 16 + data_size;
+        // the signature header is padded to 8 bytes
+        if header == "signature" {
+            size = size.next_multiple_of(8);
+        }
+        file.seek(SeekFrom::Current(size as i64))?;
+    }
+    extract_cpio(decompressed(file)?, root)
+}
+
+/// Creates the directories of the archive path `dir` in `root` that are missing, without following links out of it
+fn create_dirs(root: &Path, dir: &Path) -> io::Result<()> {
+    let mut current = PathBuf::new();
+    for component in dir.components() {
+        current.push(component);
+        let path = inside(root, &current).ok_or_else(|| invalid(format!("{} is outside of the package root", dir.display())))?;
+        if !path.exists() {
+            std::fs::create_dir(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// `path` in `root` with its missing parent directories created, `None` when it is outside of `root`
+fn prepare(root: &Path, path: &Path) -> io::Result<Option<PathBuf>> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if create_dirs(root, parent).is_err() {
+            return Ok(None);
+        }
+    }
+    Ok(inside(root, path))
+}
+
+/// Skips the padding of the archive to the next multiple of 4 bytes
+fn align(reader: &mut impl Read, offset: &mut usize) -> io::Result<()> {
+    let padding = offset.next_multiple_of(4) - *offset;
+    io::copy(&mut reader.take(padding as u64), &mut io::sink())?;
+    *offset += padding;
+    Ok(())
+}
+
+fn hex_field(header: &[u8], index: usize) -> io::Result<u32> {
+    let field = &header[6 + index * 8..14 + index * 8];
+    u32::from_str_radix(&String::from_utf8_lossy(field), 16).map_err(|_| invalid("malformed cpio header".to_string()))
+}
+
+/// Extracts a cpio archive in the `newc` format of RPM payloads. Hard links share an inode number and only the last
+/// one carries the content, the others are linked to it once it is written. An empty file has no entry with content,
+/// its links are created at the end of the archive.
+fn extract_cpio(mut reader: impl Read, root: &Path) -> io::Result<usize> {
+    let mut offset = 0usize;
+    // inode -> the mode and the paths of the links waiting for the entry with the content
+    let mut pending_links: HashMap<u32, (u32, Vec<PathBuf>)> = HashMap::new();
+    let mut extracted = 0;
+    loop {
+        let mut header = [0u8; CPIO_HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        if !CPIO_NEWC_MAGICS.iter().any(|magic| header.starts_with(magic)) {
+            return Err(invalid("the payload is not a cpio archive in the newc format".to_string()));
+        }
+        let (inode, mode, links, size, name_size) = (hex_field(&header, 0)?, hex_field(&header, 1)?, hex_field(&header, 4)?,
+            hex_field(&header, 6)? as usize, hex_field(&header, 11)? as usize);
+        let mut name = vec![0u8; name_size];
+        reader.read_exact(&mut name)?;
+        offset += CPIO_HEADER_SIZE + name_size;
+        align(&mut reader, &mut offset)?;
+        let name = String::from_utf8_lossy(&name).trim_end_matches('\0').to_string();
+        if name == CPIO_TRAILER {
+            for (_, (mode, links)) in pending_links {
+                let mut links = links.into_iter();
+                if let Some(file) = links.next() {
+                    std::fs::write(&file, b"")?;
+                    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(mode & 0o777))?;
+                    extracted += 1;
+                    for link in links {
+                        std::fs::hard_link(&file, link)?;
+                        extracted += 1;
+                    }
+                }
+            }
+            return Ok(extracted);
+        }
+        let mut content = vec![0u8; size];
+        reader.read_exact(&mut content)?;
+        offset += size;
+        align(&mut reader, &mut offset)?;
+        let path = Path::new(name.trim_start_matches("./").trim_start_matches('/'));
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        if mode & S_IFMT == S_IFDIR {
+            let _ = create_dirs(root, path);
+            continue;
+        }
+        let target = match prepare(root, path)? {
+            Some(target) => target,
+            None => continue,
+        };
+        if std::fs::symlink_metadata(&target).is_ok() {
+            std::fs::remove_file(&target)?;
+        }
+        match mode & S_IFMT {
+            S_IFLNK => std::os::unix::fs::symlink(String::from_utf8_lossy(&content).as_ref(), &target)?,
+            S_IFREG if size == 0 && links > 1 => {
+                pending_links.entry(inode).or_insert_with(|| (mode, Vec::new())).1.push(target);
+                continue;
+            }
+            S_IFREG => {
+                std::fs::write(&target, &content)?;
+                std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode & 0o777))?;
+                for link in pending_links.remove(&inode).map(|(_, links)| links).unwrap_or_default() {
+                    std::fs::hard_link(&target, link)?;
+                    extracted += 1;
+                }
+            }
+            // devices and fifos are of no use to the analysis
+            _ => continue,
+        }
+        extracted += 1;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    use crate::package_archive::{extract, extract_cpio, member};
+
+    /// A `newc` cpio entry, padded
+    fn cpio_entry(name: &str, inode: u32, mode: u32, links: u32, content: &[u8]) -> Vec<u8> {
+        let mut entry = format!("070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+            inode, mode, 0, 0, links, 0, content.len(), 0, 0, 0, 0, name.len() + 1, 0).into_bytes();
+        entry.extend(name.as_bytes());
+        entry.push(0);
+        entry.resize(entry.len().next_multiple_of(4), 0);
+        entry.extend(content);
+        entry.resize(entry.len().next_multiple_of(4), 0);
+        entry
+    }
+
+    /// An RPM header structure with `entries` index entries and `data` bytes of store
+    fn rpm_header(entries: u32, data: u32) -> Vec<u8> {
+        let mut header = vec![0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+        header.extend(entries.to_be_bytes());
+        header.extend(data.to_be_bytes());
+        header.extend(vec![0u8; (entries * 16 + data) as usize]);
+        header
+    }
+
+    #[test]
+    fn extract_should_lay_out_the_files_of_deb_and_rpm_packages() {
+        let dir = std::env::temp_dir().join(format!("lddtopo-package-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut data = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o755);
+        data.append_data(&mut header, "./usr/bin/app", &b"\x7fELF"[..]).unwrap();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(&data.into_inner().unwrap()).unwrap();
+        let data = gzip.finish().unwrap();
+        let mut deb = b"!<arch>\n".to_vec();
+        for (name, content) in [("debian-binary", &b"2.0\n"[..]), ("control.tar.gz", &b"c"[..]), ("data.tar.gz", &data[..])] {
+            deb.extend(format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", name, 0, 0, 0, 100644, content.len()).into_bytes());
+            deb.extend(content);
+            if content.len() % 2 == 1 {
+                deb.push(b'\n');
+            }
+        }
+        std::fs::write(dir.join("app.deb"), deb).unwrap();
+
+        let mut cpio = Vec::new();
+        cpio.extend(cpio_entry("./usr/lib64/libapp.so.1.0", 7, 0o100755, 2, b""));
+        cpio.extend(cpio_entry("./usr/lib64/libapp.so.1", 8, 0o120777, 1, b"libapp.so.1.0"));
+        cpio.extend(cpio_entry("./usr/lib64/libapp-compat.so.1.0", 7, 0o100755, 2, b"lib"));
+        cpio.extend(cpio_entry("../escape", 9, 0o100644, 1, b"x"));
+        cpio.extend(cpio_entry("TRAILER!!!", 0, 0, 1, b""));
+        let mut payload = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        payload.write_all(&cpio).unwrap();
+        let mut rpm = vec![0xed, 0xab, 0xee, 0xdb];
+        rpm.resize(96, 0);
+        // 16 + 16 + 3 bytes, padded to 40
+        rpm.extend(rpm_header(1, 3));
+        rpm.extend([0; 5]);
+        rpm.extend(rpm_header(2, 10));
+        rpm.extend(payload.finish().unwrap());
+        std::fs::write(dir.join("app.rpm"), rpm).unwrap();
+
+        let deb = extract(&dir.join("app.deb"), &dir.join("deb"));
+        let rpm = extract(&dir.join("app.rpm"), &dir.join("rpm"));
+        let not_a_package = extract(&dir.join("app.deb").with_extension("none"), &dir.join("none")).is_err();
+        let read = |path: &str| std::fs::read(dir.join(path)).ok();
+        let (app, libapp, compat, escape) = (read("deb/usr/bin/app"), read("rpm/usr/lib64/libapp.so.1"),
+            read("rpm/usr/lib64/libapp-compat.so.1.0"), Path::new(&dir).parent().unwrap().join("escape").exists());
+        std::os::unix::fs::symlink("/etc/hostname", dir.join("rpm/usr/lib64/libhost.so")).unwrap();
+        let members = ["/usr/lib64/libapp.so.1", "usr/lib64", "/usr/lib64/libmissing.so", "../app.rpm", "/usr/lib64/libhost.so"]
+            .map(|path| member(&dir.join("rpm"), Path::new(path)));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(1, deb.unwrap());
+        assert_eq!(Some(b"\x7fELF".to_vec()), app);
+        assert_eq!(3, rpm.unwrap());
+        assert_eq!(Some(b"lib".to_vec()), libapp);
+        assert_eq!(Some(b"lib".to_vec()), compat);
+        assert!(!escape);
+        assert!(not_a_package);
+        assert_eq!([Some(dir.join("rpm/usr/lib64/libapp.so.1")), None, None, None, None], members);
+    }
+
+    #[test]
+    fn extract_cpio_should_create_empty_hard_linked_files() {
+        let dir = std::env::temp_dir().join(format!("lddtopo-cpio-links-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut cpio = Vec::new();
+        // no entry of the inode 5 carries content
+        cpio.extend(cpio_entry("./usr/share/app/empty", 5, 0o100644, 2, b""));
+        cpio.extend(cpio_entry("./usr/share/app/empty-link", 5, 0o100644, 2, b""));
+        cpio.extend(cpio_entry("TRAILER!!!", 0, 0, 1, b""));
+        let extracted = extract_cpio(&cpio[..], &dir);
+        let metadata = ["usr/share/app/empty", "usr/share/app/empty-link"].map(|path| std::fs::metadata(dir.join(path)).ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(2, extracted.unwrap());
+        let [empty, link] = metadata.map(Option::unwrap);
+        assert_eq!(0, empty.len());
+        assert_eq!(empty.ino(), link.ino());
+        assert_eq!(2, empty.nlink());
+        assert_eq!(0o644, empty.mode() & 0o777);
+    }
+}
@@ -0,0 +1,333 @@
+use regex::Regex;
+
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{EdgeKind, TopoSortResult};
+
+/// Where the package of a library was found, see `--resolve-packages` and `--osv`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageSource {
+    /// The dpkg database, i.e. Debian and Ubuntu
+    Dpkg,
+    /// `rpm -qf`, i.e. Red Hat, SUSE and their derivatives
+    Rpm,
+    /// A version string in the library itself, e.g. `libcurl/8.5.0`, the name is a guess from the library name
+    Embedded,
+}
+
+/// The installed package a library belongs to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// The source package it was built from, which is what security advisories of distributions name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_name: Option<String>,
+    pub source: PackageSource,
+}
+
+impl Package {
+    /// The name advisories use for the package
+    pub fn advisory_name(&self) -> &str {
+        self.source_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DpkgPackage {
+    version: String,
+    source_name: Option<String>,
+}
+
+/// The installed packages of a dpkg database and the files they own
+#[derive(Debug, Default)]
+pub struct DpkgDatabase {
+    packages: HashMap<String, DpkgPackage>,
+    owners: HashMap<String, String>,
+}
+
+/// The installed packages of a dpkg `status` file by name, with their version and source package
+fn parse_status(status: &str) -> HashMap<String, DpkgPackage> {
+    let mut packages = HashMap::new();
+    for paragraph in status.split("\n\n") {
+        let field = |name: &str| paragraph.lines()
+            .find_map(|line| line.strip_prefix(name).and_then(|rest| rest.strip_prefix(':')))
+            .map(str::trim);
+        let installed = field("Status").is_some_and(|status| status.ends_with(" installed"));
+        if let (Some(name), Some(version), true) = (field("Package"), field("Version"), installed) {
+            // `Source: openssl (3.0.11-1)` when the source version differs from the binary one
+            let source_name = field("Source").and_then(|source| source.split_whitespace().next()).map(String::from);
+            packages.insert(name.to_string(), DpkgPackage { version: version.to_string(), source_name });
+        }
+    }
+    packages
+}
+
+impl DpkgDatabase {
+    /// The dpkg database under `root`, `None` when there is none
+    pub fn load(root: &Path) -> Option<DpkgDatabase> {
+        let dpkg_dir = root.join("var/lib/dpkg");
+        let packages = parse_status(&std::fs::read_to_string(dpkg_dir.join("status")).ok()?);
+        let mut owners = HashMap::new();
+        for entry in std::fs::read_dir(dpkg_dir.join("info")).ok()?.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let package = match file_name.strip_suffix(".list") {
+                // `libc6:amd64.list` for multiarch packages
+                Some(package) => package.split(':').next().unwrap_or(package).to_string(),
+                None => continue,
+            };
+            if let Ok(list) = std::fs::read_to_string(entry.path()) {
+                owners.extend(list.lines().map(|file| (file.to_string(), package.clone())));
+            }
+        }
+        Some(DpkgDatabase { packages, owners })
+    }
+
+    /// The package owning the file at `path` relative to the root. With merged /usr, `/lib/libz.so.1` is the
+    /// `/usr/lib/libz.so.1` of the package and the other way around.
+    pub fn owner(&self, path: &str) -> Option<Package> {
+        let alternative = path.strip_prefix("/usr").map(String::from).unwrap_or_else(|| format!("/usr{}", path));
+        let name = self.owners.get(path).or_else(|| self.owners.get(&alternative))?;
+        let package = self.packages.get(name)?;
+        Some(Package { name: name.clone(), version: package.version.clone(), source_name: package.source_name.clone(), source: PackageSource::Dpkg })
+    }
+}
+
+/// The source package name of a `SOURCERPM` such as `openssl-3.0.7-27.el9.src.rpm`
+fn rpm_source_name(source_rpm: &str) -> Option<String> {
+    let stem = source_rpm.strip_suffix(".src.rpm")?;
+    let (rest, _release) = stem.rsplit_once('-')?;
+    let (name, _version) = rest.rsplit_once('-')?;
+    Some(name.to_string())
+}
+
+/// The package owning `path` under `root` according to `rpm -qf`, `None` without rpm or when no package owns it
+pub fn rpm_owner(root: &Path, path: &str) -> Option<Package> {
+    let output = Command::new("rpm")
+        .arg("--root").arg(root)
+        .args(["-qf", "--queryformat", "%{NAME}\\t%|EPOCH?{%{EPOCH}:}:{}|%{VERSION}-%{RELEASE}\\t%{SOURCERPM}\\n", path])
+        .output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.lines().next()?.split('\t');
+    let (name, version) = (fields.next()?, fields.next()?);
+    Some(Package {
+        name: name.to_string(),
+        version: version.to_string(),
+        source_name: fields.next().and_then(rpm_source_name),
+        source: PackageSource::Rpm,
+    })
+}
+
+/// The version a library states in its own strings, e.g. `8.5.0` from `libcurl/8.5.0` in `libcurl.so.4`. The
+/// package is named after the library without `lib` and the `.so` suffix, so this is a guess for libraries that
+/// no package manager knows.
+pub fn embedded_version(library: &str, bytes: &[u8]) -> Option<Package> {
+    let stem = library.split(".so").next().unwrap_or(library);
+    let name = stem.strip_prefix("lib").filter(|name| !name.is_empty()).unwrap_or(stem);
+    let pattern = Regex::new(&format!(r"(?i)(?:^|[^a-z0-9])(?:lib)?{}[ /-]v?(\d+(?:\.\d+)+[a-z]?)\b", regex::escape(name))).ok()?;
+    let strings = bytes.split(|b| !(0x20..0x7f).contains(b)).filter(|s| s.len() >= 4);
+    let version = strings.filter_map(|s| std::str::from_utf8(s).ok())
+        .find_map(|s| pattern.captures(s).map(|captures| captures[1].to_string()))?;
+    Some(Package { name: name.to_string(), version, source_name: None, source: PackageSource::Embedded })
+}
+
+/// Finds the package of every library: dpkg and rpm first, the version strings of the file last
+#[derive(Debug)]
+pub struct PackageResolver {
+    root: PathBuf,
+    dpkg: Option<DpkgDatabase>,
+}
+
+impl PackageResolver {
+    pub fn new(root: &Path) -> PackageResolver {
+        PackageResolver { root: root.to_path_buf(), dpkg: DpkgDatabase::load(root) }
+    }
+
+    /// The distribution package owning the file at `path`, which is under the root: from dpkg when the root has a
+    /// dpkg database, from rpm otherwise
+    pub fn owner(&self, path: &str) -> Option<Package> {
+        let in_root = Path::new(path).strip_prefix(&self.root).map(|p| format!("/{}", p.display())).unwrap_or_else(|_| path.to_string());
+        // the package owns the real file, not necessarily the soname link
+        let real = std::fs::canonicalize(path).ok()
+            .and_then(|real| real.strip_prefix(std::fs::canonicalize(&self.root).ok()?).ok().map(|p| format!("/{}", p.display())));
+        match &self.dpkg {
+            Some(dpkg) => [Some(in_root.as_str()), real.as_deref()].into_iter().flatten().find_map(|candidate| dpkg.owner(candidate)),
+            None => rpm_owner(&self.root, &in_root),
+        }
+    }
+
+    /// The `owner` of `library` resolved to `path`, or the version string in the file when no package owns it
+    pub fn package_of(&self, library: &str, path: &str) -> Option<Package> {
+        self.owner(path).or_else(|| embedded_version(library, &std::fs::read(path).ok()?))
+    }
+}
+
+/// The packages every package of `result` depends on, from the NEEDED edges between libraries of different
+/// packages. Libraries without a package are left out.
+pub fn package_dependencies(result: &TopoSortResult) -> BTreeMap<String, Vec<String>> {
+    let package_of = |name: &str| result.topo_sorted_libs.iter()
+        .find(|lib| lib.name == name)
+        .and_then(|lib| lib.package.as_ref())
+        .map(|package| package.name.clone());
+    let mut dependencies: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for lib in &result.topo_sorted_libs {
+        if let Some(package) = &lib.package {
+            dependencies.entry(package.name.clone()).or_default();
+        }
+    }
+    for edge in result.edges.iter().filter(|edge| edge.kind == EdgeKind::Needed) {
+        if let (Some(dependency), Some(dependent)) = (package_of(&edge.src), package_of(&edge.dst)) {
+            if dependency != dependent {
+                dependencies.entry(dependent).or_default().insert(dependency);
+            }
+        }
+    }
+    dependencies.into_iter().map(|(package, dependencies)| (package, dependencies.into_iter().collect())).collect()
+}
+
+/// Orders Debian package versions like `dpkg --compare-versions`: epoch, then upstream version and revision, each
+/// compared by alternating non-digit and digit runs, where `~` sorts before everything, even the end of the version.
+/// RPM versions of the form `version-release` order the same way in all but exotic cases.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (u64, &str, &str) {
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => (epoch.parse().unwrap_or(0), rest),
+            _ => (0, version),
+        };
+        let (upstream, revision) = rest.rsplit_once('-').unwrap_or((rest, ""));
+        (epoch, upstream, revision)
+    }
+    fn order(c: Option<char>) -> i32 {
+        match c {
+            Some('~') => -1,
+            None => 0,
+            Some(c) if c.is_ascii_alphabetic() => c as i32,
+            Some(c) => c as i32 + 256,
+        }
+    }
+    fn compare_part(mut a: &str, mut b: &str) -> Ordering {
+        while !a.is_empty() || !b.is_empty() {
+            let a_text = a.find(|c: char| c.is_ascii_digit()).unwrap_or(a.len());
+            let b_text = b.find(|c: char| c.is_ascii_digit()).unwrap_or(b.len());
+            let (mut a_chars, mut b_chars) = (a[..a_text].chars(), b[..b_text].chars());
+            loop {
+                let (x, y) = (a_chars.next(), b_chars.next());
+                if x.is_none() && y.is_none() {
+                    break;
+                }
+                match order(x).cmp(&order(y)) {
+                    Ordering::Equal => {}
+                    other => return other,
+                }
+            }
+            a = &a[a_text..];
+            b = &b[b_text..];
+            let a_digits = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let b_digits = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let number = |digits: &str| digits.trim_start_matches('0').to_string();
+            let (x, y) = (number(&a[..a_digits]), number(&b[..b_digits]));
+            match x.len().cmp(&y.len()).then_with(|| x.cmp(&y)) {
+                Ordering::Equal => {}
+                other => return other,
+            }
+            a = &a[a_digits..];
+            b = &b[b_digits..];
+        }
+        Ordering::Equal
+    }
+    let (a_epoch, a_upstream, a_revision) = split(a);
+    let (b_epoch, b_upstream, b_revision) = split(b);
+    a_epoch.cmp(&b_epoch)
+        .then_with(|| compare_part(a_upstream, b_upstream))
+        .then_with(|| compare_part(a_revision, b_revision))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::packages::{compare_versions, embedded_version, package_dependencies, parse_status, rpm_source_name, DpkgDatabase, DpkgPackage, Package, PackageSource};
+    use crate::query::tests::small_dag;
+    use std::cmp::Ordering;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn compare_versions_should_follow_dpkg() {
+        assert_eq!(Ordering::Less, compare_versions("1.2.13-1", "1.2.13-2"));
+        assert_eq!(Ordering::Less, compare_versions("1.2.9", "1.2.13"));
+        assert_eq!(Ordering::Less, compare_versions("3.0.11~rc1-1", "3.0.11-1"));
+        assert_eq!(Ordering::Greater, compare_versions("1:0.9", "2.0"));
+        assert_eq!(Ordering::Greater, compare_versions("1.0a", "1.0"));
+        assert_eq!(Ordering::Less, compare_versions("2.36-9+deb12u3", "2.36-9+deb12u4"));
+        assert_eq!(Ordering::Equal, compare_versions("1.02", "1.2"));
+        assert_eq!(Ordering::Less, compare_versions("3.0.7-25.el9", "3.0.7-27.el9"));
+    }
+
+    #[test]
+    fn dpkg_database_should_find_the_owner_across_merged_usr() {
+        let status = "\
+Package: libssl3
+Status: install ok installed
+Architecture: amd64
+Source: openssl (3.0.11-1)
+Version: 3.0.11-1~deb12u2
+
+Package: zlib1g
+Status: install ok installed
+Version: 1:1.2.13.dfsg-1
+
+Package: libold
+Status: deinstall ok config-files
+Version: 1.0
+";
+        let packages = parse_status(status);
+        assert_eq!(Some(&DpkgPackage { version: "3.0.11-1~deb12u2".to_string(), source_name: Some("openssl".to_string()) }), packages.get("libssl3"));
+        assert!(!packages.contains_key("libold"));
+
+        let owners = HashMap::from([
+            ("/usr/lib/x86_64-linux-gnu/libssl.so.3".to_string(), "libssl3".to_string()),
+            ("/lib/x86_64-linux-gnu/libz.so.1".to_string(), "zlib1g".to_string()),
+        ]);
+        let dpkg = DpkgDatabase { packages, owners };
+        let ssl = dpkg.owner("/lib/x86_64-linux-gnu/libssl.so.3").unwrap();
+        assert_eq!(("libssl3", "openssl", PackageSource::Dpkg), (ssl.name.as_str(), ssl.advisory_name(), ssl.source));
+        assert_eq!("zlib1g", dpkg.owner("/usr/lib/x86_64-linux-gnu/libz.so.1").unwrap().advisory_name());
+        assert!(dpkg.owner("/opt/lib/libfoo.so").is_none());
+    }
+
+    #[test]
+    fn package_dependencies_should_lift_the_edges_to_packages() {
+        let mut result = small_dag();
+        for lib in &mut result.topo_sorted_libs {
+            let name = match lib.name.as_str() {
+                "A" => "app",
+                "B" | "C" => "libbc",
+                "D" | "E" => "libde",
+                _ => continue,
+            };
+            lib.package = Some(Package { name: name.to_string(), version: "1.0-1".to_string(), source_name: None, source: PackageSource::Dpkg });
+        }
+        let dependencies = package_dependencies(&result);
+        assert_eq!(BTreeMap::from([
+            ("app".to_string(), vec!["libbc".to_string()]),
+            ("libbc".to_string(), vec!["libde".to_string()]),
+            ("libde".to_string(), vec![]),
+        ]), dependencies);
+    }
+
+    #[test]
+    fn embedded_version_should_find_the_version_string() {
+        let bytes = b"\x7fELF\0\0libcurl/8.5.0 OpenSSL/3.0.11\0\x01CURL_OPENSSL_3\0";
+        let curl = embedded_version("libcurl.so.4", bytes).unwrap();
+        assert_eq!(("curl", "8.5.0", PackageSource::Embedded), (curl.name.as_str(), curl.version.as_str(), curl.source));
+        assert!(embedded_version("libz.so.1", bytes).is_none());
+        assert_eq!(Some("openssl".to_string()), rpm_source_name("openssl-3.0.7-27.el9.src.rpm"));
+    }
+}
@@ -0,0 +1,57 @@
+/// Upper bound on the number of path expansions shared by all path-enumerating operations of a run,
+/// so that graphs with high fan-out and deep chains cannot make them hang or run out of memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathBudget {
+    limit: usize,
+    used: usize,
+}
+
+pub const DEFAULT_PATH_BUDGET: usize = 1_000_000;
+
+impl PathBudget {
+    pub fn new(limit: usize) -> PathBudget {
+        PathBudget { limit, used: 0 }
+    }
+
+    /// Accounts for one expansion, `false` once the budget is exhausted
+    pub fn spend(&mut self) -> bool {
+        if self.used >= self.limit {
+            return false;
+        }
+        self.used += 1;
+        true
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn exceeded_message(&self) -> String {
+        format!("path budget exceeded; results truncated after {} of {} path expansions, raise --path-budget to explore further",
+            self.used, self.limit)
+    }
+}
+
+impl Default for PathBudget {
+    fn default() -> Self {
+        PathBudget::new(DEFAULT_PATH_BUDGET)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::path_budget::PathBudget;
+
+    #[test]
+    fn spend_should_stop_at_limit() {
+        let mut budget = PathBudget::new(2);
+        assert!(budget.spend());
+        assert!(budget.spend());
+        assert!(!budget.spend());
+        assert_eq!(2, budget.used());
+    }
+}
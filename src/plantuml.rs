@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::dot::clusters_by_dir;
+use crate::TopoSortResult;
+
+/// PlantUML has no escape for `"` inside a quoted name, so it becomes `'`; a line break is written as `\n`
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "'").replace('\n', "\\n"))
+}
+
+/// The NEEDED graph as a PlantUML component diagram. Every library is a component named `c<index>` in `vertices`
+/// order, and every NEEDED edge a dependency arrow from the dependent to the library it needs, the UML direction
+/// and the reverse of `edges`. With `group_by_dir` the components resolved from the same directory are put in
+/// a package named after it, see `dot::clusters_by_dir`. `label` renders the name of a component, e.g. `dot::text_label`.
+pub fn to_plantuml(result: &TopoSortResult, group_by_dir: bool, label: &dyn Fn(&str) -> String) -> String {
+    let index: HashMap<&str, usize> = result.vertices.iter().enumerate().map(|(idx, v)| (v.as_str(), idx)).collect();
+    let packages = if group_by_dir {
+        clusters_by_dir(&result.vertices, &result.topo_sorted_libs)
+    } else {
+        BTreeMap::new()
+    };
+    let packaged: HashSet<&str> = packages.values().flatten().map(String::as_str).collect();
+    let mut plantuml = String::from("@startuml\n");
+    for (idx, v) in result.vertices.iter().enumerate() {
+        if !packaged.contains(v.as_str()) {
+            writeln!(plantuml, "component {} as c{}", quoted(&label(v)), idx).unwrap();
+        }
+    }
+    for (dir, members) in &packages {
+        writeln!(plantuml, "package {} {{", quoted(dir)).unwrap();
+        for member in members {
+            writeln!(plantuml, "  component {} as c{}", quoted(&label(member)), index[member.as_str()]).unwrap();
+        }
+        plantuml.push_str("}\n");
+    }
+    for edge in &result.edges {
+        if let (Some(src), Some(dst)) = (index.get(edge.src.as_str()), index.get(edge.dst.as_str())) {
+            writeln!(plantuml, "c{} ..> c{}", dst, src).unwrap();
+        }
+    }
+    plantuml.push_str("@enduml\n");
+    plantuml
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::dot::text_label;
+    use crate::label_template::LabelTemplate;
+    use crate::plantuml::to_plantuml;
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn to_plantuml_should_draw_components_and_dependencies() {
+        let mut result = small_dag();
+        result.vertices[5] = "lib\"F\"".to_string();
+        result.edges.retain(|edge| edge.src != "F");
+        let plantuml = to_plantuml(&result, false, &str::to_string);
+        assert!(plantuml.starts_with("@startuml\ncomponent \"A\" as c0\n"));
+        assert!(plantuml.contains("component \"lib'F'\" as c5\n"));
+        assert!(!plantuml.contains("package"));
+        // D is needed by B
+        assert!(plantuml.contains("\nc1 ..> c3\n"));
+        assert_eq!(result.edges.len(), plantuml.matches(" ..> ").count());
+        assert!(plantuml.ends_with("@enduml\n"));
+    }
+
+    #[test]
+    fn to_plantuml_with_group_by_dir_should_put_components_in_packages() {
+        let mut result = small_dag();
+        result.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "E").for_each(|lib| lib.path = Some("/opt/lib/E".to_string()));
+        let plantuml = to_plantuml(&result, true, &str::to_string);
+        assert!(plantuml.contains("package \"/opt/lib\" {\n  component \"E\" as c4\n}\n"));
+        assert!(plantuml.contains("package \"/tmp\" {\n  component \"A\" as c0\n}\n"));
+        assert_eq!(result.vertices.len(), plantuml.matches("component ").count());
+    }
+
+    #[test]
+    fn to_plantuml_with_label_template_should_name_every_component() {
+        let result = small_dag();
+        let template: LabelTemplate = "{name}\n{depth}".parse().unwrap();
+        let plantuml = to_plantuml(&result, false, &text_label(&result, Some(&template)));
+        assert!(plantuml.contains("component \"A\\n0\" as c0\n"));
+        assert!(plantuml.contains("component \"E\\n3\" as c4\n"));
+    }
+}
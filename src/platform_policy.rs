@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::path::Path;
+
+use crate::symbol_versions::compare_versions;
+use crate::{EdgeKind, Lib, TopoSortResult};
+
+/// Libraries every manylinux platform guarantees, as listed by auditwheel
+const MANYLINUX_LIBRARIES: [&str; 19] = [
+    "libgcc_s.so.1", "libstdc++.so.6", "libm.so.6", "libdl.so.2", "librt.so.1", "libc.so.6", "libnsl.so.1",
+    "libutil.so.1", "libpthread.so.0", "libresolv.so.2", "libX11.so.6", "libXext.so.6", "libXrender.so.1",
+    "libICE.so.6", "libSM.so.6", "libGL.so.1", "libgobject-2.0.so.0", "libgthread-2.0.so.0", "libglib-2.0.so.0",
+];
+
+/// Libraries every musllinux platform guarantees, as listed by auditwheel
+const MUSLLINUX_LIBRARIES: [&str; 3] = ["libc.so", "libgcc_s.so.1", "libstdc++.so.6"];
+
+/// An auditwheel-style platform policy, see `check --policy manylinux_2_28`
+#[derive(Debug, PartialEq, Eq)]
+pub struct PlatformPolicy {
+    pub name: &'static str,
+    /// Legacy names of the policy, e.g. `manylinux2014` for `manylinux_2_17`
+    pub aliases: &'static [&'static str],
+    /// The external libraries a binary may need
+    pub libraries: &'static [&'static str],
+    /// The newest symbol version of each family a binary may need, for x86_64
+    pub max_versions: &'static [&'static str],
+}
+
+pub const PLATFORM_POLICIES: [PlatformPolicy; 7] = [
+    PlatformPolicy {
+        name: "manylinux_2_5",
+        aliases: &["manylinux1"],
+        libraries: &MANYLINUX_LIBRARIES,
+        max_versions: &["GLIBC_2.5", "CXXABI_1.3.1", "GLIBCXX_3.4.9", "GCC_4.2.0"],
+    },
+    PlatformPolicy {
+        name: "manylinux_2_12",
+        aliases: &["manylinux2010"],
+        libraries: &MANYLINUX_LIBRARIES,
+        max_versions: &["GLIBC_2.12", "CXXABI_1.3.3", "GLIBCXX_3.4.13", "GCC_4.3.0"],
+    },
+    PlatformPolicy {
+        name: "manylinux_2_17",
+        aliases: &["manylinux2014"],
+        libraries: &MANYLINUX_LIBRARIES,
+        max_versions: &["GLIBC_2.17", "CXXABI_1.3.7", "GLIBCXX_3.4.19", "GCC_4.8.0"],
+    },
+    PlatformPolicy {
+        name: "manylinux_2_24",
+        aliases: &[],
+        libraries: &MANYLINUX_LIBRARIES,
+        max_versions: &["GLIBC_2.24", "CXXABI_1.3.10", "GLIBCXX_3.4.22", "GCC_7.0.0"],
+    },
+    PlatformPolicy {
+        name: "manylinux_2_28",
+        aliases: &[],
+        libraries: &MANYLINUX_LIBRARIES,
+        max_versions: &["GLIBC_2.28", "CXXABI_1.3.11", "GLIBCXX_3.4.25", "GCC_7.0.0"],
+    },
+    PlatformPolicy {
+        name: "musllinux_1_1",
+        aliases: &[],
+        libraries: &MUSLLINUX_LIBRARIES,
+        max_versions: &[],
+    },
+    PlatformPolicy {
+        name: "musllinux_1_2",
+        aliases: &[],
+        libraries: &MUSLLINUX_LIBRARIES,
+        max_versions: &[],
+    },
+];
+
+/// The built-in platform policy called `name`, or with `name` as an alias
+pub fn find(name: &str) -> Option<&'static PlatformPolicy> {
+    PLATFORM_POLICIES.iter().find(|policy| policy.name == name || policy.aliases.contains(&name))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PlatformViolation {
+    /// `library` needs `dependency`, which the platform does not guarantee
+    NotWhitelisted { library: String, dependency: String },
+    /// `library` needs the symbol version `version`, newer than `max_version`
+    VersionTooNew { library: String, version: String, max_version: &'static str },
+}
+
+impl PlatformViolation {
+    /// The library the violation has to be fixed in, e.g. by bundling it
+    pub fn library(&self) -> &str {
+        match self {
+            PlatformViolation::NotWhitelisted { dependency, .. } => dependency,
+            PlatformViolation::VersionTooNew { library, .. } => library,
+        }
+    }
+
+    pub fn describe(&self, policy: &PlatformPolicy) -> String {
+        match self {
+            PlatformViolation::NotWhitelisted { library, dependency } =>
+                format!("{} needs {}, which is not one of the libraries of {}", library, dependency, policy.name),
+            PlatformViolation::VersionTooNew { library, version, max_version } =>
+                format!("{} needs {}, {} allows at most {}", library, version, policy.name, max_version),
+        }
+    }
+}
+
+impl PlatformPolicy {
+    /// Every violation of the policy by `main_lib_name` and the external libraries it pulls in, like auditwheel
+    /// does: starting at the main library, each library that is not on the whitelist may only need whitelisted
+    /// libraries and symbol versions up to `max_versions`. The dependencies of whitelisted libraries are the
+    /// platform's business and are not checked, neither is the ELF interpreter. `needed_versions` gives the symbol
+    /// versions a library needs.
+    pub fn violations(&self, result: &TopoSortResult, main_lib_name: &str,
+                      needed_versions: impl Fn(&Lib) -> BTreeSet<String>) -> Vec<PlatformViolation> {
+        let interpreter = result.interpreter.as_deref().and_then(|path| Path::new(path).file_name()).and_then(|name| name.to_str());
+        let mut violations: Vec<PlatformViolation> = Vec::new();
+        let mut seen: HashSet<&str> = HashSet::from([main_lib_name]);
+        let mut queue: VecDeque<&str> = VecDeque::from([main_lib_name]);
+        while let Some(name) = queue.pop_front() {
+            if let Some(lib) = result.topo_sorted_libs.iter().find(|lib| lib.name == name) {
+                for version in needed_versions(lib) {
+                    let max_version = self.max_versions.iter()
+                        .find(|max| compare_versions(&version, max) == Some(Ordering::Greater));
+                    if let Some(max_version) = max_version {
+                        violations.push(PlatformViolation::VersionTooNew { library: name.to_string(), version, max_version });
+                    }
+                }
+            }
+            let mut dependencies: Vec<&str> = result.edges.iter()
+                .filter(|edge| edge.dst == name && edge.kind == EdgeKind::Needed)
+                .map(|edge| edge.src.as_str())
+                .collect();
+            dependencies.sort();
+            for dependency in dependencies {
+                if self.libraries.contains(&dependency) || Some(dependency) == interpreter {
+                    continue;
+                }
+                violations.push(PlatformViolation::NotWhitelisted { library: name.to_string(), dependency: dependency.to_string() });
+                if seen.insert(dependency) {
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::platform_policy::{find, PlatformViolation};
+    use crate::query::tests::small_dag;
+    use crate::Lib;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn find_should_know_names_and_aliases() {
+        assert_eq!("manylinux_2_17", find("manylinux2014").unwrap().name);
+        assert_eq!("manylinux_2_28", find("manylinux_2_28").unwrap().name);
+        assert!(find("musllinux_1_2").unwrap().max_versions.is_empty());
+        assert!(find("manylinux_3_0").is_none());
+    }
+
+    #[test]
+    fn violations_should_stop_at_whitelisted_libraries() {
+        let mut result = small_dag();
+        // B and C are whitelisted, so D and E are not examined, but A needs F itself
+        for edge in &mut result.edges {
+            for name in [&mut edge.src, &mut edge.dst] {
+                match name.as_str() {
+                    "B" => *name = "libc.so.6".to_string(),
+                    "C" => *name = "libm.so.6".to_string(),
+                    _ => {}
+                }
+            }
+        }
+        let policy = find("manylinux_2_17").unwrap();
+        let versions = |lib: &Lib| -> BTreeSet<String> {
+            match lib.name.as_str() {
+                "A" => ["GLIBC_2.2.5", "GLIBC_2.34", "GLIBCXX_3.4.19", "GLIBC_PRIVATE"].map(String::from).into(),
+                "F" => ["GLIBC_2.28".to_string()].into(),
+                _ => BTreeSet::new(),
+            }
+        };
+        let violations = policy.violations(&result, "A", versions);
+        assert_eq!(vec![
+            PlatformViolation::VersionTooNew { library: "A".to_string(), version: "GLIBC_2.34".to_string(), max_version: "GLIBC_2.17" },
+            PlatformViolation::NotWhitelisted { library: "A".to_string(), dependency: "F".to_string() },
+            PlatformViolation::VersionTooNew { library: "F".to_string(), version: "GLIBC_2.28".to_string(), max_version: "GLIBC_2.17" },
+        ], violations);
+        assert_eq!("F", violations[1].library());
+        assert_eq!("A needs F, which is not one of the libraries of manylinux_2_17", violations[1].describe(policy));
+        assert_eq!("A needs GLIBC_2.34, manylinux_2_17 allows at most GLIBC_2.17", violations[0].describe(policy));
+    }
+}
@@ -0,0 +1,101 @@
+use serde::Deserialize;
+
+use std::path::Path;
+
+use crate::filter::LibraryPattern;
+use crate::TopoSortResult;
+
+/// Rules every dependency of a result has to follow, see `check --policy`. Patterns are matched against the library
+/// name in the syntax of `--exclude`. An empty list does not restrict anything.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// A dependency has to match one of these
+    #[serde(default)]
+    pub allowed: Vec<LibraryPattern>,
+    /// A dependency must not match any of these
+    #[serde(default)]
+    pub denied: Vec<LibraryPattern>,
+    /// A resolved dependency has to live below one of these directories
+    #[serde(default)]
+    pub allowed_path_prefixes: Vec<String>,
+}
+
+/// The rule of a `Policy` a library breaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    NotAllowed,
+    Denied,
+    PathNotAllowed,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub library: String,
+    pub rule: Rule,
+    pub path: Option<String>,
+}
+
+impl Violation {
+    pub fn describe(&self) -> String {
+        match self.rule {
+            Rule::NotAllowed => format!("{} matches none of the allowed patterns", self.library),
+            Rule::Denied => format!("{} is denied", self.library),
+            Rule::PathNotAllowed => format!("{} resolves to {}, outside of the allowed path prefixes",
+                self.library, self.path.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+impl Policy {
+    /// Reads a policy from TOML when `path` ends with `.toml`, from YAML (or JSON) otherwise
+    pub fn read(path: &Path) -> Result<Policy, String> {
+        let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content).map_err(|err| err.to_string()),
+            _ => serde_yaml::from_str(&content).map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Every rule broken by a dependency of `result`, i.e. a library other than `main_lib_name`, in load order.
+    /// A library can break several rules; unresolved libraries have no path to check.
+    pub fn violations(&self, result: &TopoSortResult, main_lib_name: &str) -> Vec<Violation> {
+        let mut violations: Vec<Violation> = Vec::new();
+        for lib in result.topo_sorted_libs.iter().filter(|lib| lib.name != main_lib_name) {
+            let mut violation = |rule: Rule| violations.push(Violation { library: lib.name.clone(), rule, path: lib.path.clone() });
+            if !self.allowed.is_empty() && !self.allowed.iter().any(|pattern| pattern.matches(&lib.name, None)) {
+                violation(Rule::NotAllowed);
+            }
+            if self.denied.iter().any(|pattern| pattern.matches(&lib.name, None)) {
+                violation(Rule::Denied);
+            }
+            if let Some(path) = &lib.path {
+                if !self.allowed_path_prefixes.is_empty() && !self.allowed_path_prefixes.iter().any(|prefix| Path::new(path).starts_with(prefix)) {
+                    violation(Rule::PathNotAllowed);
+                }
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::policy::{Policy, Rule};
+    use crate::query::tests::small_dag;
+
+    #[test]
+    fn violations_should_report_every_broken_rule() {
+        let result = small_dag();
+        let policy: Policy = serde_yaml::from_str("allowed: ['[B-E]']\ndenied: [C]\nallowed_path_prefixes: [/usr/lib]\n").unwrap();
+        assert!(policy.violations(&result, "A").iter().all(|v| v.library != "A"));
+        let rules: Vec<(String, Rule)> = policy.violations(&result, "A").into_iter().map(|v| (v.library, v.rule)).collect();
+        assert_eq!(vec![("F".to_string(), Rule::NotAllowed), ("C".to_string(), Rule::Denied)], rules);
+
+        let policy: Policy = toml::from_str("allowed_path_prefixes = [\"/opt\"]").unwrap();
+        assert_eq!(5, policy.violations(&result, "A").len());
+        assert!(policy.violations(&result, "A").iter().all(|v| v.rule == Rule::PathNotAllowed));
+        assert!(serde_yaml::from_str::<Policy>("allowed: ['re:(']").is_err());
+        assert!(serde_yaml::from_str::<Policy>("allow: [libc.so.6]").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::TopoSortResult;
+
+/// Treats the unresolved libraries named in `sonames` as provided by the runtime: they stay in the graph,
+/// marked `assumed_provided`, but are no longer reported as unresolved. Returns the libraries it applied to.
+pub fn assume_provided(result: &mut TopoSortResult, sonames: &[String]) -> Vec<String> {
+    let assumed: Vec<String> = result.unresolved.iter().filter(|name| sonames.contains(name)).cloned().collect();
+    if assumed.is_empty() {
+        return assumed;
+    }
+    result.unresolved.retain(|name| !assumed.contains(name));
+    result.unresolved_chains.retain(|chain| !assumed.contains(&chain.missing));
+    result.deepest_unresolved_chain = result.unresolved_chains.iter()
+        .max_by(|a, b| a.chain.len().cmp(&b.chain.len()).then_with(|| b.missing.cmp(&a.missing)))
+        .cloned();
+    for lib in result.topo_sorted_libs.iter_mut().chain(result.library_map.values_mut()) {
+        if assumed.contains(&lib.name) {
+            lib.assumed_provided = true;
+        }
+    }
+    assumed
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::binary_kind::BinaryKind;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::provided::assume_provided;
+    use crate::{get_topologically_sorted_result, AnalysisOptions};
+
+    #[test]
+    fn assume_provided_should_only_clear_listed_unresolved_libraries() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("libGL.so.1")
+            .add_needed("libfoo.so")
+            .add_needed("libz.so.1")
+            .add_missing_library("libGL.so.1")
+            .add_library("libfoo.so", &["libbar.so"])
+            .add_missing_library("libbar.so")
+            .add_library("libz.so.1", &[])
+            .build();
+        let mut result = get_topologically_sorted_result("app", "/opt/app", BinaryKind::Executable, &dt, &AnalysisOptions::default()).unwrap();
+        let sonames = vec!["libGL.so.1".to_string(), "libz.so.1".to_string(), "libEGL.so.1".to_string()];
+        assert_eq!(vec!["libGL.so.1"], assume_provided(&mut result, &sonames));
+        assert_eq!(vec!["libbar.so"], result.unresolved);
+        assert_eq!(vec!["libbar.so"], result.unresolved_chains.iter().map(|c| c.missing.as_str()).collect::<Vec<_>>());
+        assert_eq!("libbar.so", result.deepest_unresolved_chain.unwrap().missing);
+        assert!(result.vertices.contains(&"libGL.so.1".to_string()));
+        assert!(result.library_map["libGL.so.1"].assumed_provided);
+        assert!(!result.library_map["libz.so.1"].assumed_provided);
+        assert!(result.topo_sorted_libs.iter().any(|lib| lib.name == "libGL.so.1" && lib.assumed_provided));
+    }
+}
@@ -0,0 +1,390 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+use crate::path_budget::PathBudget;
+use crate::TopoSortResult;
+
+/// Everything that (transitively) depends on a library, i.e. what has to be re-linked or at least
+/// re-tested when it changes
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Impact {
+    pub target: String,
+    pub count: usize,
+    /// The dependents in load order
+    pub dependents: Vec<String>,
+}
+
+/// Adjacency in the `is needed by` direction: library -> libraries that depend on it directly
+fn dependents_map(result: &TopoSortResult) -> HashMap<&str, Vec<&str>> {
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in &result.edges {
+        dependents.entry(edge.src.as_str()).or_default().push(edge.dst.as_str());
+    }
+    dependents
+}
+
+pub fn impact_of(result: &TopoSortResult, target: &str) -> Result<Impact, String> {
+    ensure_vertex(result, target)?;
+    let dependents = dependents_map(result);
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = vec![target];
+    while let Some(v) = stack.pop() {
+        for dependent in dependents.get(v).into_iter().flatten() {
+            if visited.insert(dependent) {
+                stack.push(dependent);
+            }
+        }
+    }
+    // A library in a cycle with the target would otherwise list the target as its own dependent
+    visited.remove(target);
+    let dependents: Vec<String> = result.topo_sorted_libs.iter()
+        .filter(|lib| visited.contains(lib.name.as_str()))
+        .map(|lib| lib.name.clone())
+        .collect();
+    Ok(Impact { target: target.to_string(), count: dependents.len(), dependents })
+}
+
+/// Adjacency in the `depends on` direction: library -> libraries it needs directly, sorted
+fn dependencies_map(result: &TopoSortResult) -> HashMap<&str, Vec<&str>> {
+    let mut dependencies: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in &result.edges {
+        dependencies.entry(edge.dst.as_str()).or_default().push(edge.src.as_str());
+    }
+    dependencies.values_mut().for_each(|deps| deps.sort());
+    dependencies
+}
+
+fn ensure_vertex(result: &TopoSortResult, name: &str) -> Result<(), String> {
+    if result.vertices.iter().any(|v| v == name) {
+        Ok(())
+    } else {
+        Err(format!("{} is not part of the dependency graph", name))
+    }
+}
+
+/// Libraries `target` needs directly
+pub fn direct_dependencies_of(result: &TopoSortResult, target: &str) -> Result<Vec<String>, String> {
+    ensure_vertex(result, target)?;
+    Ok(dependencies_map(result).get(target).into_iter().flatten().map(|d| d.to_string()).collect())
+}
+
+/// Libraries that need `target` directly, sorted
+pub fn direct_dependents_of(result: &TopoSortResult, target: &str) -> Result<Vec<String>, String> {
+    ensure_vertex(result, target)?;
+    let mut dependents: Vec<String> = dependents_map(result).get(target).into_iter().flatten().map(|d| d.to_string()).collect();
+    dependents.sort();
+    Ok(dependents)
+}
+
+/// Everything `target` (transitively) depends on, in load order
+pub fn dependencies_of(result: &TopoSortResult, target: &str) -> Result<Vec<String>, String> {
+    ensure_vertex(result, target)?;
+    let dependencies = dependencies_map(result);
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = vec![target];
+    while let Some(v) = stack.pop() {
+        for dependency in dependencies.get(v).into_iter().flatten() {
+            if visited.insert(dependency) {
+                stack.push(dependency);
+            }
+        }
+    }
+    visited.remove(target);
+    Ok(result.topo_sorted_libs.iter()
+        .filter(|lib| visited.contains(lib.name.as_str()))
+        .map(|lib| lib.name.clone())
+        .collect())
+}
+
+/// The shortest NEEDED chain from `from` to `to`, `[from, ..., to]`, or `None` if `from` does not depend on `to`
+pub fn path_between(result: &TopoSortResult, from: &str, to: &str) -> Result<Option<Vec<String>>, String> {
+    ensure_vertex(result, from)?;
+    ensure_vertex(result, to)?;
+    let dependencies = dependencies_map(result);
+    let mut parent: HashMap<&str, &str> = HashMap::new();
+    let mut visited: HashSet<&str> = HashSet::from([from]);
+    let mut queue: VecDeque<&str> = VecDeque::from([from]);
+    while let Some(v) = queue.pop_front() {
+        if v == to {
+            let mut chain = vec![v.to_string()];
+            let mut current = v;
+            while let Some(p) = parent.get(current) {
+                chain.push(p.to_string());
+                current = p;
+            }
+            chain.reverse();
+            return Ok(Some(chain));
+        }
+        for dependency in dependencies.get(v).into_iter().flatten() {
+            if visited.insert(dependency) {
+                parent.insert(dependency, v);
+                queue.push_back(dependency);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Every NEEDED chain from one library to another, as enumerated within the path budget
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct AllPaths {
+    pub from: String,
+    pub to: String,
+    /// The chains `[from, ..., to]` in lexicographic order of their library names
+    pub paths: Vec<Vec<String>>,
+    /// Whether the path budget ran out, in which case `paths` is incomplete
+    pub truncated: bool,
+    /// Number of path expansions spent on this enumeration
+    pub explored: usize,
+}
+
+/// Enumerates every chain from `from` to `to` in the `depends on` direction by depth-first search,
+/// spending one unit of `budget` per expansion and stopping once it is exhausted
+pub fn all_paths(result: &TopoSortResult, from: &str, to: &str, budget: &mut PathBudget) -> Result<AllPaths, String> {
+    ensure_vertex(result, from)?;
+    ensure_vertex(result, to)?;
+    let dependencies = dependencies_map(result);
+    // Only expand libraries that can still reach `to`, everything else is a dead end
+    let mut reaches_to: HashSet<&str> = HashSet::from([to]);
+    let dependents = dependents_map(result);
+    let mut stack: Vec<&str> = vec![to];
+    while let Some(v) = stack.pop() {
+        for dependent in dependents.get(v).into_iter().flatten() {
+            if reaches_to.insert(dependent) {
+                stack.push(dependent);
+            }
+        }
+    }
+
+    let used_before = budget.used();
+    let mut paths: Vec<Vec<String>> = Vec::new();
+    let mut truncated = false;
+    let mut path: Vec<&str> = vec![from];
+    // Each frame holds the dependencies of the library at the same position of `path` still to visit
+    let mut pending: Vec<Vec<&str>> = vec![next_steps(&dependencies, &reaches_to, from, to)];
+    while let Some(frame) = pending.last_mut() {
+        let next = match frame.pop() {
+            Some(next) => next,
+            None => {
+                pending.pop();
+                path.pop();
+                continue;
+            }
+        };
+        if path.contains(&next) {
+            continue;
+        }
+        if !budget.spend() {
+            truncated = true;
+            break;
+        }
+        path.push(next);
+        if next == to {
+            paths.push(path.iter().map(|v| v.to_string()).collect());
+            path.pop();
+        } else {
+            pending.push(next_steps(&dependencies, &reaches_to, next, to));
+        }
+    }
+    if from == to && paths.is_empty() {
+        paths.push(vec![from.to_string()]);
+    }
+    paths.sort();
+    Ok(AllPaths { from: from.to_string(), to: to.to_string(), paths, truncated, explored: budget.used() - used_before })
+}
+
+/// Dependencies of `v` worth expanding, reversed so that popping visits them in name order
+fn next_steps<'a>(dependencies: &HashMap<&'a str, Vec<&'a str>>, reaches_to: &HashSet<&str>, v: &str, to: &str) -> Vec<&'a str> {
+    if v == to {
+        return Vec::new();
+    }
+    let mut steps: Vec<&str> = dependencies.get(v).into_iter().flatten().copied().filter(|d| reaches_to.contains(d)).collect();
+    steps.reverse();
+    steps
+}
+
+/// Libraries resolved from one directory, in load order
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct DirGroup {
+    /// Parent directory of the resolved path, or `unresolved`
+    pub dir: String,
+    pub libs: Vec<String>,
+}
+
+pub const UNRESOLVED_GROUP: &str = "unresolved";
+
+/// The load order split by the directory each library was resolved from. A group is a run of libraries loaded one
+/// after the other from the same directory, so a directory the load order comes back to has a group per run.
+/// Unresolved libraries are grouped last.
+pub fn load_order_by_dir(result: &TopoSortResult) -> Vec<DirGroup> {
+    let mut groups: Vec<DirGroup> = Vec::new();
+    let mut unresolved: Vec<String> = Vec::new();
+    for lib in &result.topo_sorted_libs {
+        let dir = lib.path.as_deref()
+            .filter(|_| !result.unresolved.contains(&lib.name))
+            .and_then(|path| Path::new(path).parent())
+            .and_then(|dir| dir.to_str())
+            .filter(|dir| !dir.is_empty());
+        match dir {
+            None => unresolved.push(lib.name.clone()),
+            Some(dir) => match groups.last_mut().filter(|group| group.dir == dir) {
+                Some(group) => group.libs.push(lib.name.clone()),
+                None => groups.push(DirGroup { dir: dir.to_string(), libs: vec![lib.name.clone()] }),
+            },
+        }
+    }
+    if !unresolved.is_empty() {
+        groups.push(DirGroup { dir: UNRESOLVED_GROUP.to_string(), libs: unresolved });
+    }
+    groups
+}
+
+/// The library the result was computed for, the only one at depth 0. Results without depths, e.g.
+/// merged ones, have no single main library.
+pub fn main_library(result: &TopoSortResult) -> Option<&str> {
+    let mut roots = result.topo_sorted_libs.iter().filter(|lib| lib.min_depth == Some(0));
+    match (roots.next(), roots.next()) {
+        (Some(root), None) => Some(root.name.as_str()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::binary_kind::BinaryKind;
+    use crate::dep_tree_builder::DependencyTreeBuilder;
+    use crate::path_budget::PathBudget;
+    use crate::query::{all_paths, dependencies_of, direct_dependencies_of, direct_dependents_of, impact_of, load_order_by_dir, main_library, path_between, DirGroup};
+    use crate::{get_topologically_sorted_result, AnalysisOptions, TopoSortResult};
+
+    /// The DAG documented in `get_topologically_sorted_result`: A needs B, C and F, B and C need D, D needs E, E needs F
+    pub(crate) fn small_dag() -> TopoSortResult {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("C")
+            .add_needed("F")
+            .add_library("B", &["D"])
+            .add_library("C", &["D"])
+            .add_library("D", &["E"])
+            .add_library("E", &["F"])
+            .add_library("F", &[])
+            .build();
+        get_topologically_sorted_result("A", "/tmp/A", BinaryKind::SharedLibrary, &dt, &AnalysisOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn impact_of_should_list_transitive_dependents_in_load_order() {
+        let result = small_dag();
+        let impact = impact_of(&result, "D").unwrap();
+        assert_eq!(3, impact.count);
+        assert_eq!(vec!["B", "C", "A"], impact.dependents);
+
+        let impact = impact_of(&result, "F").unwrap();
+        assert_eq!(vec!["E", "D", "B", "C", "A"], impact.dependents);
+    }
+
+    #[test]
+    fn impact_of_when_target_is_main_library_should_be_empty() {
+        let impact = impact_of(&small_dag(), "A").unwrap();
+        assert_eq!(0, impact.count);
+        assert!(impact.dependents.is_empty());
+    }
+
+    #[test]
+    fn impact_of_when_target_is_absent_should_fail() {
+        assert!(impact_of(&small_dag(), "libnope.so").is_err());
+    }
+
+    #[test]
+    fn dependencies_of_should_list_transitive_dependencies_in_load_order() {
+        let result = small_dag();
+        assert_eq!(vec!["F", "E", "D"], dependencies_of(&result, "B").unwrap());
+        assert_eq!(vec!["B", "C", "F"], direct_dependencies_of(&result, "A").unwrap());
+        assert_eq!(vec!["B", "C"], direct_dependents_of(&result, "D").unwrap());
+        assert!(dependencies_of(&result, "F").unwrap().is_empty());
+    }
+
+    #[test]
+    fn path_between_should_return_shortest_chain() {
+        let result = small_dag();
+        assert_eq!(Some(vec!["A".to_string(), "F".to_string()]), path_between(&result, "A", "F").unwrap());
+        assert_eq!(Some(vec!["B".to_string(), "D".to_string(), "E".to_string()]), path_between(&result, "B", "E").unwrap());
+        assert_eq!(None, path_between(&result, "F", "A").unwrap());
+        assert!(path_between(&result, "A", "Z").is_err());
+    }
+
+    #[test]
+    fn all_paths_should_enumerate_every_chain() {
+        let result = small_dag();
+        let mut budget = PathBudget::default();
+        let paths = all_paths(&result, "A", "F", &mut budget).unwrap();
+        let expected: Vec<Vec<String>> = [
+            vec!["A", "B", "D", "E", "F"],
+            vec!["A", "C", "D", "E", "F"],
+            vec!["A", "F"],
+        ].iter().map(|p| p.iter().map(|v| v.to_string()).collect()).collect();
+        assert_eq!(expected, paths.paths);
+        assert!(!paths.truncated);
+        assert_eq!(budget.used(), paths.explored);
+        assert!(all_paths(&result, "F", "A", &mut budget).unwrap().paths.is_empty());
+    }
+
+    #[test]
+    fn all_paths_when_budget_runs_out_should_truncate() {
+        let result = small_dag();
+        let mut budget = PathBudget::new(5);
+        let paths = all_paths(&result, "A", "F", &mut budget).unwrap();
+        assert!(paths.truncated);
+        assert_eq!(5, paths.explored);
+        assert_eq!(vec![vec!["A".to_string(), "B".to_string(), "D".to_string(), "E".to_string(), "F".to_string()]], paths.paths);
+        assert!(all_paths(&result, "A", "F", &mut budget).unwrap().truncated);
+    }
+
+    #[test]
+    fn load_order_by_dir_should_keep_load_order_and_put_unresolved_last() {
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("C")
+            .add_library_at("B", "/opt/app/lib/B", &["D", "E"])
+            .add_library_at("C", "/usr/lib/C", &["D"])
+            .add_library_at("D", "/usr/lib/D", &[])
+            .add_missing_library("E")
+            .build();
+        let result = get_topologically_sorted_result("A", "/opt/app/bin/A", BinaryKind::Executable, &dt, &AnalysisOptions::default()).unwrap();
+        let names: Vec<&str> = result.topo_sorted_libs.iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(vec!["D", "C", "E", "B", "A"], names);
+        let group = |dir: &str, libs: &[&str]| DirGroup { dir: dir.to_string(), libs: libs.iter().map(|l| l.to_string()).collect() };
+        assert_eq!(vec![
+            group("/usr/lib", &["D", "C"]),
+            group("/opt/app/lib", &["B"]),
+            group("/opt/app/bin", &["A"]),
+            group("unresolved", &["E"]),
+        ], load_order_by_dir(&result));
+
+        // the load order goes from /usr/lib to /opt/app/lib and back
+        let dt = DependencyTreeBuilder::new()
+            .add_needed("B")
+            .add_needed("C")
+            .add_library_at("B", "/opt/app/lib/B", &["D"])
+            .add_library_at("C", "/usr/lib/C", &["B"])
+            .add_library_at("D", "/usr/lib/D", &[])
+            .build();
+        let result = get_topologically_sorted_result("A", "/opt/app/bin/A", BinaryKind::Executable, &dt, &AnalysisOptions::default()).unwrap();
+        assert_eq!(vec![
+            group("/usr/lib", &["D"]),
+            group("/opt/app/lib", &["B"]),
+            group("/usr/lib", &["C"]),
+            group("/opt/app/bin", &["A"]),
+        ], load_order_by_dir(&result));
+    }
+
+    #[test]
+    fn main_library_should_be_the_library_at_depth_zero() {
+        let mut result = small_dag();
+        assert_eq!(Some("A"), main_library(&result));
+        result.topo_sorted_libs.iter_mut().for_each(|lib| lib.min_depth = None);
+        assert_eq!(None, main_library(&result));
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use layout::backends::svg::SVGWriter;
+use layout::core::base::Orientation;
+use layout::core::color::Color;
+use layout::core::geometry::{get_size_for_str, pad_shape_scalar};
+use layout::core::style::{LineStyleKind, StyleAttr};
+use layout::std_shapes::shapes::{Arrow, Element, LineEndKind, ShapeKind};
+use layout::topo::layout::VisualGraph;
+
+use crate::collapse::CollapsedView;
+use crate::EdgeKind;
+
+/// Image of `--render FORMAT=PATH`. Only SVG can be laid out without Graphviz, a PNG is left to converters
+/// such as `rsvg-convert`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderTarget {
+    pub path: PathBuf,
+}
+
+impl FromStr for RenderTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("svg", path)) if !path.is_empty() => Ok(RenderTarget { path: PathBuf::from(path) }),
+            Some(("svg", _)) => Err("the path after svg= is empty".to_string()),
+            Some((format, _)) => Err(format!("{} cannot be rendered, only svg is supported; convert the SVG, e.g. with rsvg-convert", format)),
+            None => Err(format!("{} is not FORMAT=PATH, e.g. svg=graph.svg", s)),
+        }
+    }
+}
+
+/// The graph of `view` laid out top to bottom by `layout-rs` and drawn as SVG, the same nodes and edges as the
+/// DOT output. Merged edges are labelled with their multiplicity and guessed `dlopen` edges are dashed.
+/// `label` renders the text of a node, e.g. `dot::text_label`, and `note`, e.g. why the graph is truncated, is drawn
+/// in a gray box of its own.
+pub fn to_svg(view: &CollapsedView, label: &dyn Fn(&str) -> String, note: Option<&str>) -> String {
+    let mut graph = VisualGraph::new(Orientation::TopToBottom);
+    if let Some(note) = note {
+        let look = StyleAttr::new(Color::fast("gray"), 1, Some(Color::fast("lightyellow")), 5, 15);
+        let size = pad_shape_scalar(get_size_for_str(note, look.font_size), 10.0);
+        graph.add_node(Element::create(ShapeKind::new_box(note), look, Orientation::TopToBottom, size));
+    }
+    let handles: HashMap<&str, _> = view.vertices.iter()
+        .map(|v| {
+            let look = StyleAttr::simple();
+            let text = label(v);
+            let size = pad_shape_scalar(get_size_for_str(&text, look.font_size), 10.0);
+            let node = Element::create(ShapeKind::new_box(&text), look, Orientation::TopToBottom, size);
+            (v.as_str(), graph.add_node(node))
+        })
+        .collect();
+    for (edge, multiplicity) in &view.edges {
+        let (line_style, text) = match edge.kind {
+            EdgeKind::Dlopen => (LineStyleKind::Dashed, String::new()),
+            EdgeKind::Runtime => (LineStyleKind::Dotted, String::new()),
+            EdgeKind::Interpreter => (LineStyleKind::Normal, "interpreter".to_string()),
+            EdgeKind::Weak => (LineStyleKind::Dashed, "weak".to_string()),
+            EdgeKind::Needed if *multiplicity > 1 => (LineStyleKind::Normal, multiplicity.to_string()),
+            EdgeKind::Needed => (LineStyleKind::Normal, String::new()),
+        };
+        let arrow = Arrow::new(LineEndKind::None, LineEndKind::Arrow, line_style, &text, &StyleAttr::simple(), &None, &None);
+        graph.add_edge(arrow, handles[edge.src.as_str()], handles[edge.dst.as_str()]);
+    }
+    let mut svg = SVGWriter::new();
+    if !view.vertices.is_empty() {
+        graph.do_it(false, false, false, &mut svg);
+    }
+    svg.finalize()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::collapse::{collapse_result, CollapsedView};
+    use crate::dot::text_label;
+    use crate::label_template::LabelTemplate;
+    use crate::node_limit::limit_nodes;
+    use crate::query::tests::small_dag;
+    use crate::render::{to_svg, RenderTarget};
+    use crate::Edge;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn render_target_should_accept_only_svg() {
+        assert_eq!(Ok(RenderTarget { path: PathBuf::from("/tmp/out.svg") }), "svg=/tmp/out.svg".parse());
+        assert!("png=/tmp/out.png".parse::<RenderTarget>().unwrap_err().contains("only svg"));
+        assert!("svg=".parse::<RenderTarget>().is_err());
+        assert!("/tmp/out.svg".parse::<RenderTarget>().is_err());
+    }
+
+    #[test]
+    fn to_svg_should_draw_every_library() {
+        let result = small_dag();
+        let edges = result.edges.iter()
+            .map(|edge| (Edge { src: edge.src.clone(), dst: edge.dst.clone(), kind: edge.kind, ..Default::default() }, 1))
+            .collect();
+        let view = CollapsedView { vertices: result.vertices.clone(), edges, groups: BTreeMap::new() };
+        let svg = to_svg(&view, &str::to_string, None);
+        assert!(svg.contains("<svg"));
+        for v in &result.vertices {
+            assert!(svg.contains(&format!(">{}</tspan>", v)), "{} is not drawn", v);
+        }
+    }
+
+    #[test]
+    fn to_svg_with_note_should_draw_it() {
+        let result = small_dag();
+        let mut view = collapse_result(&result, &[]);
+        limit_nodes(&mut view, "A", 2);
+        let svg = to_svg(&view, &str::to_string, Some("Truncated by --node-limit: 3 of 6 libraries shown"));
+        assert!(svg.contains(">Truncated by --node-limit: 3 of 6 libraries shown</tspan>"));
+        assert!(svg.contains(">D</tspan>"));
+        assert!(!svg.contains(">B</tspan>"));
+    }
+
+    #[test]
+    fn to_svg_with_label_template_should_label_every_library() {
+        let result = small_dag();
+        let view = collapse_result(&result, &[]);
+        let template: LabelTemplate = "{name} at {depth}".parse().unwrap();
+        let svg = to_svg(&view, &text_label(&result, Some(&template)), None);
+        assert!(svg.contains(">A at 0</tspan>"));
+        assert!(svg.contains(">E at 3</tspan>"));
+    }
+}
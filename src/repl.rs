@@ -0,0 +1,169 @@
+use std::io::{BufRead, Write};
+
+use crate::path_budget::PathBudget;
+use crate::query::{all_paths, dependencies_of, direct_dependencies_of, direct_dependents_of, impact_of, path_between};
+use crate::TopoSortResult;
+
+pub const HELP: &str = "\
+deps LIB       everything LIB (transitively) depends on, in load order
+rdeps LIB      everything that (transitively) depends on LIB, in load order
+path FROM TO   shortest NEEDED chain from FROM to TO
+paths FROM TO  every NEEDED chain from FROM to TO, within the path budget
+why --from FROM --to TO [--all-paths]
+               why FROM ends up needing TO: the shortest chain, or every chain within the path budget
+explain LIB    path, depth, direct dependencies and dependents of LIB
+stats          size of the graph
+help           this message
+quit           leave (or end of input)";
+
+/// Line-based prompt answering queries against an already analyzed graph
+pub struct Repl<'a> {
+    result: &'a TopoSortResult,
+    main_lib_name: &'a str,
+    /// Path budget of every single command
+    path_budget: usize,
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(result: &'a TopoSortResult, main_lib_name: &'a str, path_budget: usize) -> Repl<'a> {
+        Repl { result, main_lib_name, path_budget }
+    }
+
+    /// Reads commands from `input` until `quit` or end of input, answers go to `output` and errors are prefixed with `error:`
+    pub fn run<R: BufRead, W: Write>(&self, input: R, mut output: W) -> std::io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line == "quit" || line == "exit" {
+                break;
+            }
+            if !line.is_empty() {
+                match self.execute(line) {
+                    Ok(answer) => writeln!(output, "{}", answer)?,
+                    Err(err) => writeln!(output, "error: {}", err)?,
+                }
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    pub fn execute(&self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["deps", lib] => dependencies_of(self.result, lib).map(|deps| deps.join("\n")),
+            ["rdeps", lib] => impact_of(self.result, lib).map(|impact| impact.dependents.join("\n")),
+            ["path", from, to] => self.path(from, to),
+            ["paths", from, to] => self.paths(from, to),
+            ["why", options @ ..] => self.why(options),
+            ["explain", lib] => self.explain(lib),
+            ["stats"] => Ok(self.stats()),
+            ["help"] => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command '{}', try help", line)),
+        }
+    }
+
+    fn path(&self, from: &str, to: &str) -> Result<String, String> {
+        match path_between(self.result, from, to)? {
+            Some(chain) => Ok(chain.join(" -> ")),
+            None => Err(format!("{} does not depend on {}", from, to)),
+        }
+    }
+
+    fn paths(&self, from: &str, to: &str) -> Result<String, String> {
+        let mut budget = PathBudget::new(self.path_budget);
+        let paths = all_paths(self.result, from, to, &mut budget)?;
+        let mut lines: Vec<String> = paths.paths.iter().map(|path| path.join(" -> ")).collect();
+        if paths.truncated {
+            lines.push(budget.exceeded_message());
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// `why --from FROM --to TO [--all-paths]`, the options in any order
+    fn why(&self, options: &[&str]) -> Result<String, String> {
+        let (mut from, mut to, mut all) = (None, None, false);
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match *option {
+                "--from" => from = options.next(),
+                "--to" => to = options.next(),
+                "--all-paths" => all = true,
+                _ => return Err(format!("unknown option '{}' of why, expected --from FROM --to TO [--all-paths]", option)),
+            }
+        }
+        match (from, to) {
+            (Some(from), Some(to)) if all => self.paths(from, to),
+            (Some(from), Some(to)) => self.path(from, to),
+            _ => Err("why needs --from FROM and --to TO".to_string()),
+        }
+    }
+
+    fn explain(&self, lib: &str) -> Result<String, String> {
+        let dependencies = direct_dependencies_of(self.result, lib)?;
+        let dependents = direct_dependents_of(self.result, lib)?;
+        let entry = self.result.topo_sorted_libs.iter().find(|l| l.name == lib);
+        let mut lines = vec![lib.to_string()];
+        if self.result.unresolved.iter().any(|u| u == lib) {
+            lines.push("  unresolved".to_string());
+        } else if let Some(path) = entry.and_then(|l| l.path.as_deref()) {
+            lines.push(format!("  path: {}", path));
+        }
+        if let Some(entry) = entry {
+            if let (Some(min), Some(max)) = (entry.min_depth, entry.max_depth) {
+                lines.push(format!("  depth: {}..{}", min, max));
+            }
+        }
+        if lib != self.main_lib_name {
+            match path_between(self.result, self.main_lib_name, lib) {
+                Ok(Some(chain)) => lines.push(format!("  needed via: {}", chain.join(" -> "))),
+                _ => lines.push(format!("  not needed by {}", self.main_lib_name)),
+            }
+        }
+        lines.push(format!("  needs: {}", dependencies.join(", ")));
+        lines.push(format!("  needed by: {}", dependents.join(", ")));
+        Ok(lines.join("\n"))
+    }
+
+    fn stats(&self) -> String {
+        let max_depth = self.result.topo_sorted_libs.iter().filter_map(|lib| lib.max_depth).max().unwrap_or(0);
+        format!("{} libraries, {} edges, {} unresolved, {} unreachable, max depth {}",
+            self.result.vertices.len(), self.result.edges.len(), self.result.unresolved.len(), self.result.unreachable.len(), max_depth)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::query::tests::small_dag;
+    use crate::repl::Repl;
+
+    #[test]
+    fn execute_should_answer_queries() {
+        let result = small_dag();
+        let repl = Repl::new(&result, "A", 100);
+        assert_eq!(Ok("F\nE\nD".to_string()), repl.execute("deps B"));
+        assert_eq!(Ok("B\nC\nA".to_string()), repl.execute("rdeps D"));
+        assert_eq!(Ok("A -> B -> D".to_string()), repl.execute("path  A D"));
+        assert_eq!(Ok("6 libraries, 7 edges, 0 unresolved, 0 unreachable, max depth 4".to_string()), repl.execute("stats"));
+        assert_eq!(Ok("D\n  path: /usr/lib/D\n  depth: 2..2\n  needed via: A -> B -> D\n  needs: E\n  needed by: B, C".to_string()), repl.execute("explain D"));
+        assert_eq!(Ok("A -> B -> D\nA -> C -> D".to_string()), repl.execute("paths A D"));
+        assert_eq!(Ok("A -> F".to_string()), repl.execute("why --from A --to F"));
+        assert_eq!(Ok("A -> B -> D -> E -> F\nA -> C -> D -> E -> F\nA -> F".to_string()), repl.execute("why --to F --from A --all-paths"));
+        assert!(repl.execute("why --from A").is_err());
+        assert!(repl.execute("why --from A --to F --bogus").is_err());
+        assert!(repl.execute("path F A").is_err());
+        assert!(repl.execute("deps libnope.so").is_err());
+        assert!(repl.execute("frobnicate").is_err());
+    }
+
+    #[test]
+    fn run_should_stop_at_quit() {
+        let result = small_dag();
+        let mut output: Vec<u8> = Vec::new();
+        Repl::new(&result, "A", 100).run("deps E\n\nbogus\nquit\nstats\n".as_bytes(), &mut output).unwrap();
+        assert_eq!("> F\n> > error: unknown command 'bogus', try help\n> \n", String::from_utf8(output).unwrap());
+    }
+}
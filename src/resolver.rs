@@ -0,0 +1,993 @@
+use goblin::elf::header::{EI_OSABI, ELFOSABI_GNU, ELFOSABI_NONE, EM_386, EM_AARCH64, EM_X86_64};
+use goblin::elf::Elf;
+
+use lddtree::{DependencyTree, Error, Library};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ld_cache::LdCache;
+use crate::multiarch::{tuples_for, Target};
+use crate::nix_store::NixStore;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// A library file that was found but not analyzed, its own dependencies are missing from the graph
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLib {
+    pub name: String,
+    pub path: String,
+    pub reason: String,
+}
+
+/// Dependency tree of a binary along with the libraries that were skipped while building it
+#[derive(Debug)]
+pub struct Resolution {
+    pub tree: DependencyTree,
+    pub skipped: Vec<SkippedLib>,
+}
+
+/// What the resolution needs from an ELF file, owned so that it can be produced on another thread
+#[derive(Debug)]
+struct ElfSummary {
+    is_64: bool,
+    little_endian: bool,
+    machine: u16,
+    osabi: u8,
+    interpreter: Option<String>,
+    needed: Vec<String>,
+    rpaths: Vec<String>,
+    runpaths: Vec<String>,
+}
+
+impl ElfSummary {
+    fn parse(bytes: &[u8]) -> Result<ElfSummary, goblin::error::Error> {
+        let elf = Elf::parse(bytes)?;
+        Ok(ElfSummary {
+            is_64: elf.is_64,
+            little_endian: elf.little_endian,
+            machine: elf.header.e_machine,
+            osabi: elf.header.e_ident[EI_OSABI],
+            interpreter: elf.interpreter.map(str::to_string),
+            needed: elf.libraries.iter().map(|l| l.to_string()).collect(),
+            rpaths: elf.rpaths.iter().map(|p| p.to_string()).collect(),
+            runpaths: elf.runpaths.iter().map(|p| p.to_string()).collect(),
+        })
+    }
+
+    /// Same bit size, endianness, machine and a compatible OS ABI
+    fn compatible_with(&self, other: &ElfSummary) -> bool {
+        let compatible_osabis = [ELFOSABI_NONE, ELFOSABI_GNU];
+        self.is_64 == other.is_64
+            && self.little_endian == other.little_endian
+            && self.machine == other.machine
+            && (self.osabi == other.osabi || compatible_osabis.contains(&self.osabi) || compatible_osabis.contains(&other.osabi))
+    }
+}
+
+/// Outcome of reading one candidate library file
+enum Parsed {
+    Elf(ElfSummary),
+    Invalid,
+    TimedOut,
+}
+
+/// Resolves the dependency tree of a binary the way `lddtree::DependencyAnalyzer` does, quirks
+/// included, while reading every library file separately so that a single file can be bounded in
+/// time. Unlike lddtree, LD_LIBRARY_PATH comes before RUNPATH as for the dynamic loader, and it
+/// is only searched when given with `ld_library_path`.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    root: PathBuf,
+    library_paths: Vec<PathBuf>,
+    parse_timeout: Option<Duration>,
+    /// Number of threads looking up libraries
+    jobs: usize,
+    /// Looked up instead of the ld.so.conf directories when set
+    ld_cache: Option<Arc<LdCache>>,
+    /// Colon-separated directories searched like LD_LIBRARY_PATH
+    ld_library_path: Option<String>,
+    /// Values of `$LIB` and `$PLATFORM` in search paths, the defaults of the architecture when unset
+    tokens: Tokens,
+    /// glibc-hwcaps subdirectories tried before every search directory, most preferred first
+    hwcaps: Vec<String>,
+    /// The platform of the root when it is not the one of this machine
+    target: Option<Target>,
+    /// Where the store paths of Nix or Guix built binaries are, the system directories are not searched when set
+    nix_store: Option<NixStore>,
+    read_elf: fn(&Path) -> Option<ElfSummary>,
+}
+
+fn read_elf(path: &Path) -> Option<ElfSummary> {
+    std::fs::read(path).ok().and_then(|bytes| ElfSummary::parse(&bytes).ok())
+}
+
+impl Resolver {
+    pub fn new(root: PathBuf) -> Resolver {
+        Resolver { root, library_paths: Vec::new(), parse_timeout: None, jobs: 1, ld_cache: None, ld_library_path: None, tokens: Tokens::default(), hwcaps: Vec::new(), target: None, nix_store: None, read_elf }
+    }
+
+    /// Additional library paths are treated as absolute paths, not relative to root, and searched last
+    pub fn library_paths(mut self, library_paths: Vec<PathBuf>) -> Self {
+        self.library_paths = library_paths;
+        self
+    }
+
+    /// Skip a library file whose reading and parsing takes longer than `timeout`
+    pub fn parse_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.parse_timeout = timeout;
+        self
+    }
+
+    /// Look up and parse the libraries of each level of the tree on up to `jobs` threads. The result does not
+    /// depend on it: every library is looked up the same way whichever thread does it, and the outcomes are
+    /// collected in name order before anything is built from them
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Look the libraries up in `ld_cache`, the `ld.so.cache` of the root, after the RPATH, RUNPATH and
+    /// LD_LIBRARY_PATH directories, the way the dynamic loader does. The ld.so.conf directories are not searched then,
+    /// only the trusted directories after the cache.
+    pub fn ld_cache(mut self, ld_cache: Option<Arc<LdCache>>) -> Self {
+        self.ld_cache = ld_cache;
+        self
+    }
+
+    /// Search the colon-separated directories of `ld_library_path` the way the dynamic loader searches
+    /// LD_LIBRARY_PATH: after the RPATH and before the RUNPATH of the binary. Absolute directories are inside the
+    /// root, empty entries are the working directory and `$ORIGIN` is expanded. The environment of this process is
+    /// not read unless it is passed here.
+    pub fn ld_library_path(mut self, ld_library_path: Option<String>) -> Self {
+        self.ld_library_path = ld_library_path;
+        self
+    }
+
+    /// The values of the `$LIB` and `$PLATFORM` tokens in RPATH, RUNPATH and LD_LIBRARY_PATH entries. Left
+    /// unset, `$LIB` is `lib64` for 64-bit and `lib` for 32-bit binaries as in upstream glibc, and `$PLATFORM` the
+    /// name of the architecture for x86 and AArch64; entries with a token without a value are dropped.
+    pub fn tokens(mut self, lib: Option<String>, platform: Option<String>) -> Self {
+        self.tokens = Tokens { lib, platform };
+        self
+    }
+
+    /// Assume the CPU supports the glibc-hwcaps levels `hwcaps`, e.g. `x86-64-v3` and `x86-64-v2`, most preferred
+    /// first: as glibc 2.33 and later do, `<dir>/glibc-hwcaps/<level>` is tried before every search directory, and
+    /// the ld.so.cache entries of the levels that are not listed are ignored
+    pub fn hwcaps(mut self, hwcaps: Vec<String>) -> Self {
+        self.hwcaps = hwcaps;
+        self
+    }
+
+    /// Analyze binaries built for `target`: its multiarch directories, e.g. `/usr/lib/aarch64-linux-gnu`, are
+    /// searched before the trusted ones, `$LIB` is `lib/<multiarch tuple>` unless set with `tokens`, and the search
+    /// paths of its C library are used instead of those of this machine. Without a target, the multiarch
+    /// directories of the architecture of the binary are searched when the root has them.
+    pub fn target(mut self, target: Option<Target>) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Resolve binaries built by Nix or Guix, whose RUNPATH and interpreter name store paths: those paths are
+    /// looked up where `nix_store` says rather than under the root, and neither the ld.so.conf nor the trusted
+    /// directories are searched, as the C library of the store does not either
+    pub fn nix_store(mut self, nix_store: Option<NixStore>) -> Self {
+        self.nix_store = nix_store;
+        self
+    }
+
+    /// Where the absolute path `path` of the analyzed system is on this machine: in the store for store paths, under
+    /// the root otherwise
+    fn locate(&self, path: &str) -> PathBuf {
+        self.nix_store.as_ref().and_then(|store| store.locate(Path::new(path)))
+            .unwrap_or_else(|| self.root.join(path.strip_prefix('/').unwrap_or(path)))
+    }
+
+    pub fn analyze(&self, path: impl AsRef<Path>) -> Result<Resolution, Error> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let elf = ElfSummary::parse(&bytes)?;
+        let search = SearchPaths::load(self, path, &elf);
+        let main_paths = self.object_paths(&search, path, &elf, None);
+
+        // A library is searched for with the RPATH chain or the RUNPATH of the first object that needs it, and
+        // loaded once, so the tree is resolved level by level in load order
+        let mut libraries: HashMap<String, Library> = HashMap::new();
+        let mut object_paths: HashMap<String, ObjectPaths> = HashMap::new();
+        let mut skipped: Vec<SkippedLib> = Vec::new();
+        let mut level: Vec<(String, Option<String>)> = elf.needed.iter().map(|needed| (needed.clone(), None)).collect();
+        while !level.is_empty() {
+            let mut seen: HashSet<String> = HashSet::new();
+            level.retain(|(name, _)| seen.insert(name.clone()));
+            let names: Vec<(&str, &ObjectPaths)> = level.iter()
+                .map(|(name, loader)| (name.as_str(), loader.as_ref().map_or(&main_paths, |loader| &object_paths[loader])))
+                .collect();
+            let found = self.find_libraries(&search, &elf, &names);
+            let mut next_level: Vec<(String, Option<String>)> = Vec::new();
+            for ((lib_name, _), (library, skipped_lib, paths)) in level.into_iter().zip(found) {
+                next_level.extend(library.needed.iter()
+                    .filter(|needed| !libraries.contains_key(*needed))
+                    .map(|needed| (needed.clone(), Some(lib_name.clone()))));
+                skipped.extend(skipped_lib);
+                object_paths.insert(lib_name.clone(), paths);
+                libraries.insert(lib_name, library);
+            }
+            next_level.retain(|(needed, _)| !libraries.contains_key(needed));
+            level = next_level;
+        }
+
+        if let Some(interp) = &elf.interpreter {
+            if !libraries.contains_key(interp) {
+                let interp_path = self.locate(interp);
+                let interp_name = interp_path.file_name().and_then(|n| n.to_str()).unwrap_or(interp).to_string();
+                libraries.insert(interp.clone(), Library {
+                    name: interp_name,
+                    path: interp_path,
+                    realpath: std::fs::canonicalize(interp).ok(),
+                    needed: Vec::new(),
+                    rpath: Vec::new(),
+                    runpath: Vec::new(),
+                });
+            }
+        }
+        skipped.sort_by(|a, b| a.name.cmp(&b.name));
+        let tree = DependencyTree {
+            interpreter: elf.interpreter.clone(),
+            needed: elf.needed.clone(),
+            libraries,
+            rpath: search.rpaths,
+            runpath: search.runpaths,
+        };
+        Ok(Resolution { tree, skipped })
+    }
+
+    fn parse_file(&self, path: &Path) -> Parsed {
+        let read_and_parse = self.read_elf;
+        let parsed = match self.parse_timeout {
+            None => Some(read_and_parse(path)),
+            Some(timeout) => {
+                let (sender, receiver) = mpsc::channel();
+                let path = path.to_path_buf();
+                // A file that exceeds the budget keeps its thread busy until it is done, the
+                // resolution moves on without waiting for it
+                thread::spawn(move || {
+                    let _ = sender.send(read_and_parse(&path));
+                });
+                receiver.recv_timeout(timeout).ok()
+            }
+        };
+        match parsed {
+            Some(Some(elf)) => Parsed::Elf(elf),
+            Some(None) => Parsed::Invalid,
+            None => Parsed::TimedOut,
+        }
+    }
+
+    /// The search paths the ELF file at `path` gives its dependencies, `loader` being those of the object that
+    /// needed it first
+    fn object_paths(&self, search: &SearchPaths, path: &Path, elf: &ElfSummary, loader: Option<&ObjectPaths>) -> ObjectPaths {
+        let has_runpath = !elf.runpaths.is_empty();
+        let mut rpaths = match has_runpath {
+            true => Vec::new(),
+            false => elf.rpaths.last().map(|p| parse_ld_paths(self, p, path, &search.tokens)).unwrap_or_default(),
+        };
+        for rpath in loader.map(|loader| loader.rpaths.as_slice()).unwrap_or_default() {
+            if !rpaths.contains(rpath) {
+                rpaths.push(rpath.clone());
+            }
+        }
+        let runpaths = elf.runpaths.last().map(|p| parse_ld_paths(self, p, path, &search.tokens)).unwrap_or_default();
+        ObjectPaths { rpaths, runpaths, has_runpath }
+    }
+
+    /// `find_library` for every name of `names` with the paths of the object that needs it, in the same order
+    fn find_libraries(&self, search: &SearchPaths, elf: &ElfSummary, names: &[(&str, &ObjectPaths)]) -> Vec<(Library, Option<SkippedLib>, ObjectPaths)> {
+        if self.jobs == 1 || names.len() < 2 {
+            return names.iter().map(|(name, loader)| self.find_library(search, loader, elf, name)).collect();
+        }
+        let chunk_size = names.len().div_ceil(self.jobs);
+        thread::scope(|scope| {
+            let handles: Vec<_> = names.chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(|(name, loader)| self.find_library(search, loader, elf, name)).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        })
+    }
+
+    /// `lib` in the glibc-hwcaps subdirectories of `dir`, then in `dir`
+    fn hwcaps_candidates(&self, dir: &Path, lib: &str) -> Vec<PathBuf> {
+        self.hwcaps.iter()
+            .map(|level| dir.join("glibc-hwcaps").join(level).join(lib))
+            .chain(std::iter::once(dir.join(lib)))
+            .collect()
+    }
+
+    /// Looks `lib` up for the object with the search paths `loader`, and returns it with the search paths it gives
+    /// its own dependencies
+    fn find_library(&self, search: &SearchPaths, loader: &ObjectPaths, elf: &ElfSummary, lib: &str) -> (Library, Option<SkippedLib>, ObjectPaths) {
+        let in_root = |path: &String| self.locate(path);
+        let cached = self.ld_cache.as_deref().map(|cache| cache.lookup(lib)).unwrap_or_default();
+        // the order of the dynamic loader: the RPATH chain, which a RUNPATH turns off, LD_LIBRARY_PATH, RUNPATH
+        let rpaths = if loader.has_runpath { &[][..] } else { loader.rpaths.as_slice() };
+        let candidates = rpaths.iter()
+            .chain(search.env_ld_paths.iter())
+            .chain(loader.runpaths.iter())
+            .flat_map(|ld_path| self.hwcaps_candidates(Path::new(ld_path), lib))
+            .chain(cached.iter().filter(|path| hwcaps_level(path).is_none_or(|level| self.hwcaps.iter().any(|h| h == level))).map(in_root))
+            .chain(search.conf_ld_paths.iter().flat_map(|ld_path| self.hwcaps_candidates(&in_root(ld_path), lib)))
+            .chain(self.library_paths.iter().map(|ld_path| ld_path.join(lib)));
+        for lib_path in candidates {
+            if !lib_path.exists() {
+                continue;
+            }
+            match self.parse_file(&lib_path) {
+                Parsed::Elf(lib_elf) if elf.compatible_with(&lib_elf) => {
+                    let paths = self.object_paths(search, &lib_path, &lib_elf, Some(loader));
+                    return (Library {
+                        name: lib.to_string(),
+                        realpath: std::fs::canonicalize(&lib_path).ok(),
+                        needed: lib_elf.needed.clone(),
+                        rpath: lib_elf.rpaths.iter().last().map(|p| parse_ld_paths(self, p, &lib_path, &search.tokens)).unwrap_or_default(),
+                        runpath: paths.runpaths.clone(),
+                        path: lib_path,
+                    }, None, paths);
+                }
+                Parsed::Elf(_) | Parsed::Invalid => continue,
+                Parsed::TimedOut => {
+                    let skipped = SkippedLib {
+                        name: lib.to_string(),
+                        path: lib_path.display().to_string(),
+                        reason: format!("parsing took longer than {} ms", self.parse_timeout.unwrap_or_default().as_millis()),
+                    };
+                    return (Library {
+                        name: lib.to_string(),
+                        realpath: std::fs::canonicalize(&lib_path).ok(),
+                        path: lib_path,
+                        needed: Vec::new(),
+                        rpath: Vec::new(),
+                        runpath: Vec::new(),
+                    }, Some(skipped), ObjectPaths::default());
+                }
+            }
+        }
+        (Library {
+            name: lib.to_string(),
+            path: PathBuf::from(lib),
+            realpath: None,
+            needed: Vec::new(),
+            rpath: Vec::new(),
+            runpath: Vec::new(),
+        }, None, ObjectPaths::default())
+    }
+}
+
+/// The glibc-hwcaps level of a library in a `glibc-hwcaps/<level>/` subdirectory
+fn hwcaps_level(path: &str) -> Option<&str> {
+    let (_, rest) = path.split_once("/glibc-hwcaps/")?;
+    rest.split('/').next()
+}
+
+/// Values of the dynamic string tokens `$LIB` and `$PLATFORM`
+#[derive(Debug, Clone, Default)]
+struct Tokens {
+    lib: Option<String>,
+    platform: Option<String>,
+}
+
+impl Tokens {
+    /// `tokens` with the values left unset filled in from the architecture of `elf` and the `multiarch` tuple of the
+    /// root, like the dynamic loader of the binary would
+    fn for_elf(tokens: &Tokens, elf: &ElfSummary, multiarch: Option<&str>) -> Tokens {
+        let platform = match elf.machine {
+            EM_X86_64 => Some("x86_64"),
+            EM_386 => Some("i686"),
+            EM_AARCH64 => Some("aarch64"),
+            _ => None,
+        };
+        Tokens {
+            lib: tokens.lib.clone()
+                .or_else(|| multiarch.map(|tuple| format!("lib/{}", tuple)))
+                .or_else(|| Some(if elf.is_64 { "lib64" } else { "lib" }.to_string())),
+            platform: tokens.platform.clone().or_else(|| platform.map(String::from)),
+        }
+    }
+}
+
+/// The search paths an object gives the lookup of the dependencies it needs first
+#[derive(Debug, Clone, Default)]
+struct ObjectPaths {
+    /// Its RPATH unless it has a RUNPATH, then those of the objects that needed it before, up to the binary
+    rpaths: Vec<String>,
+    runpaths: Vec<String>,
+    /// A DT_RUNPATH turns the whole RPATH chain off for the object's dependencies, even when empty
+    has_runpath: bool,
+}
+
+/// Directories searched for the dependencies of the analyzed binary. The RPATH, LD_LIBRARY_PATH and RUNPATH
+/// directories are paths on this machine, under the root, the ld.so.conf ones are inside the root.
+#[derive(Debug, Default)]
+struct SearchPaths {
+    /// RPATH of the analyzed binary, empty when it has a RUNPATH
+    rpaths: Vec<String>,
+    runpaths: Vec<String>,
+    env_ld_paths: Vec<String>,
+    conf_ld_paths: Vec<String>,
+    tokens: Tokens,
+}
+
+impl SearchPaths {
+    fn load(resolver: &Resolver, elf_path: &Path, elf: &ElfSummary) -> SearchPaths {
+        let root = &resolver.root;
+        let multiarch = match &resolver.target {
+            Some(target) => target.multiarch,
+            // a Debian-style root keeps the libraries of every architecture in a directory named after its tuple
+            None => tuples_for(elf.machine, elf.is_64, elf.little_endian).into_iter()
+                .find(|tuple| ["lib", "usr/lib"].iter().any(|dir| root.join(dir).join(tuple).is_dir())),
+        };
+        let tokens = Tokens::for_elf(&resolver.tokens, elf, multiarch);
+        let runpaths = elf.runpaths.last().map(|p| parse_ld_paths(resolver, p, elf_path, &tokens)).unwrap_or_default();
+        // If both RPATH and RUNPATH are set, only the latter is used
+        let rpaths = if elf.runpaths.is_empty() {
+            elf.rpaths.last().map(|p| parse_ld_paths(resolver, p, elf_path, &tokens)).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let env_ld_paths = resolver.ld_library_path.as_deref().map(|p| parse_ld_paths(resolver, p, elf_path, &tokens)).unwrap_or_default();
+        let musl = resolver.target.as_ref().map_or_else(is_musl_host, |target| target.musl);
+        let mut conf_ld_paths = if resolver.nix_store.is_some() {
+            Vec::new()
+        } else if musl {
+            musl_ld_paths(root)
+        } else {
+            let mut paths = if resolver.ld_cache.is_some() { Vec::new() } else { ld_so_conf_paths(root) };
+            if let Some(tuple) = multiarch {
+                paths.extend([format!("/lib/{}", tuple), format!("/usr/lib/{}", tuple)]);
+            }
+            // the trusted directories are not necessarily in ld.so.conf
+            paths.extend(["/lib", "/lib64/", "/usr/lib", "/usr/lib64"].iter().map(|p| p.to_string()));
+            paths
+        };
+        conf_ld_paths.dedup();
+        SearchPaths { rpaths, runpaths, env_ld_paths, conf_ld_paths, tokens }
+    }
+}
+
+/// Whether `name` matches the glob `pattern`, in which `*` is any run of characters and `?` any one
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, _) => name.is_empty(),
+        (Some((b'*', rest)), _) => glob_match(rest, name) || (!name.is_empty() && glob_match(pattern, &name[1..])),
+        (Some((b'?', rest)), Some((_, name_rest))) => glob_match(rest, name_rest),
+        (Some((p, rest)), Some((n, name_rest))) => p == n && glob_match(rest, name_rest),
+        (Some(_), None) => false,
+    }
+}
+
+/// The files an `include` pattern of `conf` stands for, sorted like ldconfig does. Relative patterns are relative
+/// to the directory of `conf`, absolute ones to `root`. Only the file name may contain wildcards.
+fn included_files(root: &Path, conf: &Path, pattern: &str) -> Vec<PathBuf> {
+    let pattern = match pattern.strip_prefix('/') {
+        Some(absolute) => root.join(absolute),
+        None => conf.parent().unwrap_or(root).join(pattern),
+    };
+    let (dir, file_pattern) = match (pattern.parent(), pattern.file_name().and_then(|name| name.to_str())) {
+        (Some(dir), Some(name)) => (dir, name),
+        _ => return Vec::new(),
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.flatten()
+            .filter(|entry| glob_match(file_pattern.as_bytes(), entry.file_name().as_encoded_bytes()))
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// The directories listed in `conf` and the files it includes, as absolute paths inside `root`. Files in `read`
+/// are skipped, so that include cycles end.
+fn read_ld_so_conf(root: &Path, conf: &Path, read: &mut Vec<PathBuf>, paths: &mut Vec<String>) {
+    if read.iter().any(|file| file == conf) {
+        return;
+    }
+    read.push(conf.to_path_buf());
+    let content = match std::fs::read_to_string(conf) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(pattern) = line.strip_prefix("include").filter(|rest| rest.starts_with(char::is_whitespace)) {
+            for pattern in pattern.split_whitespace() {
+                for file in included_files(root, conf, pattern) {
+                    read_ld_so_conf(root, &file, read, paths);
+                }
+            }
+        } else if !line.starts_with("hwcap") {
+            // ldconfig also accepts several directories on a line, separated by blanks, colons or commas
+            paths.extend(line.split(|c: char| c.is_whitespace() || c == ':' || c == ',')
+                .filter(|dir| dir.starts_with('/'))
+                .map(String::from));
+        }
+    }
+}
+
+/// The directories of `etc/ld.so.conf` under `root` and the files it includes, as absolute paths inside the root,
+/// e.g. `/usr/local/lib` for `<root>/usr/local/lib`. Without an `ld.so.conf`, the `etc/ld.so.conf.d/*.conf` files
+/// most distributions include are read directly.
+pub fn ld_so_conf_paths(root: &Path) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut read = Vec::new();
+    let conf = root.join("etc/ld.so.conf");
+    if conf.is_file() {
+        read_ld_so_conf(root, &conf, &mut read, &mut paths);
+    } else {
+        for file in included_files(root, &conf, "ld.so.conf.d/*.conf") {
+            read_ld_so_conf(root, &file, &mut read, &mut paths);
+        }
+    }
+    paths
+}
+
+/// Replaces the dynamic string tokens of a search path entry, `$NAME` or `${NAME}`: `ORIGIN` with `origin`, `LIB`
+/// and `PLATFORM` with their values. `None` when a token has no value, the dynamic loader drops such entries as
+/// well. Other `$` sequences are kept.
+fn expand_tokens(path: &str, origin: Option<&str>, tokens: &Tokens) -> Option<String> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{').and_then(|braced| braced.find('}').map(|end| (&braced[..end], end + 2))) {
+            Some(braced) => braced,
+            None => {
+                let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        let value = match name {
+            "ORIGIN" => origin,
+            "LIB" => tokens.lib.as_deref(),
+            "PLATFORM" => tokens.platform.as_deref(),
+            _ => {
+                expanded.push('$');
+                rest = after;
+                continue;
+            }
+        };
+        expanded.push_str(value?);
+        rest = &after[len..];
+    }
+    expanded.push_str(rest);
+    Some(expanded)
+}
+
+/// Splits a colon-delimited list of paths and applies the ld.so rules: an empty entry is the
+/// current directory, `$ORIGIN` is the directory the ELF file really is in, `$LIB` and `$PLATFORM`
+/// come from `tokens` and other paths are found with `Resolver::locate`. Paths that do not exist are dropped.
+fn parse_ld_paths(resolver: &Resolver, ld_path: &str, elf_path: &Path, tokens: &Tokens) -> Vec<String> {
+    // symbolic links are followed, a bundle linked into /usr/bin still finds its libraries next to the real file
+    let origin = || std::fs::canonicalize(elf_path).ok()?.parent()?.to_str().map(String::from);
+    ld_path.split(':')
+        .filter_map(|path| {
+            let normpath = if path.is_empty() {
+                env::current_dir().ok()?
+            } else if path.contains("$ORIGIN") || path.contains("${ORIGIN}") {
+                PathBuf::from(expand_tokens(path, origin().as_deref(), tokens)?)
+            } else {
+                resolver.locate(&expand_tokens(path, None, tokens)?)
+            };
+            std::fs::canonicalize(normpath).ok().map(|p| p.display().to_string())
+        })
+        .collect()
+}
+
+fn is_musl_host() -> bool {
+    std::fs::read_dir("/lib").map(|entries| entries.flatten().any(|entry| {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        name.starts_with("libc.musl-") && name.ends_with(".so.1")
+    })).unwrap_or(false)
+}
+
+/// musl reads its search path from the first `etc/ld-musl-*.path` of the root and falls back to the defaults, the
+/// paths are inside the root
+fn musl_ld_paths(root: &Path) -> Vec<String> {
+    let mut path_files: Vec<PathBuf> = std::fs::read_dir(root.join("etc"))
+        .map(|entries| entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|p| p.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with("ld-musl-") && n.ends_with(".path")).unwrap_or(false))
+            .collect())
+        .unwrap_or_default();
+    path_files.sort();
+    let mut paths: Vec<String> = path_files.first()
+        .and_then(|path_file| std::fs::read_to_string(path_file).ok())
+        .map(|content| content.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect())
+        .unwrap_or_default();
+    if paths.is_empty() {
+        paths = ["/lib", "/usr/local/lib", "/usr/lib"].iter().map(|p| p.to_string()).collect();
+    }
+    paths
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::binary_kind::BinaryKind;
+    use crate::ld_cache::LdCache;
+    use crate::multiarch::Target;
+    use crate::nix_store::NixStore;
+    use crate::resolver::{expand_tokens, ld_so_conf_paths, musl_ld_paths, read_elf, ElfSummary, ObjectPaths, Resolver, SearchPaths, Tokens};
+    use crate::{get_topologically_sorted_result, AnalysisOptions};
+
+    type LibrarySummary = (String, Option<PathBuf>, Vec<String>);
+
+    /// Compares everything but the iteration order of the library map
+    fn summary(tree: &lddtree::DependencyTree) -> (Option<String>, Vec<String>, BTreeMap<String, LibrarySummary>) {
+        let libraries = tree.libraries.iter()
+            .map(|(key, lib)| (key.clone(), (lib.name.clone(), lib.realpath.clone(), lib.needed.clone())))
+            .collect();
+        (tree.interpreter.clone(), tree.needed.clone(), libraries)
+    }
+
+    #[test]
+    fn analyze_should_match_lddtree() {
+        let binary = std::env::current_exe().unwrap();
+        let expected = lddtree::DependencyAnalyzer::new(PathBuf::from("/")).analyze(&binary).unwrap();
+        // lddtree always reads the environment, which cargo sets for tests
+        let resolution = Resolver::new(PathBuf::from("/")).ld_library_path(std::env::var("LD_LIBRARY_PATH").ok()).analyze(&binary).unwrap();
+        assert_eq!(summary(&expected), summary(&resolution.tree));
+        assert!(resolution.skipped.is_empty());
+    }
+
+    #[test]
+    fn analyze_when_parsing_exceeds_timeout_should_skip_library() {
+        // the test binary needs libc.so.6 on glibc hosts only
+        if !cfg!(all(target_os = "linux", target_env = "gnu")) {
+            return;
+        }
+        fn slow_libc(path: &Path) -> Option<ElfSummary> {
+            if path.file_name()? == "libc.so.6" {
+                std::thread::sleep(Duration::from_millis(500));
+            }
+            bundled_elf(path)
+        }
+        let root = std::env::temp_dir().join(format!("lddtopo-timeout-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("lib")).unwrap();
+        std::fs::write(root.join("lib/libc.so.6"), b"").unwrap();
+        let binary = std::env::current_exe().unwrap();
+        let mut resolver = Resolver::new(root.clone()).parse_timeout(Some(Duration::from_millis(100)));
+        resolver.read_elf = slow_libc;
+        let resolution = resolver.analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(vec!["libc.so.6"], resolution.skipped.iter().map(|s| s.name.as_str()).collect::<Vec<_>>());
+        assert_eq!("parsing took longer than 100 ms", resolution.skipped[0].reason);
+        let libc = &resolution.tree.libraries["libc.so.6"];
+        assert!(libc.found());
+        // libplugin.so, which the fixture libc.so.6 needs, is never looked for
+        assert!(libc.needed.is_empty());
+        assert!(!resolution.tree.libraries.contains_key("libplugin.so"));
+    }
+
+    #[test]
+    fn analyze_with_jobs_should_produce_the_same_result_as_serial() {
+        // The test binary and every library it resolves to
+        let binary = std::env::current_exe().unwrap();
+        let mut fixtures = vec![binary.clone()];
+        fixtures.extend(Resolver::new(PathBuf::from("/")).analyze(&binary).unwrap().tree.libraries.values()
+            .filter(|lib| lib.found())
+            .map(|lib| lib.path.clone()));
+        fixtures.sort();
+        assert!(fixtures.len() > 1);
+        let analyze = |path: &Path, jobs: usize| {
+            let resolution = Resolver::new(PathBuf::from("/")).jobs(jobs).analyze(path).unwrap();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let kind = BinaryKind::detect(path).unwrap();
+            let result = get_topologically_sorted_result(name, path.to_str().unwrap(), kind, &resolution.tree, &AnalysisOptions::default()).unwrap();
+            serde_json::to_string(&result).unwrap()
+        };
+        for fixture in &fixtures {
+            let serial = analyze(fixture, 1);
+            for jobs in [2, 4, 16] {
+                assert_eq!(serial, analyze(fixture, jobs), "{} with {} jobs", fixture.display(), jobs);
+            }
+        }
+    }
+
+    #[test]
+    fn ld_so_conf_paths_should_read_the_conf_of_the_root() {
+        let root = std::env::temp_dir().join(format!("lddtopo-ld-so-conf-{}", std::process::id()));
+        let conf_d = root.join("etc/ld.so.conf.d");
+        std::fs::create_dir_all(&conf_d).unwrap();
+        std::fs::write(conf_d.join("b.conf"), "/opt/b/lib\n").unwrap();
+        std::fs::write(conf_d.join("a.conf"), "# comment\n/opt/a/lib:/opt/a/lib64 # trailing comment\n\nhwcap 0 nosegneg\n").unwrap();
+        std::fs::write(conf_d.join("ignored.txt"), "/opt/ignored\n").unwrap();
+        assert_eq!(vec!["/opt/a/lib", "/opt/a/lib64", "/opt/b/lib"], ld_so_conf_paths(&root));
+
+        std::fs::write(root.join("etc/ld.so.conf"), "/usr/local/lib\ninclude /etc/ld.so.conf.d/b*.conf\ninclude ld.so.conf\n").unwrap();
+        let paths = ld_so_conf_paths(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        // including itself does not read it again
+        assert_eq!(vec!["/usr/local/lib", "/opt/b/lib"], paths);
+    }
+
+    #[test]
+    fn analyze_should_search_the_ld_so_conf_directories_of_the_root() {
+        let root = std::env::temp_dir().join(format!("lddtopo-ld-so-conf-root-{}", std::process::id()));
+        let binary = std::env::current_exe().unwrap();
+        let libc = Resolver::new(PathBuf::from("/")).analyze(&binary).unwrap().tree.libraries["libc.so.6"].realpath.clone().unwrap();
+        std::fs::create_dir_all(root.join("etc")).unwrap();
+        std::fs::create_dir_all(root.join("opt/vendor/lib")).unwrap();
+        std::fs::write(root.join("etc/ld.so.conf"), "/opt/vendor/lib\n").unwrap();
+        std::fs::copy(&libc, root.join("opt/vendor/lib/libc.so.6")).unwrap();
+        let resolution = Resolver::new(root.clone()).analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(root.join("opt/vendor/lib/libc.so.6"), resolution.tree.libraries["libc.so.6"].path);
+    }
+
+    #[test]
+    fn analyze_with_ld_cache_should_resolve_through_the_cache() {
+        let root = std::env::temp_dir().join(format!("lddtopo-ld-cache-root-{}", std::process::id()));
+        let binary = std::env::current_exe().unwrap();
+        let libc = Resolver::new(PathBuf::from("/")).analyze(&binary).unwrap().tree.libraries["libc.so.6"].realpath.clone().unwrap();
+        for dir in ["etc", "opt/cached", "opt/conf"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::copy(&libc, root.join("opt/cached/libc.so.6")).unwrap();
+        std::fs::copy(&libc, root.join("opt/conf/libc.so.6")).unwrap();
+        std::fs::write(root.join("etc/ld.so.conf"), "/opt/conf\n").unwrap();
+        std::fs::write(root.join("etc/ld.so.cache"), crate::ld_cache::tests::new_format(&[("libc.so.6", "/opt/cached/libc.so.6")])).unwrap();
+        let cache = LdCache::load(&root).unwrap();
+        let resolution = Resolver::new(root.clone()).ld_cache(Some(Arc::new(cache))).analyze(&binary).unwrap();
+        let without_cache = Resolver::new(root.clone()).analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(root.join("opt/cached/libc.so.6"), resolution.tree.libraries["libc.so.6"].path);
+        assert_eq!(root.join("opt/conf/libc.so.6"), without_cache.tree.libraries["libc.so.6"].path);
+    }
+
+    #[test]
+    fn find_library_should_search_ld_library_path_after_rpath_and_before_runpath() {
+        let root = std::env::temp_dir().join(format!("lddtopo-ld-library-path-{}", std::process::id()));
+        let binary = std::env::current_exe().unwrap();
+        let elf = read_elf(&binary).unwrap();
+        let libc = Resolver::new(PathBuf::from("/")).analyze(&binary).unwrap().tree.libraries["libc.so.6"].realpath.clone().unwrap();
+        for dir in ["rpath", "env", "runpath"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::copy(&libc, root.join(dir).join("libc.so.6")).unwrap();
+        }
+        let dir = |name: &str| root.join(name).display().to_string();
+        let resolver = Resolver::new(root.clone());
+        let search = SearchPaths { env_ld_paths: vec![dir("env")], ..SearchPaths::default() };
+        let found = |loader: ObjectPaths| resolver.find_library(&search, &loader, &elf, "libc.so.6").0.path;
+        let with_runpath = ObjectPaths { rpaths: vec![dir("rpath")], runpaths: vec![dir("runpath")], has_runpath: true };
+        let with_rpath = ObjectPaths { rpaths: vec![dir("rpath")], runpaths: Vec::new(), has_runpath: false };
+        assert_eq!(root.join("env/libc.so.6"), found(with_runpath));
+        assert_eq!(root.join("rpath/libc.so.6"), found(with_rpath));
+
+        // the directories of ld_library_path are inside the root
+        let resolution = Resolver::new(root.clone()).ld_library_path(Some("/env".to_string())).analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(root.join("env/libc.so.6"), resolution.tree.libraries["libc.so.6"].path);
+    }
+
+    #[test]
+    fn expand_tokens_should_replace_the_dynamic_string_tokens() {
+        let tokens = Tokens { lib: Some("lib64".to_string()), platform: None };
+        assert_eq!(Some("/opt/app/lib/../lib64".to_string()), expand_tokens("$ORIGIN/../$LIB", Some("/opt/app/lib"), &tokens));
+        assert_eq!(Some("/opt/app/lib/../lib64/x".to_string()), expand_tokens("${ORIGIN}/../${LIB}/x", Some("/opt/app/lib"), &tokens));
+        assert_eq!(Some("/opt/$HOME/$ORIGINAL".to_string()), expand_tokens("/opt/$HOME/$ORIGINAL", None, &tokens));
+        assert_eq!(None, expand_tokens("/opt/$PLATFORM", None, &tokens));
+        assert_eq!(None, expand_tokens("$ORIGIN/lib", None, &tokens));
+    }
+
+    /// libc.so.6 needs libplugin.so with a RUNPATH of `$ORIGIN/../plugins`, the other files have no dependencies
+    fn bundled_elf(path: &Path) -> Option<ElfSummary> {
+        let mut elf = read_elf(&std::env::current_exe().unwrap())?;
+        elf.interpreter = None;
+        elf.rpaths = Vec::new();
+        elf.runpaths = Vec::new();
+        elf.needed = Vec::new();
+        if path.file_name()? == "libc.so.6" {
+            elf.needed = vec!["libplugin.so".to_string()];
+            elf.runpaths = vec!["$ORIGIN/../plugins".to_string()];
+        }
+        Some(elf)
+    }
+
+    #[test]
+    fn analyze_should_expand_origin_relative_to_the_real_location_of_each_library() {
+        let root = std::env::temp_dir().join(format!("lddtopo-origin-{}", std::process::id()));
+        let binary = std::env::current_exe().unwrap();
+        for dir in ["bundle/lib", "real/lib", "real/plugins", "bundle/plugins"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("real/lib/libc.so.6"), b"").unwrap();
+        std::fs::write(root.join("real/plugins/libplugin.so"), b"").unwrap();
+        // next to the link, where an $ORIGIN of the link would point
+        std::fs::write(root.join("bundle/plugins/libplugin.so"), b"").unwrap();
+        std::os::unix::fs::symlink(root.join("real/lib/libc.so.6"), root.join("bundle/lib/libc.so.6")).unwrap();
+        let mut resolver = Resolver::new(root.clone()).ld_library_path(Some("/bundle/lib".to_string()));
+        resolver.read_elf = bundled_elf;
+        let resolution = resolver.analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        let libc = &resolution.tree.libraries["libc.so.6"];
+        assert_eq!(root.join("bundle/lib/libc.so.6"), libc.path);
+        assert_eq!(vec![root.join("real/plugins").display().to_string()], libc.runpath);
+        assert_eq!(root.join("real/plugins/libplugin.so"), resolution.tree.libraries["libplugin.so"].path);
+    }
+
+    /// libc.so.6 needs libplugin.so with an absolute RPATH of `/opt/plugins`, the other files have no dependencies
+    fn rpath_elf(path: &Path) -> Option<ElfSummary> {
+        let mut elf = bundled_elf(path)?;
+        if path.file_name()? == "libc.so.6" {
+            elf.runpaths = Vec::new();
+            elf.rpaths = vec!["/opt/plugins".to_string()];
+        }
+        Some(elf)
+    }
+
+    #[test]
+    fn analyze_should_search_absolute_rpath_directories_inside_the_root_once() {
+        let root = std::env::temp_dir().join(format!("lddtopo-rpath-root-{}", std::process::id()));
+        let binary = std::env::current_exe().unwrap();
+        for dir in ["lib", "opt/plugins"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("lib/libc.so.6"), b"").unwrap();
+        std::fs::write(root.join("opt/plugins/libplugin.so"), b"").unwrap();
+        let mut resolver = Resolver::new(root.clone());
+        resolver.read_elf = rpath_elf;
+        let resolution = resolver.analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        let root = std::fs::canonicalize(std::env::temp_dir()).unwrap().join(root.file_name().unwrap());
+        assert_eq!(vec![root.join("opt/plugins").display().to_string()], resolution.tree.libraries["libc.so.6"].rpath);
+        assert_eq!(root.join("opt/plugins/libplugin.so"), resolution.tree.libraries["libplugin.so"].path);
+    }
+
+    #[test]
+    fn musl_ld_paths_should_name_the_directories_inside_the_root() {
+        let root = std::env::temp_dir().join(format!("lddtopo-musl-{}", std::process::id()));
+        assert_eq!(vec!["/lib", "/usr/local/lib", "/usr/lib"], musl_ld_paths(&root));
+        std::fs::create_dir_all(root.join("etc")).unwrap();
+        std::fs::create_dir_all(root.join("opt/musl/lib")).unwrap();
+        std::fs::write(root.join("etc/ld-musl-x86_64.path"), "/opt/musl/lib\n\n/usr/lib\n").unwrap();
+        assert_eq!(vec!["/opt/musl/lib", "/usr/lib"], musl_ld_paths(&root));
+
+        // and the resolver looks for them under the root
+        let binary = std::env::current_exe().unwrap();
+        let target = match crate::multiarch::tuples_of_file(&binary).unwrap().first() {
+            Some(tuple) => Target::parse(&tuple.replacen("-linux-gnu", "-unknown-linux-musl", 1)).unwrap(),
+            None => return std::fs::remove_dir_all(&root).unwrap(),
+        };
+        std::fs::write(root.join("opt/musl/lib/libc.so.6"), b"").unwrap();
+        let mut resolver = Resolver::new(root.clone()).target(Some(target));
+        resolver.read_elf = bundled_elf;
+        let resolution = resolver.analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(root.join("opt/musl/lib/libc.so.6"), resolution.tree.libraries["libc.so.6"].path);
+    }
+
+    #[test]
+    fn analyze_with_hwcaps_should_prefer_the_variants_of_the_assumed_levels() {
+        let root = std::env::temp_dir().join(format!("lddtopo-hwcaps-{}", std::process::id()));
+        let binary = std::env::current_exe().unwrap();
+        let libc = Resolver::new(PathBuf::from("/")).analyze(&binary).unwrap().tree.libraries["libc.so.6"].realpath.clone().unwrap();
+        for dir in ["etc", "opt/lib/glibc-hwcaps/x86-64-v3", "usr/lib/glibc-hwcaps/x86-64-v2"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for lib in ["opt/lib", "opt/lib/glibc-hwcaps/x86-64-v3", "usr/lib/glibc-hwcaps/x86-64-v2"] {
+            std::fs::copy(&libc, root.join(lib).join("libc.so.6")).unwrap();
+        }
+        let levels = |levels: &[&str]| levels.iter().map(|level| level.to_string()).collect::<Vec<_>>();
+        let resolve = |resolver: Resolver| resolver.analyze(&binary).unwrap().tree.libraries["libc.so.6"].path.clone();
+        let in_opt = || Resolver::new(root.clone()).ld_library_path(Some("/opt/lib".to_string()));
+        let from_opt_v3 = resolve(in_opt().hwcaps(levels(&["x86-64-v4", "x86-64-v3", "x86-64-v2"])));
+        let from_opt = resolve(in_opt().hwcaps(levels(&["x86-64-v2"])));
+        let from_opt_without_hwcaps = resolve(in_opt());
+
+        std::fs::write(root.join("etc/ld.so.cache"), crate::ld_cache::tests::new_format(&[
+            ("libc.so.6", "/usr/lib/glibc-hwcaps/x86-64-v2/libc.so.6"),
+            ("libc.so.6", "/opt/lib/libc.so.6"),
+        ])).unwrap();
+        let cache = Arc::new(LdCache::load(&root).unwrap());
+        let from_cache_v2 = resolve(Resolver::new(root.clone()).ld_cache(Some(cache.clone())).hwcaps(levels(&["x86-64-v2"])));
+        let from_cache = resolve(Resolver::new(root.clone()).ld_cache(Some(cache)));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(root.join("opt/lib/glibc-hwcaps/x86-64-v3/libc.so.6"), from_opt_v3);
+        assert_eq!(root.join("opt/lib/libc.so.6"), from_opt);
+        assert_eq!(root.join("opt/lib/libc.so.6"), from_opt_without_hwcaps);
+        assert_eq!(root.join("usr/lib/glibc-hwcaps/x86-64-v2/libc.so.6"), from_cache_v2);
+        assert_eq!(root.join("opt/lib/libc.so.6"), from_cache);
+    }
+
+    #[test]
+    fn analyze_with_target_should_search_its_multiarch_directories() {
+        let binary = std::env::current_exe().unwrap();
+        let target = match crate::multiarch::tuples_of_file(&binary).unwrap().first() {
+            Some(tuple) => Target::parse(&tuple.replacen("-linux", "-unknown-linux", 1)).unwrap(),
+            None => return,
+        };
+        let tuple = target.multiarch.unwrap();
+        let root = std::env::temp_dir().join(format!("lddtopo-target-{}", std::process::id()));
+        let libc = Resolver::new(PathBuf::from("/")).analyze(&binary).unwrap().tree.libraries["libc.so.6"].realpath.clone().unwrap();
+        std::fs::create_dir_all(root.join("usr/lib").join(tuple)).unwrap();
+        std::fs::copy(&libc, root.join("usr/lib").join(tuple).join("libc.so.6")).unwrap();
+        let resolution = Resolver::new(root.clone()).target(Some(target.clone())).analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(root.join("usr/lib").join(tuple).join("libc.so.6"), resolution.tree.libraries["libc.so.6"].path);
+
+        let elf = read_elf(&binary).unwrap();
+        assert_eq!(Some(format!("lib/{}", tuple)), Tokens::for_elf(&Tokens::default(), &elf, Some(tuple)).lib);
+        let lib = Tokens { lib: Some("lib64".to_string()), platform: None };
+        assert_eq!(Some("lib64".to_string()), Tokens::for_elf(&lib, &elf, Some(tuple)).lib);
+    }
+
+    #[test]
+    fn analyze_should_detect_the_multiarch_directories_of_the_root() {
+        let binary = std::env::current_exe().unwrap();
+        let tuple = match crate::multiarch::tuples_of_file(&binary).unwrap().first() {
+            Some(tuple) => *tuple,
+            None => return,
+        };
+        let foreign = if tuple == "aarch64-linux-gnu" { "x86_64-linux-gnu" } else { "aarch64-linux-gnu" };
+        let root = std::env::temp_dir().join(format!("lddtopo-multiarch-{}", std::process::id()));
+        let libc = Resolver::new(PathBuf::from("/")).analyze(&binary).unwrap().tree.libraries["libc.so.6"].realpath.clone().unwrap();
+        std::fs::create_dir_all(&root).unwrap();
+        let in_empty_root = Resolver::new(root.clone()).analyze(&binary).unwrap();
+        for dir in [format!("lib/{}", foreign), format!("lib/{}", tuple)] {
+            std::fs::create_dir_all(root.join(&dir)).unwrap();
+            std::fs::copy(&libc, root.join(&dir).join("libc.so.6")).unwrap();
+        }
+        let resolution = Resolver::new(root.clone()).analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(!in_empty_root.tree.libraries["libc.so.6"].found());
+        assert_eq!(root.join("lib").join(tuple).join("libc.so.6"), resolution.tree.libraries["libc.so.6"].path);
+    }
+
+    /// A `libc.so.6` built by Nix, needing `libplugin.so` from its store RUNPATH and `libz.so.1`, which is in no store path
+    fn nix_elf(path: &Path) -> Option<ElfSummary> {
+        let mut elf = bundled_elf(path)?;
+        if path.file_name()? == "libc.so.6" {
+            elf.needed = vec!["libplugin.so".to_string(), "libz.so.1".to_string()];
+            elf.runpaths = vec!["/nix/store/abc-glibc-2.38/lib".to_string()];
+        }
+        Some(elf)
+    }
+
+    #[test]
+    fn analyze_with_nix_store_should_locate_store_paths_and_skip_the_system_directories() {
+        let dir = std::env::temp_dir().join(format!("lddtopo-nix-{}", std::process::id()));
+        let (root, store) = (dir.join("root"), dir.join("closure/nix/store"));
+        let binary = std::env::current_exe().unwrap();
+        for lib in ["root/app/libc.so.6", "root/lib/libplugin.so", "root/lib/libz.so.1", "closure/nix/store/abc-glibc-2.38/lib/libplugin.so"] {
+            std::fs::create_dir_all(dir.join(lib).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(lib), b"").unwrap();
+        }
+        let mut resolver = Resolver::new(root.clone()).ld_library_path(Some("/app".to_string())).nix_store(Some(NixStore::new(store.clone())));
+        resolver.read_elf = nix_elf;
+        let resolution = resolver.analyze(&binary).unwrap();
+        resolver.nix_store = None;
+        let without_store = resolver.analyze(&binary).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(store.join("abc-glibc-2.38/lib/libplugin.so"), resolution.tree.libraries["libplugin.so"].path);
+        assert!(!resolution.tree.libraries["libz.so.1"].found());
+        // the RUNPATH is missing under the root, the system directories are searched instead
+        assert_eq!(root.join("lib/libplugin.so"), without_store.tree.libraries["libplugin.so"].path);
+        assert!(without_store.tree.libraries["libz.so.1"].found());
+    }
+}
@@ -0,0 +1,146 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// How deep response files may include each other before expansion gives up
+pub const MAX_DEPTH: usize = 16;
+
+/// Splits the content of a response file into arguments. Arguments are separated by any whitespace,
+/// including newlines; single quotes keep everything up to the closing quote literally, double quotes
+/// keep whitespace but still honour `\` escapes, and outside single quotes `\` escapes the next character.
+pub fn split(content: &str) -> Result<Vec<String>, String> {
+    let mut args: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => arg.push(c),
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+fn expand_into(arg: OsString, depth: usize, including: &mut Vec<PathBuf>, expanded: &mut Vec<OsString>) -> Result<(), String> {
+    let path = match arg.to_str().and_then(|s| s.strip_prefix('@')) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => {
+            expanded.push(arg);
+            return Ok(());
+        }
+    };
+    if depth >= MAX_DEPTH {
+        return Err(format!("response file {} is nested deeper than {} levels", path.display(), MAX_DEPTH));
+    }
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if including.contains(&canonical) {
+        return Err(format!("response file {} includes itself", path.display()));
+    }
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| format!("cannot read response file {}: {}", path.display(), err))?;
+    let args = split(&content).map_err(|err| format!("cannot parse response file {}: {}", path.display(), err))?;
+    including.push(canonical);
+    for arg in args {
+        expand_into(OsString::from(arg), depth + 1, including, expanded)?;
+    }
+    including.pop();
+    Ok(())
+}
+
+/// Replaces every `@FILE` argument after the program name by the arguments read from FILE, recursively.
+/// Relative paths, including those of nested response files, are relative to the working directory.
+pub fn expand<I: IntoIterator<Item = OsString>>(args: I) -> Result<Vec<OsString>, String> {
+    let mut args = args.into_iter();
+    let mut expanded: Vec<OsString> = args.next().into_iter().collect();
+    for arg in args {
+        expand_into(arg, 0, &mut Vec::new(), &mut expanded)?;
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::response_file::{expand, split};
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    fn os(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    /// A fresh directory of the test named `test`, which the test removes again
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lddtopo-rs-response-file-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn split_should_honour_quotes_and_escapes() {
+        let args = split("--library-paths /opt/a\n  '/opt/with space' \"/opt/\\\"quoted\\\"\"\n--root-path=/sys\\ root ''").unwrap();
+        assert_eq!(vec!["--library-paths", "/opt/a", "/opt/with space", "/opt/\"quoted\"", "--root-path=/sys root", ""], args);
+        assert!(split("'open").is_err());
+        assert!(split("\"open").is_err());
+        assert!(split("trailing\\").is_err());
+    }
+
+    #[test]
+    fn expand_should_replace_nested_response_files() {
+        let dir = temp_dir("nested");
+        let inner = dir.join("inner.rsp");
+        let outer = dir.join("outer.rsp");
+        std::fs::write(&inner, "--library-paths /opt/b").unwrap();
+        std::fs::write(&outer, format!("--library-paths /opt/a\n@{}\n", inner.display())).unwrap();
+        let expanded = expand(os(&["lddtopo-rs", "--machine", &format!("@{}", outer.display()), "@"]));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let expanded = expanded.unwrap();
+        assert_eq!(os(&["lddtopo-rs", "--machine", "--library-paths", "/opt/a", "--library-paths", "/opt/b", "@"]), expanded);
+        // The program name is never expanded
+        assert_eq!(os(&["@prog"]), expand(os(&["@prog"])).unwrap());
+    }
+
+    #[test]
+    fn expand_should_reject_missing_and_recursive_response_files() {
+        let dir = temp_dir("loop");
+        let looping = dir.join("loop.rsp");
+        std::fs::write(&looping, format!("--machine @{}", looping.display())).unwrap();
+        let expanded = expand(os(&["lddtopo-rs", &format!("@{}", looping.display())]));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(expanded.unwrap_err().contains("includes itself"));
+        assert!(expand(os(&["lddtopo-rs", "@/nonexistent/args.rsp"])).unwrap_err().starts_with("cannot read response file"));
+    }
+}
@@ -0,0 +1,244 @@
+use goblin::elf::Elf;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::diagnostics::Severity;
+use crate::{Lib, TopoSortResult};
+
+/// The dynamic section entry a search path comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathTag {
+    Rpath,
+    Runpath,
+}
+
+impl PathTag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PathTag::Rpath => "RPATH",
+            PathTag::Runpath => "RUNPATH",
+        }
+    }
+}
+
+/// What makes a search path entry insecure, see `check --rpath-audit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpathProblem {
+    /// Neither absolute nor starting with `$ORIGIN`, so it is resolved against the working directory of the process
+    Relative,
+    /// An empty component, which the dynamic linker reads as the working directory
+    Empty,
+    /// The directory or one of its parents can be written by anyone, who can then plant libraries in it
+    WorldWritable,
+    /// `$ORIGIN` followed by enough `..` to leave the install prefix
+    OriginEscape,
+}
+
+impl RpathProblem {
+    /// The diagnostic code the problem is reported with
+    pub fn code(&self) -> &'static str {
+        match self {
+            RpathProblem::Relative => "relative_rpath",
+            RpathProblem::Empty => "empty_rpath_entry",
+            RpathProblem::WorldWritable => "world_writable_rpath",
+            RpathProblem::OriginEscape => "rpath_origin_escape",
+        }
+    }
+
+    /// Entries that let whoever controls the working directory or a shared directory inject a library fail the
+    /// check, an escape from the install prefix may be deliberate and only warns
+    pub fn severity(&self) -> Severity {
+        match self {
+            RpathProblem::OriginEscape => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpathFinding {
+    pub library: String,
+    pub tag: PathTag,
+    /// The entry as written in the file, e.g. `$ORIGIN/../lib`
+    pub entry: String,
+    pub problem: RpathProblem,
+}
+
+impl RpathFinding {
+    pub fn describe(&self) -> String {
+        let problem = match self.problem {
+            RpathProblem::Relative => "is relative to the working directory",
+            RpathProblem::Empty => "is empty, which means the working directory",
+            RpathProblem::WorldWritable => "is in a world-writable directory",
+            RpathProblem::OriginEscape => "leaves the install prefix",
+        };
+        format!("The {} entry \"{}\" of {} {}", self.tag.as_str(), self.entry, self.library, problem)
+    }
+}
+
+/// The DT_RPATH and DT_RUNPATH entries of `elf` as written, split at `:` and with empty components kept
+pub fn search_path_entries(elf: &Elf) -> Vec<(PathTag, String)> {
+    let split = |tag: PathTag, paths: &[&str]| -> Vec<(PathTag, String)> {
+        paths.iter().flat_map(|path| path.split(':')).map(|entry| (tag, entry.to_string())).collect()
+    };
+    let mut entries = split(PathTag::Rpath, &elf.rpaths);
+    entries.extend(split(PathTag::Runpath, &elf.runpaths));
+    entries
+}
+
+/// The search path entries of the ELF file at `path`, `None` when it cannot be read or parsed
+pub fn read_search_path_entries(path: &Path) -> Option<Vec<(PathTag, String)>> {
+    let bytes = std::fs::read(path).ok()?;
+    let elf = Elf::parse(&bytes).ok()?;
+    Some(search_path_entries(&elf))
+}
+
+/// Whether anyone can create files in `dir`, false when it does not exist
+pub fn is_world_writable(dir: &Path) -> bool {
+    std::fs::metadata(dir).map(|metadata| metadata.is_dir() && metadata.permissions().mode() & 0o002 != 0).unwrap_or(false)
+}
+
+/// Resolves `.` and `..` without touching the filesystem, like the dynamic linker does
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// The rest of `entry` after the first of `tokens` it starts with as a whole path component, e.g. `/lib` for
+/// `$ORIGIN/lib` but nothing for `$ORIGINAL/lib`
+fn after_token<'a>(entry: &'a str, tokens: &[&str]) -> Option<&'a str> {
+    tokens.iter()
+        .filter_map(|token| entry.strip_prefix(token))
+        .find(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The directory `entry` stands for when it starts with `$ORIGIN` or `${ORIGIN}`, `origin` being the directory
+/// of the file
+fn expand_origin(entry: &str, origin: &Path) -> Option<PathBuf> {
+    let rest = after_token(entry, &["${ORIGIN}", "$ORIGIN"])?;
+    Some(origin.join(rest.trim_start_matches('/')))
+}
+
+/// Whether `entry` starts with `$LIB` or `$PLATFORM`, the dynamic loader fills them in from its own configuration
+/// like it does `$ORIGIN`
+fn starts_with_loader_token(entry: &str) -> bool {
+    after_token(entry, &["$LIB", "${LIB}", "$PLATFORM", "${PLATFORM}"]).is_some()
+}
+
+/// The problems of one search path entry of a file in `origin`. `prefix` is the install prefix `$ORIGIN` entries
+/// have to stay in, `world_writable` tells whether a directory is world-writable. `$LIB` and `$PLATFORM` are
+/// accepted and kept as directory names, their values depend on the dynamic loader.
+pub fn audit_entry(entry: &str, origin: &Path, prefix: &Path, world_writable: impl Fn(&Path) -> bool) -> Vec<RpathProblem> {
+    if entry.is_empty() {
+        return vec![RpathProblem::Empty];
+    }
+    let mut problems = Vec::new();
+    let dir = match expand_origin(entry, origin) {
+        Some(dir) => {
+            let dir = normalize(&dir);
+            if !dir.starts_with(normalize(prefix)) {
+                problems.push(RpathProblem::OriginEscape);
+            }
+            dir
+        }
+        None if entry.starts_with('/') => normalize(Path::new(entry)),
+        None if starts_with_loader_token(entry) => return problems,
+        None => return vec![RpathProblem::Relative],
+    };
+    if dir.ancestors().any(world_writable) {
+        problems.push(RpathProblem::WorldWritable);
+    }
+    problems
+}
+
+/// The install prefix of the analyzed binary when none is given: the parent of its directory, e.g. `/opt/app`
+/// for `/opt/app/bin/app`, so that the usual `$ORIGIN/../lib` stays inside
+pub fn default_install_prefix(result: &TopoSortResult, main_lib_name: &str) -> Option<PathBuf> {
+    let main = result.topo_sorted_libs.iter().find(|lib| lib.name == main_lib_name)?;
+    let dir = Path::new(main.path.as_deref()?).parent()?;
+    Some(dir.parent().unwrap_or(dir).to_path_buf())
+}
+
+/// Audits the search path entries of every resolved library of `result` in load order. `entries` gives the
+/// entries of a library, see `read_search_path_entries`.
+pub fn audit(result: &TopoSortResult, prefix: &Path, entries: impl Fn(&Lib) -> Option<Vec<(PathTag, String)>>,
+             world_writable: impl Fn(&Path) -> bool) -> Vec<RpathFinding> {
+    let mut findings = Vec::new();
+    for lib in &result.topo_sorted_libs {
+        let origin = match lib.path.as_deref().and_then(|path| Path::new(path).parent()) {
+            Some(origin) => origin,
+            None => continue,
+        };
+        for (tag, entry) in entries(lib).unwrap_or_default() {
+            for problem in audit_entry(&entry, origin, prefix, &world_writable) {
+                findings.push(RpathFinding { library: lib.name.clone(), tag, entry: entry.clone(), problem });
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::diagnostics::Severity;
+    use crate::query::tests::small_dag;
+    use crate::rpath_audit::{audit, audit_entry, default_install_prefix, PathTag, RpathFinding, RpathProblem};
+    use std::path::Path;
+
+    #[test]
+    fn audit_entry_should_flag_insecure_entries() {
+        let origin = Path::new("/opt/app/bin");
+        let prefix = Path::new("/opt/app");
+        let tmp = |dir: &Path| dir == Path::new("/tmp");
+        assert!(audit_entry("$ORIGIN/../lib", origin, prefix, tmp).is_empty());
+        assert!(audit_entry("/usr/lib/app", origin, prefix, tmp).is_empty());
+        assert_eq!(vec![RpathProblem::Empty], audit_entry("", origin, prefix, tmp));
+        assert_eq!(vec![RpathProblem::Relative], audit_entry("lib", origin, prefix, tmp));
+        assert_eq!(vec![RpathProblem::Relative], audit_entry("./lib", origin, prefix, tmp));
+        for entry in ["$LIB", "${LIB}/app", "$PLATFORM/lib", "${PLATFORM}", "/usr/$LIB", "$ORIGIN/../${LIB}"] {
+            assert!(audit_entry(entry, origin, prefix, tmp).is_empty(), "{}", entry);
+        }
+        assert_eq!(vec![RpathProblem::Relative], audit_entry("$LIBRARY/app", origin, prefix, tmp));
+        // not $ORIGIN followed by AL/lib
+        assert_eq!(vec![RpathProblem::Relative], audit_entry("$ORIGINAL/lib", origin, prefix, tmp));
+        assert_eq!(vec![RpathProblem::Relative], audit_entry("${ORIGIN}AL/lib", origin, prefix, tmp));
+        assert!(audit_entry("$ORIGIN", origin, prefix, tmp).is_empty());
+        assert_eq!(vec![RpathProblem::WorldWritable], audit_entry("/tmp/$PLATFORM", origin, prefix, tmp));
+        assert_eq!(vec![RpathProblem::OriginEscape], audit_entry("${ORIGIN}/../../lib", origin, prefix, tmp));
+        assert_eq!(vec![RpathProblem::WorldWritable], audit_entry("/tmp/build/lib", origin, prefix, tmp));
+        assert_eq!(vec![RpathProblem::OriginEscape, RpathProblem::WorldWritable],
+            audit_entry("$ORIGIN/../../../tmp", origin, prefix, tmp));
+        assert_eq!(Severity::Warning, RpathProblem::OriginEscape.severity());
+        assert_eq!(Severity::Error, RpathProblem::Empty.severity());
+    }
+
+    #[test]
+    fn audit_should_report_the_entries_of_every_resolved_library() {
+        let mut result = small_dag();
+        for lib in &mut result.topo_sorted_libs {
+            lib.path = Some(format!("/opt/app/{}/{}", if lib.name == "A" { "bin" } else { "lib" }, lib.name));
+        }
+        assert_eq!(Some(Path::new("/opt/app").to_path_buf()), default_install_prefix(&result, "A"));
+        let entries = |lib: &crate::Lib| match lib.name.as_str() {
+            "A" => Some(vec![(PathTag::Runpath, "$ORIGIN/../lib".to_string()), (PathTag::Runpath, String::new())]),
+            "D" => Some(vec![(PathTag::Rpath, "build/lib".to_string())]),
+            _ => None,
+        };
+        let findings = audit(&result, Path::new("/opt/app"), entries, |_| false);
+        assert_eq!(vec![
+            RpathFinding { library: "D".to_string(), tag: PathTag::Rpath, entry: "build/lib".to_string(), problem: RpathProblem::Relative },
+            RpathFinding { library: "A".to_string(), tag: PathTag::Runpath, entry: String::new(), problem: RpathProblem::Empty },
+        ], findings);
+        assert_eq!("The RPATH entry \"build/lib\" of D is relative to the working directory", findings[0].describe());
+    }
+}
@@ -0,0 +1,160 @@
+use regex::Regex;
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::{Edge, EdgeKind, Lib, TopoSortResult};
+
+/// A library load seen in a runtime trace, see `--runtime-trace`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracedLoad {
+    /// File name of the loaded library
+    pub library: String,
+    /// The file it was loaded from, when the trace tells
+    pub path: Option<String>,
+    /// File name of the library that needed or `dlopen`ed it, when the trace tells
+    pub loaded_by: Option<String>,
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path).to_string()
+}
+
+/// The loads of an `LD_DEBUG=libs` or `LD_DEBUG=files` log, or of an `ltrace` log of `dlopen` calls, in the order
+/// of the trace without duplicates. Lines of other formats are ignored.
+///
+/// * `find library=libfoo.so.1 [0]; searching`, followed by `trying file=/usr/lib/libfoo.so.1` (`LD_DEBUG=libs`)
+/// * `file=libfoo.so.1 [0];  needed by /usr/bin/app [0]` or `dynamically loaded by` (`LD_DEBUG=files`)
+/// * `libapp.so->dlopen("libfoo.so.1", 2) = 0x55d1c1e0` or without the caller (`ltrace`), failed calls are skipped
+pub fn parse_trace(trace: &str) -> Vec<TracedLoad> {
+    let pid = Regex::new(r"^\s*\d+:\s*").unwrap();
+    let find = Regex::new(r"^find library=(\S+) \[\d+\]; searching").unwrap();
+    let trying = Regex::new(r"^trying file=(\S+)").unwrap();
+    let loaded_by = Regex::new(r"^file=(\S+) \[\d+\];\s+(?:needed|dynamically loaded) by (\S+) \[\d+\]").unwrap();
+    let dlopen = Regex::new(r#"^(?:(\S+)->)?dlm?open\((?:[^,]*, )?"([^"]+)".*\)\s*=\s*(\S+)"#).unwrap();
+    let mut loads: Vec<TracedLoad> = Vec::new();
+    let mut searching: Option<usize> = None;
+    for line in trace.lines() {
+        let line = pid.replace(line, "");
+        let line = line.trim_end();
+        let load = if let Some(captures) = find.captures(line) {
+            Some(TracedLoad { library: file_name(&captures[1]), path: None, loaded_by: None })
+        } else if let Some(captures) = trying.captures(line) {
+            // the last file tried is the one that was found
+            if let Some(idx) = searching {
+                loads[idx].path = Some(captures[1].to_string());
+            }
+            continue;
+        } else if let Some(captures) = loaded_by.captures(line) {
+            let path = captures[1].contains('/').then(|| captures[1].to_string());
+            Some(TracedLoad { library: file_name(&captures[1]), path, loaded_by: Some(file_name(&captures[2])) })
+        } else if let Some(captures) = dlopen.captures(line) {
+            let failed = matches!(&captures[3], "0" | "nil" | "0x0");
+            let path = captures[2].contains('/').then(|| captures[2].to_string());
+            (!failed).then(|| TracedLoad { library: file_name(&captures[2]), path, loaded_by: captures.get(1).map(|m| file_name(m.as_str())) })
+        } else {
+            None
+        };
+        if let Some(load) = load {
+            let is_search = find.is_match(line);
+            match loads.iter().position(|l| l.library == load.library && l.loaded_by == load.loaded_by) {
+                Some(idx) => searching = is_search.then_some(idx),
+                None => {
+                    loads.push(load);
+                    searching = is_search.then_some(loads.len() - 1);
+                }
+            }
+        }
+    }
+    loads
+}
+
+/// Adds the loads of a trace that the static graph misses to `runtime_edges` and `runtime_libs`. A load by an
+/// unknown library is attributed to `main_lib_name`, unless the library already is in the graph, which then
+/// explains it. Loads that match a NEEDED edge are not repeated. Returns the number of edges added.
+pub fn merge_trace(result: &mut TopoSortResult, loads: &[TracedLoad], main_lib_name: &str) -> usize {
+    let vertices: HashSet<&str> = result.vertices.iter().map(String::as_str).collect();
+    let needed: HashSet<(&str, &str)> = result.edges.iter().map(|e| (e.src.as_str(), e.dst.as_str())).collect();
+    let mut runtime_libs: BTreeMap<String, Lib> = BTreeMap::new();
+    let mut runtime_edges: Vec<Edge> = Vec::new();
+    for load in loads {
+        let known = |name: &str| vertices.contains(name) || runtime_libs.contains_key(name);
+        let dst = match &load.loaded_by {
+            Some(loaded_by) if known(loaded_by) => loaded_by.as_str(),
+            _ if vertices.contains(load.library.as_str()) => continue,
+            _ => main_lib_name,
+        };
+        if load.library == dst || needed.contains(&(load.library.as_str(), dst)) {
+            continue;
+        }
+        if !vertices.contains(load.library.as_str()) {
+            runtime_libs.entry(load.library.clone())
+                .or_insert_with(|| Lib { name: load.library.clone(), path: load.path.clone(), ..Default::default() });
+        }
+        let edge = Edge { src: load.library.clone(), dst: dst.to_string(), kind: EdgeKind::Runtime, ..Default::default() };
+        if !runtime_edges.contains(&edge) {
+            runtime_edges.push(edge);
+        }
+    }
+    runtime_edges.sort();
+    let added = runtime_edges.len();
+    result.runtime_edges = runtime_edges;
+    result.runtime_libs = runtime_libs.into_values().collect();
+    added
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::query::tests::small_dag;
+    use crate::runtime_trace::{merge_trace, parse_trace, TracedLoad};
+
+    fn load(library: &str, path: Option<&str>, loaded_by: Option<&str>) -> TracedLoad {
+        TracedLoad { library: library.to_string(), path: path.map(String::from), loaded_by: loaded_by.map(String::from) }
+    }
+
+    #[test]
+    fn parse_trace_should_read_ld_debug_and_ltrace_logs() {
+        let trace = "\
+     4242:	find library=B [0]; searching
+     4242:	 search cache=/etc/ld.so.cache
+     4242:	  trying file=/opt/lib/B
+     4242:	  trying file=/usr/lib/B
+     4242:
+     4242:	file=libplugin.so [0];  dynamically loaded by /usr/lib/C [0]
+     4242:	file=/opt/plugins/libext.so [0];  needed by /usr/lib/libplugin.so [0]
+     4242:	find library=libnss_files.so.2 [0]; searching
+A->dlopen(\"libcodec.so.3\", 1) = 0x55d1c1e0
+dlopen(\"libmissing.so\", 2) = 0
+dlopen(\"/opt/lib/libgui.so\", 258) = 0x55d1c2f0
+";
+        assert_eq!(vec![
+            load("B", Some("/usr/lib/B"), None),
+            load("libplugin.so", None, Some("C")),
+            load("libext.so", Some("/opt/plugins/libext.so"), Some("libplugin.so")),
+            load("libnss_files.so.2", None, None),
+            load("libcodec.so.3", None, Some("A")),
+            load("libgui.so", Some("/opt/lib/libgui.so"), None),
+        ], parse_trace(trace));
+    }
+
+    #[test]
+    fn merge_trace_should_add_what_the_static_graph_misses() {
+        let mut result = small_dag();
+        let loads = vec![
+            // already explained by the static graph
+            load("B", Some("/usr/lib/B"), None),
+            load("D", None, Some("B")),
+            // a runtime edge between libraries of the graph
+            load("F", None, Some("C")),
+            load("libplugin.so", Some("/opt/lib/libplugin.so"), Some("C")),
+            load("libext.so", None, Some("libplugin.so")),
+            load("libnss_files.so.2", None, None),
+        ];
+        assert_eq!(4, merge_trace(&mut result, &loads, "A"));
+        let edges: Vec<(&str, &str)> = result.runtime_edges.iter().map(|e| (e.src.as_str(), e.dst.as_str())).collect();
+        assert_eq!(vec![("F", "C"), ("libext.so", "libplugin.so"), ("libnss_files.so.2", "A"), ("libplugin.so", "C")], edges);
+        let libs: Vec<(&str, Option<&str>)> = result.runtime_libs.iter().map(|l| (l.name.as_str(), l.path.as_deref())).collect();
+        assert_eq!(vec![("libext.so", None), ("libnss_files.so.2", None), ("libplugin.so", Some("/opt/lib/libplugin.so"))], libs);
+        assert_eq!(6, result.vertices.len());
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::binary_kind::BinaryKind;
+use crate::query::main_library;
+use crate::{Lib, TopoSortResult};
+
+/// Standard of the `sbom` subcommand
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON, see `to_cyclonedx`
+    Cyclonedx,
+    /// SPDX 2.3 JSON, see `to_spdx`
+    Spdx,
+}
+
+/// A CycloneDX 1.5 JSON document, only the parts that describe a set of libraries and their dependencies
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Bom {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: Metadata,
+    components: Vec<Component>,
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Serialize, Debug)]
+struct Metadata {
+    tools: Tools,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    component: Option<Component>,
+}
+
+#[derive(Serialize, Debug)]
+struct Tools {
+    components: Vec<Tool>,
+}
+
+#[derive(Serialize, Debug)]
+struct Tool {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+struct Component {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: String,
+    name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evidence: Option<Evidence>,
+}
+
+#[derive(Serialize, Debug)]
+struct Hash {
+    alg: &'static str,
+    content: String,
+}
+
+/// Where the library was found, CycloneDX has no other place for a file path
+#[derive(Serialize, Debug)]
+struct Evidence {
+    occurrences: Vec<Occurrence>,
+}
+
+#[derive(Serialize, Debug)]
+struct Occurrence {
+    location: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Dependency {
+    #[serde(rename = "ref")]
+    reference: String,
+    depends_on: Vec<String>,
+}
+
+/// `result` as a CycloneDX 1.5 SBOM. The main library is the `metadata.component`, an `application` when it is
+/// an executable, and every other library of the graph a `library` component, referenced by its name. The path
+/// a library was resolved to is recorded as an evidence occurrence, and `sha256` holds the hashes of the files by
+/// library name; libraries without a path or hash are listed with the name only. `dependencies` has an entry per
+/// library with the ones it needs directly.
+pub fn to_cyclonedx(result: &TopoSortResult, sha256: &BTreeMap<String, String>) -> Bom {
+    let paths: BTreeMap<&str, &str> = result.topo_sorted_libs.iter()
+        .filter_map(|lib| lib.path.as_deref().map(|path| (lib.name.as_str(), path)))
+        .collect();
+    let component = |name: &str, kind: &'static str| Component {
+        kind,
+        bom_ref: name.to_string(),
+        name: name.to_string(),
+        hashes: sha256.get(name).map(|content| Hash { alg: "SHA-256", content: content.clone() }).into_iter().collect(),
+        evidence: paths.get(name).map(|path| Evidence { occurrences: vec![Occurrence { location: path.to_string() }] }),
+    };
+    let main = main_library(result);
+    let mut depends_on: BTreeMap<&str, BTreeSet<&str>> = result.vertices.iter().map(|v| (v.as_str(), BTreeSet::new())).collect();
+    for edge in &result.edges {
+        depends_on.entry(edge.dst.as_str()).or_default().insert(edge.src.as_str());
+    }
+    Bom {
+        bom_format: "CycloneDX",
+        spec_version: "1.5",
+        version: 1,
+        metadata: Metadata {
+            tools: Tools { components: vec![Tool { kind: "application", name: "lddtopo-rs", version: env!("CARGO_PKG_VERSION") }] },
+            component: main.map(|name| match result.kind {
+                BinaryKind::Executable | BinaryKind::PieExecutable => component(name, "application"),
+                BinaryKind::SharedLibrary | BinaryKind::Other => component(name, "library"),
+            }),
+        },
+        components: result.vertices.iter()
+            .filter(|v| Some(v.as_str()) != main)
+            .map(|v| component(v, "library"))
+            .collect(),
+        dependencies: depends_on.into_iter()
+            .map(|(name, needed)| Dependency { reference: name.to_string(), depends_on: needed.into_iter().map(str::to_string).collect() })
+            .collect(),
+    }
+}
+
+/// The SPDX license identifiers the licenses of libraries are mapped to, compared case-insensitively
+const SPDX_LICENSES: [&str; 52] = [
+    "0BSD", "AFL-2.1", "Apache-1.1", "Apache-2.0", "Artistic-1.0", "Artistic-2.0", "BSD-1-Clause", "BSD-2-Clause",
+    "BSD-3-Clause", "BSD-4-Clause", "BSL-1.0", "bzip2-1.0.6", "CC0-1.0", "CC-BY-4.0", "CDDL-1.0", "curl", "EPL-1.0",
+    "EPL-2.0", "FTL", "GFDL-1.3-or-later", "GPL-1.0-only", "GPL-1.0-or-later", "GPL-2.0-only", "GPL-2.0-or-later",
+    "GPL-3.0-only", "GPL-3.0-or-later", "HPND", "ICU", "IJG", "ISC", "LGPL-2.0-only", "LGPL-2.0-or-later",
+    "LGPL-2.1-only", "LGPL-2.1-or-later", "LGPL-3.0-only", "LGPL-3.0-or-later", "libtiff", "Libpng", "libpng-2.0", "MIT",
+    "MPL-1.1", "MPL-2.0", "NCSA", "OpenSSL", "PSF-2.0", "Python-2.0", "Unicode-DFS-2016", "Unlicense", "X11", "Zlib",
+    "zlib-acknowledgement", "AGPL-3.0-or-later",
+];
+
+/// The SPDX license exceptions that may follow `WITH`
+const SPDX_EXCEPTIONS: [&str; 5] = ["GCC-exception-3.1", "Autoconf-exception-3.0", "Classpath-exception-2.0", "LLVM-exception", "OpenSSL-exception"];
+
+/// The SPDX identifier of the license `name`, which may also be a Debian short name like `GPL-2+` or `Expat`, or a
+/// deprecated SPDX identifier like `GPL-2.0+`
+fn spdx_license_id(name: &str) -> Option<&'static str> {
+    let find = |name: &str| SPDX_LICENSES.iter().find(|id| id.eq_ignore_ascii_case(name)).copied();
+    if let Some(id) = find(name) {
+        return Some(id);
+    }
+    let (base, or_later) = match name.strip_suffix('+') {
+        Some(base) => (base, true),
+        None => (name, false),
+    };
+    if base.eq_ignore_ascii_case("expat") {
+        return Some("MIT");
+    }
+    let (family, version) = base.split_once('-')?;
+    if !["GPL", "LGPL", "AGPL"].iter().any(|gpl| gpl.eq_ignore_ascii_case(family)) {
+        return None;
+    }
+    let version = if version.contains('.') { version.to_string() } else { format!("{}.0", version) };
+    find(&format!("{}-{}-{}", family, version, if or_later { "or-later" } else { "only" }))
+}
+
+/// `LicenseRef-` and `name` with the characters SPDX does not allow in it replaced by `-`
+fn license_ref(name: &str) -> String {
+    let sanitized: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '-' }).collect();
+    format!("LicenseRef-{}", sanitized)
+}
+
+/// `license` as an SPDX license expression, with the licenses SPDX does not know as `LicenseRef-` references, which
+/// are returned along with the names they stand for. `None` when it is not an expression, e.g. has unbalanced
+/// parentheses, dangling operators or an unknown exception
+fn spdx_expression(license: &str) -> Option<(String, BTreeMap<String, String>)> {
+    #[derive(PartialEq)]
+    enum Expect { License, Exception, Operator }
+    let spaced = license.replace('(', " ( ").replace(')', " ) ");
+    let mut expression = String::new();
+    let mut refs: BTreeMap<String, String> = BTreeMap::new();
+    // WITH only follows a license, not a parenthesized expression or an exception
+    let (mut depth, mut expect, mut after_license) = (0usize, Expect::License, false);
+    for token in spaced.split_whitespace() {
+        let operator = token.to_ascii_uppercase();
+        match (&expect, operator.as_str()) {
+            (Expect::License, "(") => {
+                depth += 1;
+                expression.push('(');
+                continue;
+            }
+            (Expect::Operator, ")") if depth > 0 => {
+                depth -= 1;
+                expression.push(')');
+                after_license = false;
+                continue;
+            }
+            (Expect::Operator, "AND" | "OR") => expect = Expect::License,
+            (Expect::Operator, "WITH") if after_license => expect = Expect::Exception,
+            (Expect::Exception, _) => {
+                expression.push_str(SPDX_EXCEPTIONS.iter().find(|id| id.eq_ignore_ascii_case(token))?);
+                expect = Expect::Operator;
+                after_license = false;
+                continue;
+            }
+            (Expect::License, _) if token != ")" => {
+                match spdx_license_id(token) {
+                    Some(id) => expression.push_str(id),
+                    None => {
+                        let reference = license_ref(token);
+                        expression.push_str(&reference);
+                        refs.insert(reference, token.to_string());
+                    }
+                }
+                expect = Expect::Operator;
+                after_license = true;
+                continue;
+            }
+            _ => return None,
+        }
+        expression.push_str(&format!(" {} ", operator));
+    }
+    match (depth, expect) {
+        (0, Expect::Operator) => Some((expression, refs)),
+        _ => None,
+    }
+}
+
+/// An SPDX 2.3 JSON document, only the parts that describe a set of libraries and their dependencies
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxDocument {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: CreationInfo,
+    packages: Vec<Package>,
+    relationships: Vec<Relationship>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    has_extracted_licensing_infos: Vec<ExtractedLicensingInfo>,
+}
+
+/// A license SPDX has no identifier for, referred to as `LicenseRef-...` by the packages
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExtractedLicensingInfo {
+    license_id: String,
+    name: String,
+    extracted_text: String,
+}
+
+#[derive(Serialize, Debug)]
+struct CreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Package {
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package_file_name: Option<String>,
+    download_location: &'static str,
+    files_analyzed: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<Checksum>,
+    license_concluded: String,
+    license_declared: String,
+    copyright_text: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Checksum {
+    algorithm: &'static str,
+    checksum_value: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Relationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+/// `result` as an SPDX 2.3 document created at `created`, an RFC 3339 UTC timestamp, see `utc_timestamp`. Every
+/// library of the graph is a package `SPDXRef-Package-<index>` in `vertices` order with the file it was resolved
+/// to, the hash from `sha256` and the license of `--license-map` as an SPDX expression, `NOASSERTION` for what is
+/// unknown or no expression. Licenses SPDX does not know are `LicenseRef-` references, defined in
+/// `hasExtractedLicensingInfos`. The document
+/// describes the main library, and every NEEDED edge is a `DEPENDS_ON` relationship from the dependent.
+pub fn to_spdx(result: &TopoSortResult, sha256: &BTreeMap<String, String>, created: &str) -> SpdxDocument {
+    let libs: BTreeMap<&str, &Lib> = result.topo_sorted_libs.iter().map(|lib| (lib.name.as_str(), lib)).collect();
+    let ids: BTreeMap<&str, String> = result.vertices.iter().enumerate()
+        .map(|(idx, v)| (v.as_str(), format!("SPDXRef-Package-{}", idx)))
+        .collect();
+    let main = main_library(result);
+    let name = main.unwrap_or("lddtopo-rs").to_string();
+    let mut license_refs: BTreeMap<String, String> = BTreeMap::new();
+    let packages = result.vertices.iter()
+        .map(|v| {
+            let lib = libs.get(v.as_str());
+            let license = match lib.and_then(|lib| lib.license.as_deref()).and_then(spdx_expression) {
+                Some((expression, refs)) => {
+                    license_refs.extend(refs);
+                    expression
+                }
+                None => "NOASSERTION".to_string(),
+            };
+            Package {
+                spdx_id: ids[v.as_str()].clone(),
+                name: v.clone(),
+                package_file_name: lib.and_then(|lib| lib.path.clone()),
+                download_location: "NOASSERTION",
+                files_analyzed: false,
+                checksums: sha256.get(v).map(|value| Checksum { algorithm: "SHA256", checksum_value: value.clone() }).into_iter().collect(),
+                license_concluded: license.clone(),
+                license_declared: license,
+                copyright_text: "NOASSERTION",
+            }
+        })
+        .collect();
+    let describes = main.map(|main| Relationship {
+        spdx_element_id: "SPDXRef-DOCUMENT".to_string(),
+        relationship_type: "DESCRIBES",
+        related_spdx_element: ids[main].clone(),
+    });
+    let depends_on = result.edges.iter()
+        .filter_map(|edge| match (ids.get(edge.dst.as_str()), ids.get(edge.src.as_str())) {
+            (Some(dependent), Some(dependency)) => Some(Relationship {
+                spdx_element_id: dependent.clone(),
+                relationship_type: "DEPENDS_ON",
+                related_spdx_element: dependency.clone(),
+            }),
+            _ => None,
+        });
+    SpdxDocument {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        document_namespace: format!("https://spdx.org/spdxdocs/lddtopo-rs/{}-{}", name, created),
+        name,
+        creation_info: CreationInfo { created: created.to_string(), creators: vec![format!("Tool: lddtopo-rs-{}", env!("CARGO_PKG_VERSION"))] },
+        packages,
+        relationships: describes.into_iter().chain(depends_on).collect(),
+        has_extracted_licensing_infos: license_refs.into_iter()
+            .map(|(license_id, name)| ExtractedLicensingInfo { extracted_text: format!("The {} license", name), license_id, name })
+            .collect(),
+    }
+}
+
+/// `secs` since the Unix epoch as an RFC 3339 UTC timestamp, e.g. `2023-11-14T22:13:20Z`
+pub fn utc_timestamp(secs: u64) -> String {
+    // Days to a civil date, http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    let time = secs % 86400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::query::tests::small_dag;
+    use crate::sbom::{spdx_expression, to_cyclonedx, to_spdx, utc_timestamp};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn to_cyclonedx_should_list_components_and_dependencies() {
+        let result = small_dag();
+        let sha256 = BTreeMap::from([("D".to_string(), "ab".repeat(32))]);
+        let bom = serde_json::to_value(to_cyclonedx(&result, &sha256)).unwrap();
+        assert_eq!(json!("CycloneDX"), bom["bomFormat"]);
+        assert_eq!(json!("1.5"), bom["specVersion"]);
+        assert_eq!(json!("A"), bom["metadata"]["component"]["bom-ref"]);
+        let components = bom["components"].as_array().unwrap();
+        assert_eq!(result.vertices.len() - 1, components.len());
+        let d = components.iter().find(|c| c["name"] == "D").unwrap();
+        assert_eq!(json!({
+            "type": "library",
+            "bom-ref": "D",
+            "name": "D",
+            "hashes": [{"alg": "SHA-256", "content": "ab".repeat(32)}],
+            "evidence": {"occurrences": [{"location": "/usr/lib/D"}]}
+        }), *d);
+        let dependencies = bom["dependencies"].as_array().unwrap();
+        assert_eq!(result.vertices.len(), dependencies.len());
+        assert!(dependencies.contains(&json!({"ref": "A", "dependsOn": ["B", "C", "F"]})));
+        assert!(dependencies.contains(&json!({"ref": "F", "dependsOn": []})));
+    }
+
+    #[test]
+    fn to_spdx_should_list_packages_and_relationships() {
+        let mut result = small_dag();
+        result.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "D").for_each(|lib| lib.license = Some("MIT".to_string()));
+        let sha256 = BTreeMap::from([("D".to_string(), "ab".repeat(32))]);
+        let spdx = serde_json::to_value(to_spdx(&result, &sha256, "2023-11-14T22:13:20Z")).unwrap();
+        assert_eq!(json!("SPDX-2.3"), spdx["spdxVersion"]);
+        assert_eq!(json!("https://spdx.org/spdxdocs/lddtopo-rs/A-2023-11-14T22:13:20Z"), spdx["documentNamespace"]);
+        let packages = spdx["packages"].as_array().unwrap();
+        assert_eq!(result.vertices.len(), packages.len());
+        assert_eq!(json!({
+            "SPDXID": "SPDXRef-Package-3",
+            "name": "D",
+            "packageFileName": "/usr/lib/D",
+            "downloadLocation": "NOASSERTION",
+            "filesAnalyzed": false,
+            "checksums": [{"algorithm": "SHA256", "checksumValue": "ab".repeat(32)}],
+            "licenseConcluded": "MIT",
+            "licenseDeclared": "MIT",
+            "copyrightText": "NOASSERTION"
+        }), packages[3]);
+        let relationships = spdx["relationships"].as_array().unwrap();
+        assert_eq!(result.edges.len() + 1, relationships.len());
+        assert_eq!(json!({"spdxElementId": "SPDXRef-DOCUMENT", "relationshipType": "DESCRIBES", "relatedSpdxElement": "SPDXRef-Package-0"}), relationships[0]);
+        // B needs D
+        assert!(relationships.contains(&json!({"spdxElementId": "SPDXRef-Package-1", "relationshipType": "DEPENDS_ON", "relatedSpdxElement": "SPDXRef-Package-3"})));
+    }
+
+    #[test]
+    fn spdx_expression_should_map_licenses_to_spdx_identifiers() {
+        let expression = |license: &str| spdx_expression(license).map(|(expression, _)| expression);
+        assert_eq!(Some("GPL-2.0-or-later"), expression("GPL-2+").as_deref());
+        assert_eq!(Some("libtiff AND BSD-3-Clause"), expression("libtiff AND BSD-3-clause").as_deref());
+        assert_eq!(Some("(GPL-2.0-only OR Artistic-1.0) AND MIT"), expression("(GPL-2 or Artistic-1.0) and Expat").as_deref());
+        assert_eq!(Some("GPL-3.0-or-later WITH GCC-exception-3.1"), expression("GPL-3.0+ with GCC-exception-3.1").as_deref());
+        let (public_domain, refs) = spdx_expression("public domain").unwrap_or_default();
+        assert_eq!("", public_domain);
+        assert!(refs.is_empty());
+        let (reference, refs) = spdx_expression("public-domain").unwrap();
+        assert_eq!("LicenseRef-public-domain", reference);
+        assert_eq!(Some("public-domain"), refs.get("LicenseRef-public-domain").map(String::as_str));
+        for broken in ["(MIT", "MIT AND", "MIT WITH Unknown-exception", "(MIT) WITH GCC-exception-3.1", ""] {
+            assert_eq!(None, expression(broken), "{}", broken);
+        }
+    }
+
+    #[test]
+    fn to_spdx_should_define_the_license_references() {
+        let mut result = small_dag();
+        result.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "D").for_each(|lib| lib.license = Some("GPL-2+ AND custom".to_string()));
+        result.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "E").for_each(|lib| lib.license = Some("MIT AND".to_string()));
+        let spdx = serde_json::to_value(to_spdx(&result, &BTreeMap::new(), "2023-11-14T22:13:20Z")).unwrap();
+        assert_eq!(json!("GPL-2.0-or-later AND LicenseRef-custom"), spdx["packages"][3]["licenseConcluded"]);
+        assert_eq!(json!("NOASSERTION"), spdx["packages"][4]["licenseConcluded"]);
+        assert_eq!(json!([{"licenseId": "LicenseRef-custom", "name": "custom", "extractedText": "The custom license"}]), spdx["hasExtractedLicensingInfos"]);
+    }
+
+    #[test]
+    fn utc_timestamp_should_format_rfc_3339() {
+        assert_eq!("1970-01-01T00:00:00Z", utc_timestamp(0));
+        assert_eq!("2023-11-14T22:13:20Z", utc_timestamp(1_700_000_000));
+        assert_eq!("2024-02-29T23:59:59Z", utc_timestamp(1_709_251_199));
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::TopoSortResult;
+
+/// Outcome of `--scan-dir`: one analysis per ELF file found under `dir`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScanReport {
+    pub dir: String,
+    /// Number of ELF files found under `dir`, analyzed or not
+    pub discovered: usize,
+    /// Set when only a sample of the discovered files was analyzed, see `--sample`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<Sample>,
+    pub binaries: Vec<ScannedBinary>,
+}
+
+/// How the analyzed files were picked out of the discovered ones
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub fraction: f64,
+    pub seed: u64,
+    /// Number of files analyzed
+    pub selected: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScannedBinary {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<TopoSortResult>,
+    /// Why there is no `result`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Parses the `--sample` fraction, which must be in `(0, 1]`
+pub fn parse_fraction(s: &str) -> Result<f64, String> {
+    let fraction: f64 = s.parse().map_err(|_| format!("{} is not a number", s))?;
+    if fraction > 0.0 && fraction <= 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!("{} is not in (0, 1]", s))
+    }
+}
+
+/// Whether the file at `path` starts with the ELF magic
+pub fn is_elf(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map(|_| magic == *b"\x7fELF")
+        .unwrap_or(false)
+}
+
+/// Every regular ELF file under `dir`, recursively and sorted by path. Symlinks are not followed, so
+/// a library reachable under several names is found once and link loops cannot trap the walk.
+/// Unreadable directories are skipped.
+pub fn discover(dir: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut pending: Vec<PathBuf> = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = match std::fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => pending.push(entry.path()),
+                Ok(file_type) if file_type.is_file() && is_elf(&entry.path()) => found.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+/// FNV-1a of the seed and the path followed by the splitmix64 finalizer, spelled out instead of
+/// `DefaultHasher` because its output may change between Rust releases and samples must not
+fn sample_key(seed: u64, relative_path: &Path) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in seed.to_le_bytes().iter().chain(relative_path.to_string_lossy().as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+/// A pseudo-random `fraction` of `paths` (rounded up, at least one file unless there are none), kept in input order.
+/// Files are ranked by a hash of `seed` and their path relative to `dir`, so the same seed picks the same files
+/// wherever the directory is mounted, and growing the fraction only ever adds files to the sample.
+pub fn sample(paths: &[PathBuf], dir: &Path, fraction: f64, seed: u64) -> Vec<PathBuf> {
+    let count = ((paths.len() as f64 * fraction).ceil() as usize).clamp(paths.len().min(1), paths.len());
+    let mut ranked: Vec<(u64, usize)> = paths.iter().enumerate()
+        .map(|(idx, path)| (sample_key(seed, path.strip_prefix(dir).unwrap_or(path)), idx))
+        .collect();
+    ranked.sort();
+    let mut picked: Vec<usize> = ranked.into_iter().take(count).map(|(_, idx)| idx).collect();
+    picked.sort();
+    picked.into_iter().map(|idx| paths[idx].clone()).collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::scan_dir::{discover, parse_fraction, sample};
+    use std::path::{Path, PathBuf};
+
+    fn paths(n: usize) -> Vec<PathBuf> {
+        (0..n).map(|i| PathBuf::from(format!("/image/usr/lib/lib{}.so", i))).collect()
+    }
+
+    #[test]
+    fn sample_should_be_reproducible_and_depend_on_the_seed() {
+        let all = paths(100);
+        let picked = sample(&all, Path::new("/image"), 0.1, 7);
+        assert_eq!(10, picked.len());
+        assert_eq!(picked, sample(&all, Path::new("/image"), 0.1, 7));
+        assert_ne!(picked, sample(&all, Path::new("/image"), 0.1, 8));
+        let positions: Vec<usize> = picked.iter().map(|p| all.iter().position(|a| a == p).unwrap()).collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        // Same relative paths under another mount point give the same sample
+        let moved: Vec<PathBuf> = all.iter().map(|p| Path::new("/mnt").join(p.strip_prefix("/image").unwrap())).collect();
+        let picked_moved = sample(&moved, Path::new("/mnt"), 0.1, 7);
+        assert_eq!(picked.iter().map(|p| p.strip_prefix("/image").unwrap()).collect::<Vec<_>>(),
+            picked_moved.iter().map(|p| p.strip_prefix("/mnt").unwrap()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sample_should_grow_monotonically_and_never_be_empty() {
+        let all = paths(50);
+        let small = sample(&all, Path::new("/image"), 0.2, 1);
+        let large = sample(&all, Path::new("/image"), 0.6, 1);
+        assert!(small.iter().all(|p| large.contains(p)));
+        assert_eq!(1, sample(&all, Path::new("/image"), 0.0001, 1).len());
+        assert_eq!(all, sample(&all, Path::new("/image"), 1.0, 1));
+        assert!(sample(&[], Path::new("/image"), 0.5, 1).is_empty());
+    }
+
+    #[test]
+    fn parse_fraction_should_accept_only_the_unit_interval() {
+        assert_eq!(Ok(0.25), parse_fraction("0.25"));
+        assert_eq!(Ok(1.0), parse_fraction("1"));
+        assert!(parse_fraction("0").is_err());
+        assert!(parse_fraction("1.5").is_err());
+        assert!(parse_fraction("a lot").is_err());
+    }
+
+    #[test]
+    fn discover_should_find_elf_files_only() {
+        let exe = std::env::current_exe().unwrap();
+        let found = discover(exe.parent().unwrap());
+        assert!(found.contains(&exe));
+        assert!(!found.iter().any(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("d" | "rlib" | "rmeta"))));
+    }
+}
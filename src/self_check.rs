@@ -0,0 +1,108 @@
+use jsonschema::JSONSchema;
+
+use serde::Serialize;
+
+/// JSON Schema of the documents written by the tool, shipped as `schema/lddtopo-rs.schema.json`
+pub const SCHEMA: &str = include_str!("../schema/lddtopo-rs.schema.json");
+
+/// Validates `value` against [`SCHEMA`], returning every violation found
+pub fn validate_against_schema<T: Serialize>(value: &T) -> Result<(), Vec<String>> {
+    let schema: serde_json::Value = serde_json::from_str(SCHEMA).expect("The bundled schema is valid JSON");
+    validate(&schema, value)
+}
+
+/// Validates `value` against the definition `definition` of [`SCHEMA`], e.g. `TopoSortResult`. The violations name
+/// the offending field, where those of [`validate_against_schema`] can only say that no kind of document matches
+pub fn validate_against_definition<T: Serialize>(value: &T, definition: &str) -> Result<(), Vec<String>> {
+    let mut schema: serde_json::Value = serde_json::from_str(SCHEMA).expect("The bundled schema is valid JSON");
+    let root = schema.as_object_mut().expect("The bundled schema is an object");
+    root.remove("oneOf");
+    root.insert("$ref".to_string(), serde_json::Value::String(format!("#/definitions/{}", definition)));
+    validate(&schema, value)
+}
+
+fn validate<T: Serialize>(schema: &serde_json::Value, value: &T) -> Result<(), Vec<String>> {
+    let compiled = JSONSchema::compile(schema).expect("The bundled schema is a valid JSON Schema");
+    let instance = serde_json::to_value(value).map_err(|err| vec![err.to_string()])?;
+    let result = compiled.validate(&instance)
+        .map_err(|errors| errors.map(|err| format!("{} at '{}'", err, err.instance_path)).collect());
+    result
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde::Serialize;
+    use crate::query::tests::small_dag;
+    use crate::scan_dir::{Sample, ScanReport, ScannedBinary};
+    use crate::self_check::{validate_against_definition, validate_against_schema};
+    use crate::{CycleComponent, CycleReport, Edge, EdgeKind};
+
+    #[test]
+    fn validate_against_schema_when_output_is_valid_should_pass() {
+        assert_eq!(Ok(()), validate_against_schema(&small_dag()));
+        let mut hashed = small_dag();
+        for lib in &mut hashed.topo_sorted_libs {
+            lib.sha256 = Some("ab".repeat(32));
+            lib.size_bytes = Some(4096);
+            lib.machine = Some("X86_64".to_string());
+            lib.class = Some(64);
+            lib.os_abi = Some("sysv".to_string());
+            lib.build_id = Some("0123abcd".to_string());
+        }
+        assert_eq!(Ok(()), validate_against_schema(&hashed));
+        let report = CycleReport {
+            cycles: vec![vec!["A".to_string(), "B".to_string(), "A".to_string()]],
+            components: vec![CycleComponent {
+                libraries: vec!["A".to_string(), "B".to_string()],
+                edges: vec![
+                    Edge { src: "A".to_string(), dst: "B".to_string(), kind: EdgeKind::Needed, ..Default::default() },
+                    Edge { src: "B".to_string(), dst: "A".to_string(), kind: EdgeKind::Needed, ..Default::default() },
+                ],
+            }],
+        };
+        assert_eq!(Ok(()), validate_against_schema(&report));
+        let scan = ScanReport {
+            dir: "/image".to_string(),
+            discovered: 4,
+            sample: Some(Sample { fraction: 0.5, seed: 7, selected: 2 }),
+            binaries: vec![
+                ScannedBinary { path: "/image/A".to_string(), result: Some(small_dag()), error: None },
+                ScannedBinary { path: "/image/B".to_string(), result: None, error: Some("Malformed entity".to_string()) },
+            ],
+        };
+        assert_eq!(Ok(()), validate_against_schema(&scan));
+    }
+
+    #[test]
+    fn validate_against_schema_when_output_is_broken_should_fail() {
+        #[derive(Serialize)]
+        struct Broken {
+            kind: &'static str,
+            interpreter: Option<String>,
+            vertices: &'static str,
+            edges: Vec<u32>,
+            library_map: (),
+            topo_sorted_libs: Vec<String>,
+        }
+        let broken = Broken {
+            kind: "static_library",
+            interpreter: None,
+            vertices: "A",
+            edges: vec![1],
+            library_map: (),
+            topo_sorted_libs: vec![],
+        };
+        let errors = validate_against_schema(&broken).unwrap_err();
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn validate_against_definition_should_name_the_offending_field() {
+        let mut value = serde_json::to_value(small_dag()).unwrap();
+        assert_eq!(Ok(()), validate_against_definition(&value, "TopoSortResult"));
+        value["edges"][0]["bogus"] = serde_json::Value::from(1);
+        let errors = validate_against_definition(&value, "TopoSortResult").unwrap_err();
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("bogus") && errors[0].ends_with("at '/edges/0'"), "{}", errors[0]);
+    }
+}
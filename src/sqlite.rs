@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::TopoSortResult;
+
+/// Tables of `--format sqlite`. Every row carries the analyzed `binary`, see `binary_key`, so that the results of many
+/// binaries can be collected in one database and queried together.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS vertices (binary TEXT NOT NULL, id INTEGER NOT NULL, name TEXT NOT NULL, PRIMARY KEY (binary, id));
+CREATE TABLE IF NOT EXISTS edges (binary TEXT NOT NULL, src TEXT NOT NULL, dst TEXT NOT NULL, kind TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS libraries (binary TEXT NOT NULL, name TEXT NOT NULL, path TEXT, load_order INTEGER NOT NULL,
+    min_depth INTEGER, max_depth INTEGER, license TEXT, unresolved INTEGER NOT NULL, PRIMARY KEY (binary, name));
+CREATE INDEX IF NOT EXISTS edges_src ON edges (src);
+CREATE INDEX IF NOT EXISTS edges_dst ON edges (dst);
+CREATE INDEX IF NOT EXISTS libraries_name ON libraries (name);
+";
+
+/// The path of the analyzed binary, the paths of all of them separated by `:` for a merged graph. Binaries that share a
+/// file name, e.g. two versions of one library, or merged graphs do not replace each other's rows
+fn binary_key(result: &TopoSortResult) -> String {
+    let mut roots: Vec<&str> = result.topo_sorted_libs.iter()
+        .filter(|lib| lib.min_depth == Some(0))
+        .map(|lib| lib.path.as_deref().unwrap_or(&lib.name))
+        .collect();
+    roots.sort();
+    roots.join(":")
+}
+
+/// Adds `result` to the SQLite database at `path`, which is created when missing. `vertices` holds the graph
+/// nodes in `vertices` order, `edges` the NEEDED and guessed `dlopen` edges with `src` loaded before `dst`,
+/// and `libraries` every library in load order. Rows of an earlier run for the same binary are replaced.
+pub fn write_sqlite(result: &TopoSortResult, path: &Path) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+    let binary = binary_key(result);
+    let unresolved: HashSet<&str> = result.unresolved.iter().map(String::as_str).collect();
+    let tx = connection.transaction()?;
+    for table in ["vertices", "edges", "libraries"] {
+        tx.execute(&format!("DELETE FROM {} WHERE binary = ?1", table), params![binary])?;
+    }
+    {
+        let mut insert = tx.prepare("INSERT INTO vertices (binary, id, name) VALUES (?1, ?2, ?3)")?;
+        for (id, v) in result.vertices.iter().enumerate() {
+            insert.execute(params![binary, id as i64, v])?;
+        }
+        let mut insert = tx.prepare("INSERT INTO edges (binary, src, dst, kind) VALUES (?1, ?2, ?3, ?4)")?;
+        for edge in result.edges.iter().chain(&result.dlopen_edges).chain(&result.runtime_edges) {
+            insert.execute(params![binary, edge.src, edge.dst, edge.kind.as_str()])?;
+        }
+        let mut insert = tx.prepare("INSERT INTO libraries (binary, name, path, load_order, min_depth, max_depth, license, unresolved) \
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+        for (load_order, lib) in result.topo_sorted_libs.iter().enumerate() {
+            // rusqlite binds no usize, SQLite integers are i64
+            let depths = (lib.min_depth.map(|depth| depth as i64), lib.max_depth.map(|depth| depth as i64));
+            insert.execute(params![binary, lib.name, lib.path, load_order as i64, depths.0, depths.1, lib.license,
+                unresolved.contains(lib.name.as_str())])?;
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::merge::{merge, MergeStrategy};
+    use crate::query::tests::small_dag;
+    use crate::sqlite::{binary_key, write_sqlite};
+    use rusqlite::Connection;
+
+    #[test]
+    fn write_sqlite_should_fill_the_tables_and_replace_earlier_rows() {
+        let path = std::env::temp_dir().join(format!("lddtopo-rs-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let result = small_dag();
+        write_sqlite(&result, &path).unwrap();
+        write_sqlite(&result, &path).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let count = |table: &str| -> i64 {
+            connection.query_row(&format!("SELECT COUNT(*) FROM {} WHERE binary = '/tmp/A'", table), [], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(result.vertices.len() as i64, count("vertices"));
+        assert_eq!(result.edges.len() as i64, count("edges"));
+        assert_eq!(result.topo_sorted_libs.len() as i64, count("libraries"));
+        let dependents: Vec<String> = connection.prepare("SELECT dst FROM edges WHERE src = 'D' ORDER BY dst").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(vec!["B", "C"], dependents);
+        let (path_of_d, depth): (Option<String>, Option<i64>) = connection
+            .query_row("SELECT path, min_depth FROM libraries WHERE name = 'D'", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((Some("/usr/lib/D".to_string()), Some(2)), (path_of_d, depth));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn binary_key_should_tell_binaries_with_the_same_name_and_merged_graphs_apart() {
+        let mut other = small_dag();
+        other.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "A").for_each(|lib| lib.path = Some("/opt/A".to_string()));
+        assert_eq!("/tmp/A", binary_key(&small_dag()));
+        assert_eq!("/opt/A", binary_key(&other));
+        let mut plugin = small_dag();
+        plugin.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "A").for_each(|lib| lib.name = "P".to_string());
+        plugin.topo_sorted_libs.iter_mut().filter(|lib| lib.name == "P").for_each(|lib| lib.path = Some("/tmp/P".to_string()));
+        plugin.vertices.iter_mut().filter(|v| *v == "A").for_each(|v| *v = "P".to_string());
+        plugin.edges.iter_mut().filter(|edge| edge.dst == "A").for_each(|edge| edge.dst = "P".to_string());
+        let merged = merge(&[small_dag(), plugin], MergeStrategy::First).unwrap();
+        assert_eq!("/tmp/A:/tmp/P", binary_key(&merged));
+    }
+}
@@ -0,0 +1,205 @@
+use goblin::elf::Elf;
+
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::TopoSortResult;
+
+/// Symbol version families of libstdc++ and its C++ ABI
+pub const CXX_FAMILIES: [&str; 2] = ["GLIBCXX", "CXXABI"];
+
+/// The libstdc++ the dependency closure of the analyzed library needs, see `--cxx-versions`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CxxRequirements {
+    /// Newest `GLIBCXX_*` version any library needs, the oldest libstdc++ the binary runs on
+    pub glibcxx: Option<String>,
+    /// Newest `CXXABI_*` version any library needs
+    pub cxxabi: Option<String>,
+    /// Libraries that need a `GLIBCXX_*` or `CXXABI_*` version, in load order
+    pub libraries: Vec<String>,
+}
+
+/// A symbol version such as `GLIBC_2.17` split into its family `GLIBC` and its numbers `[2, 17]`. `None` for
+/// versions without numbers, e.g. `GLIBC_PRIVATE`.
+pub fn split_version(version: &str) -> Option<(&str, Vec<u32>)> {
+    let (family, numbers) = version.rsplit_once('_')?;
+    let numbers: Vec<u32> = numbers.split('.').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+    Some((family, numbers))
+}
+
+/// Orders two versions of the same family by their numbers, `None` when they are of different families or
+/// not numbered
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let (a_family, a_numbers) = split_version(a)?;
+    let (b_family, b_numbers) = split_version(b)?;
+    (a_family == b_family).then(|| a_numbers.cmp(&b_numbers))
+}
+
+/// The newest of `versions` in `family`, e.g. `GLIBC_2.34` for `GLIBC`, `None` when there is none
+pub fn newest_version<'a>(versions: impl IntoIterator<Item = &'a str>, family: &str) -> Option<&'a str> {
+    versions.into_iter()
+        .filter_map(|version| split_version(version).filter(|(f, _)| *f == family).map(|(_, numbers)| (numbers, version)))
+        .max()
+        .map(|(_, version)| version)
+}
+
+/// The glibc release `versions` require, e.g. `2.34` for `GLIBC_2.2.5` and `GLIBC_2.34`
+pub fn glibc_version<'a>(versions: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    newest_version(versions, "GLIBC").map(|version| version["GLIBC_".len()..].to_string())
+}
+
+/// The newest `glibc_version` of the libraries of `result`, the oldest glibc the whole closure runs on, and the
+/// libraries that require it
+pub fn min_glibc_version(result: &TopoSortResult) -> Option<(String, Vec<String>)> {
+    let versions: Vec<String> = result.topo_sorted_libs.iter()
+        .filter_map(|lib| lib.glibc_version.as_ref().map(|version| format!("GLIBC_{}", version)))
+        .collect();
+    let newest = glibc_version(versions.iter().map(String::as_str))?;
+    let libraries = result.topo_sorted_libs.iter()
+        .filter(|lib| lib.glibc_version.as_ref() == Some(&newest))
+        .map(|lib| lib.name.clone())
+        .collect();
+    Some((newest, libraries))
+}
+
+/// The `GLIBCXX_*` and `CXXABI_*` versions among `versions`, sorted by family and number
+pub fn cxx_versions<'a>(versions: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut cxx: Vec<(&str, Vec<u32>, &str)> = versions.into_iter()
+        .filter_map(|version| split_version(version).map(|(family, numbers)| (family, numbers, version)))
+        .filter(|(family, _, _)| CXX_FAMILIES.contains(family))
+        .collect();
+    cxx.sort();
+    cxx.into_iter().map(|(_, _, version)| version.to_string()).collect()
+}
+
+/// Summarizes the `cxx_versions` of the libraries of `result`
+pub fn cxx_requirements(result: &TopoSortResult) -> CxxRequirements {
+    let needed = || result.topo_sorted_libs.iter().flat_map(|lib| lib.cxx_versions.iter().map(String::as_str));
+    CxxRequirements {
+        glibcxx: newest_version(needed(), "GLIBCXX").map(String::from),
+        cxxabi: newest_version(needed(), "CXXABI").map(String::from),
+        libraries: result.topo_sorted_libs.iter()
+            .filter(|lib| !lib.cxx_versions.is_empty())
+            .map(|lib| lib.name.clone())
+            .collect(),
+    }
+}
+
+/// Every symbol version `elf` needs from its dependencies (DT_VERNEED), e.g. `GLIBC_2.34`, sorted
+pub fn needed_versions(elf: &Elf) -> BTreeSet<String> {
+    let mut versions = BTreeSet::new();
+    if let Some(verneed) = &elf.verneed {
+        for need_file in verneed.iter() {
+            for need_version in need_file.iter() {
+                if let Some(name) = elf.dynstrtab.get_at(need_version.vna_name) {
+                    versions.insert(name.to_string());
+                }
+            }
+        }
+    }
+    versions
+}
+
+/// Every symbol version `elf` defines (DT_VERDEF), sorted
+pub fn defined_versions(elf: &Elf) -> BTreeSet<String> {
+    let mut versions = BTreeSet::new();
+    if let Some(verdef) = &elf.verdef {
+        for definition in verdef.iter() {
+            versions.extend(definition.iter().filter_map(|aux| elf.dynstrtab.get_at(aux.vda_name)).map(String::from));
+        }
+    }
+    versions
+}
+
+/// The symbol versions the ELF file at `path` needs, `None` when it cannot be read or parsed
+pub fn read_needed_versions(path: &Path) -> Option<BTreeSet<String>> {
+    let bytes = std::fs::read(path).ok()?;
+    let elf = Elf::parse(&bytes).ok()?;
+    Some(needed_versions(&elf))
+}
+
+/// The glibc release the ELF file at `path` requires, `None` when it cannot be parsed, needs no GLIBC version or
+/// is a part of glibc itself, i.e. defines GLIBC versions, whose requirements only tell which glibc is installed
+pub fn read_glibc_version(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    let elf = Elf::parse(&bytes).ok()?;
+    if newest_version(defined_versions(&elf).iter().map(String::as_str), "GLIBC").is_some() {
+        return None;
+    }
+    glibc_version(needed_versions(&elf).iter().map(String::as_str))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::query::tests::small_dag;
+    use crate::symbol_versions::{compare_versions, cxx_requirements, cxx_versions, glibc_version, min_glibc_version, newest_version, read_glibc_version, read_needed_versions, split_version};
+    use std::cmp::Ordering;
+    use std::path::Path;
+
+    #[test]
+    fn compare_versions_should_order_numbers_of_the_same_family() {
+        assert_eq!(Some(("GLIBCXX", vec![3, 4, 19])), split_version("GLIBCXX_3.4.19"));
+        assert_eq!(Some(("CXXABI_TM", vec![1])), split_version("CXXABI_TM_1"));
+        assert_eq!(None, split_version("GLIBC_PRIVATE"));
+        assert_eq!(Some(Ordering::Greater), compare_versions("GLIBC_2.28", "GLIBC_2.5"));
+        assert_eq!(Some(Ordering::Less), compare_versions("GLIBC_2.2.5", "GLIBC_2.3"));
+        assert_eq!(Some(Ordering::Equal), compare_versions("GCC_3.0", "GCC_3.0"));
+        assert_eq!(None, compare_versions("GLIBC_2.17", "GLIBCXX_3.4"));
+    }
+
+    #[test]
+    fn glibc_version_should_pick_the_newest_glibc_release() {
+        let versions = ["GLIBC_2.2.5", "GLIBC_2.34", "GLIBC_2.17", "GLIBC_PRIVATE", "GLIBCXX_3.4.30"];
+        assert_eq!(Some("GLIBCXX_3.4.30"), newest_version(versions, "GLIBCXX"));
+        assert_eq!(Some("2.34".to_string()), glibc_version(versions));
+        assert_eq!(None, glibc_version(["GCC_3.0"]));
+    }
+
+    #[test]
+    fn min_glibc_version_should_name_the_libraries_that_need_it() {
+        let mut result = small_dag();
+        assert_eq!(None, min_glibc_version(&result));
+        for lib in &mut result.topo_sorted_libs {
+            lib.glibc_version = match lib.name.as_str() {
+                "A" | "D" => Some("2.34".to_string()),
+                "E" => Some("2.4".to_string()),
+                _ => None,
+            };
+        }
+        assert_eq!(Some(("2.34".to_string(), vec!["D".to_string(), "A".to_string()])), min_glibc_version(&result));
+    }
+
+    #[test]
+    fn cxx_requirements_should_summarize_the_libstdcxx_versions() {
+        let versions = ["GLIBCXX_3.4.9", "CXXABI_1.3", "GLIBC_2.34", "GLIBCXX_3.4.30", "CXXABI_1.3.13", "GLIBCXX_3.4"];
+        assert_eq!(vec!["CXXABI_1.3", "CXXABI_1.3.13", "GLIBCXX_3.4", "GLIBCXX_3.4.9", "GLIBCXX_3.4.30"], cxx_versions(versions));
+
+        let mut result = small_dag();
+        for lib in &mut result.topo_sorted_libs {
+            lib.cxx_versions = match lib.name.as_str() {
+                "A" => vec!["CXXABI_1.3".to_string(), "GLIBCXX_3.4.9".to_string()],
+                "D" => vec!["GLIBCXX_3.4.21".to_string()],
+                _ => Vec::new(),
+            };
+        }
+        let requirements = cxx_requirements(&result);
+        assert_eq!(Some("GLIBCXX_3.4.21"), requirements.glibcxx.as_deref());
+        assert_eq!(Some("CXXABI_1.3"), requirements.cxxabi.as_deref());
+        assert_eq!(vec!["D", "A"], requirements.libraries);
+    }
+
+    #[test]
+    fn read_needed_versions_should_list_the_versions_of_the_test_binary() {
+        let versions = read_needed_versions(&std::env::current_exe().unwrap()).unwrap();
+        if cfg!(all(target_os = "linux", target_env = "gnu")) {
+            assert!(versions.iter().any(|version| version.starts_with("GLIBC_")));
+        }
+        assert!(read_needed_versions(Path::new("/nonexistent/libfoo.so")).is_none());
+        if cfg!(all(target_os = "linux", target_env = "gnu")) {
+            assert!(read_glibc_version(&std::env::current_exe().unwrap()).is_some());
+        }
+    }
+}
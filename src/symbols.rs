@@ -0,0 +1,300 @@
+use goblin::elf::section_header::{SHN_ABS, SHN_UNDEF};
+use goblin::elf::sym::{STB_GLOBAL, STB_WEAK, STT_FILE, STT_SECTION};
+use goblin::elf::Elf;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use crate::{Edge, EdgeKind, Lib, TopoSortResult};
+
+/// The global and weak symbols of the dynamic symbol table of an ELF file, see `--symbols`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DynamicSymbols {
+    /// Symbols the file exports
+    pub defined: BTreeSet<String>,
+    /// Symbols the file expects one of its dependencies to define
+    pub undefined: BTreeSet<String>,
+    /// The weak ones among `undefined`, which may stay undefined
+    pub weak_undefined: BTreeSet<String>,
+}
+
+/// Undefined symbols of a library that no library of the closure but the analyzed executable defines, see
+/// `--report-underlinking`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Underlinked {
+    pub library: String,
+    /// Defined by no library at all, loading or calling them fails unless something is preloaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unresolved: Vec<String>,
+    /// Only defined by the analyzed executable, the library breaks when it is used by another one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub from_executable: Vec<String>,
+}
+
+impl DynamicSymbols {
+    pub fn from_elf(elf: &Elf) -> DynamicSymbols {
+        let mut symbols = DynamicSymbols::default();
+        for sym in elf.dynsyms.iter() {
+            if !matches!(sym.st_bind(), STB_GLOBAL | STB_WEAK) || matches!(sym.st_type(), STT_SECTION | STT_FILE) {
+                continue;
+            }
+            let name = match elf.dynstrtab.get_at(sym.st_name) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => continue,
+            };
+            if sym.st_shndx == SHN_UNDEF as usize {
+                if sym.st_bind() == STB_WEAK {
+                    symbols.weak_undefined.insert(name.clone());
+                }
+                symbols.undefined.insert(name);
+            } else if sym.st_shndx != SHN_ABS as usize {
+                // absolute symbols name the version definitions, e.g. GLIBC_2.34
+                symbols.defined.insert(name);
+            }
+        }
+        symbols
+    }
+
+    /// The dynamic symbols of the ELF file at `path`, `None` when it cannot be read or parsed
+    pub fn read(path: &Path) -> Option<DynamicSymbols> {
+        let bytes = std::fs::read(path).ok()?;
+        let elf = Elf::parse(&bytes).ok()?;
+        Some(DynamicSymbols::from_elf(&elf))
+    }
+
+    /// The undefined symbols of `self` that `dependency` defines, sorted
+    pub fn resolved_by(&self, dependency: &DynamicSymbols) -> Vec<String> {
+        self.undefined.intersection(&dependency.defined).cloned().collect()
+    }
+}
+
+/// Records on every NEEDED edge of `result` how many undefined symbols of the dependent the dependency defines,
+/// and with `detailed` which ones. A symbol defined by several dependencies counts for each of them. Edges with a
+/// library whose symbols are unknown, e.g. an unresolved one, are left without a count.
+pub fn annotate_edges(result: &mut TopoSortResult, symbols: &HashMap<String, DynamicSymbols>, detailed: bool) {
+    for edge in result.edges.iter_mut().filter(|edge| edge.kind == EdgeKind::Needed) {
+        if let (Some(dependency), Some(dependent)) = (symbols.get(&edge.src), symbols.get(&edge.dst)) {
+            let resolved = dependent.resolved_by(dependency);
+            edge.resolved_symbols_count = Some(resolved.len());
+            edge.resolved_symbols = if detailed { resolved } else { Vec::new() };
+        }
+    }
+}
+
+/// Symbols the linker defines in every shared object, which never conflict in practice
+const LINKER_SYMBOLS: [&str; 7] = ["_init", "_fini", "_edata", "_end", "__bss_start", "_DYNAMIC", "_GLOBAL_OFFSET_TABLE_"];
+
+/// A symbol exported by more than one library, see `--report-interposition`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SymbolConflict {
+    pub symbol: String,
+    /// The library whose definition every reference binds to, the first of `libraries`
+    pub winner: String,
+    /// Every library that exports the symbol, in symbol search order
+    pub libraries: Vec<String>,
+}
+
+/// The order the dynamic linker searches the libraries of `result` for a symbol: the main library first, then the
+/// libraries breadth-first. It is approximated by `min_depth`, ties are broken by the load order since the order of
+/// the NEEDED entries is not recorded.
+pub fn symbol_search_order(result: &TopoSortResult) -> Vec<&Lib> {
+    let mut order: Vec<&Lib> = result.topo_sorted_libs.iter().collect();
+    order.sort_by_key(|lib| lib.min_depth.unwrap_or(usize::MAX));
+    order
+}
+
+/// Every symbol, but those of `LINKER_SYMBOLS`, that more than one library of `result` exports, sorted by name.
+/// The first library in `symbol_search_order` interposes the definitions of the others.
+pub fn interposition(result: &TopoSortResult, symbols: &HashMap<String, DynamicSymbols>) -> Vec<SymbolConflict> {
+    let mut exporters: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for lib in symbol_search_order(result) {
+        for symbol in symbols.get(&lib.name).map(|lib_symbols| &lib_symbols.defined).into_iter().flatten() {
+            if !LINKER_SYMBOLS.contains(&symbol.as_str()) {
+                exporters.entry(symbol.as_str()).or_default().push(lib.name.as_str());
+            }
+        }
+    }
+    exporters.into_iter()
+        .filter(|(_, libraries)| libraries.len() > 1)
+        .map(|(symbol, libraries)| SymbolConflict {
+            symbol: symbol.to_string(),
+            winner: libraries[0].to_string(),
+            libraries: libraries.into_iter().map(String::from).collect(),
+        })
+        .collect()
+}
+
+/// The libraries of `result`, in load order, with strong undefined symbols that no other library in `symbols`
+/// defines, or only `main_lib_name` does when it is an executable. A main library is just one more library that
+/// defines symbols. The symbols of unresolved libraries are unknown, so the libraries that need one of them are
+/// likely listed as well.
+pub fn underlinking(result: &TopoSortResult, symbols: &HashMap<String, DynamicSymbols>, main_lib_name: &str) -> Vec<Underlinked> {
+    let executable = result.kind.is_executable();
+    let defined_by_libraries: HashSet<&str> = symbols.iter()
+        .filter(|(name, _)| !executable || *name != main_lib_name)
+        .flat_map(|(_, lib_symbols)| lib_symbols.defined.iter().map(String::as_str))
+        .collect();
+    let defined_by_main = symbols.get(main_lib_name).filter(|_| executable).map(|main| &main.defined);
+    let mut underlinked = Vec::new();
+    for lib in &result.topo_sorted_libs {
+        let lib_symbols = match symbols.get(&lib.name) {
+            Some(lib_symbols) => lib_symbols,
+            None => continue,
+        };
+        let mut entry = Underlinked { library: lib.name.clone(), unresolved: Vec::new(), from_executable: Vec::new() };
+        for symbol in lib_symbols.undefined.difference(&lib_symbols.weak_undefined) {
+            if defined_by_libraries.contains(symbol.as_str()) {
+                continue;
+            }
+            if lib.name != main_lib_name && defined_by_main.map(|main| main.contains(symbol)).unwrap_or(false) {
+                entry.from_executable.push(symbol.clone());
+            } else {
+                entry.unresolved.push(symbol.clone());
+            }
+        }
+        if !entry.unresolved.is_empty() || !entry.from_executable.is_empty() {
+            underlinked.push(entry);
+        }
+    }
+    underlinked
+}
+
+/// Marks the NEEDED edges whose dependency defines none of the symbols the dependent needs, i.e. a `--symbols`
+/// count of 0, and returns them. Such a DT_NEEDED entry can usually be dropped from the link line, e.g. with
+/// `--as-needed`.
+pub fn mark_overlinking(result: &mut TopoSortResult) -> Vec<&Edge> {
+    for edge in &mut result.edges {
+        edge.overlinked = edge.resolved_symbols_count == Some(0);
+    }
+    result.edges.iter().filter(|edge| edge.overlinked).collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::query::tests::small_dag;
+    use crate::symbols::{annotate_edges, interposition, mark_overlinking, underlinking, DynamicSymbols, SymbolConflict, Underlinked};
+    use crate::binary_kind::BinaryKind;
+    use crate::resolver::Resolver;
+    use crate::{analyze_file, AnalysisOptions, TopoSortResult};
+    use std::collections::{BTreeSet, HashMap};
+    use std::path::{Path, PathBuf};
+
+    /// Symbols of a library, `undefined` ones ending with `?` are weak
+    fn symbols(defined: &[&str], undefined: &[&str]) -> DynamicSymbols {
+        DynamicSymbols {
+            defined: defined.iter().map(|s| s.to_string()).collect::<BTreeSet<_>>(),
+            undefined: undefined.iter().map(|s| s.trim_end_matches('?').to_string()).collect::<BTreeSet<_>>(),
+            weak_undefined: undefined.iter().filter_map(|s| s.strip_suffix('?')).map(String::from).collect::<BTreeSet<_>>(),
+        }
+    }
+
+    #[test]
+    fn read_should_find_imports_of_the_test_binary() {
+        let binary = std::env::current_exe().unwrap();
+        let symbols = DynamicSymbols::read(&binary).unwrap();
+        // the system allocator of std imports malloc, which glibc's libc.so.6 defines
+        if !cfg!(all(target_os = "linux", target_env = "gnu")) {
+            return;
+        }
+        assert!(symbols.undefined.contains("malloc"));
+
+        let mut result = analyze_file(&binary, &Resolver::new(PathBuf::from("/")), &AnalysisOptions::default()).unwrap();
+        let symbols: HashMap<String, DynamicSymbols> = result.topo_sorted_libs.iter()
+            .filter_map(|lib| lib.path.as_ref().map(|path| (lib, path)))
+            .filter_map(|(lib, path)| DynamicSymbols::read(Path::new(path)).map(|symbols| (lib.name.clone(), symbols)))
+            .collect();
+        assert!(symbols["libc.so.6"].defined.contains("malloc"));
+        annotate_edges(&mut result, &symbols, true);
+        let name = binary.file_name().unwrap().to_str().unwrap();
+        let edge = result.edges.iter().find(|edge| edge.src == "libc.so.6" && edge.dst == name).unwrap();
+        assert!(edge.resolved_symbols.iter().any(|symbol| symbol == "malloc"));
+    }
+
+    #[test]
+    fn from_elf_should_skip_the_absolute_version_symbols() {
+        // glibc defines its symbol versions as absolute symbols, they are no definitions another library could bind to
+        let libc = ["/lib/x86_64-linux-gnu/libc.so.6", "/lib/aarch64-linux-gnu/libc.so.6", "/lib64/libc.so.6"].iter()
+            .find_map(|path| DynamicSymbols::read(Path::new(path)));
+        if let Some(libc) = libc {
+            assert!(libc.defined.contains("malloc"));
+            assert!(!libc.defined.iter().any(|name| name.starts_with("GLIBC_2.")), "a version is defined");
+        }
+    }
+
+    #[test]
+    fn annotate_edges_should_count_the_symbols_a_dependency_defines() {
+        let mut result = small_dag();
+        let symbols = HashMap::from([
+            ("A".to_string(), symbols(&["main"], &["b_init", "c_init", "memcpy", "f_run"])),
+            ("B".to_string(), symbols(&["b_init"], &["d_run"])),
+            ("C".to_string(), symbols(&["c_init", "memcpy"], &[])),
+            ("D".to_string(), symbols(&["d_run", "memcpy"], &[])),
+            ("F".to_string(), symbols(&["f_run"], &[])),
+        ]);
+        let count = |result: &TopoSortResult, src: &str, dst: &str| {
+            let edge = result.edges.iter().find(|edge| edge.src == src && edge.dst == dst).unwrap();
+            (edge.resolved_symbols_count, edge.resolved_symbols.clone())
+        };
+        annotate_edges(&mut result, &symbols, true);
+        assert_eq!((Some(2), vec!["c_init".to_string(), "memcpy".to_string()]), count(&result, "C", "A"));
+        assert_eq!((Some(1), vec!["d_run".to_string()]), count(&result, "D", "B"));
+        assert_eq!((Some(0), vec![]), count(&result, "D", "C"));
+        // E has no symbols
+        assert_eq!((None, vec![]), count(&result, "E", "D"));
+
+        annotate_edges(&mut result, &symbols, false);
+        assert_eq!((Some(2), vec![]), count(&result, "C", "A"));
+
+        // C needs D but defines memcpy itself, and F defines nothing E needs
+        let overlinked: Vec<(&str, &str)> = mark_overlinking(&mut result).iter().map(|edge| (edge.src.as_str(), edge.dst.as_str())).collect();
+        assert_eq!(vec![("D", "C")], overlinked);
+        assert!(result.edges.iter().filter(|edge| edge.overlinked).all(|edge| edge.src == "D" && edge.dst == "C"));
+    }
+
+    #[test]
+    fn underlinking_should_report_symbols_nobody_or_only_the_executable_defines() {
+        let result = TopoSortResult { kind: BinaryKind::Executable, ..small_dag() };
+        let symbols = HashMap::from([
+            ("A".to_string(), symbols(&["main", "app_log"], &["b_init", "preloaded", "__gmon_start__?"])),
+            ("B".to_string(), symbols(&["b_init"], &["app_log", "d_run", "memcpy"])),
+            ("C".to_string(), symbols(&["memcpy"], &["_ITM_registerTMCloneTable?"])),
+            ("D".to_string(), symbols(&["d_run"], &["memcpy", "app_log", "gone"])),
+        ]);
+        assert_eq!(vec![
+            Underlinked { library: "D".to_string(), unresolved: vec!["gone".to_string()], from_executable: vec!["app_log".to_string()] },
+            Underlinked { library: "B".to_string(), unresolved: vec![], from_executable: vec!["app_log".to_string()] },
+            Underlinked { library: "A".to_string(), unresolved: vec!["preloaded".to_string()], from_executable: vec![] },
+        ], underlinking(&result, &symbols, "A"));
+
+        // the symbols of a main library are found like those of the others
+        let library = TopoSortResult { kind: BinaryKind::SharedLibrary, ..small_dag() };
+        assert_eq!(vec![
+            Underlinked { library: "D".to_string(), unresolved: vec!["gone".to_string()], from_executable: vec![] },
+            Underlinked { library: "A".to_string(), unresolved: vec!["preloaded".to_string()], from_executable: vec![] },
+        ], underlinking(&library, &symbols, "A"));
+    }
+
+    #[test]
+    fn interposition_should_let_the_first_library_in_search_order_win() {
+        let result = small_dag();
+        // A is at depth 0, B, C and F at 1, D at 2 and E at 3
+        let symbols = HashMap::from([
+            ("A".to_string(), symbols(&["main", "malloc", "_init"], &[])),
+            ("C".to_string(), symbols(&["log_open", "_init"], &[])),
+            ("D".to_string(), symbols(&["log_open", "deflate"], &[])),
+            ("E".to_string(), symbols(&["deflate", "malloc"], &[])),
+        ]);
+        let conflict = |symbol: &str, libraries: &[&str]| SymbolConflict {
+            symbol: symbol.to_string(),
+            winner: libraries[0].to_string(),
+            libraries: libraries.iter().map(|l| l.to_string()).collect(),
+        };
+        assert_eq!(vec![
+            conflict("deflate", &["D", "E"]),
+            conflict("log_open", &["C", "D"]),
+            conflict("malloc", &["A", "E"]),
+        ], interposition(&result, &symbols));
+    }
+}
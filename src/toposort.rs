@@ -0,0 +1,100 @@
+use petgraph::graphmap::DiGraphMap;
+use petgraph::Direction;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Kahn's algorithm that, of all the vertices whose dependencies are sorted already, always takes the one with the
+/// smallest name next. The order then only depends on the names and edges of the graph, not on the order vertices
+/// and edges were added in, so the same input gives a byte-identical load order across runs and machines.
+///
+/// The edges point from a dependency to its dependent. `None` when the graph has a cycle.
+pub fn lexicographic_toposort<F>(di_graph_map: &DiGraphMap<u32, ()>, name_of: F) -> Option<Vec<u32>>
+    where F: Fn(u32) -> String {
+    let mut in_degree: HashMap<u32, usize> = di_graph_map.nodes()
+        .map(|id| (id, di_graph_map.neighbors_directed(id, Direction::Incoming).count()))
+        .collect();
+    let mut ready: BinaryHeap<Reverse<(String, u32)>> = in_degree.iter()
+        .filter(|(_, degree)| **degree == 0)
+        .map(|(id, _)| Reverse((name_of(*id), *id)))
+        .collect();
+    let mut sorted: Vec<u32> = Vec::with_capacity(di_graph_map.node_count());
+    while let Some(Reverse((_, id))) = ready.pop() {
+        sorted.push(id);
+        for dependent in di_graph_map.neighbors_directed(id, Direction::Outgoing) {
+            let degree = in_degree.get_mut(&dependent).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                ready.push(Reverse((name_of(dependent), dependent)));
+            }
+        }
+    }
+    if sorted.len() == di_graph_map.node_count() { Some(sorted) } else { None }
+}
+
+/// Groups `units`, the load units of the graph in topological order, into generations: a unit is one level above the
+/// highest unit it depends on, units without dependencies are at level 0. A unit is a single library or a group of
+/// libraries that depend on each other, whose members share a level. Levels keep the order of `units`.
+pub fn levels(di_graph_map: &DiGraphMap<u32, ()>, units: &[Vec<u32>]) -> Vec<Vec<u32>> {
+    let mut level_of: HashMap<u32, usize> = HashMap::new();
+    let mut levels: Vec<Vec<u32>> = Vec::new();
+    for unit in units {
+        let level = unit.iter()
+            .flat_map(|id| di_graph_map.neighbors_directed(*id, Direction::Incoming))
+            .filter(|dep| !unit.contains(dep))
+            .map(|dep| level_of[&dep] + 1)
+            .max()
+            .unwrap_or(0);
+        if levels.len() <= level {
+            levels.resize(level + 1, Vec::new());
+        }
+        for id in unit {
+            level_of.insert(*id, level);
+            levels[level].push(*id);
+        }
+    }
+    levels
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use petgraph::graphmap::DiGraphMap;
+    use crate::toposort::{levels, lexicographic_toposort};
+
+    fn name_of(id: u32) -> String {
+        ((b'A' + id as u8) as char).to_string()
+    }
+
+    #[test]
+    fn lexicographic_toposort_should_break_ties_by_name() {
+        // A depends on D, C and B, C depends on E
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(3, 0), (2, 0), (1, 0), (4, 2)]);
+        let order: Vec<String> = lexicographic_toposort(&graph, name_of).unwrap().into_iter().map(name_of).collect();
+        assert_eq!(vec!["B", "D", "E", "C", "A"], order);
+    }
+
+    #[test]
+    fn lexicographic_toposort_should_not_depend_on_insertion_order() {
+        let edges = [(3, 0), (2, 0), (1, 0), (4, 2), (4, 1)];
+        let mut reversed = edges;
+        reversed.reverse();
+        let forward: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&edges);
+        let backward: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&reversed);
+        assert_eq!(lexicographic_toposort(&forward, name_of), lexicographic_toposort(&backward, name_of));
+    }
+
+    #[test]
+    fn lexicographic_toposort_when_graph_has_cycle_should_return_none() {
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (0, 1), (2, 0)]);
+        assert!(lexicographic_toposort(&graph, name_of).is_none());
+    }
+
+    #[test]
+    fn levels_should_put_every_unit_above_its_dependencies() {
+        // A depends on B and C, B depends on D, C and D form a cycle
+        let graph: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (2, 0), (3, 1), (3, 2), (2, 3)]);
+        assert_eq!(vec![vec![2, 3], vec![1], vec![0]], levels(&graph, &[vec![2, 3], vec![1], vec![0]]));
+        let dag: DiGraphMap<u32, ()> = DiGraphMap::from_edges(&[(1, 0), (2, 0), (3, 1)]);
+        assert_eq!(vec![vec![3, 2], vec![1], vec![0]], levels(&dag, &[vec![3], vec![2], vec![1], vec![0]]));
+    }
+}
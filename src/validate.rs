@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::TopoSortResult;
+
+/// Checks the invariants of a `TopoSortResult`, typically one read back from a file or produced by
+/// another tool, and returns every violation found:
+/// - every edge endpoint is a vertex
+/// - `topo_sorted_libs` lists every vertex exactly once
+/// - the order respects the edges: the `src` of an edge is loaded before its `dst`, unless both are members of the
+///   same `cycle_groups` group, which has no order of its own
+/// - every `library_map` entry is keyed by its own name
+pub fn validate(result: &TopoSortResult) -> Result<(), Vec<String>> {
+    let mut violations: Vec<String> = Vec::new();
+    let vertices: HashSet<&str> = result.vertices.iter().map(String::as_str).collect();
+    if vertices.len() != result.vertices.len() {
+        violations.push("vertices contain duplicates".to_string());
+    }
+
+    for edge in &result.edges {
+        for endpoint in [&edge.src, &edge.dst] {
+            if !vertices.contains(endpoint.as_str()) {
+                violations.push(format!("edge {} -> {} references unknown vertex {}", edge.src, edge.dst, endpoint));
+            }
+        }
+    }
+
+    let mut position: HashMap<&str, usize> = HashMap::new();
+    for (idx, lib) in result.topo_sorted_libs.iter().enumerate() {
+        if position.insert(lib.name.as_str(), idx).is_some() {
+            violations.push(format!("{} appears more than once in topo_sorted_libs", lib.name));
+        }
+        if !vertices.contains(lib.name.as_str()) {
+            violations.push(format!("{} is in topo_sorted_libs but not in vertices", lib.name));
+        }
+    }
+    let mut missing_from_order: Vec<&str> = vertices.iter().filter(|v| !position.contains_key(*v)).copied().collect();
+    missing_from_order.sort();
+    for v in missing_from_order {
+        violations.push(format!("{} is in vertices but not in topo_sorted_libs", v));
+    }
+
+    let group_of: HashMap<&str, usize> = result.cycle_groups.iter().enumerate()
+        .flat_map(|(idx, group)| group.iter().map(move |member| (member.as_str(), idx)))
+        .collect();
+    let in_one_cycle = |src: &str, dst: &str| group_of.get(src).is_some_and(|group| group_of.get(dst) == Some(group));
+    for edge in result.edges.iter().filter(|edge| !in_one_cycle(&edge.src, &edge.dst)) {
+        if let (Some(src), Some(dst)) = (position.get(edge.src.as_str()), position.get(edge.dst.as_str())) {
+            if src >= dst {
+                violations.push(format!("{} must be loaded before {} but comes after it", edge.src, edge.dst));
+            }
+        }
+    }
+
+    for (key, lib) in &result.library_map {
+        if *key != lib.name {
+            violations.push(format!("library_map key {} holds library {}", key, lib.name));
+        }
+    }
+
+    if violations.is_empty() { Ok(()) } else { Err(violations) }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::query::tests::small_dag;
+    use crate::validate::validate;
+    use crate::{Edge, EdgeKind};
+
+    #[test]
+    fn validate_when_result_is_consistent_should_pass() {
+        assert_eq!(Ok(()), validate(&small_dag()));
+    }
+
+    #[test]
+    fn validate_when_edge_references_unknown_vertex_should_fail() {
+        let mut result = small_dag();
+        result.edges.push(Edge { src: "Z".to_string(), dst: "A".to_string(), kind: EdgeKind::Needed, ..Default::default() });
+        assert_eq!(Err(vec!["edge Z -> A references unknown vertex Z".to_string()]), validate(&result));
+    }
+
+    #[test]
+    fn validate_when_order_violates_edges_should_fail() {
+        let mut result = small_dag();
+        result.topo_sorted_libs.swap(0, 1);
+        let violations = validate(&result).unwrap_err();
+        assert_eq!(vec!["F must be loaded before E but comes after it".to_string()], violations);
+    }
+
+    #[test]
+    fn validate_when_edge_is_inside_a_cycle_group_should_skip_its_order() {
+        let mut result = small_dag();
+        result.edges.push(Edge { src: "E".to_string(), dst: "F".to_string(), kind: EdgeKind::Needed, ..Default::default() });
+        result.cycle_groups = vec![vec!["E".to_string(), "F".to_string()]];
+        assert_eq!(Ok(()), validate(&result));
+        result.cycle_groups = vec![vec!["E".to_string()], vec!["F".to_string()]];
+        assert_eq!(Err(vec!["E must be loaded before F but comes after it".to_string()]), validate(&result));
+    }
+
+    #[test]
+    fn validate_when_order_misses_vertex_should_fail() {
+        let mut result = small_dag();
+        result.topo_sorted_libs.remove(0);
+        let violations = validate(&result).unwrap_err();
+        assert_eq!(vec!["F is in vertices but not in topo_sorted_libs".to_string()], violations);
+    }
+
+    #[test]
+    fn validate_when_library_map_key_differs_from_name_should_fail() {
+        let mut result = small_dag();
+        let lib = result.library_map.remove("B").unwrap();
+        result.library_map.insert("X".to_string(), lib);
+        assert_eq!(Err(vec!["library_map key X holds library B".to_string()]), validate(&result));
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::path::Path;
+
+use crate::binary_kind::is_dynamic_loader;
+use crate::platform_policy::{self, PlatformPolicy};
+use crate::{EdgeKind, TopoSortResult};
+
+/// Architecture suffixes of the platform tags, `manylinux_2_17_x86_64` is the policy `manylinux_2_17` for x86_64
+const ARCHITECTURES: [&str; 7] = ["x86_64", "i686", "aarch64", "ppc64le", "s390x", "armv7l", "riscv64"];
+
+/// How the libraries of a wheel were resolved, see `--wheel`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WheelReport {
+    /// File name of the wheel
+    pub wheel: String,
+    /// The platform policy of the wheel's platform tag, e.g. `manylinux_2_17`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Extension modules and bundled libraries resolved inside the wheel, by their path in it, in load order
+    pub bundled: Vec<String>,
+    /// Libraries the wheel needs from outside of it, resolved or not, in load order, the dynamic loader aside
+    pub external: Vec<String>,
+    /// The `external` libraries `policy` does not guarantee, which the wheel has to bundle
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unbundled: Vec<String>,
+}
+
+/// Extracts the wheel `wheel` into the directory `dir`. Returns the number of files in it.
+pub fn extract(wheel: &Path, dir: &Path) -> io::Result<usize> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(wheel)?).map_err(io::Error::other)?;
+    archive.extract(dir).map_err(io::Error::other)?;
+    Ok(archive.len())
+}
+
+/// The built-in policy of the first platform tag of the wheel file name that has one, e.g. `manylinux_2_17` for
+/// `pkg-1.0-cp311-cp311-manylinux_2_17_x86_64.manylinux2014_x86_64.whl`
+pub fn policy_of(file_name: &str) -> Option<&'static PlatformPolicy> {
+    let platforms = file_name.strip_suffix(".whl")?.rsplit('-').next()?;
+    platforms.split('.').find_map(|tag| {
+        let name = ARCHITECTURES.iter().find_map(|arch| tag.strip_suffix(arch)?.strip_suffix('_'))?;
+        platform_policy::find(name)
+    })
+}
+
+/// Sorts the libraries of the merged `result` of the binaries extracted to `dir` into the bundled and external ones,
+/// like auditwheel: the external libraries are those the bundled ones need, and what the external ones `policy` does not
+/// guarantee need in turn, as they would have to be bundled too. The dependencies of guaranteed libraries are the
+/// platform's business. External libraries that `policy` does not guarantee are `unbundled`.
+pub fn report(result: &TopoSortResult, dir: &Path, file_name: &str, policy: Option<&PlatformPolicy>) -> WheelReport {
+    // the modules are named under `dir`, the libraries found through `$ORIGIN` under its canonical path
+    let canonical = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let in_wheel: HashMap<&str, String> = result.topo_sorted_libs.iter()
+        .filter_map(|lib| {
+            let path = Path::new(lib.path.as_deref()?);
+            let in_wheel = path.strip_prefix(dir).or_else(|_| path.strip_prefix(&canonical)).ok()?;
+            Some((lib.name.as_str(), in_wheel.display().to_string()))
+        })
+        .collect();
+    let guaranteed = |name: &str| policy.is_none_or(|policy| policy.libraries.contains(&name));
+    let mut external: HashSet<&str> = HashSet::new();
+    let mut queue: VecDeque<&str> = in_wheel.keys().copied().collect();
+    while let Some(name) = queue.pop_front() {
+        let dependencies = result.edges.iter()
+            .filter(|edge| edge.dst == name && edge.kind == EdgeKind::Needed)
+            .map(|edge| edge.src.as_str())
+            .filter(|dependency| !in_wheel.contains_key(dependency) && !is_dynamic_loader(dependency));
+        for dependency in dependencies {
+            if external.insert(dependency) && !guaranteed(dependency) {
+                queue.push_back(dependency);
+            }
+        }
+    }
+    let in_load_order = |include: &dyn Fn(&str) -> bool| -> Vec<String> {
+        result.topo_sorted_libs.iter().filter(|lib| include(&lib.name)).map(|lib| lib.name.clone()).collect()
+    };
+    WheelReport {
+        wheel: file_name.to_string(),
+        policy: policy.map(|policy| policy.name.to_string()),
+        bundled: result.topo_sorted_libs.iter().filter_map(|lib| in_wheel.get(lib.name.as_str()).cloned()).collect(),
+        external: in_load_order(&|name| external.contains(name)),
+        unbundled: in_load_order(&|name| external.contains(name) && !guaranteed(name)),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+    use std::path::Path;
+
+    use crate::query::tests::small_dag;
+    use crate::wheel::{extract, policy_of, report};
+    use crate::{Edge, Lib};
+
+    #[test]
+    fn policy_of_should_read_the_platform_tags_of_the_file_name() {
+        assert_eq!("manylinux_2_17", policy_of("pkg-1.0-cp311-cp311-manylinux_2_17_x86_64.manylinux2014_x86_64.whl").unwrap().name);
+        assert_eq!("manylinux_2_17", policy_of("pkg-1.0-1-cp311-cp311-linux_x86_64.manylinux2014_aarch64.whl").unwrap().name);
+        assert_eq!("musllinux_1_2", policy_of("pkg-1.0-cp312-abi3-musllinux_1_2_x86_64.whl").unwrap().name);
+        assert!(policy_of("pkg-1.0-cp311-cp311-linux_x86_64.whl").is_none());
+        assert!(policy_of("pkg-1.0.tar.gz").is_none());
+    }
+
+    #[test]
+    fn report_should_split_the_bundled_and_external_libraries() {
+        let lib = |name: &str, path: Option<&str>| Lib { name: name.to_string(), path: path.map(String::from), missing: path.is_none(), ..Lib::default() };
+        let edge = |src: &str, dst: &str| Edge { src: src.to_string(), dst: dst.to_string(), ..Edge::default() };
+        let mut result = small_dag();
+        result.topo_sorted_libs = vec![
+            lib("ld-linux-x86-64.so.2", Some("/lib64/ld-linux-x86-64.so.2")),
+            lib("libc.so.6", Some("/lib/x86_64-linux-gnu/libc.so.6")),
+            lib("libz.so.1", Some("/lib/x86_64-linux-gnu/libz.so.1")),
+            lib("libfoo.so", None),
+            lib("libgfortran-a1b2c3.so.5", Some("/tmp/wheel/pkg.libs/libgfortran-a1b2c3.so.5")),
+            lib("_core.cpython-311-x86_64-linux-gnu.so", Some("/tmp/wheel/pkg/_core.cpython-311-x86_64-linux-gnu.so")),
+        ];
+        // libc needs the loader and is guaranteed, libz is not and pulls in libfoo
+        result.edges = vec![
+            edge("ld-linux-x86-64.so.2", "libc.so.6"),
+            edge("ld-linux-x86-64.so.2", "_core.cpython-311-x86_64-linux-gnu.so"),
+            edge("libc.so.6", "libz.so.1"),
+            edge("libfoo.so", "libz.so.1"),
+            edge("libc.so.6", "libgfortran-a1b2c3.so.5"),
+            edge("libz.so.1", "_core.cpython-311-x86_64-linux-gnu.so"),
+            edge("libgfortran-a1b2c3.so.5", "_core.cpython-311-x86_64-linux-gnu.so"),
+        ];
+        let name = "pkg-1.0-cp311-cp311-manylinux_2_28_x86_64.whl";
+        let manylinux = report(&result, Path::new("/tmp/wheel"), name, policy_of(name));
+        assert_eq!(Some("manylinux_2_28".to_string()), manylinux.policy);
+        assert_eq!(vec!["pkg.libs/libgfortran-a1b2c3.so.5", "pkg/_core.cpython-311-x86_64-linux-gnu.so"], manylinux.bundled);
+        assert_eq!(vec!["libc.so.6", "libz.so.1", "libfoo.so"], manylinux.external);
+        assert_eq!(vec!["libz.so.1", "libfoo.so"], manylinux.unbundled);
+
+        // without a policy only the direct dependencies are external
+        let linux = report(&result, Path::new("/tmp/wheel"), "pkg-1.0-cp311-cp311-linux_x86_64.whl", None);
+        assert_eq!(vec!["libc.so.6", "libz.so.1"], linux.external);
+        assert!(linux.unbundled.is_empty());
+    }
+
+    #[test]
+    fn extract_should_unpack_the_wheel() {
+        let dir = std::env::temp_dir().join(format!("lddtopo-wheel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(dir.join("pkg.whl")).unwrap());
+        zip.start_file("pkg/_core.so", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"\x7fELF").unwrap();
+        zip.start_file("pkg-1.0.dist-info/WHEEL", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.finish().unwrap();
+        let extracted = extract(&dir.join("pkg.whl"), &dir.join("extracted"));
+        let core = std::fs::read(dir.join("extracted/pkg/_core.so")).ok();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(2, extracted.unwrap());
+        assert_eq!(Some(b"\x7fELF".to_vec()), core);
+    }
+}